@@ -1,7 +1,58 @@
+pub mod add;
 pub mod cat_file;
+pub mod check_ref_format;
 pub mod clone;
+pub mod commit;
 pub mod commit_tree;
+pub mod config;
+pub mod daemon;
+pub mod fsck;
+pub mod gc;
 pub mod hash_object;
+pub mod http_backend;
 pub mod init;
+pub mod ls_files;
 pub mod ls_tree;
+pub mod mktag;
+pub mod mv;
+pub mod pack_objects;
+pub mod pack_refs;
+pub mod prune;
+pub mod prune_packed;
+pub mod receive_pack;
+pub mod repack;
+pub mod rev_parse;
+pub mod rm;
+pub mod show_ref;
+pub mod status;
+pub mod symbolic_ref;
+pub mod update_index;
+pub mod update_ref;
+pub mod upload_pack;
 pub mod write_tree;
+
+use git_starter_rust::{object::ObjectId, repository::Repository};
+
+/// Opens repository the way git commands do, reporting problems that did not prevent it
+pub fn open_repository() -> anyhow::Result<Repository> {
+    let repo = Repository::from_env()?;
+    report_problems(&repo);
+    Ok(repo)
+}
+
+/// Prints problems found while opening the repository, like missing alternates
+pub fn report_problems(repo: &Repository) {
+    for problem in repo.problems() {
+        eprintln!("error: {problem}");
+    }
+}
+
+/// Resolves revision expression, printing warnings about it (ie. ambiguous reference names)
+pub fn resolve_revision(repo: &Repository, rev: &str) -> anyhow::Result<ObjectId> {
+    let mut warnings = Vec::new();
+    let id = git_starter_rust::rev_parse::resolve_with_warnings(repo, rev, &mut warnings);
+    for warning in warnings {
+        eprintln!("warning: {warning}");
+    }
+    id
+}
@@ -0,0 +1,204 @@
+use std::{ffi::OsStr, fs, os::unix::ffi::OsStrExt, path::PathBuf};
+
+use anyhow::Context;
+
+use git_starter_rust::{
+    ignore::Ignore,
+    index::{self, Entry, FileState, Index, IndexLock, Stat},
+    object::{Header, ObjectFile, ObjectType},
+    pathspec::Pathspec,
+    repository::Repository,
+    status,
+};
+
+// https://git-scm.com/docs/git-add
+
+pub struct Options {
+    /// Stage new, modified and deleted files of the whole tree without pathspec
+    pub all: bool,
+    /// Stage only modified and deleted files that are already tracked
+    pub update: bool,
+    /// Record only that new files will be added later
+    pub intent_to_add: bool,
+    /// Allow adding ignored files
+    pub force: bool,
+    pub pathspec: Vec<PathBuf>,
+}
+
+/// git add command
+pub fn invoke(options: Options) -> anyhow::Result<()> {
+    let repo = super::open_repository()?;
+    let work_tree = repo
+        .work_tree()
+        .context("this operation must be run in a work tree")?;
+
+    let pathspec = if !options.pathspec.is_empty() {
+        Pathspec::new(&repo, &options.pathspec)?
+    } else if options.all || options.update {
+        Pathspec::all()
+    } else {
+        anyhow::bail!(
+            "Nothing specified, nothing added.\nhint: Maybe you wanted to say 'git add .'?"
+        );
+    };
+    let mut matched = vec![false; pathspec.items.len()];
+    let mut mark = |path: &[u8]| {
+        for (item, matched) in pathspec.items.iter().zip(&mut matched) {
+            *matched |= item.matches(path);
+        }
+    };
+
+    let lock = IndexLock::acquire(&repo)?;
+    let mut index = Index::read(repo.index_file(), repo.algorithm())?;
+
+    // new intents do not change content of files already in the index
+    let tracked = match options.intent_to_add {
+        true => index
+            .entries
+            .iter()
+            .map(|entry| entry.path.clone())
+            .filter(|path| pathspec.matches(path))
+            .collect(),
+        false => update_tracked(&repo, &mut index, &pathspec)?,
+    };
+    for path in &tracked {
+        mark(path);
+    }
+
+    let mut ignored = Vec::new();
+    if !options.update {
+        let mut ignore = Ignore::new(&repo)?;
+        let mut files = Vec::new();
+        status::collect_files(
+            &repo,
+            (!options.force).then_some(&mut ignore),
+            b"",
+            &mut files,
+        )?;
+        for path in files.iter().filter(|path| pathspec.matches(path)) {
+            mark(path);
+            if index.contains(path) {
+                continue;
+            }
+            if let Some(conflict) = index.conflicting(path) {
+                anyhow::bail!(
+                    "'{}' conflicts with '{}', which appears as both a file and as a directory",
+                    String::from_utf8_lossy(path),
+                    String::from_utf8_lossy(&conflict.path)
+                );
+            }
+
+            if options.intent_to_add {
+                // intents are recorded with the empty blob, which is not written
+                let empty = ObjectFile {
+                    header: Header {
+                        typ: ObjectType::Blob,
+                        size: 0,
+                    },
+                    reader: &[][..],
+                }
+                .hash(repo.algorithm())?;
+                let mode = index::mode_of(&symlink_metadata(&repo, path)?);
+                let mut entry = Entry::new(path.clone(), mode, empty);
+                entry.intent_to_add = true;
+                index.add(entry);
+            } else {
+                add_file(&repo, &mut index, path)?;
+            }
+        }
+
+        // ignored files are only reported when named explicitly
+        for (item, _) in pathspec.items.iter().zip(&matched).filter(|(_, &m)| !m) {
+            let full_path = work_tree.join(OsStr::from_bytes(&item.path));
+            if let Ok(metadata) = fs::symlink_metadata(&full_path) {
+                if ignore.is_ignored(&item.path, metadata.is_dir())? {
+                    ignored.push(item.original.clone());
+                }
+            }
+        }
+    }
+
+    for (item, _) in pathspec.items.iter().zip(&matched).filter(|(_, &m)| !m) {
+        anyhow::ensure!(
+            ignored.contains(&item.original),
+            "pathspec '{}' did not match any files",
+            item.original
+        );
+    }
+
+    lock.commit(&index)?;
+
+    anyhow::ensure!(
+        ignored.is_empty(),
+        "The following paths are ignored by one of your .gitignore files:\n{}\n\
+         hint: Use -f if you really want to add them.",
+        ignored.join("\n")
+    );
+
+    Ok(())
+}
+
+/// Stages current content of tracked files matching the pathspec and removes deleted ones
+/// from the index. Returns paths of all matching files.
+pub fn update_tracked(
+    repo: &Repository,
+    index: &mut Index,
+    pathspec: &Pathspec,
+) -> anyhow::Result<Vec<Vec<u8>>> {
+    let mut tracked: Vec<Vec<u8>> = index
+        .entries
+        .iter()
+        .map(|entry| entry.path.clone())
+        .filter(|path| pathspec.matches(path))
+        .collect();
+    tracked.dedup();
+
+    for path in &tracked {
+        let state = match index.get(path, 0) {
+            Some(entry) if !entry.intent_to_add => index.file_state(repo, entry)?,
+            // conflicts are resolved and intents fulfilled with the file content
+            _ => match symlink_metadata(repo, path) {
+                Ok(_) => FileState::Modified,
+                Err(_) => FileState::Deleted,
+            },
+        };
+        match state {
+            FileState::Unchanged(stat) => {
+                index.get_mut(path, 0).expect("checked above").stat = stat;
+            }
+            FileState::Modified => add_file(repo, index, path)?,
+            FileState::Deleted => {
+                index.remove(path);
+            }
+        }
+    }
+
+    Ok(tracked)
+}
+
+fn symlink_metadata(repo: &Repository, path: &[u8]) -> anyhow::Result<fs::Metadata> {
+    let full_path = repo
+        .work_tree()
+        .context("this operation must be run in a work tree")?
+        .join(OsStr::from_bytes(path));
+    fs::symlink_metadata(&full_path).with_context(|| format!("stat {}", full_path.display()))
+}
+
+/// Stores content of the work tree file as a blob and stages it
+fn add_file(repo: &Repository, index: &mut Index, path: &[u8]) -> anyhow::Result<()> {
+    let name = String::from_utf8_lossy(path);
+    let metadata = symlink_metadata(repo, path)?;
+    let full_path = repo
+        .work_tree()
+        .expect("checked by symlink_metadata")
+        .join(OsStr::from_bytes(path));
+
+    let oid = repo
+        .hash_file(&full_path, true)
+        .with_context(|| format!("unable to index file '{name}'"))?;
+
+    let mut entry = Entry::new(path.to_vec(), index::mode_of(&metadata), oid);
+    entry.stat = Stat::from_metadata(&metadata);
+    index.add(entry);
+    Ok(())
+}
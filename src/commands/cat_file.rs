@@ -1,6 +1,11 @@
+use std::io::prelude::*;
+
 use anyhow::Context;
 
-use crate::object::{ObjectFile, ObjectType};
+use git_starter_rust::{
+    object::{ObjectType, Tag},
+    rev_parse,
+};
 
 /// git cat-file command
 pub fn invoke(
@@ -10,33 +15,54 @@ pub fn invoke(
     type_only: bool,
     size_only: bool,
 ) -> anyhow::Result<()> {
-    let mut object = ObjectFile::read(hash, None)?;
-
-    let real_object_type = object.header.typ;
-    let size = object.header.size;
+    let repo = super::open_repository()?;
+    let id = super::resolve_revision(&repo, hash)?;
 
     if let Some(object_type) = object_type {
-        if object_type != real_object_type.to_string() {
-            anyhow::bail!("File is not {}", object_type)
-        }
-    }
-
-    if type_only {
-        println!("{real_object_type}");
+        let object_type: ObjectType = object_type.parse()?;
+        let id = rev_parse::peel(repo.odb(), &id, object_type)?;
+        let mut object = repo.read_object(&id)?;
+        let mut stdout = std::io::stdout().lock();
+        std::io::copy(&mut object.reader, &mut stdout)
+            .context("streaming file content to stdout")?;
         return Ok(());
     }
 
-    if size_only {
-        println!("{size}");
+    if type_only || size_only {
+        let header = repo
+            .odb()
+            .read_header(&id)?
+            .with_context(|| format!("object {id} not found"))?;
+        if type_only {
+            println!("{}", header.typ);
+        } else {
+            println!("{}", header.size);
+        }
         return Ok(());
     }
 
+    let mut object = repo.read_object(&id)?;
+    let real_object_type = object.header.typ;
+
     if pretty_print && real_object_type == ObjectType::Tree {
-        return super::ls_tree::invoke(hash, false, false);
+        return super::ls_tree::invoke(&id.to_string(), false, false);
     }
 
     let mut stdout = std::io::stdout().lock();
 
+    if pretty_print && real_object_type == ObjectType::Tag {
+        let mut data = Vec::new();
+        object
+            .reader
+            .read_to_end(&mut data)
+            .context("reading tag content")?;
+        let tag = Tag::parse(&data).with_context(|| format!("parsing tag {id}"))?;
+        stdout
+            .write_all(&tag.serialize())
+            .context("writing tag content to stdout")?;
+        return Ok(());
+    }
+
     std::io::copy(&mut object.reader, &mut stdout).context("streaming file content to stdin")?;
 
     Ok(())
@@ -0,0 +1,32 @@
+use git_starter_rust::refs::{self, NameOptions};
+
+// https://git-scm.com/docs/git-check-ref-format
+
+/// git check-ref-format command, exits with 1 when the name is invalid
+pub fn invoke(
+    name: &str,
+    normalize: bool,
+    options: NameOptions,
+    branch: bool,
+) -> anyhow::Result<()> {
+    if branch {
+        anyhow::ensure!(
+            !name.starts_with('-') && refs::is_valid_name(&format!("refs/heads/{name}")),
+            "'{name}' is not a valid branch name"
+        );
+        println!("{name}");
+        return Ok(());
+    }
+
+    let name = match normalize {
+        true => refs::normalize_name(name),
+        false => name.to_string(),
+    };
+    if !refs::check_name(&name, options) {
+        std::process::exit(1);
+    }
+    if normalize {
+        println!("{name}");
+    }
+    Ok(())
+}
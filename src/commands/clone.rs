@@ -1,21 +1,39 @@
 use anyhow::{Context, Ok};
 use flate2::read::ZlibDecoder;
 use reqwest::StatusCode;
+use std::ffi::OsStr;
+use std::fmt::Write;
 use std::fs;
-use std::io::{BufRead, Read};
+use std::io::Read;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::PermissionsExt;
 use std::path::{Path, PathBuf};
-use std::{fmt::Write, io::BufReader};
 
-use bytes::{Buf, BufMut, Bytes, BytesMut};
+use bytes::{Buf, Bytes};
 
-use crate::{
-    commands,
-    object::{self, ObjectFile, ObjectType},
+use git_starter_rust::{
+    index::{Entry, Index, IndexLock, Stat},
+    object::{self, tree, HashAlgorithm, ObjectFile, ObjectId, ObjectType, Tree},
+    odb::{alternates, ObjectDatabase},
+    pack,
+    refs::{self, Expected},
+    repository::Repository,
 };
 
+use super::repack;
+
 const SERVICE_NAME: &str = "git-upload-pack";
 
-pub fn invoke(repository_url: &str, dir: Option<PathBuf>) -> anyhow::Result<()> {
+pub struct Options {
+    /// Repositories to borrow objects from, only objects missing in them are fetched
+    pub reference: Vec<PathBuf>,
+    /// Borrow all objects from the local source repository instead of copying them
+    pub shared: bool,
+    /// Copy borrowed objects into the clone and stop borrowing them
+    pub dissociate: bool,
+}
+
+pub fn invoke(repository_url: &str, dir: Option<PathBuf>, options: Options) -> anyhow::Result<()> {
     // References:
     // https://www.git-scm.com/docs/http-protocol
     // https://www.git-scm.com/book/en/v2/Git-Internals-Transfer-Protocols
@@ -48,15 +66,41 @@ pub fn invoke(repository_url: &str, dir: Option<PathBuf>) -> anyhow::Result<()>
         }
     };
 
-    commands::init::create_git_dirs(Some(dir.as_path())).with_context(|| {
-        format!(
-            "initializing Git repository in '{}' directory",
-            dir.display()
-        )
-    })?;
+    // https://git-scm.com/docs/git-clone#Documentation/git-clone.txt---reference-if-ableltrepositorygt
+    let mut borrowed = Vec::new();
+    let mut haves = Vec::new();
+    for path in &options.reference {
+        let reference = Repository::open(path)
+            .with_context(|| format!("reference repository '{}'", path.display()))?;
+        borrowed.push(absolute(&reference.objects_dir())?);
+        // objects reachable from references of the reference repository are not fetched
+        haves.extend(reference.head()?);
+        for r in reference.references()? {
+            haves.push(r.hash);
+        }
+    }
+
+    if options.shared {
+        // https://git-scm.com/docs/git-clone#Documentation/git-clone.txt---shared
+        let path = repository_url
+            .strip_prefix("file://")
+            .unwrap_or(&repository_url);
+        let source = Repository::open(path)
+            .with_context(|| format!("--shared requires a local repository, got '{path}'"))?;
+        let head = source
+            .head()?
+            .with_context(|| format!("repository '{path}' has no commits"))?;
+        borrowed.insert(0, absolute(&source.objects_dir())?);
+
+        let repo = init(&dir, source.algorithm(), &borrowed)?;
+        println!("Cloning into '{}'...", dir.display());
+        return finish(&repo, &repository_url, &dir, &head, &options);
+    }
+
+    let (mut pack_data, head_ref_hash, algorithm) =
+        get_pack_data(repository_url.clone(), &haves).context("getting pack from remote")?;
 
-    let (mut pack_data, head_ref_hash) =
-        get_pack_data(repository_url).context("getting pack from remote")?;
+    let repo = init(&dir, algorithm, &borrowed)?;
 
     println!("Cloning into '{}'...", dir.display());
 
@@ -98,13 +142,14 @@ pub fn invoke(repository_url: &str, dir: Option<PathBuf>) -> anyhow::Result<()>
             shift += 7;
         }
 
-        let mut base_obj_hash = String::new();
+        let mut base_obj_id = None;
         if obj_type == ObjectType::RefDelta {
-            // 20-byte name of the base object
-            base_obj_hash = hex::encode(pack_data.get(..20).ok_or(anyhow::anyhow!(
+            // binary name of the base object
+            let name = pack_data.get(..algorithm.size()).ok_or(anyhow::anyhow!(
                 "could not get OBJ_REF_DELTA base object name"
-            ))?);
-            pack_data.advance(20);
+            ))?;
+            base_obj_id = Some(ObjectId::from_bytes(algorithm, name)?);
+            pack_data.advance(algorithm.size());
         }
 
         let mut obj_reader = pack_data.as_ref().reader();
@@ -124,70 +169,126 @@ pub fn invoke(repository_url: &str, dir: Option<PathBuf>) -> anyhow::Result<()>
                 .context("streaming object's data to sink")?;
             pack_data.advance(obj.reader.total_in() as usize);
             println!("OBJ_OFS_DELTA objects are not supported");
-        } else if obj.header.typ == ObjectType::RefDelta {
+        } else if let Some(base_obj_id) = &base_obj_id {
             // OBJ_REF_DELTA processing
 
-            let mut base_obj = ObjectFile::read(&base_obj_hash, Some(dir.as_path()))?;
+            let mut base_obj = ObjectFile::read(base_obj_id, repo.odb())?;
 
-            process_delta_object(&dir, &mut obj, &mut base_obj)
+            process_delta_object(repo.odb(), &mut obj, &mut base_obj)
                 .context("processing delta object")?;
             pack_data.advance(obj.reader.total_in() as usize);
             resolved_deltas += 1;
         } else {
             // Regular object (blob, tree, commmit)
 
-            obj.write(Some(dir.as_path()))?;
+            obj.write(repo.odb())?;
             pack_data.advance(obj.reader.total_in() as usize);
             received_objects += 1;
         }
     }
 
-    anyhow::ensure!(pack_data.remaining() == 20, "cannot get pack checksum");
+    anyhow::ensure!(
+        pack_data.remaining() == algorithm.size(),
+        "cannot get pack checksum"
+    );
     println!(
         "Pack checksum: {}",
         hex::encode((pack_data.get(..)).context("reading checksum")?)
     );
 
-    // reconstruct files according to the HEAD
-    let head_commit_obj = ObjectFile::read(&head_ref_hash, Some(dir.as_path()))?;
+    println!("Received objects: {}", received_objects);
+    println!("Resolved deltas: {}", resolved_deltas);
+
+    finish(
+        &repo,
+        &repository_url,
+        &dir,
+        &head_ref_hash.parse()?,
+        &options,
+    )
+}
+
+/// Creates the repository of the clone borrowing objects from the object directories
+fn init(dir: &Path, algorithm: HashAlgorithm, borrowed: &[PathBuf]) -> anyhow::Result<Repository> {
     anyhow::ensure!(
-        head_commit_obj.header.typ == ObjectType::Commit,
-        "HEAD does not point to commit"
+        !dir.exists(),
+        "destination path '{}' already exists",
+        dir.display()
     );
 
-    let mut head_tree_hash = String::new();
-
-    let mut head_commit = BufReader::new(head_commit_obj.reader);
-    let mut line = String::new();
-    while head_commit
-        .read_line(&mut line)
-        .context("reading commit line")?
-        > 0
-    {
-        if let Some((k, v)) = line.split_once(' ') {
-            if *k.to_string() == ObjectType::Tree.to_string() {
-                head_tree_hash = v.trim().to_string();
-                break;
-            }
-        } else {
-            anyhow::bail!("failed to parse commit line");
-        };
+    // the new repository uses the same object format as the remote
+    let repo = Repository::init(dir, algorithm).with_context(|| {
+        format!(
+            "initializing Git repository in '{}' directory",
+            dir.display()
+        )
+    })?;
+    if borrowed.is_empty() {
+        return Ok(repo);
     }
 
-    if head_tree_hash.is_empty() {
-        anyhow::bail!("could not get tree from commit {}", head_ref_hash);
-    }
+    alternates::write(&repo.objects_dir(), borrowed)?;
+    let repo = Repository::open(dir)?;
+    super::report_problems(&repo);
+    Ok(repo)
+}
 
-    reconstruct_repo_files(dir.as_path(), dir.as_path(), &head_tree_hash)
-        .context("reconstructing files")?;
+/// Points the current branch to the cloned commit and checks it out
+fn finish(
+    repo: &Repository,
+    repository_url: &str,
+    dir: &Path,
+    head: &ObjectId,
+    options: &Options,
+) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        refs::head_target(repo.git_dir())?.is_some(),
+        "HEAD is not a symbolic reference"
+    );
+    let message = format!("clone: from {repository_url}");
+    repo.update_ref("HEAD", head, Expected::Missing, &message)?;
 
-    println!("Received objects: {}", received_objects);
-    println!("Resolved deltas: {}", resolved_deltas);
+    // reconstruct files according to the HEAD
+    let head_commit = repo
+        .read_commit(head)
+        .with_context(|| format!("HEAD {head} does not point to commit"))?;
+
+    let lock = IndexLock::acquire(repo)?;
+    let mut index = Index::new(repo.algorithm());
+    reconstruct_repo_files(repo.odb(), dir, b"", &head_commit.tree, &mut index)
+        .context("reconstructing files")?;
+    lock.commit(&index)?;
+
+    if options.dissociate {
+        // copy everything borrowed into a pack of the clone, then stop borrowing
+        repack::repack(
+            repo,
+            &repack::Options {
+                all: true,
+                loosen_unreachable: false,
+                delete: true,
+                window: 10,
+                depth: 50,
+            },
+        )
+        .context("copying borrowed objects")?;
+        alternates::write(&repo.objects_dir(), &[])?;
+    }
 
     Ok(())
 }
 
-fn get_pack_data(repository_url: String) -> anyhow::Result<(Bytes, String)> {
+/// Alternates are stored as absolute paths, so that they do not depend on the location
+/// of the clone
+fn absolute(path: &Path) -> anyhow::Result<PathBuf> {
+    path.canonicalize()
+        .with_context(|| format!("resolving {}", path.display()))
+}
+
+fn get_pack_data(
+    repository_url: String,
+    haves: &[ObjectId],
+) -> anyhow::Result<(Bytes, String, HashAlgorithm)> {
     // GET $GIT_URL/info/refs?service=git-upload-pack HTTP/1.0
     let url = format!("{repository_url}/info/refs?service={SERVICE_NAME}");
 
@@ -251,27 +352,54 @@ fn get_pack_data(repository_url: String) -> anyhow::Result<(Bytes, String)> {
     // The stream SHOULD be sorted by name according to the C locale ordering.
     // The stream SHOULD include the default ref named HEAD as the first ref.
     // The stream MUST include capability declarations behind a NUL on the first ref.
-    let _line_len = data.get_u32();
-
-    let head_ref_hash = std::str::from_utf8(
-        data.get(0..40)
-            .context("reading 40 bytes of HEAD ref hash")?,
-    )
-    .context("reading HEAD ref hash")?;
-
-    let ref_name = data.get(40..46).context("chcecking presence of HEAD ref")?;
+    let line_len = std::str::from_utf8(data.get(0..4).context("reading pkt-line length")?)
+        .ok()
+        .and_then(|len| usize::from_str_radix(len, 16).ok())
+        .context("invalid pkt-line length")?;
+    let line = data.get(4..line_len).context("reading HEAD ref line")?;
+
+    let (head_ref_hash, rest) = line
+        .iter()
+        .position(|&b| b == b' ')
+        .map(|pos| line.split_at(pos))
+        .context("reading HEAD ref hash")?;
+    let head_ref_hash = std::str::from_utf8(head_ref_hash).context("reading HEAD ref hash")?;
     anyhow::ensure!(
-        ref_name.starts_with(b" HEAD\0"),
+        rest.starts_with(b" HEAD\0"),
         "HEAD ref is not present in response"
     );
 
+    // servers without the object-format capability use SHA-1
+    let capabilities = String::from_utf8_lossy(&rest[6..]);
+    let object_format = capabilities
+        .split_ascii_whitespace()
+        .find_map(|capability| capability.strip_prefix("object-format="));
+    let algorithm = match object_format {
+        Some(name) => name.parse()?,
+        None => HashAlgorithm::Sha1,
+    };
+    anyhow::ensure!(
+        algorithm.is_hex(head_ref_hash),
+        "invalid HEAD ref hash {head_ref_hash}"
+    );
+
     // POST $GIT_URL/git-upload-pack HTTP/1.0
     let url = format!("{repository_url}/{SERVICE_NAME}");
 
     // The returned stream is the side-band-64k protocol supported by the git-upload-pack service, and the pack is embedded into stream 1.
     // Progress messages from the server side MAY appear in stream 2.
-    let mut want = format!("0032want {head_ref_hash}\n");
+    // the client has to confirm the object format it is going to use
+    let mut want = match object_format {
+        Some(name) => format!("want {head_ref_hash} object-format={name}\n"),
+        None => format!("want {head_ref_hash}\n"),
+    };
+    want = format!("{:04x}{want}", want.len() + 4);
     write!(want, "0000")?;
+    // the server leaves out objects reachable from those we already have
+    for have in haves.iter().filter(|have| have.algorithm() == algorithm) {
+        let line = format!("have {have}\n");
+        write!(want, "{:04x}{line}", line.len() + 4)?;
+    }
     writeln!(want, "0009done")?;
 
     let resp = client
@@ -309,15 +437,22 @@ fn get_pack_data(repository_url: String) -> anyhow::Result<(Bytes, String)> {
         .bytes()
         .with_context(|| format!("reading response body bytes {url}"))?;
 
+    // NAK when none of our objects is common, otherwise ACK of the first common one
+    let line_len = std::str::from_utf8(data.get(0..4).unwrap_or_default())
+        .ok()
+        .and_then(|len| usize::from_str_radix(len, 16).ok())
+        .context("malformed pack header: invalid pkt-line length")?;
+    let line = data.get(4..line_len).unwrap_or_default();
     anyhow::ensure!(
-        data.get(0..8).unwrap_or_default().starts_with(b"0008NAK\n"),
-        "malformed pack header: missing NAK line"
+        line == b"NAK\n" || line.starts_with(b"ACK "),
+        "malformed pack header: missing NAK or ACK line"
     );
+    data.advance(line_len);
     anyhow::ensure!(
-        data.get(8..12).unwrap_or_default().starts_with(b"PACK"),
+        data.get(0..4).unwrap_or_default().starts_with(b"PACK"),
         "malformed pack header: missing PACK"
     );
-    data.advance(12);
+    data.advance(4);
 
     let version = data.get_u32();
     anyhow::ensure!(
@@ -325,56 +460,21 @@ fn get_pack_data(repository_url: String) -> anyhow::Result<(Bytes, String)> {
         "server returned unsupported pack version {version}"
     );
 
-    Ok((data, head_ref_hash.to_string()))
+    Ok((data, head_ref_hash.to_string(), algorithm))
 }
 
 // OBJ_REF_DELTA processing
 fn process_delta_object(
-    dir: impl AsRef<Path>,
+    odb: &dyn ObjectDatabase,
     obj: &mut ObjectFile<ZlibDecoder<impl Read>>,
     base_obj: &mut ObjectFile<impl Read>,
 ) -> anyhow::Result<()> {
-    let mut buf = Vec::new();
+    // delta_obj_data contains decompressed delta object data
+    let mut delta_obj_data = Vec::new();
     obj.reader
-        .read_to_end(&mut buf)
+        .read_to_end(&mut delta_obj_data)
         .context("reading object data to buffer")?;
 
-    // delta_obj_data contains decompressed delta object data
-    let mut delta_obj_data = BytesMut::new();
-    delta_obj_data.extend_from_slice(&buf);
-
-    /* The delta begins with the source and target lengths, both encoded as variable-length integers, which is useful for error checking,
-    but is not essential.
-    After this, there are a series of instructions, which may be either “copy” (MSB = 1) or “insert” (MSB = 0). */
-
-    // get source lenth
-    let b = delta_obj_data.get_u8();
-    let mut msb = b & 0b1000_0000 > 0;
-    let mut source_length = (b & 0b0111_1111) as usize;
-    let mut shift = 7;
-    while msb {
-        let b = delta_obj_data.get_u8();
-        if b & 0b1000_0000 == 0 {
-            msb = false;
-        }
-        source_length += ((b & 0b0111_1111) as usize) << shift;
-        shift += 7;
-    }
-
-    // get target length
-    let b = delta_obj_data.get_u8();
-    let mut msb = b & 0b1000_0000 > 0;
-    let mut target_length = (b & 0b0111_1111) as usize;
-    let mut shift = 7;
-    while msb {
-        let b = delta_obj_data.get_u8();
-        if b & 0b1000_0000 == 0 {
-            msb = false;
-        }
-        target_length += ((b & 0b0111_1111) as usize) << shift;
-        shift += 7;
-    }
-
     // base_obj_data containds decompressed base object data
     let mut base_obj_data = Vec::new();
     base_obj
@@ -382,169 +482,82 @@ fn process_delta_object(
         .read_to_end(&mut base_obj_data)
         .context("reading base object data to buffer")?;
 
-    anyhow::ensure!(
-        base_obj.header.size == source_length,
-        "incorrect base object length, expected {}, got {}",
-        source_length,
-        base_obj.header.size
-    );
-
     // new_data contains data from base object with applied delta chunks
-    let mut new_data = BytesMut::new();
-
-    // read delta instructions
-    loop {
-        // get insert/copy instruction; msb 0 = insert, 1 = copy
-        let instruction = delta_obj_data.get_u8();
-        let msb = instruction >> 7; // MSB
-
-        if msb == 0 {
-            // INSERT
-            // The insert instruction itself is the number of bytes to copy from the delta object to the output.
-            // Since insert instructions all have their MSB set to 0, the maximum number of bytes to insert is 127.
-            // So, if the instruction is 01001011, that means that we should read the next 75 bytes of the delta object and copy them to the output.
-
-            let length = instruction as usize;
-            let delta = delta_obj_data.get(0..length).ok_or(anyhow::anyhow!(
-                "could not read delta object data to insert them"
-            ))?;
-
-            new_data.put(delta);
-            delta_obj_data.advance(length);
-        } else if msb == 1 {
-            // COPY
-            // Copy instructions signal that we should copy a consecutive chunk of bytes from the base object to the output.
-            // There are two numbers that are necessary to perform this operation: the location (offset) of the first byte to copy, and the number of bytes to copy.
-            // These are stored as little-endian variable-length integers after each copy instruction; however, their contents are compressed.
-            //
-            // Even though the byte offset is a 32-bit integer, Git only includes the non-zero bytes to save space,
-            // and the last four bits of the copy instruction signal how many bytes to read.
-            //
-            // For example, let’s say that the last four bits of the copy instruction are 1010 and the next two bytes are 11010111 01001011.
-            // This means that the byte offset is 01001011 00000000 11010111 00000000, which is 1,258,346,240.
-            //
-            // The copy length is interpreted the same way, with the middle three bits of the instruction signifying whether to advance the cursor or not,
-            // just as the last four bits signify whether to advance the cursor when constructing the byte offset.
-
-            let mut offset = 0;
-            let mut length = 0;
-
-            let flag = 0b0000_1111 & instruction; // ex. 1010
-            for i in 0..4 {
-                let mut b = 0;
-                if flag & (1u8 << i) > 0 {
-                    b = delta_obj_data.get_u8();
-                }
-                offset += (b as usize) << (i * 8);
-            }
-
-            let flag = 0b0111_0000 & instruction; // ex. 010
-            for i in 0..3 {
-                let mut b = 0;
-                if flag & (1u8 << (i + 4)) > 0 {
-                    b = delta_obj_data.get_u8();
-                }
-                length += (b as usize) << (i * 8);
-            }
-
-            let delta = base_obj_data
-                .get(offset..offset + length)
-                .ok_or(anyhow::anyhow!(
-                    "could not read base object data to copy them"
-                ))?;
-            new_data.put(delta);
-        } else {
-            anyhow::bail!("incorrect delta instruction {instruction}");
-        }
-
-        if delta_obj_data.remaining() == 0 {
-            break;
-        }
-    }
-
-    anyhow::ensure!(
-        new_data.len() == target_length,
-        "incorrect new base object length, expected {}, got {}",
-        target_length,
-        new_data.len()
-    );
+    let new_data = pack::delta::apply(&base_obj_data, &delta_obj_data)?;
 
     let mut new_obj = ObjectFile {
         header: object::Header {
             typ: base_obj.header.typ.clone(),
             size: new_data.len(),
         },
-        reader: new_data.reader(),
+        reader: new_data.as_slice(),
     };
 
-    new_obj.write(Some(dir.as_ref()))?;
+    new_obj.write(odb)?;
 
     Ok(())
 }
 
+/// Checks out the tree into the directory, records the files in the index with their
+/// path below `prefix`
 fn reconstruct_repo_files(
-    clone_dir: impl AsRef<Path>,
-    current_dir: impl AsRef<Path>,
-    tree_hash: &str,
+    odb: &dyn ObjectDatabase,
+    current_dir: &Path,
+    prefix: &[u8],
+    tree_id: &ObjectId,
+    index: &mut Index,
 ) -> anyhow::Result<()> {
-    let clone_dir = clone_dir.as_ref();
-    let current_dir = current_dir.as_ref();
-
-    let mut tree_obj = ObjectFile::read(tree_hash, Some(clone_dir))
-        .with_context(|| format!("opening tree file {tree_hash}"))?;
-
-    let typ = tree_obj.header.typ;
-    anyhow::ensure!(
-        typ == ObjectType::Tree,
-        "incorrect tree object type '{typ}'"
-    );
+    let tree = Tree::read(tree_id, odb).with_context(|| format!("opening tree file {tree_id}"))?;
 
-    loop {
-        // parsing tree object copied from list_tree function in ls-tree command
-        let mut buf = Vec::new();
-        let n = tree_obj
-            .reader
-            .read_until(0, &mut buf)
-            .context("reading mode and name for tree item")?;
-        if n == 0 {
-            break;
-        }
+    for entry in tree.entries {
+        let path = current_dir.join(OsStr::from_bytes(&entry.name));
+        let index_path = match prefix.is_empty() {
+            true => entry.name.clone(),
+            false => [prefix, b"/", &entry.name].concat(),
+        };
 
-        let item = std::ffi::CStr::from_bytes_with_nul(&buf)
-            .expect("should be null terminated string")
-            .to_str()
-            .context("mode and name in tree item is not valid UTF-8")?;
-
-        let (mode, name) = item
-            .split_once(' ')
-            .with_context(|| format!("parsing object mode and name from {item}"))?;
-
-        let mut hash = [0; 20];
-        tree_obj
-            .reader
-            .read_exact(&mut hash)
-            .context("reading sha hash of tree item")?;
-        let hash = hex::encode(hash);
-
-        let mut kind = ObjectType::Blob;
-        if mode.starts_with('4') {
-            kind = ObjectType::Tree;
+        match entry.kind() {
+            ObjectType::Tree => {
+                fs::create_dir(&path)
+                    .with_context(|| format!("creating dir {}", path.display()))?;
+                reconstruct_repo_files(odb, &path, &index_path, &entry.oid, index)
+                    .with_context(|| format!("witing content of dir {}", path.display()))?;
+                continue;
+            }
+            // submodules are not cloned, only their directory is created
+            ObjectType::Commit => {
+                fs::create_dir(&path)
+                    .with_context(|| format!("creating dir {}", path.display()))?;
+                index.add(Entry::new(index_path, entry.mode, entry.oid));
+                continue;
+            }
+            _ if entry.mode == tree::MODE_SYMLINK => {
+                let mut target = Vec::new();
+                ObjectFile::read(&entry.oid, odb)?
+                    .reader
+                    .read_to_end(&mut target)?;
+                std::os::unix::fs::symlink(OsStr::from_bytes(&target), &path)
+                    .with_context(|| format!("creating symlink {}", path.display()))?;
+            }
+            _ => {
+                let mut blob = ObjectFile::read(&entry.oid, odb)?;
+                let mut f = fs::File::create(&path)
+                    .with_context(|| format!("creating file {}", path.display()))?;
+                std::io::copy(&mut blob.reader, &mut f)
+                    .with_context(|| format!("witing content to file {}", path.display()))?;
+                if entry.mode == tree::MODE_EXECUTABLE {
+                    f.set_permissions(fs::Permissions::from_mode(0o755))
+                        .with_context(|| format!("making {} executable", path.display()))?;
+                }
+            }
         }
 
-        let mut path = PathBuf::from(current_dir);
-        path.push(name);
-
-        if kind == ObjectType::Tree {
-            fs::create_dir(&path).with_context(|| format!("creating dir {}", path.display()))?;
-            reconstruct_repo_files(clone_dir, &path, &hash)
-                .with_context(|| format!("witing content of dir {}", path.display()))?
-        } else {
-            let mut blob = ObjectFile::read(&hash, Some(clone_dir))?;
-            let mut f = fs::File::create(&path)
-                .with_context(|| format!("creating file {}", path.display()))?;
-            std::io::copy(&mut blob.reader, &mut f)
-                .with_context(|| format!("witing content to file {}", path.display()))?;
-        }
+        let metadata = fs::symlink_metadata(&path)
+            .with_context(|| format!("reading metadata of {}", path.display()))?;
+        index.add(Entry {
+            stat: Stat::from_metadata(&metadata),
+            ..Entry::new(index_path, entry.mode, entry.oid)
+        });
     }
 
     Ok(())
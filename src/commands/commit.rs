@@ -0,0 +1,258 @@
+use std::{
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::Context;
+
+use git_starter_rust::{
+    config,
+    index::{Index, IndexLock},
+    object::ObjectId,
+    pathspec::Pathspec,
+    refs::Expected,
+    repository::Repository,
+    status::{self, Branch, Status, UntrackedFiles},
+};
+
+use super::{add, status::write_long};
+
+// https://git-scm.com/docs/git-commit
+
+pub struct Options {
+    /// Paragraphs of the message
+    pub message: Vec<String>,
+    /// File to read the message from, `-` for standard input
+    pub file: Option<PathBuf>,
+    /// Stage modified and deleted tracked files first
+    pub all: bool,
+    /// Replace the commit at the tip of the current branch
+    pub amend: bool,
+    /// Allow commit with the same tree as its parent
+    pub allow_empty: bool,
+    /// Allow commit with empty message
+    pub allow_empty_message: bool,
+}
+
+/// git commit command
+pub fn invoke(options: Options) -> anyhow::Result<()> {
+    let repo = super::open_repository()?;
+    let lock = IndexLock::acquire(&repo)?;
+    let mut index = Index::read(repo.index_file(), repo.algorithm())?;
+    if options.all {
+        add::update_tracked(&repo, &mut index, &Pathspec::all())?;
+    }
+    anyhow::ensure!(
+        index.entries.iter().all(|entry| entry.stage == 0),
+        "Committing is not possible because you have unmerged files."
+    );
+
+    let branch = Branch::read(&repo)?;
+    let head = branch.oid.map(|oid| repo.read_commit(&oid)).transpose()?;
+    let merge_heads = read_merge_heads(&repo)?;
+    let merging = !merge_heads.is_empty() && !options.amend;
+
+    let (parents, author, initial_message) = if options.amend {
+        let head = head.as_ref().context("You have nothing to amend.")?;
+        let parents = head.parents.clone();
+        let message = String::from_utf8_lossy(&head.message).into_owned();
+        (parents, Some(head.author.clone()), message)
+    } else {
+        let parents = branch.oid.into_iter().chain(merge_heads).collect();
+        let message = match merging {
+            true => fs::read_to_string(repo.git_dir().join("MERGE_MSG")).unwrap_or_default(),
+            false => String::new(),
+        };
+        (parents, None, message)
+    };
+
+    let status = Status::collect(&repo, &mut index, UntrackedFiles::Normal)?;
+    let prefix = repo.work_tree_path(".")?;
+    if !status.has_staged() && !options.allow_empty && !options.amend && !merging {
+        // like git, the status explains why there is nothing to commit
+        let mut out = String::new();
+        write_long(
+            &mut out,
+            &repo,
+            &status,
+            &branch,
+            &prefix,
+            UntrackedFiles::Normal,
+            true,
+        )?;
+        print!("{out}");
+        drop(lock);
+        std::process::exit(1);
+    }
+
+    let edited = options.message.is_empty() && options.file.is_none();
+    let message = if !options.message.is_empty() {
+        options.message.join("\n\n")
+    } else if let Some(file) = &options.file {
+        read_message_file(file)?
+    } else {
+        let mut out = String::new();
+        write_long(
+            &mut out,
+            &repo,
+            &status,
+            &branch,
+            &prefix,
+            UntrackedFiles::Normal,
+            false,
+        )?;
+        edit_message(&repo, &initial_message, &out)?
+    };
+    let message = cleanup(&message, edited);
+    anyhow::ensure!(
+        !message.is_empty() || options.allow_empty_message,
+        "Aborting commit due to empty commit message."
+    );
+    if !edited {
+        let path = repo.git_dir().join("COMMIT_EDITMSG");
+        fs::write(&path, &message).with_context(|| format!("writing {}", path.display()))?;
+    }
+
+    let tree = index.write_tree(repo.odb(), false)?;
+    let committer = repo.committer()?;
+    let author = match author {
+        Some(author) => author,
+        None => repo.author()?,
+    };
+    let oid = repo.create_commit(&tree, &parents, &author, &committer, &message)?;
+
+    let subject = message.lines().next().unwrap_or_default();
+    let kind = if options.amend {
+        "commit (amend)"
+    } else if branch.oid.is_none() {
+        "commit (initial)"
+    } else if merging {
+        "commit (merge)"
+    } else {
+        "commit"
+    };
+    let log_message = format!("{kind}: {subject}");
+    // the branch is created by the first commit, detached HEAD is updated itself
+    let expected = match branch.oid {
+        Some(old) => Expected::Value(old),
+        None => Expected::Missing,
+    };
+    repo.update_ref("HEAD", &oid, expected, &log_message)?;
+
+    lock.commit(&index)?;
+    for file in ["MERGE_HEAD", "MERGE_MSG", "MERGE_MODE"] {
+        let _ = fs::remove_file(repo.git_dir().join(file));
+    }
+
+    let target = match &branch.name {
+        Some(name) => status::short_ref_name(name),
+        None => "detached HEAD",
+    };
+    let root = match parents.is_empty() {
+        true => " (root-commit)",
+        false => "",
+    };
+    println!("[{target}{root} {}] {subject}", &oid.to_string()[..7]);
+
+    Ok(())
+}
+
+/// Reads commits being merged from `MERGE_HEAD`
+fn read_merge_heads(repo: &Repository) -> anyhow::Result<Vec<ObjectId>> {
+    let path = repo.git_dir().join("MERGE_HEAD");
+    match fs::read_to_string(&path) {
+        Ok(content) => content.lines().map(|line| line.trim().parse()).collect(),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(err).with_context(|| format!("reading {}", path.display())),
+    }
+}
+
+fn read_message_file(file: &Path) -> anyhow::Result<String> {
+    let mut message = String::new();
+    if file == Path::new("-") {
+        std::io::stdin()
+            .read_to_string(&mut message)
+            .context("could not read log from standard input")?;
+    } else {
+        message = fs::read_to_string(file)
+            .with_context(|| format!("could not read log file '{}'", file.display()))?;
+    }
+    Ok(message)
+}
+
+/// Lets the user edit the initial message in `COMMIT_EDITMSG`, with the status shown
+/// as comments below it
+fn edit_message(repo: &Repository, initial: &str, status: &str) -> anyhow::Result<String> {
+    let mut template = initial.to_string();
+    template.push_str(
+        "\n# Please enter the commit message for your changes. Lines starting\n\
+         # with '#' will be ignored, and an empty message aborts the commit.\n#\n",
+    );
+    for line in status.lines() {
+        match line {
+            "" => template.push_str("#\n"),
+            line if line.starts_with('\t') => template.push_str(&format!("#{line}\n")),
+            line => template.push_str(&format!("# {line}\n")),
+        }
+    }
+
+    let path = repo.git_dir().join("COMMIT_EDITMSG");
+    fs::write(&path, template).with_context(|| format!("writing {}", path.display()))?;
+
+    let editor = editor(repo)?;
+    if editor != ":" {
+        // the editor may be given with arguments, as a shell command
+        let status = Command::new("sh")
+            .arg("-c")
+            .arg(format!("{editor} \"$@\""))
+            .arg(&editor)
+            .arg(&path)
+            .status()
+            .with_context(|| format!("unable to start editor '{editor}'"))?;
+        anyhow::ensure!(
+            status.success(),
+            "There was a problem with the editor '{editor}'."
+        );
+    }
+
+    fs::read_to_string(&path).with_context(|| format!("reading {}", path.display()))
+}
+
+/// Editor configured by `GIT_EDITOR`, `core.editor`, `VISUAL` or `EDITOR`
+fn editor(repo: &Repository) -> anyhow::Result<String> {
+    if let Ok(editor) = std::env::var("GIT_EDITOR") {
+        return Ok(editor);
+    }
+    if let Some(editor) = config::get(repo.git_dir(), "core.editor")? {
+        return Ok(editor);
+    }
+    Ok(std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string()))
+}
+
+/// Removes trailing whitespace, leading, trailing and repeated blank lines and, when
+/// `strip_comments`, lines starting with `#`
+fn cleanup(message: &str, strip_comments: bool) -> String {
+    let mut result = String::new();
+    let mut blank = false;
+    for line in message.lines() {
+        if strip_comments && line.starts_with('#') {
+            continue;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            blank = !result.is_empty();
+            continue;
+        }
+        if blank {
+            result.push('\n');
+            blank = false;
+        }
+        result.push_str(line);
+        result.push('\n');
+    }
+    result
+}
@@ -1,52 +1,106 @@
-use std::fmt::Write;
-use std::fs;
+use std::{fs, io::Read, path::PathBuf};
 
 use anyhow::Context;
 
-use crate::object::{Header, ObjectFile, ObjectType};
+use git_starter_rust::{
+    config, gpg,
+    object::{Commit, HashAlgorithm, ObjectId},
+};
 
-/// git hash-object command
-pub fn invoke(
-    tree_hash: &str,
-    message: &str,
-    parent_hash: Option<String>,
-) -> anyhow::Result<[u8; 20]> {
-    // check tree existence
-    let tree_path = ObjectFile::hash_to_path(tree_hash);
-    fs::metadata(&tree_path)
-        .with_context(|| format!("tree object does not exist: {}", tree_path.display()))?;
+// https://git-scm.com/docs/git-commit-tree
 
-    let mut commit = String::new();
-    writeln!(commit, "tree {tree_hash}")?;
-
-    if let Some(parent_hash) = parent_hash {
-        writeln!(commit, "parent {parent_hash}")?;
-    }
-
-    let time = std::time::SystemTime::now()
-        .duration_since(std::time::SystemTime::UNIX_EPOCH)
-        .context("current system time is before UNIX epoch")?;
-
-    let name = "crywolf";
-    let email = "cry.wolf@centrum.cz";
+pub struct Options {
+    /// Tree of the commit
+    pub tree: String,
+    /// Parent commits in order, duplicates are ignored
+    pub parents: Vec<String>,
+    /// Paragraphs of the message
+    pub message: Vec<String>,
+    /// Files with paragraphs of the message, `-` for standard input
+    pub file: Vec<PathBuf>,
+    /// Sign with the key, empty for the default key
+    pub gpg_sign: Option<String>,
+}
 
-    writeln!(commit, "author: {name} <{email}> {} +0000", time.as_secs())?;
+/// git commit-tree command
+pub fn invoke(options: Options) -> anyhow::Result<ObjectId> {
+    let repo = super::open_repository()?;
 
-    writeln!(
-        commit,
-        "committer {name} <{email}> {} +0000",
-        time.as_secs()
-    )?;
+    let resolve = |name: &str| {
+        super::resolve_revision(&repo, name)
+            .map_err(|_| anyhow::anyhow!("not a valid object name {name}"))
+    };
+    let tree = resolve(&options.tree)?;
+    let mut parents: Vec<ObjectId> = Vec::new();
+    for parent in &options.parents {
+        let parent = resolve(parent)?;
+        if parents.contains(&parent) {
+            eprintln!("error: duplicate parent {parent} ignored");
+            continue;
+        }
+        parents.push(parent);
+    }
 
-    writeln!(commit, "\n{message}")?;
+    // paragraphs are separated by empty lines and end with a newline, files are kept as is
+    let mut message = Vec::new();
+    for paragraph in options.message {
+        if !message.is_empty() {
+            message.push(b'\n');
+        }
+        message.extend(paragraph.into_bytes());
+        if message.last() != Some(&b'\n') {
+            message.push(b'\n');
+        }
+    }
+    for file in &options.file {
+        if !message.is_empty() {
+            message.push(b'\n');
+        }
+        if file.as_os_str() == "-" {
+            std::io::stdin()
+                .read_to_end(&mut message)
+                .context("git commit-tree: failed to read")?;
+        } else {
+            let content = fs::read(file)
+                .with_context(|| format!("could not open '{}' for reading", file.display()))?;
+            message.extend(content);
+        }
+    }
+    if message.is_empty() {
+        std::io::stdin()
+            .read_to_end(&mut message)
+            .context("git commit-tree: failed to read")?;
+    }
 
-    let mut object = ObjectFile {
-        header: Header {
-            typ: ObjectType::Commit,
-            size: commit.len(),
-        },
-        reader: std::io::Cursor::new(commit),
+    let committer = repo.committer()?;
+    let mut commit = Commit {
+        tree,
+        parents,
+        author: repo.author()?,
+        committer: committer.clone(),
+        extra_headers: Vec::new(),
+        message,
     };
 
-    object.write(None)
+    if let Some(key) = options.gpg_sign {
+        let key = match key.is_empty() {
+            true => match config::get(repo.git_dir(), "user.signingKey")? {
+                Some(key) => key,
+                None => format!("{} <{}>", committer.name, committer.email),
+            },
+            false => key,
+        };
+        let signature = gpg::sign(repo.git_dir(), &commit.serialize(), &key)
+            .context("failed to sign commit object")?;
+        let header = match repo.algorithm() {
+            HashAlgorithm::Sha1 => "gpgsig",
+            HashAlgorithm::Sha256 => "gpgsig-sha256",
+        };
+        commit.extra_headers.push((
+            header.to_string(),
+            signature.trim_end_matches('\n').to_string(),
+        ));
+    }
+
+    repo.write_commit(&commit)
 }
@@ -0,0 +1,237 @@
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use git_starter_rust::{
+    config::{self, Config, ConfigFile, Entry, Key, Origin, Scope},
+    repository::Repository,
+};
+
+// https://git-scm.com/docs/git-config
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Get,
+    GetAll,
+    Set,
+    Add,
+    ReplaceAll,
+    Unset,
+    UnsetAll,
+    List,
+}
+
+/// Config file to read or write instead of all of them
+pub enum Location {
+    System,
+    Global,
+    Local,
+    File(PathBuf),
+}
+
+/// Type values are checked against and shown in canonical form as
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Type {
+    Bool,
+    Int,
+    Path,
+}
+
+impl FromStr for Type {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bool" => Ok(Self::Bool),
+            "int" => Ok(Self::Int),
+            "path" => Ok(Self::Path),
+            _ => anyhow::bail!("unrecognized --type argument, {s}"),
+        }
+    }
+}
+
+pub struct Options {
+    pub action: Action,
+    pub location: Option<Location>,
+    pub typ: Option<Type>,
+    /// Prefix values with the file they come from
+    pub show_origin: bool,
+    pub name: Option<String>,
+    pub value: Option<String>,
+}
+
+/// git config command
+pub fn invoke(options: Options) -> anyhow::Result<()> {
+    let repo = super::open_repository().ok();
+
+    let path = match &options.location {
+        Some(Location::System) => {
+            Some(config::system_path().unwrap_or_else(|| PathBuf::from("/etc/gitconfig")))
+        }
+        Some(Location::Global) => Some(config::global_write_path()?),
+        Some(Location::Local) => Some(local_path(repo.as_ref())?),
+        Some(Location::File(path)) => Some(path.clone()),
+        None => None,
+    };
+    let name = || -> anyhow::Result<&str> {
+        options
+            .name
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("wrong number of arguments, should be 1"))
+    };
+
+    match options.action {
+        Action::Get | Action::GetAll | Action::List => {
+            let config = match &path {
+                Some(path) => Config::from_file(path, Scope::Local)?,
+                None => Config::load(repo.as_ref().map(Repository::git_dir))?,
+            };
+            let entries: Vec<&Entry> = match options.action {
+                Action::List => config.entries().iter().collect(),
+                Action::Get => config
+                    .entries_of(name()?)?
+                    .into_iter()
+                    .rev()
+                    .take(1)
+                    .collect(),
+                _ => config.entries_of(name()?)?,
+            };
+            if entries.is_empty() && options.action != Action::List {
+                std::process::exit(1);
+            }
+
+            for entry in entries {
+                if options.show_origin {
+                    print!("{}\t", origin(&entry.origin, repo.as_ref()));
+                }
+                match options.action {
+                    Action::List => match &entry.value {
+                        Some(value) => println!("{}={value}", entry.key),
+                        None => println!("{}", entry.key),
+                    },
+                    _ => println!(
+                        "{}",
+                        format(&entry.key, entry.value.as_deref(), options.typ)?
+                    ),
+                }
+            }
+        }
+        Action::Set | Action::Add | Action::ReplaceAll => {
+            let name = name()?;
+            let value = options
+                .value
+                .as_deref()
+                .ok_or_else(|| anyhow::anyhow!("wrong number of arguments, should be 2"))?;
+            // values are stored in canonical form of the type, paths as given
+            let value = match options.typ {
+                Some(typ @ (Type::Bool | Type::Int)) => format(name, Some(value), Some(typ))?,
+                _ => value.to_string(),
+            };
+
+            let path = match path {
+                Some(path) => path,
+                None => local_path(repo.as_ref())?,
+            };
+            let mut file = ConfigFile::read(&path)?;
+            let key = Key::parse(name)?.to_string();
+            let count = file
+                .variables()
+                .filter(|variable| variable.key == key)
+                .count();
+            if options.action == Action::Set && count > 1 {
+                eprintln!("warning: {key} has multiple values");
+                eprintln!(
+                    "error: cannot overwrite multiple values with a single value\n       \
+                     Use a regexp, --add or --replace-all to change {key}."
+                );
+                std::process::exit(5);
+            }
+            file.set(
+                name,
+                &value,
+                options.action == Action::Add,
+                options.action == Action::ReplaceAll,
+            )?;
+            write(&file, &path, repo.as_ref())?;
+        }
+        Action::Unset | Action::UnsetAll => {
+            let name = name()?;
+            let path = match path {
+                Some(path) => path,
+                None => local_path(repo.as_ref())?,
+            };
+            let mut file = ConfigFile::read(&path)?;
+            let key = Key::parse(name)?.to_string();
+            let count = file
+                .variables()
+                .filter(|variable| variable.key == key)
+                .count();
+            if options.action == Action::Unset && count > 1 {
+                eprintln!("warning: {key} has multiple values");
+                std::process::exit(5);
+            }
+            if count == 0 {
+                std::process::exit(5);
+            }
+            file.unset(name, true)?;
+            write(&file, &path, repo.as_ref())?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes the file, which gets permissions given by `core.sharedRepository`
+fn write(file: &ConfigFile, path: &Path, repo: Option<&Repository>) -> anyhow::Result<()> {
+    file.write(path)?;
+    match repo {
+        Some(repo) => repo.shared().adjust(path),
+        None => Ok(()),
+    }
+}
+
+/// Config file of the repository
+fn local_path(repo: Option<&Repository>) -> anyhow::Result<PathBuf> {
+    let repo = repo.ok_or_else(|| anyhow::anyhow!("not in a git directory"))?;
+    Ok(repo.git_dir().join("config"))
+}
+
+/// Shows files of the repository relative to its work tree, as git runs at its top
+fn origin(origin: &Origin, repo: Option<&Repository>) -> String {
+    let work_tree = repo.and_then(Repository::work_tree);
+    match (origin, work_tree) {
+        (Origin::File(path), Some(work_tree)) => match path.strip_prefix(work_tree) {
+            Ok(relative) => Origin::File(relative.to_path_buf()).to_string(),
+            Err(_) => origin.to_string(),
+        },
+        _ => origin.to_string(),
+    }
+}
+
+/// Value of the key in canonical form of the type
+fn format(key: &str, value: Option<&str>, typ: Option<Type>) -> anyhow::Result<String> {
+    Ok(match typ {
+        None => value.unwrap_or_default().to_string(),
+        Some(Type::Bool) => config::parse_bool(value)
+            .map_err(|_| {
+                anyhow::anyhow!(
+                    "bad boolean config value '{}' for '{key}'",
+                    value.unwrap_or_default()
+                )
+            })?
+            .to_string(),
+        Some(Type::Int) => config::parse_int(value.unwrap_or_default())
+            .map_err(|err| {
+                anyhow::anyhow!(
+                    "bad numeric config value '{}' for '{key}': {err}",
+                    value.unwrap_or_default()
+                )
+            })?
+            .to_string(),
+        Some(Type::Path) => {
+            let value = value.ok_or_else(|| anyhow::anyhow!("missing value for '{key}'"))?;
+            config::expand_path(value)?.display().to_string()
+        }
+    })
+}
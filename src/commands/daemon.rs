@@ -0,0 +1,134 @@
+use std::{
+    io::{BufReader, Write},
+    net::{TcpListener, TcpStream},
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::Context;
+
+use git_starter_rust::pkt_line;
+
+use super::upload_pack;
+
+// https://git-scm.com/docs/git-daemon
+// https://github.com/git/git/blob/795ea8776befc95ea2becd8020c7a284677b4161/Documentation/gitprotocol-pack.txt#L74
+
+/// File that marks repository as exported when `--export-all` is not used
+const EXPORT_OK_FILE: &str = "git-daemon-export-ok";
+
+pub struct Options {
+    pub base_path: Option<PathBuf>,
+    pub export_all: bool,
+    pub listen: String,
+    pub port: u16,
+    pub verbose: bool,
+}
+
+/// git daemon command
+pub fn invoke(options: Options) -> anyhow::Result<()> {
+    let addr = format!("{}:{}", options.listen, options.port);
+    let listener = TcpListener::bind(&addr).with_context(|| format!("listening on {addr}"))?;
+
+    if options.verbose {
+        eprintln!("Ready to rumble on {addr}");
+    }
+
+    std::thread::scope(|s| {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(err) => {
+                    eprintln!("accepting connection: {err}");
+                    continue;
+                }
+            };
+
+            let options = &options;
+            s.spawn(move || {
+                let peer = stream
+                    .peer_addr()
+                    .map(|addr| addr.to_string())
+                    .unwrap_or_else(|_| "unknown".to_string());
+                if let Err(err) = handle_connection(stream, options) {
+                    eprintln!("[{peer}] {err:#}");
+                }
+            });
+        }
+    });
+
+    Ok(())
+}
+
+fn handle_connection(stream: TcpStream, options: &Options) -> anyhow::Result<()> {
+    let mut writer = stream.try_clone().context("cloning connection stream")?;
+    let mut reader = BufReader::new(stream);
+
+    // git-upload-pack /project.git\0host=myserver.com\0\0version=2\0
+    let request = pkt_line::read(&mut reader)?.context("connection closed before request")?;
+    let pkt_line::Packet::Data(request) = request else {
+        anyhow::bail!("protocol error: expected request line");
+    };
+    let request = std::str::from_utf8(&request).context("request is not valid UTF-8")?;
+
+    let mut fields = request.split('\0');
+    let command = fields.next().unwrap_or_default().trim_end_matches('\n');
+    let _host = fields.next();
+    // extra parameters follow after an additional NUL byte
+    let version = upload_pack::protocol_version(fields.filter(|field| !field.is_empty()));
+
+    let (service, path) = command
+        .split_once(' ')
+        .with_context(|| format!("protocol error: malformed request '{command}'"))?;
+
+    if options.verbose {
+        eprintln!("Request {service} for '{path}'");
+    }
+
+    if service != "git-upload-pack" {
+        return deny(&mut writer, &format!("service not enabled: '{service}'"));
+    }
+
+    let Some(git_dir) = resolve_path(path, options.base_path.as_deref(), options.export_all) else {
+        return deny(
+            &mut writer,
+            &format!("access denied or repository not exported: {path}"),
+        );
+    };
+
+    upload_pack::serve(
+        &git_dir,
+        version,
+        upload_pack::Mode::Stateful,
+        &mut reader,
+        &mut writer,
+    )
+}
+
+/// Maps requested path to git directory of an exported repository
+pub fn resolve_path(path: &str, base_path: Option<&Path>, export_all: bool) -> Option<PathBuf> {
+    let path = Path::new(path);
+
+    // reject relative paths and attempts to escape the base path
+    if !path.has_root() || path.components().any(|c| c == Component::ParentDir) {
+        return None;
+    }
+
+    let path = match base_path {
+        Some(base_path) => base_path.join(path.strip_prefix("/").ok()?),
+        None => path.to_path_buf(),
+    };
+
+    let git_dir = upload_pack::enter_repo(&path)?;
+
+    if !export_all && !git_dir.join(EXPORT_OK_FILE).is_file() {
+        return None;
+    }
+
+    Some(git_dir)
+}
+
+fn deny(writer: &mut impl Write, message: &str) -> anyhow::Result<()> {
+    pkt_line::write_str(writer, &format!("ERR {message}"))?;
+    writer.flush()?;
+    anyhow::bail!("{message}")
+}
@@ -0,0 +1,315 @@
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    io::prelude::*,
+};
+
+use anyhow::Context;
+
+use git_starter_rust::{
+    object::{tree, Commit, HashAlgorithm, ObjectFile, ObjectId, ObjectType, Tag, Tree},
+    odb::{loose, Loose, ObjectDatabase, Packed},
+    pack::{self, PackIndex},
+    refs, rev_list,
+};
+
+// https://git-scm.com/docs/git-fsck
+
+pub struct Options {
+    /// Print objects that exist but are not reachable from any reference
+    pub unreachable: bool,
+    /// Do not print dangling objects
+    pub no_dangling: bool,
+}
+
+/// Object present in the database with the objects it points to and their expected types
+struct Checked {
+    typ: ObjectType,
+    links: Vec<(ObjectId, ObjectType)>,
+}
+
+/// git fsck command
+pub fn invoke(options: Options) -> anyhow::Result<()> {
+    let repo = super::open_repository()?;
+    let git_dir = repo.git_dir();
+    let algorithm = repo.algorithm();
+
+    let mut fsck = Fsck {
+        algorithm,
+        objects: HashMap::new(),
+        errors: 0,
+    };
+
+    // objects borrowed from alternates are checked as well
+    let objects_dirs = std::iter::once(repo.objects_dir()).chain(repo.alternates().to_vec());
+    for objects_dir in objects_dirs {
+        // loose and packed copies of objects are checked separately
+        let loose = Loose::new(&objects_dir, algorithm);
+        let packed = Packed::new(&objects_dir, algorithm);
+
+        for (id, path) in loose::list(&objects_dir)? {
+            match read_object(&id, &loose) {
+                Ok((typ, data)) => {
+                    let mut hasher = algorithm.hasher();
+                    hasher.update(format!("{typ} {}\0", data.len()).as_bytes());
+                    hasher.update(&data);
+                    match hasher.try_finalize() {
+                        Ok(hash) if hash == id => fsck.check(id, typ, &data),
+                        Ok(_) => fsck.error(format!(
+                            "hash mismatch for {} (expected {id})",
+                            path.display()
+                        )),
+                        Err(err) => fsck.error(format!("{}: {err}", path.display())),
+                    }
+                }
+                Err(err) => fsck.error(format!("{id}: object corrupt or missing: {err:#}")),
+            }
+        }
+
+        for path in pack::packs(&objects_dir)? {
+            match pack::verify(&path, algorithm) {
+                Ok(objects) => {
+                    for (id, typ, data) in objects {
+                        if !fsck.objects.contains_key(&id) {
+                            fsck.check(id, typ, &data);
+                        }
+                    }
+                }
+                Err(err) => {
+                    fsck.error(format!(
+                        "{}: {err:#}",
+                        path.with_extension("pack").display()
+                    ));
+                    // check objects that can still be read from the broken pack
+                    if let Ok(index) = PackIndex::read(&path.with_extension("idx"), algorithm) {
+                        for id in index.names() {
+                            if fsck.objects.contains_key(id) {
+                                continue;
+                            }
+                            match read_object(id, &packed) {
+                                Ok((typ, data)) => fsck.check(*id, typ, &data),
+                                Err(err) => fsck.error(format!("{id}: object corrupt: {err:#}")),
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // references to missing objects
+    for r in refs::list(git_dir)? {
+        if !fsck.objects.contains_key(&r.hash) {
+            fsck.error(format!("{}: invalid sha1 pointer {}", r.name, r.hash));
+        }
+    }
+    match refs::resolve(git_dir, "HEAD")? {
+        Some(hash) if !fsck.objects.contains_key(&hash) => {
+            fsck.error(format!("HEAD: invalid sha1 pointer {hash}"))
+        }
+        Some(_) => {}
+        None => eprintln!("notice: HEAD points to an unborn branch"),
+    }
+
+    let reachable = fsck.connectivity(&rev_list::roots(&repo)?);
+
+    // dangling objects are unreachable objects not referenced by other unreachable objects
+    let referenced: HashSet<&ObjectId> = fsck
+        .objects
+        .iter()
+        .filter(|(id, _)| !reachable.contains(id))
+        .flat_map(|(_, checked)| checked.links.iter().map(|(id, _)| id))
+        .collect();
+    let mut unreachable: Vec<_> = fsck
+        .objects
+        .iter()
+        .filter(|(id, _)| !reachable.contains(id))
+        .collect();
+    unreachable.sort_by(|a, b| a.0.cmp(b.0));
+    for (hash, checked) in unreachable {
+        if options.unreachable {
+            println!("unreachable {} {hash}", checked.typ);
+        } else if !options.no_dangling && !referenced.contains(hash) {
+            println!("dangling {} {hash}", checked.typ);
+        }
+    }
+
+    anyhow::ensure!(fsck.errors == 0, "fsck found {} problems", fsck.errors);
+
+    Ok(())
+}
+
+/// Reads object data, checking its size against the size declared in the header
+fn read_object(id: &ObjectId, odb: &dyn ObjectDatabase) -> anyhow::Result<(ObjectType, Vec<u8>)> {
+    let mut object = ObjectFile::read(id, odb)?;
+    let mut data = Vec::new();
+    object
+        .reader
+        .read_to_end(&mut data)
+        .context("decompressing object")?;
+    anyhow::ensure!(
+        data.len() == object.header.size,
+        "object size {} differs from size {} in header",
+        data.len(),
+        object.header.size
+    );
+    Ok((object.header.typ, data))
+}
+
+struct Fsck {
+    algorithm: HashAlgorithm,
+    objects: HashMap<ObjectId, Checked>,
+    errors: usize,
+}
+
+impl Fsck {
+    fn error(&mut self, message: String) {
+        eprintln!("error: {message}");
+        self.errors += 1;
+    }
+
+    fn object_error(&mut self, typ: &ObjectType, hash: &ObjectId, err: anyhow::Error) {
+        eprintln!("error in {typ} {hash}: {err:#}");
+        self.errors += 1;
+    }
+
+    /// Validates content of the object and records it
+    fn check(&mut self, hash: ObjectId, typ: ObjectType, data: &[u8]) {
+        let links = match typ {
+            ObjectType::Tree => check_tree(data, self.algorithm),
+            ObjectType::Commit => check_commit(data),
+            ObjectType::Tag => check_tag(data),
+            _ => Ok(Vec::new()),
+        };
+
+        match links {
+            Ok(links) => {
+                self.objects.insert(hash, Checked { typ, links });
+            }
+            Err(err) => {
+                self.object_error(&typ, &hash, err);
+                self.objects.insert(
+                    hash,
+                    Checked {
+                        typ,
+                        links: Vec::new(),
+                    },
+                );
+            }
+        }
+    }
+
+    /// Walks objects reachable from `roots`, reporting missing objects and links to objects
+    /// of unexpected type. Returns the set of reachable objects.
+    fn connectivity(&mut self, roots: &[ObjectId]) -> HashSet<ObjectId> {
+        let mut reachable = HashSet::new();
+        let mut stack: Vec<_> = roots.to_vec();
+
+        while let Some(hash) = stack.pop() {
+            if !reachable.insert(hash) {
+                continue;
+            }
+            let Some(checked) = self.objects.get(&hash) else {
+                continue;
+            };
+
+            let mut problems = Vec::new();
+            for (link, expected) in &checked.links {
+                match self.objects.get(link) {
+                    Some(target) if target.typ != *expected => problems.push(format!(
+                        "broken link from {} {hash} to {expected} {link}: object is a {}",
+                        checked.typ, target.typ
+                    )),
+                    Some(_) => stack.push(*link),
+                    None => {
+                        println!("missing {expected} {link}");
+                        problems.push(format!(
+                            "broken link from {} {hash} to {expected} {link}",
+                            checked.typ
+                        ));
+                    }
+                }
+            }
+            for problem in problems {
+                self.error(problem);
+            }
+        }
+
+        reachable
+    }
+}
+
+/// Checks that entries have valid modes and names and are sorted as git sorts them
+fn check_tree(
+    data: &[u8],
+    algorithm: HashAlgorithm,
+) -> anyhow::Result<Vec<(ObjectId, ObjectType)>> {
+    let tree = Tree::parse(data, algorithm)?;
+
+    for (i, entry) in tree.entries.iter().enumerate() {
+        anyhow::ensure!(
+            matches!(
+                entry.mode,
+                tree::MODE_FILE
+                    | tree::MODE_EXECUTABLE
+                    | tree::MODE_SYMLINK
+                    | tree::MODE_TREE
+                    | tree::MODE_GITLINK
+            ),
+            "badFilemode: contains bad file mode {:o}",
+            entry.mode
+        );
+
+        let name = entry.name.as_slice();
+        anyhow::ensure!(!name.is_empty(), "emptyName: contains empty pathname");
+        anyhow::ensure!(
+            !name.contains(&b'/'),
+            "fullPathname: contains full pathnames"
+        );
+        anyhow::ensure!(
+            name != b"." && name != b"..",
+            "hasDot: contains '.' or '..'"
+        );
+        anyhow::ensure!(
+            !name.eq_ignore_ascii_case(b".git"),
+            "hasDotgit: contains '.git'"
+        );
+
+        if let Some(previous) = i.checked_sub(1).map(|i| &tree.entries[i]) {
+            // a file and a directory of the same name are duplicates too
+            anyhow::ensure!(
+                previous.name != entry.name,
+                "duplicateEntries: contains duplicate file entries"
+            );
+            anyhow::ensure!(
+                previous.git_cmp(entry) == Ordering::Less,
+                "treeNotSorted: not properly sorted"
+            );
+        }
+    }
+
+    // submodule commits live in other repositories
+    Ok(tree
+        .entries
+        .iter()
+        .filter(|entry| entry.mode != tree::MODE_GITLINK)
+        .map(|entry| (entry.oid, entry.kind()))
+        .collect())
+}
+
+fn check_commit(data: &[u8]) -> anyhow::Result<Vec<(ObjectId, ObjectType)>> {
+    let commit = Commit::parse(data)?;
+    Ok(std::iter::once((commit.tree, ObjectType::Tree))
+        .chain(
+            commit
+                .parents
+                .iter()
+                .map(|parent| (*parent, ObjectType::Commit)),
+        )
+        .collect())
+}
+
+fn check_tag(data: &[u8]) -> anyhow::Result<Vec<(ObjectId, ObjectType)>> {
+    let tag = Tag::parse(data)?;
+    Ok(vec![(tag.object, tag.typ)])
+}
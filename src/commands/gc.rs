@@ -0,0 +1,22 @@
+use super::{prune, repack};
+
+// https://git-scm.com/docs/git-gc
+
+/// git gc command
+pub fn invoke(prune_expire: &str, aggressive: bool) -> anyhow::Result<()> {
+    let repo = super::open_repository()?;
+    let expire = prune::parse_expire(prune_expire)?;
+
+    // unreachable objects of old packs are kept loose, unless they would be pruned right away
+    let prune_now = expire.is_some_and(|expire| expire >= std::time::SystemTime::now());
+    let options = repack::Options {
+        all: true,
+        loosen_unreachable: !prune_now,
+        delete: true,
+        window: if aggressive { 250 } else { 10 },
+        depth: 50,
+    };
+    repack::repack(&repo, &options)?;
+
+    prune::prune(&repo, expire, false, false)
+}
@@ -1,17 +1,20 @@
 use std::path::Path;
 
-use crate::object::ObjectFile;
+use git_starter_rust::object::{ObjectFile, ObjectId};
 
 /// git hash-object command
-pub fn invoke(path: impl AsRef<Path>, write: bool) -> anyhow::Result<[u8; 20]> {
+pub fn invoke(path: impl AsRef<Path>, write: bool) -> anyhow::Result<ObjectId> {
     let mut object = ObjectFile::from_file(path)?;
 
     let hash = if write {
         // compress and write to disk
-        object.write(None)?
+        object.write(super::open_repository()?.odb())?
     } else {
-        // just compute object hash
-        object.hash()?
+        // just compute object hash, outside of a repository with the default algorithm
+        let algorithm = super::open_repository()
+            .map(|repo| repo.algorithm())
+            .unwrap_or_default();
+        object.hash(algorithm)?
     };
 
     Ok(hash)
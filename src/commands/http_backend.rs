@@ -0,0 +1,327 @@
+use std::{
+    io::{prelude::*, BufReader, BufWriter},
+    net::{TcpListener, TcpStream},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use flate2::read::GzDecoder;
+
+use git_starter_rust::pkt_line;
+
+use super::{
+    daemon, receive_pack,
+    upload_pack::{self, Mode},
+};
+
+// https://www.git-scm.com/docs/http-protocol
+// https://git-scm.com/docs/git-http-backend
+
+pub struct Options {
+    pub base_path: PathBuf,
+    pub export_all: bool,
+    pub listen: String,
+    pub port: u16,
+    pub receive_pack: bool,
+    pub verbose: bool,
+}
+
+/// HTTP server implementing the smart HTTP protocol (git http-backend)
+pub fn invoke(options: Options) -> anyhow::Result<()> {
+    let addr = format!("{}:{}", options.listen, options.port);
+    let listener = TcpListener::bind(&addr).with_context(|| format!("listening on {addr}"))?;
+
+    if options.verbose {
+        eprintln!("Listening on http://{addr}");
+    }
+
+    std::thread::scope(|s| {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(err) => {
+                    eprintln!("accepting connection: {err}");
+                    continue;
+                }
+            };
+
+            let options = &options;
+            s.spawn(move || {
+                let peer = stream
+                    .peer_addr()
+                    .map(|addr| addr.to_string())
+                    .unwrap_or_else(|_| "unknown".to_string());
+                if let Err(err) = handle_connection(stream, options) {
+                    eprintln!("[{peer}] {err:#}");
+                }
+            });
+        }
+    });
+
+    Ok(())
+}
+
+struct Request {
+    method: String,
+    path: String,
+    query: String,
+    headers: Vec<(String, String)>,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+fn handle_connection(stream: TcpStream, options: &Options) -> anyhow::Result<()> {
+    let mut writer = BufWriter::new(stream.try_clone().context("cloning connection stream")?);
+    let mut reader = BufReader::new(stream);
+
+    let request = read_request(&mut reader)?;
+    if options.verbose {
+        eprintln!("{} {}?{}", request.method, request.path, request.query);
+    }
+
+    let (repo_path, action) = ["/info/refs", "/git-upload-pack", "/git-receive-pack"]
+        .iter()
+        .find_map(|action| {
+            request
+                .path
+                .strip_suffix(action)
+                .map(|repo_path| (repo_path, *action))
+        })
+        .unwrap_or((&request.path, ""));
+
+    let Some(git_dir) =
+        daemon::resolve_path(repo_path, Some(&options.base_path), options.export_all)
+    else {
+        return respond_error(&mut writer, "404 Not Found", "Repository not found");
+    };
+
+    // protocol v2 is requested in Git-Protocol header
+    let version = upload_pack::protocol_version(
+        request
+            .header("Git-Protocol")
+            .unwrap_or_default()
+            .split(':'),
+    );
+
+    match (request.method.as_str(), action) {
+        ("GET", "/info/refs") => {
+            let service = request
+                .query
+                .split('&')
+                .find_map(|param| param.strip_prefix("service="))
+                .unwrap_or_default();
+            if !is_enabled(service, options) {
+                return respond_error(&mut writer, "403 Forbidden", "Service not enabled");
+            }
+
+            respond_ok(
+                &mut writer,
+                &format!("application/x-{service}-advertisement"),
+            )?;
+            if service == "git-upload-pack" && version == 2 {
+                // protocol v2 starts directly with capability advertisement
+                upload_pack::serve(&git_dir, 2, Mode::AdvertiseRefs, &mut reader, &mut writer)?;
+            } else {
+                pkt_line::write_str(&mut writer, &format!("# service={service}\n"))?;
+                pkt_line::flush(&mut writer)?;
+                serve(
+                    service,
+                    &git_dir,
+                    0,
+                    Mode::AdvertiseRefs,
+                    &mut reader,
+                    &mut writer,
+                )?;
+            }
+        }
+        ("POST", "/git-upload-pack" | "/git-receive-pack") => {
+            let service = &action[1..];
+            if !is_enabled(service, options) {
+                return respond_error(&mut writer, "403 Forbidden", "Service not enabled");
+            }
+
+            if request.header("Expect") == Some("100-continue") {
+                writer.write_all(b"HTTP/1.1 100 Continue\r\n\r\n")?;
+                writer.flush()?;
+            }
+            let body = read_body(&mut reader, &request)?;
+
+            respond_ok(&mut writer, &format!("application/x-{service}-result"))?;
+            let mut body = BufReader::new(body.as_slice());
+            serve(
+                service,
+                &git_dir,
+                version,
+                Mode::StatelessRpc,
+                &mut body,
+                &mut writer,
+            )?;
+        }
+        _ => return respond_error(&mut writer, "404 Not Found", "Not found"),
+    }
+
+    writer.flush().context("flushing response")
+}
+
+fn is_enabled(service: &str, options: &Options) -> bool {
+    match service {
+        "git-upload-pack" => true,
+        "git-receive-pack" => options.receive_pack,
+        _ => false,
+    }
+}
+
+fn serve(
+    service: &str,
+    git_dir: &Path,
+    version: u8,
+    mode: Mode,
+    reader: &mut impl BufRead,
+    writer: &mut impl Write,
+) -> anyhow::Result<()> {
+    if service == "git-receive-pack" {
+        receive_pack::serve(git_dir, mode, reader, writer)
+    } else {
+        upload_pack::serve(git_dir, version, mode, reader, writer)
+    }
+}
+
+fn read_request(reader: &mut impl BufRead) -> anyhow::Result<Request> {
+    let mut line = String::new();
+    reader
+        .read_line(&mut line)
+        .context("reading request line")?;
+
+    // GET /repo.git/info/refs?service=git-upload-pack HTTP/1.1
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        anyhow::bail!("malformed request line '{}'", line.trim_end());
+    };
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+    let mut request = Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        query: query.to_string(),
+        headers: Vec::new(),
+    };
+
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).context("reading header")?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        let (name, value) = line
+            .split_once(':')
+            .with_context(|| format!("malformed header '{line}'"))?;
+        request
+            .headers
+            .push((name.to_string(), value.trim().to_string()));
+    }
+
+    Ok(request)
+}
+
+/// Largest request body accepted, after decompression too
+const MAX_BODY_SIZE: u64 = 1 << 30;
+
+/// Largest chunk of a chunked request body
+const MAX_CHUNK_SIZE: u64 = 16 << 20;
+
+/// Reads request body sent with Content-Length or chunked encoding, decompressing gzip if needed
+fn read_body(reader: &mut impl BufRead, request: &Request) -> anyhow::Result<Vec<u8>> {
+    let mut body = Vec::new();
+
+    if request
+        .header("Transfer-Encoding")
+        .is_some_and(|encoding| encoding.eq_ignore_ascii_case("chunked"))
+    {
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).context("reading chunk size")?;
+            let size = line.trim_end().split(';').next().unwrap_or_default();
+            let size = u64::from_str_radix(size, 16)
+                .with_context(|| format!("malformed chunk size '{size}'"))?;
+            anyhow::ensure!(size <= MAX_CHUNK_SIZE, "chunk of {size} bytes is too large");
+            anyhow::ensure!(
+                (body.len() as u64)
+                    .checked_add(size)
+                    .is_some_and(|total| total <= MAX_BODY_SIZE),
+                "request body is too large"
+            );
+
+            if size == 0 {
+                // skip trailer
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).context("reading trailer")?;
+                    if line.trim_end().is_empty() {
+                        break;
+                    }
+                }
+                break;
+            }
+
+            let read = reader
+                .take(size)
+                .read_to_end(&mut body)
+                .context("reading chunk")?;
+            anyhow::ensure!(read as u64 == size, "chunk truncated");
+            let mut crlf = [0; 2];
+            reader.read_exact(&mut crlf).context("reading chunk end")?;
+        }
+    } else {
+        let length = request
+            .header("Content-Length")
+            .context("missing Content-Length header")?
+            .parse::<u64>()
+            .context("malformed Content-Length header")?;
+        anyhow::ensure!(length <= MAX_BODY_SIZE, "request body is too large");
+        reader
+            .take(length)
+            .read_to_end(&mut body)
+            .context("reading body")?;
+    }
+
+    if request.header("Content-Encoding") == Some("gzip") {
+        let mut decompressed = Vec::new();
+        GzDecoder::new(body.as_slice())
+            .take(MAX_BODY_SIZE + 1)
+            .read_to_end(&mut decompressed)
+            .context("decompressing body")?;
+        anyhow::ensure!(
+            decompressed.len() as u64 <= MAX_BODY_SIZE,
+            "request body is too large"
+        );
+        body = decompressed;
+    }
+
+    Ok(body)
+}
+
+/// Writes headers of successful response, body is terminated by closing the connection
+fn respond_ok(writer: &mut impl Write, content_type: &str) -> anyhow::Result<()> {
+    write!(
+        writer,
+        "HTTP/1.1 200 OK\r\nContent-Type: {content_type}\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n"
+    )?;
+    Ok(())
+}
+
+fn respond_error(writer: &mut impl Write, status: &str, message: &str) -> anyhow::Result<()> {
+    write!(
+        writer,
+        "HTTP/1.1 {status}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{message}\n",
+        message.len() + 1
+    )?;
+    writer.flush().context("flushing response")
+}
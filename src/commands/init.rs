@@ -1,33 +1,80 @@
-use std::{
-    fs,
-    path::{Path, PathBuf},
+use std::path::PathBuf;
+
+use git_starter_rust::{
+    config::Config,
+    repository::{InitOptions, SharedRepository},
+    HashAlgorithm, Repository,
 };
 
-use anyhow::Context;
+// https://git-scm.com/docs/git-init
+
+pub struct Options {
+    /// Directory of the repository, the current one by default
+    pub dir: Option<PathBuf>,
+    pub bare: bool,
+    pub object_format: Option<HashAlgorithm>,
+    pub initial_branch: Option<String>,
+    /// Template directory, empty for none
+    pub template: Option<String>,
+    pub separate_git_dir: Option<PathBuf>,
+    pub shared: Option<SharedRepository>,
+    /// Print only errors and warnings
+    pub quiet: bool,
+}
 
-// https://blog.meain.io/2023/what-is-in-dot-git/
+const DEFAULT_BRANCH_HINT: &str = "\
+hint: Using 'master' as the name for the initial branch. This default branch name
+hint: is subject to change. To configure the initial branch name to use in all
+hint: of your new repositories, which will suppress this warning, call:
+hint: 
+hint: \tgit config --global init.defaultBranch <name>
+hint: 
+hint: Names commonly chosen instead of 'master' are 'main', 'trunk' and
+hint: 'development'. The just-created branch can be renamed via this command:
+hint: 
+hint: \tgit branch -m <name>
+";
 
 /// git init command
-pub fn invoke() -> anyhow::Result<()> {
-    create_git_dirs(None).context("creating git directories")?;
-    println!("Initialized git directory");
-    Ok(())
-}
+pub fn invoke(options: Options) -> anyhow::Result<()> {
+    let dir = options.dir.unwrap_or_else(|| PathBuf::from("."));
+    let branch_configured = options.initial_branch.is_some()
+        || Config::load(None)?.get("init.defaultBranch")?.is_some();
 
-pub fn create_git_dirs(custom_dir: Option<&Path>) -> anyhow::Result<()> {
-    let parent = match custom_dir {
-        Some(custom_dir) => {
-            fs::create_dir(custom_dir)?;
-            PathBuf::from(custom_dir)
-        }
-        None => PathBuf::new(),
+    let init_options = InitOptions {
+        bare: options.bare,
+        algorithm: options.object_format,
+        initial_branch: options.initial_branch.clone(),
+        template: options.template.map(PathBuf::from),
+        separate_git_dir: options.separate_git_dir,
+        shared: options.shared,
     };
+    let (repo, reinitialized) = Repository::init_with(&dir, &init_options)?;
+
+    if reinitialized {
+        if let Some(branch) = &options.initial_branch {
+            eprintln!("warning: re-init: ignored --initial-branch={branch}");
+        }
+    } else if !branch_configured && !options.quiet {
+        eprint!("{DEFAULT_BRANCH_HINT}");
+    }
 
-    fs::create_dir(parent.join(".git"))?;
-    fs::create_dir(parent.join(".git/objects"))?;
-    fs::create_dir(parent.join(".git/refs"))?;
-    fs::create_dir(parent.join(".git/refs/heads"))?;
-    fs::create_dir(parent.join(".git/refs/tags"))?;
-    fs::write(parent.join(".git/HEAD"), "ref: refs/heads/master\n")?;
+    if !options.quiet {
+        let shared = match options.shared {
+            Some(SharedRepository::Umask) | None => "",
+            Some(_) => "shared ",
+        };
+        let git_dir = repo.git_dir().canonicalize()?;
+        match reinitialized {
+            true => println!(
+                "Reinitialized existing {shared}Git repository in {}/",
+                git_dir.display()
+            ),
+            false => println!(
+                "Initialized empty {shared}Git repository in {}/",
+                git_dir.display()
+            ),
+        }
+    }
     Ok(())
 }
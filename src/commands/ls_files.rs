@@ -0,0 +1,77 @@
+use git_starter_rust::{
+    index::{FileState, Index},
+    status,
+};
+
+// https://git-scm.com/docs/git-ls-files
+
+pub struct Options {
+    /// Show files in the index (the default)
+    pub cached: bool,
+    /// Show mode, object name and stage of files in the index
+    pub stage: bool,
+    /// Show files whose work tree content differs from the index
+    pub modified: bool,
+    /// Show files missing in the work tree
+    pub deleted: bool,
+    /// Show files in the work tree that are not in the index
+    pub others: bool,
+}
+
+/// git ls-files command
+pub fn invoke(options: Options) -> anyhow::Result<()> {
+    let repo = super::open_repository()?;
+    let index = Index::read(repo.index_file(), repo.algorithm())?;
+
+    // only files below the current directory are listed, relative to it
+    let mut prefix = repo.work_tree_path(".")?;
+    if !prefix.is_empty() {
+        prefix.push(b'/');
+    }
+    let show = |path: &[u8]| {
+        println!(
+            "{}",
+            String::from_utf8_lossy(path.strip_prefix(prefix.as_slice()).unwrap_or(path))
+        )
+    };
+
+    let entries = index
+        .entries
+        .iter()
+        .filter(|entry| entry.path.starts_with(&prefix));
+    let cached =
+        options.cached || !(options.stage || options.modified || options.deleted || options.others);
+
+    if options.others {
+        let mut files = Vec::new();
+        status::collect_files(&repo, None, &prefix, &mut files)?;
+        files.sort();
+        for path in files.iter().filter(|path| !index.contains(path)) {
+            show(path);
+        }
+    }
+
+    if cached || options.stage {
+        for entry in entries.clone() {
+            if options.stage {
+                print!("{:06o} {} {}\t", entry.mode, entry.oid, entry.stage);
+            }
+            show(&entry.path);
+        }
+    }
+
+    if options.deleted || options.modified {
+        for entry in entries {
+            let state = index.file_state(&repo, entry)?;
+            if options.deleted && state == FileState::Deleted {
+                show(&entry.path);
+            }
+            // deleted files are modified as well
+            if options.modified && !matches!(state, FileState::Unchanged(_)) {
+                show(&entry.path);
+            }
+        }
+    }
+
+    Ok(())
+}
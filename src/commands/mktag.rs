@@ -0,0 +1,35 @@
+use std::io::prelude::*;
+
+use anyhow::Context;
+
+use git_starter_rust::object::{ObjectId, ObjectType, Tag};
+
+// https://git-scm.com/docs/git-mktag
+
+/// git mktag command, returns hash of the written tag
+pub fn invoke() -> anyhow::Result<ObjectId> {
+    let repo = super::open_repository()?;
+    let mut data = Vec::new();
+    std::io::stdin()
+        .read_to_end(&mut data)
+        .context("reading tag from stdin")?;
+
+    let tag = Tag::parse(&data).context("tag on stdin did not pass our strict fsck check")?;
+    anyhow::ensure!(
+        tag.tagger.is_some(),
+        "tag on stdin did not pass our strict fsck check: missingTaggerEntry: invalid format - expected 'tagger' line"
+    );
+
+    let target = repo
+        .read_object(&tag.object)
+        .with_context(|| format!("could not read tagged object '{}'", tag.object))?;
+    anyhow::ensure!(
+        target.header.typ == tag.typ,
+        "object '{}' tagged as '{}', but is a '{}' type",
+        tag.object,
+        tag.typ,
+        target.header.typ
+    );
+
+    repo.write_object(ObjectType::Tag, &data)
+}
@@ -0,0 +1,169 @@
+use std::{ffi::OsStr, fs, os::unix::ffi::OsStrExt, path::PathBuf};
+
+use anyhow::Context;
+
+use git_starter_rust::index::{Index, IndexLock};
+
+// https://git-scm.com/docs/git-mv
+
+pub struct Options {
+    /// Overwrite existing destination files
+    pub force: bool,
+    /// Skip sources that cannot be moved instead of failing
+    pub skip_errors: bool,
+    /// Only show what would be moved
+    pub dry_run: bool,
+    /// Report names of moved files
+    pub verbose: bool,
+    /// Sources followed by the destination
+    pub paths: Vec<PathBuf>,
+}
+
+/// git mv command
+pub fn invoke(options: Options) -> anyhow::Result<()> {
+    let repo = super::open_repository()?;
+    let work_tree = repo
+        .work_tree()
+        .context("this operation must be run in a work tree")?;
+    let full_path = |path: &[u8]| work_tree.join(OsStr::from_bytes(path));
+
+    let Some((destination, sources)) = options.paths.split_last().filter(|(_, s)| !s.is_empty())
+    else {
+        anyhow::bail!("usage: git mv [<options>] <source>... <destination>");
+    };
+    let destination = repo.work_tree_path(destination)?;
+    let into_dir = full_path(&destination).is_dir();
+    anyhow::ensure!(
+        into_dir || sources.len() == 1,
+        "destination '{}' is not a directory",
+        String::from_utf8_lossy(&destination)
+    );
+
+    let lock = IndexLock::acquire(&repo)?;
+    let mut index = Index::read(repo.index_file(), repo.algorithm())?;
+
+    for source in sources {
+        let source = repo.work_tree_path(source)?;
+        let target = match into_dir {
+            true => {
+                let name = source.rsplit(|&b| b == b'/').next().unwrap_or(&source);
+                let mut target = destination.clone();
+                if !target.is_empty() {
+                    target.push(b'/');
+                }
+                target.extend_from_slice(name);
+                target
+            }
+            false => destination.clone(),
+        };
+
+        if let Err(err) = check_move(&index, &source, &target, full_path, options.force) {
+            if options.skip_errors {
+                continue;
+            }
+            anyhow::bail!(
+                "{err}, source={}, destination={}",
+                String::from_utf8_lossy(&source),
+                String::from_utf8_lossy(&target)
+            );
+        }
+
+        if options.dry_run {
+            println!(
+                "Checking rename of '{}' to '{}'",
+                String::from_utf8_lossy(&source),
+                String::from_utf8_lossy(&target)
+            );
+        }
+        if options.verbose || options.dry_run {
+            println!(
+                "Renaming {} to {}",
+                String::from_utf8_lossy(&source),
+                String::from_utf8_lossy(&target)
+            );
+        }
+        if options.dry_run {
+            continue;
+        }
+
+        fs::rename(full_path(&source), full_path(&target))
+            .with_context(|| format!("renaming '{}' failed", String::from_utf8_lossy(&source)))?;
+
+        // the file itself or all files of the directory are renamed, keeping their stat data
+        let entries: Vec<_> = index
+            .entries
+            .iter()
+            .filter(|entry| {
+                entry.path == source
+                    || entry
+                        .path
+                        .strip_prefix(source.as_slice())
+                        .is_some_and(|rest| rest.starts_with(b"/"))
+            })
+            .cloned()
+            .collect();
+        for mut entry in entries {
+            index.remove(&entry.path);
+            let mut path = target.clone();
+            path.extend_from_slice(&entry.path[source.len()..]);
+            entry.path = path;
+            index.add(entry);
+        }
+    }
+
+    if !options.dry_run {
+        lock.commit(&index)?;
+    }
+
+    Ok(())
+}
+
+/// Checks that the tracked source can be moved to the target, returns reason if not
+fn check_move(
+    index: &Index,
+    source: &[u8],
+    target: &[u8],
+    full_path: impl Fn(&[u8]) -> PathBuf,
+    force: bool,
+) -> Result<(), &'static str> {
+    let metadata = fs::symlink_metadata(full_path(source)).map_err(|_| "bad source")?;
+    if metadata.is_dir() {
+        if source.is_empty()
+            || target.starts_with(source) && target.get(source.len()) == Some(&b'/')
+        {
+            return Err("can not move directory into itself");
+        }
+        let mut prefix = source.to_vec();
+        prefix.push(b'/');
+        if !index
+            .entries
+            .iter()
+            .any(|entry| entry.path.starts_with(&prefix))
+        {
+            return Err("source directory is empty");
+        }
+    } else {
+        let entries = index.entries_of(source);
+        if entries.is_empty() {
+            return Err("not under version control");
+        }
+        if entries.iter().any(|entry| entry.stage != 0) {
+            return Err("conflicted");
+        }
+    }
+
+    if let Ok(existing) = fs::symlink_metadata(full_path(target)) {
+        if !force || metadata.is_dir() || existing.is_dir() {
+            return Err("destination exists");
+        }
+    }
+    let parent = target
+        .iter()
+        .rposition(|&b| b == b'/')
+        .map_or(&b""[..], |slash| &target[..slash]);
+    if !full_path(parent).is_dir() {
+        return Err("destination directory does not exist");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,120 @@
+use std::io::{prelude::*, BufReader};
+
+use anyhow::Context;
+
+use git_starter_rust::{
+    object::ObjectId,
+    pack::{self, writer::PackOptions},
+    refs,
+    repository::Repository,
+    rev_list::{self, ObjectEntry},
+};
+
+pub struct Options {
+    pub stdout: bool,
+    pub revs: bool,
+    pub all: bool,
+    pub window: usize,
+    pub depth: usize,
+    pub delta_base_offset: bool,
+}
+
+/// git pack-objects command
+pub fn invoke(base_name: Option<&str>, options: Options) -> anyhow::Result<()> {
+    let repo = super::open_repository()?;
+
+    let stdin = BufReader::new(std::io::stdin().lock());
+    let entries = if options.revs || options.all {
+        read_revs(&repo, stdin, options.all)?
+    } else {
+        read_objects(stdin)?
+    };
+
+    let pack_options = PackOptions {
+        window: options.window,
+        depth: options.depth,
+        ofs_delta: options.delta_base_offset,
+    };
+
+    if options.stdout {
+        let mut stdout = std::io::stdout().lock();
+        pack::writer::write(repo.odb(), &entries, &pack_options, &mut stdout)?;
+        return stdout.flush().context("flushing pack");
+    }
+
+    let base_name = base_name.context("base name of the pack files is required")?;
+
+    let mut data = Vec::new();
+    let (checksum, mut index) =
+        pack::writer::write(repo.odb(), &entries, &pack_options, &mut data)?;
+    index.sort_unstable_by_key(|entry| entry.hash);
+
+    let name = checksum.to_string();
+    let pack_path = format!("{base_name}-{name}.pack");
+    std::fs::write(&pack_path, data).with_context(|| format!("writing {pack_path}"))?;
+    let mut idx = Vec::new();
+    pack::write_index(&index, &checksum, &mut idx)?;
+    let idx_path = format!("{base_name}-{name}.idx");
+    std::fs::write(&idx_path, idx).with_context(|| format!("writing {idx_path}"))?;
+
+    println!("{name}");
+
+    Ok(())
+}
+
+/// Reads object names, optionally followed by a path, one per line
+fn read_objects(input: impl BufRead) -> anyhow::Result<Vec<ObjectEntry>> {
+    let mut entries = Vec::new();
+    for line in input.lines() {
+        let line = line.context("reading object names")?;
+        if line.is_empty() {
+            continue;
+        }
+        let (hash, path) = line.split_once(' ').unwrap_or((&line, ""));
+        entries.push(ObjectEntry {
+            hash: ObjectId::from_hex(hash)?,
+            path: path.to_string(),
+        });
+    }
+
+    Ok(entries)
+}
+
+/// Reads revisions like `git rev-list` does, `^<rev>` excludes objects reachable from the revision
+fn read_revs(
+    repo: &Repository,
+    input: impl BufRead,
+    all: bool,
+) -> anyhow::Result<Vec<ObjectEntry>> {
+    let mut include = Vec::new();
+    let mut exclude = Vec::new();
+
+    let git_dir = repo.git_dir();
+    if all {
+        include.extend(refs::list(git_dir)?.into_iter().map(|r| r.hash));
+        include.extend(refs::resolve(git_dir, "HEAD")?);
+    }
+
+    let mut not = false;
+    for line in input.lines() {
+        let line = line.context("reading revisions")?;
+        match line.as_str() {
+            "" => continue,
+            "--not" => not = !not,
+            _ => {
+                let (negated, rev) = match line.strip_prefix('^') {
+                    Some(rev) => (true, rev),
+                    None => (false, line.as_str()),
+                };
+                let hash = super::resolve_revision(repo, rev)?;
+                if negated != not {
+                    exclude.push(hash);
+                } else {
+                    include.push(hash);
+                }
+            }
+        }
+    }
+
+    rev_list::objects(repo.odb(), &include, &exclude)
+}
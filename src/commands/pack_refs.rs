@@ -0,0 +1,7 @@
+// https://git-scm.com/docs/git-pack-refs
+
+/// git pack-refs command
+pub fn invoke(all: bool, no_prune: bool) -> anyhow::Result<()> {
+    let repo = super::open_repository()?;
+    repo.pack_refs(all, !no_prune)
+}
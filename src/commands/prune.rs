@@ -0,0 +1,107 @@
+use std::{
+    collections::HashSet,
+    fs,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Context;
+
+use git_starter_rust::{object::ObjectId, odb::loose, repository::Repository, rev_list};
+
+use super::prune_packed;
+
+// https://git-scm.com/docs/git-prune
+
+/// git prune command
+pub fn invoke(expire: Option<&str>, dry_run: bool, verbose: bool) -> anyhow::Result<()> {
+    let expire = match expire {
+        Some(expire) => parse_expire(expire)?,
+        // without --expire all unreachable objects are pruned
+        None => Some(SystemTime::now()),
+    };
+
+    prune(&super::open_repository()?, expire, dry_run, verbose)
+}
+
+/// Removes unreachable loose objects not modified after `expire` (`None` keeps all of them)
+/// and loose objects that are already packed
+pub fn prune(
+    repo: &Repository,
+    expire: Option<SystemTime>,
+    dry_run: bool,
+    verbose: bool,
+) -> anyhow::Result<()> {
+    let objects_dir = repo.objects_dir();
+
+    if let Some(expire) = expire {
+        let roots = rev_list::roots(repo)?;
+        let reachable: HashSet<ObjectId> = rev_list::objects(repo.odb(), &roots, &[])?
+            .into_iter()
+            .map(|entry| entry.hash)
+            .collect();
+
+        for (hash, path) in loose::list(&objects_dir)? {
+            if reachable.contains(&hash) {
+                continue;
+            }
+
+            let mtime = fs::metadata(&path)
+                .and_then(|metadata| metadata.modified())
+                .with_context(|| format!("stat {}", path.display()))?;
+            if mtime > expire {
+                continue;
+            }
+
+            if dry_run || verbose {
+                println!("{hash}");
+            }
+            if !dry_run {
+                fs::remove_file(&path).with_context(|| format!("removing {}", path.display()))?;
+                if let Some(dir) = path.parent() {
+                    // fails unless the fan-out directory is empty
+                    let _ = fs::remove_dir(dir);
+                }
+            }
+        }
+    }
+
+    prune_packed::prune_packed(&objects_dir, repo.algorithm(), dry_run)
+}
+
+/// Parses expiry date like `now`, `never`, `2.weeks.ago` or unix timestamp.
+/// Returns `None` if objects should never expire.
+pub fn parse_expire(value: &str) -> anyhow::Result<Option<SystemTime>> {
+    match value {
+        "now" | "all" => return Ok(Some(SystemTime::now())),
+        "never" | "false" => return Ok(None),
+        _ => {}
+    }
+
+    if let Ok(timestamp) = value.parse::<u64>() {
+        return Ok(Some(UNIX_EPOCH + Duration::from_secs(timestamp)));
+    }
+
+    // <count>.<unit>.ago, also with spaces instead of dots
+    let parts: Vec<&str> = value.split(['.', ' ']).collect();
+    if let [count, unit, "ago"] = parts[..] {
+        let count: u64 = count
+            .parse()
+            .with_context(|| format!("invalid expiry date '{value}'"))?;
+        let seconds = match unit.strip_suffix('s').unwrap_or(unit) {
+            "second" => 1,
+            "minute" => 60,
+            "hour" => 60 * 60,
+            "day" => 24 * 60 * 60,
+            "week" => 7 * 24 * 60 * 60,
+            "month" => 30 * 24 * 60 * 60,
+            "year" => 365 * 24 * 60 * 60,
+            _ => anyhow::bail!("invalid expiry date '{value}'"),
+        };
+        let ago = Duration::from_secs(count * seconds);
+        return Ok(Some(
+            SystemTime::now().checked_sub(ago).unwrap_or(UNIX_EPOCH),
+        ));
+    }
+
+    anyhow::bail!("invalid expiry date '{value}'")
+}
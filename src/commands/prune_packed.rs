@@ -0,0 +1,50 @@
+use std::{collections::HashSet, fs, path::Path};
+
+use anyhow::Context;
+
+use git_starter_rust::{
+    object::HashAlgorithm,
+    odb::loose,
+    pack::{self, PackIndex},
+};
+
+// https://git-scm.com/docs/git-prune-packed
+
+/// git prune-packed command
+pub fn invoke(dry_run: bool) -> anyhow::Result<()> {
+    let repo = super::open_repository()?;
+    prune_packed(&repo.objects_dir(), repo.algorithm(), dry_run)
+}
+
+/// Removes loose objects that are also stored in packs.
+/// With `dry_run` only prints commands that would remove the objects.
+pub fn prune_packed(
+    objects_dir: &Path,
+    algorithm: HashAlgorithm,
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    let mut packed = HashSet::new();
+    for path in pack::packs(objects_dir)? {
+        let index = PackIndex::read(&path.with_extension("idx"), algorithm)?;
+        packed.extend(index.names().iter().copied());
+    }
+
+    for (hash, path) in loose::list(objects_dir)? {
+        if !packed.contains(&hash) {
+            continue;
+        }
+
+        if dry_run {
+            println!("rm -f {}", path.display());
+            continue;
+        }
+
+        fs::remove_file(&path).with_context(|| format!("removing {}", path.display()))?;
+        if let Some(dir) = path.parent() {
+            // fails unless the fan-out directory is empty
+            let _ = fs::remove_dir(dir);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,523 @@
+use std::{
+    collections::HashSet,
+    io::{prelude::*, BufReader},
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use anyhow::Context;
+
+use git_starter_rust::{
+    config,
+    object::{ObjectFile, ObjectId, ObjectType},
+    pack,
+    pkt_line::{self, Band, Packet, SidebandWriter},
+    refs::{self, Expected, RefUpdate, Transaction},
+    repository::Repository,
+};
+
+use super::upload_pack::{self, Mode, AGENT};
+
+// https://github.com/git/git/blob/795ea8776befc95ea2becd8020c7a284677b4161/Documentation/gitprotocol-pack.txt#L480
+// https://git-scm.com/docs/githooks#pre-receive
+
+/// git receive-pack command, receives pushed objects over stdin/stdout (used by ssh and file:// transports)
+pub fn invoke(dir: &Path, mode: Mode) -> anyhow::Result<()> {
+    let git_dir = upload_pack::enter_repo(dir)
+        .with_context(|| format!("'{}' does not appear to be a git repository", dir.display()))?;
+
+    let mut stdin = BufReader::new(std::io::stdin().lock());
+    let mut stdout = std::io::stdout().lock();
+
+    serve(&git_dir, mode, &mut stdin, &mut stdout)
+}
+
+/// Runs receive-pack service for repository in `git_dir` over the connection
+pub fn serve(
+    git_dir: &Path,
+    mode: Mode,
+    reader: &mut impl BufRead,
+    writer: &mut impl Write,
+) -> anyhow::Result<()> {
+    let git_dir = git_dir
+        .canonicalize()
+        .with_context(|| format!("resolving path {}", git_dir.display()))?;
+
+    let receive_pack = ReceivePack {
+        repo: Repository::open(git_dir)?,
+        sideband: false,
+    };
+    super::report_problems(&receive_pack.repo);
+
+    if mode != Mode::StatelessRpc {
+        receive_pack.advertise_refs(writer)?;
+        if mode == Mode::AdvertiseRefs {
+            return Ok(());
+        }
+    }
+
+    receive_pack.receive(reader, writer)
+}
+
+struct ReceivePack {
+    repo: Repository,
+    sideband: bool,
+}
+
+/// Reference update requested by client
+struct UpdateCommand {
+    old: ObjectId,
+    new: ObjectId,
+    name: String,
+    /// Reason why the update was rejected
+    error: Option<String>,
+}
+
+impl UpdateCommand {
+    fn is_delete(&self) -> bool {
+        is_null(&self.new)
+    }
+
+    fn reject(&mut self, reason: &str) {
+        if self.error.is_none() {
+            self.error = Some(reason.to_string());
+        }
+    }
+}
+
+impl ReceivePack {
+    /// Sends reference advertisement with capabilities behind the first ref
+    fn advertise_refs(&self, writer: &mut impl Write) -> anyhow::Result<()> {
+        let capabilities = format!(
+            "report-status delete-refs side-band-64k quiet atomic object-format={} agent={AGENT}",
+            self.repo.algorithm()
+        );
+
+        let refs = refs::list(self.repo.git_dir())?;
+        if refs.is_empty() {
+            pkt_line::write_str(
+                writer,
+                &format!(
+                    "{} capabilities^{{}}\0{capabilities}\n",
+                    self.repo.algorithm().null()
+                ),
+            )?;
+        }
+
+        for (i, r) in refs.iter().enumerate() {
+            if i == 0 {
+                pkt_line::write_str(writer, &format!("{} {}\0{capabilities}\n", r.hash, r.name))?;
+            } else {
+                pkt_line::write_str(writer, &format!("{} {}\n", r.hash, r.name))?;
+            }
+        }
+
+        pkt_line::flush(writer)
+    }
+
+    fn receive(mut self, reader: &mut impl BufRead, writer: &mut impl Write) -> anyhow::Result<()> {
+        // <old-oid> <new-oid> <ref-name>\0<capabilities>
+        let mut commands = Vec::new();
+        let mut capabilities = Vec::new();
+        loop {
+            match pkt_line::read(reader)? {
+                // client found nothing to push and closed the connection
+                None => return Ok(()),
+                Some(Packet::Flush) => break,
+                Some(packet) => {
+                    let line = packet
+                        .as_text()
+                        .context("command line is not valid UTF-8")?;
+                    let (command, caps) = line.split_once('\0').unwrap_or((line, ""));
+                    if commands.is_empty() {
+                        capabilities.extend(caps.split(' ').map(|c| c.to_string()));
+                    }
+
+                    let mut words = command.splitn(3, ' ');
+                    let (Some(old), Some(new), Some(name)) =
+                        (words.next(), words.next(), words.next())
+                    else {
+                        anyhow::bail!("protocol error: malformed command '{command}'");
+                    };
+                    anyhow::ensure!(
+                        self.repo.algorithm().is_hex(old) && self.repo.algorithm().is_hex(new),
+                        "protocol error: malformed command '{command}'"
+                    );
+
+                    commands.push(UpdateCommand {
+                        old: old.parse()?,
+                        new: new.parse()?,
+                        name: name.to_string(),
+                        error: None,
+                    });
+                }
+            }
+        }
+
+        if commands.is_empty() {
+            return Ok(());
+        }
+
+        if let Some(format) = capabilities
+            .iter()
+            .find_map(|c| c.strip_prefix("object-format="))
+        {
+            anyhow::ensure!(
+                format == self.repo.algorithm().name(),
+                "client requested object format {format}, repository uses {}",
+                self.repo.algorithm()
+            );
+        }
+
+        let has_capability = |name: &str| capabilities.iter().any(|c| c == name);
+        self.sideband = has_capability("side-band-64k");
+        let report_status = has_capability("report-status");
+        let atomic = has_capability("atomic");
+
+        // pack is sent only when there is something else than deletes
+        let unpack_result = if commands.iter().any(|cmd| !cmd.is_delete()) {
+            self.unpack(reader)
+        } else {
+            Ok(HashSet::new())
+        };
+
+        match &unpack_result {
+            Ok(received) => self.check_commands(&mut commands, received)?,
+            Err(_) => {
+                for cmd in &mut commands {
+                    cmd.reject("unpacker error");
+                }
+            }
+        }
+
+        self.run_pre_receive_hook(&mut commands, writer)?;
+        self.run_update_hooks(&mut commands, writer)?;
+
+        if atomic {
+            self.update_refs_atomic(&mut commands);
+        } else {
+            self.update_refs(&mut commands);
+        }
+
+        if report_status {
+            self.report(&unpack_result, &commands, writer)?;
+        }
+
+        self.run_post_receive_hook(&commands, writer)?;
+
+        if self.sideband {
+            pkt_line::flush(writer)?;
+        }
+        writer.flush()?;
+
+        unpack_result.map(|_| ())
+    }
+
+    /// Receives pack and stores it in the repository. Returns names of received objects.
+    fn unpack(&self, reader: &mut impl BufRead) -> anyhow::Result<HashSet<ObjectId>> {
+        let pack = pack::receive(reader, self.repo.algorithm()).context("receiving pack")?;
+
+        // empty pack is sent when the client knows that we already have all objects
+        if pack[8..12] == [0; 4] {
+            return Ok(HashSet::new());
+        }
+
+        let received = pack::store(&self.repo, pack).context("storing pack")?;
+
+        Ok(received.into_iter().collect())
+    }
+
+    /// Validates requested updates before running hooks
+    fn check_commands(
+        &self,
+        commands: &mut [UpdateCommand],
+        received: &HashSet<ObjectId>,
+    ) -> anyhow::Result<()> {
+        // pushing into the checked out branch would make working tree out of sync
+        let checked_out = match self.is_bare()? {
+            true => None,
+            false => refs::head_target(self.repo.git_dir())?,
+        };
+
+        for cmd in commands {
+            if !cmd.name.starts_with("refs/") || !refs::is_valid_name(&cmd.name) {
+                cmd.reject("funny refname");
+                continue;
+            }
+
+            if checked_out.as_deref() == Some(cmd.name.as_str()) {
+                cmd.reject(if cmd.is_delete() {
+                    "deletion of the current branch prohibited"
+                } else {
+                    "branch is currently checked out"
+                });
+                continue;
+            }
+
+            if cmd.is_delete() {
+                continue;
+            }
+
+            match ObjectFile::read(&cmd.new, self.repo.odb()) {
+                Ok(object) => {
+                    if cmd.name.starts_with("refs/heads/")
+                        && object.header.typ != ObjectType::Commit
+                    {
+                        cmd.reject("non-commit object pushed to a branch");
+                        continue;
+                    }
+                }
+                Err(_) => {
+                    cmd.reject("bad pack");
+                    continue;
+                }
+            }
+
+            if !self.is_connected(&cmd.new, received)? {
+                cmd.reject("missing necessary objects");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks that all objects reachable from `hash` are present in the repository.
+    /// Objects that were present before the push are expected to be complete already.
+    fn is_connected(&self, id: &ObjectId, received: &HashSet<ObjectId>) -> anyhow::Result<bool> {
+        let mut seen = HashSet::new();
+        let mut stack = vec![*id];
+
+        while let Some(id) = stack.pop() {
+            if !seen.insert(id) {
+                continue;
+            }
+
+            let Ok(mut object) = ObjectFile::read(&id, self.repo.odb()) else {
+                return Ok(false);
+            };
+
+            if received.contains(&id) {
+                stack.extend(object.links(self.repo.algorithm())?);
+            }
+        }
+
+        Ok(true)
+    }
+
+    fn update_refs(&self, commands: &mut [UpdateCommand]) {
+        for cmd in commands.iter_mut().filter(|cmd| cmd.error.is_none()) {
+            let mut transaction = Transaction::new(self.repo.git_dir(), self.repo.shared());
+            let result = transaction
+                .add(ref_update(cmd))
+                .and_then(|_| transaction.commit());
+            if let Err(err) = result {
+                cmd.reject(&format!("failed to update ref: {err:#}"));
+            }
+        }
+    }
+
+    /// Updates all references or none of them
+    fn update_refs_atomic(&self, commands: &mut [UpdateCommand]) {
+        if commands.iter().any(|cmd| cmd.error.is_some()) {
+            for cmd in commands.iter_mut() {
+                cmd.reject("atomic push failure");
+            }
+            return;
+        }
+
+        let mut transaction = Transaction::new(self.repo.git_dir(), self.repo.shared());
+        for i in 0..commands.len() {
+            if let Err(err) = transaction.add(ref_update(&commands[i])) {
+                commands[i].reject(&format!("failed to update ref: {err:#}"));
+                for cmd in commands.iter_mut() {
+                    cmd.reject("atomic transaction failed");
+                }
+                return;
+            }
+        }
+
+        if let Err(err) = transaction.commit() {
+            for cmd in commands.iter_mut() {
+                cmd.reject(&format!("failed to update ref: {err:#}"));
+            }
+        }
+    }
+
+    /// Sends report-status with result of unpacking and of each reference update
+    fn report(
+        &self,
+        unpack_result: &anyhow::Result<HashSet<ObjectId>>,
+        commands: &[UpdateCommand],
+        writer: &mut impl Write,
+    ) -> anyhow::Result<()> {
+        let mut report = Vec::new();
+
+        match unpack_result {
+            Ok(_) => pkt_line::write_str(&mut report, "unpack ok\n")?,
+            Err(err) => {
+                let err = format!("{err:#}").replace('\n', " ");
+                pkt_line::write_str(&mut report, &format!("unpack {err}\n"))?
+            }
+        }
+
+        for cmd in commands {
+            match &cmd.error {
+                None => pkt_line::write_str(&mut report, &format!("ok {}\n", cmd.name))?,
+                Some(reason) => {
+                    pkt_line::write_str(&mut report, &format!("ng {} {reason}\n", cmd.name))?
+                }
+            }
+        }
+        pkt_line::flush(&mut report)?;
+
+        if self.sideband {
+            let mut data =
+                SidebandWriter::new(&mut *writer, Band::Data, pkt_line::MAX_SIDEBAND_DATA);
+            data.write_all(&report)?;
+        } else {
+            writer.write_all(&report)?;
+        }
+
+        Ok(())
+    }
+
+    fn run_pre_receive_hook(
+        &self,
+        commands: &mut [UpdateCommand],
+        writer: &mut impl Write,
+    ) -> anyhow::Result<()> {
+        let stdin = hook_input(commands.iter());
+        if stdin.is_empty() {
+            return Ok(());
+        }
+
+        if let Some(false) = self.run_hook("pre-receive", &[], &stdin, writer)? {
+            for cmd in commands {
+                cmd.reject("pre-receive hook declined");
+            }
+        }
+
+        Ok(())
+    }
+
+    fn run_update_hooks(
+        &self,
+        commands: &mut [UpdateCommand],
+        writer: &mut impl Write,
+    ) -> anyhow::Result<()> {
+        for cmd in commands.iter_mut().filter(|cmd| cmd.error.is_none()) {
+            let (old, new) = (cmd.old.to_string(), cmd.new.to_string());
+            let args = [cmd.name.as_str(), old.as_str(), new.as_str()];
+            if let Some(false) = self.run_hook("update", &args, b"", writer)? {
+                cmd.reject("hook declined");
+            }
+        }
+
+        Ok(())
+    }
+
+    fn run_post_receive_hook(
+        &self,
+        commands: &[UpdateCommand],
+        writer: &mut impl Write,
+    ) -> anyhow::Result<()> {
+        let stdin = hook_input(commands.iter());
+        if !stdin.is_empty() {
+            // the refs are already updated, exit status does not matter
+            self.run_hook("post-receive", &[], &stdin, writer)?;
+        }
+
+        Ok(())
+    }
+
+    /// Runs hook if it exists and is executable, relaying its output to the client.
+    /// Returns whether the hook succeeded or `None` if there is no such hook.
+    fn run_hook(
+        &self,
+        name: &str,
+        args: &[&str],
+        stdin: &[u8],
+        writer: &mut impl Write,
+    ) -> anyhow::Result<Option<bool>> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = self.repo.git_dir().join("hooks").join(name);
+        let Ok(metadata) = path.metadata() else {
+            return Ok(None);
+        };
+        if !metadata.is_file() || metadata.permissions().mode() & 0o111 == 0 {
+            return Ok(None);
+        }
+
+        let mut child = Command::new(&path)
+            .args(args)
+            .current_dir(self.repo.git_dir())
+            .env("GIT_DIR", self.repo.git_dir())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("running {name} hook"))?;
+
+        // input is written from another thread so a hook printing before it reads
+        // cannot block on a full output pipe
+        let mut child_stdin = child.stdin.take().expect("stdin is piped");
+        let output = std::thread::scope(|s| {
+            let input = s.spawn(move || match child_stdin.write_all(stdin) {
+                // hook does not have to read its input
+                Err(err) if err.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+                result => result,
+            });
+            let output = child.wait_with_output();
+            let input = input.join().expect("writing hook input does not panic");
+            input.with_context(|| format!("writing input of {name} hook"))?;
+            output.with_context(|| format!("waiting for {name} hook"))
+        })?;
+
+        let mut messages = output.stdout;
+        messages.extend(output.stderr);
+        if !messages.is_empty() {
+            if self.sideband {
+                let mut progress =
+                    SidebandWriter::new(&mut *writer, Band::Progress, pkt_line::MAX_SIDEBAND_DATA);
+                progress.write_all(&messages)?;
+            } else {
+                std::io::stderr().write_all(&messages)?;
+            }
+        }
+
+        Ok(Some(output.status.success()))
+    }
+
+    /// Bare repositories have no branch checked out, `core.bare` takes precedence
+    /// over the git directory having a work tree
+    fn is_bare(&self) -> anyhow::Result<bool> {
+        let bare = config::get_bool(self.repo.git_dir(), "core.bare")?;
+        Ok(bare.unwrap_or_else(|| self.repo.work_tree().is_none()))
+    }
+}
+
+/// Input of pre-receive and post-receive hooks, one `<old> <new> <ref>` line per accepted update
+fn hook_input<'a>(commands: impl Iterator<Item = &'a UpdateCommand>) -> Vec<u8> {
+    let mut input = Vec::new();
+    for cmd in commands.filter(|cmd| cmd.error.is_none()) {
+        input.extend(format!("{} {} {}\n", cmd.old, cmd.new, cmd.name).bytes());
+    }
+    input
+}
+
+fn ref_update(cmd: &UpdateCommand) -> RefUpdate {
+    RefUpdate {
+        name: cmd.name.clone(),
+        expected: match is_null(&cmd.old) {
+            true => Expected::Missing,
+            false => Expected::Value(cmd.old),
+        },
+        new: (!cmd.is_delete()).then_some(cmd.new),
+    }
+}
+
+/// Checks for the null object name used for missing refs
+fn is_null(id: &ObjectId) -> bool {
+    *id == id.algorithm().null()
+}
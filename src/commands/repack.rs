@@ -0,0 +1,145 @@
+use std::{collections::HashSet, fs, path::Path};
+
+use anyhow::Context;
+
+use git_starter_rust::{
+    object::{ObjectFile, ObjectId},
+    odb::{Loose, ObjectDatabase},
+    pack::{self, writer::PackOptions, PackIndex},
+    repository::Repository,
+    rev_list::{self, ObjectEntry},
+};
+
+use super::prune_packed;
+
+// https://git-scm.com/docs/git-repack
+
+pub struct Options {
+    /// Pack all reachable objects into a single pack instead of only loose ones
+    pub all: bool,
+    /// Like `all`, but unreachable objects of removed packs are kept as loose objects
+    pub loosen_unreachable: bool,
+    /// Remove redundant packs and loose objects
+    pub delete: bool,
+    pub window: usize,
+    pub depth: usize,
+}
+
+/// git repack command
+pub fn invoke(options: Options) -> anyhow::Result<()> {
+    repack(&super::open_repository()?, &options)
+}
+
+/// Packs objects reachable from references, reflogs and the index into a new pack
+pub fn repack(repo: &Repository, options: &Options) -> anyhow::Result<()> {
+    let objects_dir = repo.objects_dir();
+    let algorithm = repo.algorithm();
+    let all = options.all || options.loosen_unreachable;
+
+    // objects in packs marked with .keep file are left where they are
+    let mut kept = HashSet::new();
+    let mut old_packs = Vec::new();
+    for path in pack::packs(&objects_dir)? {
+        if path.with_extension("keep").exists() {
+            let index = PackIndex::read(&path.with_extension("idx"), algorithm)?;
+            kept.extend(index.names().iter().copied());
+        } else {
+            old_packs.push(path);
+        }
+    }
+
+    let roots = rev_list::roots(repo)?;
+    let entries: Vec<ObjectEntry> = rev_list::objects(repo.odb(), &roots, &[])?
+        .into_iter()
+        .filter(|entry| !kept.contains(&entry.hash))
+        .filter(|entry| all || is_loose(&objects_dir, &entry.hash))
+        .collect();
+
+    let new_pack = if entries.is_empty() {
+        None
+    } else {
+        let pack_options = PackOptions {
+            window: options.window,
+            depth: options.depth,
+            ofs_delta: true,
+        };
+        let mut data = Vec::new();
+        let (checksum, mut index) =
+            pack::writer::write(repo.odb(), &entries, &pack_options, &mut data)?;
+        Some(pack::install(&objects_dir, &data, &mut index, &checksum)?)
+    };
+
+    if !options.delete {
+        return Ok(());
+    }
+
+    if all {
+        let packed: HashSet<&ObjectId> = entries.iter().map(|entry| &entry.hash).collect();
+        for path in old_packs {
+            if Some(&path) == new_pack.as_ref() {
+                continue;
+            }
+            if options.loosen_unreachable {
+                loosen_objects(repo, &path, &packed)?;
+            }
+            remove_pack(&path)?;
+        }
+    }
+
+    prune_packed::prune_packed(&objects_dir, algorithm, false)?;
+
+    Ok(())
+}
+
+fn is_loose(objects_dir: &Path, id: &ObjectId) -> bool {
+    let hash = id.to_string();
+    objects_dir.join(&hash[..2]).join(&hash[2..]).exists()
+}
+
+/// Writes objects of the pack that are not in `packed` as loose objects.
+/// They get modification time of the pack, so that their grace period for pruning
+/// is counted from the time they were packed.
+fn loosen_objects(
+    repo: &Repository,
+    pack: &Path,
+    packed: &HashSet<&ObjectId>,
+) -> anyhow::Result<()> {
+    let pack_path = pack.with_extension("pack");
+    let mtime = fs::metadata(&pack_path)
+        .and_then(|metadata| metadata.modified())
+        .with_context(|| format!("stat {}", pack_path.display()))?;
+
+    let loose = Loose::configured(repo.git_dir(), repo.objects_dir(), repo.algorithm())?;
+    let index = PackIndex::read(&pack.with_extension("idx"), repo.algorithm())?;
+    for name in index.names() {
+        if packed.contains(name) || loose.contains(name)? {
+            continue;
+        }
+
+        let mut object =
+            ObjectFile::read(name, repo.odb()).with_context(|| format!("reading object {name}"))?;
+        object.write(&loose)?;
+
+        let path = loose.path(name);
+        fs::File::open(&path)
+            .and_then(|f| f.set_modified(mtime))
+            .with_context(|| format!("setting modification time of {}", path.display()))?;
+    }
+
+    Ok(())
+}
+
+/// Removes pack together with its index and other auxiliary files
+fn remove_pack(path: &Path) -> anyhow::Result<()> {
+    // index goes first, so that readers never see an index without its pack
+    for ext in ["idx", "pack", "rev", "bitmap"] {
+        let file = path.with_extension(ext);
+        match fs::remove_file(&file) {
+            Ok(()) => {}
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err).with_context(|| format!("removing {}", file.display())),
+        }
+    }
+
+    Ok(())
+}
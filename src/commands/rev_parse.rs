@@ -0,0 +1,14 @@
+/// git rev-parse command
+pub fn invoke(revs: &[String], verify: bool) -> anyhow::Result<()> {
+    let repo = super::open_repository()?;
+
+    if verify {
+        anyhow::ensure!(revs.len() == 1, "Needed a single revision");
+    }
+
+    for rev in revs {
+        println!("{}", super::resolve_revision(&repo, rev)?);
+    }
+
+    Ok(())
+}
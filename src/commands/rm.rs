@@ -0,0 +1,174 @@
+use std::{collections::HashMap, ffi::OsStr, fs, os::unix::ffi::OsStrExt, path::PathBuf};
+
+use anyhow::Context;
+
+use git_starter_rust::{
+    index::{FileState, Index, IndexLock},
+    pathspec::Pathspec,
+    repository::Repository,
+};
+
+// https://git-scm.com/docs/git-rm
+
+pub struct Options {
+    /// Only remove the files from the index, keeping them in the work tree
+    pub cached: bool,
+    /// Remove files even if they are modified
+    pub force: bool,
+    /// Allow removing directories recursively
+    pub recursive: bool,
+    /// Do not print removed files
+    pub quiet: bool,
+    /// Exit successfully even if nothing matched
+    pub ignore_unmatch: bool,
+    pub pathspec: Vec<PathBuf>,
+}
+
+/// git rm command
+pub fn invoke(options: Options) -> anyhow::Result<()> {
+    let repo = super::open_repository()?;
+    let work_tree = repo
+        .work_tree()
+        .context("this operation must be run in a work tree")?;
+    anyhow::ensure!(
+        !options.pathspec.is_empty(),
+        "No pathspec was given. Which files should I remove?"
+    );
+    let pathspec = Pathspec::new(&repo, &options.pathspec)?;
+
+    let lock = IndexLock::acquire(&repo)?;
+    let mut index = Index::read(repo.index_file(), repo.algorithm())?;
+
+    let mut paths: Vec<Vec<u8>> = Vec::new();
+    for item in &pathspec.items {
+        let mut matched = false;
+        for entry in index
+            .entries
+            .iter()
+            .filter(|entry| item.matches(&entry.path))
+        {
+            anyhow::ensure!(
+                options.recursive || item.matches_exactly(&entry.path),
+                "not removing '{}' recursively without -r",
+                item.original
+            );
+            matched = true;
+            paths.push(entry.path.clone());
+        }
+        anyhow::ensure!(
+            matched || options.ignore_unmatch,
+            "pathspec '{}' did not match any files",
+            item.original
+        );
+    }
+    paths.sort();
+    paths.dedup();
+
+    if !options.force {
+        check_removable(&repo, &index, &paths, options.cached)?;
+    }
+
+    for path in &paths {
+        index.remove(path);
+        if !options.quiet {
+            println!("rm '{}'", String::from_utf8_lossy(path));
+        }
+        if options.cached {
+            continue;
+        }
+
+        let full_path = work_tree.join(OsStr::from_bytes(path));
+        match fs::remove_file(&full_path) {
+            Ok(()) => {}
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => {
+                return Err(err).with_context(|| format!("git rm: '{}'", full_path.display()))
+            }
+        }
+        // directories left empty are removed too
+        let mut dir = full_path.parent();
+        while let Some(parent) = dir.filter(|dir| *dir != work_tree) {
+            if fs::remove_dir(parent).is_err() {
+                break;
+            }
+            dir = parent.parent();
+        }
+    }
+
+    lock.commit(&index)?;
+
+    Ok(())
+}
+
+/// Makes sure that removing the paths loses no content that is not committed: the staged
+/// content must match `HEAD` and, unless the files are kept, the files must match the index
+fn check_removable(
+    repo: &Repository,
+    index: &Index,
+    paths: &[Vec<u8>],
+    cached: bool,
+) -> anyhow::Result<()> {
+    let head: HashMap<_, _> = match repo.head()? {
+        Some(head) => repo
+            .walk_tree(&repo.read_commit(&head)?.tree)?
+            .into_iter()
+            .map(|(path, entry)| (path, (entry.mode, entry.oid)))
+            .collect(),
+        None => HashMap::new(),
+    };
+
+    let mut both = Vec::new();
+    let mut staged = Vec::new();
+    let mut local = Vec::new();
+    for path in paths {
+        // conflicts can always be removed
+        let Some(entry) = index.get(path, 0) else {
+            continue;
+        };
+        let name = String::from_utf8_lossy(path).into_owned();
+        let is_staged = head.get(path) != Some(&(entry.mode, entry.oid));
+        let is_local = matches!(index.file_state(repo, entry)?, FileState::Modified);
+
+        if is_staged && is_local && !entry.intent_to_add {
+            both.push(name);
+        } else if !cached {
+            if is_local {
+                local.push(name);
+            } else if is_staged {
+                staged.push(name);
+            }
+        }
+    }
+
+    let mut errors = Vec::new();
+    let mut report = |files: Vec<String>, problem: &str, hint: &str| {
+        if !files.is_empty() {
+            let files_have = match files.len() {
+                1 => "file has",
+                _ => "files have",
+            };
+            errors.push(format!(
+                "the following {files_have} {problem}:\n    {}\n({hint})",
+                files.join("\n    ")
+            ));
+        }
+    };
+    report(
+        both,
+        "staged content different from both the\nfile and the HEAD",
+        "use -f to force removal",
+    );
+    report(
+        staged,
+        "changes staged in the index",
+        "use --cached to keep the file, or -f to force removal",
+    );
+    report(
+        local,
+        "local modifications",
+        "use --cached to keep the file, or -f to force removal",
+    );
+    anyhow::ensure!(errors.is_empty(), "{}", errors.join("\n"));
+
+    Ok(())
+}
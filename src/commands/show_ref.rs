@@ -0,0 +1,99 @@
+use git_starter_rust::{object::ObjectId, refs::Ref};
+
+// https://git-scm.com/docs/git-show-ref
+
+pub struct Options {
+    /// Show `HEAD` before the references
+    pub head: bool,
+    /// Show objects annotated tags point to as `<tag>^{}`
+    pub dereference: bool,
+    /// Show only object names, abbreviated to the length unless 0
+    pub hash: Option<usize>,
+    /// Abbreviate object names to the length
+    pub abbrev: Option<usize>,
+    /// Patterns are full reference names that must exist
+    pub verify: bool,
+    pub heads: bool,
+    pub tags: bool,
+    /// Only set the exit status
+    pub quiet: bool,
+    /// References ending with the patterns in whole components are shown
+    pub patterns: Vec<String>,
+}
+
+/// git show-ref command, exits with 1 when no reference matches
+pub fn invoke(options: Options) -> anyhow::Result<()> {
+    let repo = super::open_repository()?;
+
+    let mut found = Vec::new();
+    if options.verify {
+        for pattern in &options.patterns {
+            let hash = match pattern.starts_with("refs/") || pattern == "HEAD" {
+                true => repo.resolve_ref(pattern)?,
+                false => None,
+            };
+            match hash {
+                Some(hash) => found.push(Ref {
+                    name: pattern.clone(),
+                    hash,
+                }),
+                None if options.quiet => std::process::exit(1),
+                None => anyhow::bail!("'{pattern}' - not a valid ref"),
+            }
+        }
+    } else {
+        if options.head {
+            if let Some(hash) = repo.head()? {
+                found.push(Ref {
+                    name: "HEAD".to_string(),
+                    hash,
+                });
+            }
+        }
+        let kinds = match (options.heads, options.tags) {
+            (false, false) => vec!["refs/"],
+            (heads, tags) => [("refs/heads/", heads), ("refs/tags/", tags)]
+                .into_iter()
+                .filter_map(|(prefix, shown)| shown.then_some(prefix))
+                .collect(),
+        };
+        for r in repo.references()? {
+            let matches = options.patterns.is_empty()
+                || options
+                    .patterns
+                    .iter()
+                    .any(|pattern| r.name == *pattern || r.name.ends_with(&format!("/{pattern}")));
+            if matches && kinds.iter().any(|kind| r.name.starts_with(kind)) {
+                found.push(r);
+            }
+        }
+    }
+
+    if found.is_empty() {
+        std::process::exit(1);
+    }
+    if options.quiet {
+        return Ok(());
+    }
+    for r in found {
+        show(&options, &r.name, &r.hash);
+        if options.dereference {
+            if let Some(peeled) = repo.peel_tag(&r.hash)? {
+                show(&options, &format!("{}^{{}}", r.name), &peeled);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn show(options: &Options, name: &str, id: &ObjectId) {
+    let hash = id.to_string();
+    let length = match options.hash.filter(|&length| length > 0).or(options.abbrev) {
+        Some(length) if length > 0 => length.clamp(4, hash.len()),
+        _ => hash.len(),
+    };
+    match options.hash {
+        Some(_) => println!("{}", &hash[..length]),
+        None => println!("{} {name}", &hash[..length]),
+    }
+}
@@ -0,0 +1,462 @@
+use std::fmt::{self, Write};
+
+use git_starter_rust::{
+    index::{Index, IndexLock},
+    repository::Repository,
+    status::{self, Branch, Change, Status, UntrackedFiles},
+    ObjectId,
+};
+
+// https://git-scm.com/docs/git-status
+
+pub struct Options {
+    /// Give the output in the short format
+    pub short: bool,
+    /// Show branch and tracking info in the short format
+    pub branch: bool,
+    /// Machine-readable format version (`v1` or `v2`)
+    pub porcelain: Option<String>,
+    pub untracked_files: UntrackedFiles,
+}
+
+enum Format {
+    Long,
+    Short,
+    PorcelainV1,
+    PorcelainV2,
+}
+
+/// git status command
+pub fn invoke(options: Options) -> anyhow::Result<()> {
+    let format = match options.porcelain.as_deref() {
+        Some("v1" | "1") => Format::PorcelainV1,
+        Some("v2" | "2") => Format::PorcelainV2,
+        Some(version) => anyhow::bail!("unsupported porcelain version '{version}'"),
+        None if options.short => Format::Short,
+        None => Format::Long,
+    };
+
+    let repo = super::open_repository()?;
+
+    // refreshed stat data is written back unless another process holds the lock
+    let lock = IndexLock::acquire(&repo).ok();
+    let mut index = Index::read(repo.index_file(), repo.algorithm())?;
+    let status = Status::collect(&repo, &mut index, options.untracked_files)?;
+    if let Some(lock) = lock {
+        lock.commit(&index)?;
+    }
+
+    let branch = Branch::read(&repo)?;
+    // paths are shown relative to the current directory except in porcelain formats
+    let prefix = repo.work_tree_path(".")?;
+
+    match format {
+        Format::Long => {
+            let mut out = String::new();
+            write_long(
+                &mut out,
+                &repo,
+                &status,
+                &branch,
+                &prefix,
+                options.untracked_files,
+                true,
+            )?;
+            print!("{out}");
+        }
+        Format::Short => print_short(&status, options.branch.then_some(&branch), &prefix),
+        Format::PorcelainV1 => print_short(&status, options.branch.then_some(&branch), b""),
+        Format::PorcelainV2 => {
+            print_porcelain_v2(&repo, &status, options.branch.then_some(&branch))
+        }
+    }
+
+    Ok(())
+}
+
+/// Converts path relative to the top of the work tree into path relative to `prefix`
+fn relative(path: &[u8], prefix: &[u8]) -> String {
+    let mut path = path;
+    let mut up = 0;
+    for dir in prefix.split(|&b| b == b'/').filter(|dir| !dir.is_empty()) {
+        match path
+            .strip_prefix(dir)
+            .and_then(|rest| rest.strip_prefix(b"/"))
+        {
+            Some(rest) if up == 0 => path = rest,
+            _ => up += 1,
+        }
+    }
+
+    let path = String::from_utf8_lossy(path);
+    match (up, path.is_empty()) {
+        (0, true) => "./".to_string(),
+        _ => format!("{}{path}", "../".repeat(up)),
+    }
+}
+
+/// Writes the long format, `hints` adds advice on commands to run
+pub fn write_long(
+    out: &mut impl Write,
+    repo: &Repository,
+    status: &Status,
+    branch: &Branch,
+    prefix: &[u8],
+    untracked_files: UntrackedFiles,
+    hints: bool,
+) -> fmt::Result {
+    match &branch.name {
+        Some(name) => writeln!(out, "On branch {}", status::short_ref_name(name))?,
+        None => writeln!(
+            out,
+            "HEAD detached at {}",
+            branch.oid.map(abbrev).unwrap_or_default()
+        )?,
+    }
+    write_tracking(out, branch, hints)?;
+    if branch.oid.is_none() {
+        writeln!(out, "\nNo commits yet\n")?;
+    }
+
+    let merging = repo.git_dir().join("MERGE_HEAD").exists();
+    let hint = |out: &mut dyn Write, hint: &str| match hints {
+        true => writeln!(out, "  ({hint})"),
+        false => Ok(()),
+    };
+    if merging {
+        if status.unmerged.is_empty() {
+            writeln!(out, "All conflicts fixed but you are still merging.")?;
+            hint(out, "use \"git commit\" to conclude merge")?;
+        } else {
+            writeln!(out, "You have unmerged paths.")?;
+            hint(out, "fix conflicts and run \"git commit\"")?;
+            hint(out, "use \"git merge --abort\" to abort the merge")?;
+        }
+        writeln!(out)?;
+    }
+
+    // staged changes of a merge are not meant to be unstaged
+    let unstage_hint = match branch.oid {
+        _ if merging => None,
+        Some(_) => Some("use \"git restore --staged <file>...\" to unstage"),
+        None => Some("use \"git rm --cached <file>...\" to unstage"),
+    };
+
+    if !status.unmerged.is_empty() {
+        writeln!(out, "Unmerged paths:")?;
+        if let Some(unstage_hint) = unstage_hint {
+            hint(out, unstage_hint)?;
+        }
+        let codes: Vec<_> = status.unmerged.iter().map(|entry| entry.code()).collect();
+        let both_deleted = codes.contains(&"DD");
+        let deleted_modified = codes.iter().any(|&code| code == "UD" || code == "DU");
+        hint(
+            out,
+            match (both_deleted, deleted_modified) {
+                (_, true) => "use \"git add/rm <file>...\" as appropriate to mark resolution",
+                (true, false) => "use \"git rm <file>...\" to mark resolution",
+                (false, false) => "use \"git add <file>...\" to mark resolution",
+            },
+        )?;
+        for (entry, code) in status.unmerged.iter().zip(codes) {
+            let label = match code {
+                "DD" => "both deleted:",
+                "AU" => "added by us:",
+                "UD" => "deleted by them:",
+                "UA" => "added by them:",
+                "DU" => "deleted by us:",
+                "AA" => "both added:",
+                _ => "both modified:",
+            };
+            writeln!(out, "\t{label:<17}{}", relative(&entry.path, prefix))?;
+        }
+        writeln!(out)?;
+    }
+
+    if status.has_staged() {
+        writeln!(out, "Changes to be committed:")?;
+        if let Some(unstage_hint) = unstage_hint {
+            hint(out, unstage_hint)?;
+        }
+        for entry in &status.entries {
+            let path = match &entry.orig_path {
+                Some(orig_path) => format!(
+                    "{} -> {}",
+                    relative(orig_path, prefix),
+                    relative(&entry.path, prefix)
+                ),
+                None => relative(&entry.path, prefix),
+            };
+            if let Some(label) = label(entry.staged) {
+                writeln!(out, "\t{label:<12}{path}")?;
+            }
+        }
+        writeln!(out)?;
+    }
+
+    let unstaged: Vec<_> = status
+        .entries
+        .iter()
+        .filter(|entry| entry.unstaged != Change::Unmodified)
+        .collect();
+    if !unstaged.is_empty() {
+        writeln!(out, "Changes not staged for commit:")?;
+        match unstaged
+            .iter()
+            .any(|entry| entry.unstaged == Change::Deleted)
+        {
+            true => hint(
+                out,
+                "use \"git add/rm <file>...\" to update what will be committed",
+            )?,
+            false => hint(
+                out,
+                "use \"git add <file>...\" to update what will be committed",
+            )?,
+        }
+        hint(
+            out,
+            "use \"git restore <file>...\" to discard changes in working directory",
+        )?;
+        for entry in unstaged {
+            if let Some(label) = label(entry.unstaged) {
+                writeln!(out, "\t{label:<12}{}", relative(&entry.path, prefix))?;
+            }
+        }
+        writeln!(out)?;
+    }
+
+    if !status.untracked.is_empty() {
+        writeln!(out, "Untracked files:")?;
+        hint(
+            out,
+            "use \"git add <file>...\" to include in what will be committed",
+        )?;
+        for path in &status.untracked {
+            writeln!(out, "\t{}", relative(path, prefix))?;
+        }
+        writeln!(out)?;
+    }
+
+    if status.has_staged() {
+        if untracked_files == UntrackedFiles::No {
+            writeln!(
+                out,
+                "Untracked files not listed (use -u option to show untracked files)"
+            )?;
+        }
+    } else if status.has_unstaged() {
+        writeln!(
+            out,
+            "no changes added to commit (use \"git add\" and/or \"git commit -a\")"
+        )?;
+    } else if !status.untracked.is_empty() {
+        writeln!(
+            out,
+            "nothing added to commit but untracked files present (use \"git add\" to track)"
+        )?;
+    } else if branch.oid.is_none() {
+        writeln!(
+            out,
+            "nothing to commit (create/copy files and use \"git add\" to track)"
+        )?;
+    } else if untracked_files == UntrackedFiles::No {
+        writeln!(out, "nothing to commit (use -u to show untracked files)")?;
+    } else {
+        writeln!(out, "nothing to commit, working tree clean")?;
+    }
+
+    Ok(())
+}
+
+/// Label of the change in the long format
+fn label(change: Change) -> Option<&'static str> {
+    match change {
+        Change::Unmodified => None,
+        Change::Added => Some("new file:"),
+        Change::Modified => Some("modified:"),
+        Change::Deleted => Some("deleted:"),
+        Change::TypeChanged => Some("typechange:"),
+        Change::Renamed => Some("renamed:"),
+    }
+}
+
+/// Writes how the branch relates to its upstream in the long format
+fn write_tracking(out: &mut impl Write, branch: &Branch, hints: bool) -> fmt::Result {
+    let Some(upstream) = &branch.upstream else {
+        return Ok(());
+    };
+    let upstream = status::short_ref_name(upstream);
+    let commits = |n: usize| match n {
+        1 => "1 commit".to_string(),
+        n => format!("{n} commits"),
+    };
+
+    let hint = match branch.ahead_behind {
+        None => {
+            writeln!(
+                out,
+                "Your branch is based on '{upstream}', but the upstream is gone."
+            )?;
+            Some("use \"git branch --unset-upstream\" to fixup")
+        }
+        Some((0, 0)) => {
+            writeln!(out, "Your branch is up to date with '{upstream}'.")?;
+            None
+        }
+        Some((ahead, 0)) => {
+            writeln!(
+                out,
+                "Your branch is ahead of '{upstream}' by {}.",
+                commits(ahead)
+            )?;
+            Some("use \"git push\" to publish your local commits")
+        }
+        Some((0, behind)) => {
+            writeln!(
+                out,
+                "Your branch is behind '{upstream}' by {}, and can be fast-forwarded.",
+                commits(behind)
+            )?;
+            Some("use \"git pull\" to update your local branch")
+        }
+        Some((ahead, behind)) => {
+            writeln!(out, "Your branch and '{upstream}' have diverged,")?;
+            writeln!(
+                out,
+                "and have {ahead} and {behind} different commits each, respectively."
+            )?;
+            Some("use \"git pull\" to merge the remote branch into yours")
+        }
+    };
+    if let Some(hint) = hint.filter(|_| hints) {
+        writeln!(out, "  ({hint})")?;
+    }
+    writeln!(out)
+}
+
+fn abbrev(oid: ObjectId) -> String {
+    oid.to_string()[..7].to_string()
+}
+
+/// Prints the short format, or porcelain v1 format when paths are not relative to a prefix
+fn print_short(status: &Status, branch: Option<&Branch>, prefix: &[u8]) {
+    if let Some(branch) = branch {
+        let mut line = match (&branch.name, branch.oid) {
+            (None, _) => "## HEAD (no branch)".to_string(),
+            (Some(name), None) => format!("## No commits yet on {}", status::short_ref_name(name)),
+            (Some(name), Some(_)) => format!("## {}", status::short_ref_name(name)),
+        };
+        if let Some(upstream) = &branch.upstream {
+            line.push_str(&format!("...{}", status::short_ref_name(upstream)));
+            match branch.ahead_behind {
+                None => line.push_str(" [gone]"),
+                Some((0, 0)) => {}
+                Some((ahead, 0)) => line.push_str(&format!(" [ahead {ahead}]")),
+                Some((0, behind)) => line.push_str(&format!(" [behind {behind}]")),
+                Some((ahead, behind)) => {
+                    line.push_str(&format!(" [ahead {ahead}, behind {behind}]"))
+                }
+            }
+        }
+        println!("{line}");
+    }
+
+    let mut lines: Vec<(&[u8], String)> = Vec::new();
+    for entry in &status.unmerged {
+        lines.push((
+            &entry.path,
+            format!("{} {}", entry.code(), relative(&entry.path, prefix)),
+        ));
+    }
+    for entry in &status.entries {
+        let path = match &entry.orig_path {
+            Some(orig_path) => format!(
+                "{} -> {}",
+                relative(orig_path, prefix),
+                relative(&entry.path, prefix)
+            ),
+            None => relative(&entry.path, prefix),
+        };
+        lines.push((
+            &entry.path,
+            format!("{}{} {path}", entry.staged.code(), entry.unstaged.code()),
+        ));
+    }
+    lines.sort();
+    for (_, line) in lines {
+        println!("{line}");
+    }
+
+    for path in &status.untracked {
+        println!("?? {}", relative(path, prefix));
+    }
+}
+
+/// Prints the porcelain v2 format, which shows modes and objects of every changed path
+fn print_porcelain_v2(repo: &Repository, status: &Status, branch: Option<&Branch>) {
+    let null = repo.algorithm().null();
+
+    if let Some(branch) = branch {
+        match branch.oid {
+            Some(oid) => println!("# branch.oid {oid}"),
+            None => println!("# branch.oid (initial)"),
+        }
+        match &branch.name {
+            Some(name) => println!("# branch.head {}", status::short_ref_name(name)),
+            None => println!("# branch.head (detached)"),
+        }
+        if let Some(upstream) = &branch.upstream {
+            println!("# branch.upstream {}", status::short_ref_name(upstream));
+            if let Some((ahead, behind)) = branch.ahead_behind {
+                println!("# branch.ab +{ahead} -{behind}");
+            }
+        }
+    }
+
+    let code = |change: Change| match change {
+        Change::Unmodified => '.',
+        change => change.code(),
+    };
+    let mut lines: Vec<(&[u8], String)> = Vec::new();
+    for entry in &status.unmerged {
+        let [m1, m2, m3] = entry.stages.map(|stage| stage.map_or(0, |(mode, _)| mode));
+        let [h1, h2, h3] = entry.stages.map(|stage| stage.map_or(null, |(_, oid)| oid));
+        lines.push((
+            &entry.path,
+            format!(
+                "u {} N... {m1:06o} {m2:06o} {m3:06o} {:06o} {h1} {h2} {h3} {}",
+                entry.code(),
+                entry.work_tree_mode,
+                String::from_utf8_lossy(&entry.path)
+            ),
+        ));
+    }
+    for entry in &status.entries {
+        let (head_mode, head_oid) = entry.head.unwrap_or((0, null));
+        let (index_mode, index_oid) = entry.index.unwrap_or((0, null));
+        let fields = format!(
+            "{}{} N... {head_mode:06o} {index_mode:06o} {:06o} {head_oid} {index_oid}",
+            code(entry.staged),
+            code(entry.unstaged),
+            entry.work_tree_mode
+        );
+        let path = String::from_utf8_lossy(&entry.path);
+        let line = match &entry.orig_path {
+            Some(orig_path) => format!(
+                "2 {fields} R100 {path}\t{}",
+                String::from_utf8_lossy(orig_path)
+            ),
+            None => format!("1 {fields} {path}"),
+        };
+        lines.push((&entry.path, line));
+    }
+    lines.sort();
+    for (_, line) in lines {
+        println!("{line}");
+    }
+
+    for path in &status.untracked {
+        println!("? {}", String::from_utf8_lossy(path));
+    }
+}
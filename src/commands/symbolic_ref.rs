@@ -0,0 +1,73 @@
+use git_starter_rust::refs;
+
+// https://git-scm.com/docs/git-symbolic-ref
+
+pub struct Options {
+    pub name: String,
+    /// Reference to point the symbolic reference to, shows the current one if missing
+    pub target: Option<String>,
+    pub delete: bool,
+    /// Exit with 1 instead of failing when the reference is not symbolic
+    pub quiet: bool,
+    /// Show the target without `refs/heads/` and similar prefixes
+    pub short: bool,
+    /// Reflog message of the change
+    pub message: Option<String>,
+}
+
+/// git symbolic-ref command
+pub fn invoke(options: Options) -> anyhow::Result<()> {
+    let repo = super::open_repository()?;
+    let git_dir = repo.git_dir();
+    let name = &options.name;
+
+    if options.delete {
+        anyhow::ensure!(name != "HEAD", "deleting 'HEAD' is not allowed");
+        if refs::read_symbolic(git_dir, name)?.is_none() {
+            if options.quiet {
+                std::process::exit(1);
+            }
+            anyhow::bail!("Cannot delete {name}, not a symbolic ref");
+        }
+        return refs::delete_symbolic(git_dir, name);
+    }
+
+    let Some(target) = &options.target else {
+        let Some(target) = refs::read_symbolic(git_dir, name)? else {
+            if options.quiet {
+                std::process::exit(1);
+            }
+            anyhow::bail!("ref {name} is not a symbolic ref");
+        };
+        match options.short {
+            true => println!("{}", shorten(&target)),
+            false => println!("{target}"),
+        }
+        return Ok(());
+    };
+
+    anyhow::ensure!(
+        name != "HEAD" || target.starts_with("refs/"),
+        "Refusing to point HEAD outside of refs/"
+    );
+    anyhow::ensure!(
+        refs::is_valid_name(target),
+        "Refusing to set '{name}' to invalid ref '{target}'"
+    );
+    let old = repo.resolve_ref(name)?;
+    refs::write_symbolic(git_dir, repo.shared(), name, target)?;
+
+    if let Some(new) = repo.resolve_ref(target)? {
+        let message = options.message.as_deref().unwrap_or_default();
+        repo.log_ref_update(name, old.as_ref(), &new, &repo.committer()?, message)?;
+    }
+    Ok(())
+}
+
+/// Strips the prefix of the reference kind
+fn shorten(name: &str) -> &str {
+    ["refs/heads/", "refs/tags/", "refs/remotes/", "refs/"]
+        .iter()
+        .find_map(|prefix| name.strip_prefix(prefix))
+        .unwrap_or(name)
+}
@@ -0,0 +1,193 @@
+use std::{
+    ffi::OsStr,
+    fs,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+
+use git_starter_rust::{
+    index::{self, Entry, FileState, Index, IndexLock, Stat},
+    object::{tree, ObjectId},
+    repository::Repository,
+};
+
+// https://git-scm.com/docs/git-update-index
+
+pub struct Options {
+    /// Add files that are not in the index yet
+    pub add: bool,
+    /// Remove files that are in the index but missing in the work tree
+    pub remove: bool,
+    /// Update stat data of unchanged files
+    pub refresh: bool,
+    /// Set (`+x`) or clear (`-x`) the executable bit of the files in the index
+    pub chmod: Option<String>,
+    /// Entries given as `<mode>,<object>,<path>`
+    pub cacheinfo: Vec<String>,
+    pub files: Vec<PathBuf>,
+}
+
+/// git update-index command
+pub fn invoke(options: Options) -> anyhow::Result<()> {
+    let repo = super::open_repository()?;
+    let executable = match options.chmod.as_deref() {
+        None => None,
+        Some("+x") => Some(true),
+        Some("-x") => Some(false),
+        Some(chmod) => anyhow::bail!("option 'chmod' expects \"+x\" or \"-x\", got '{chmod}'"),
+    };
+
+    let lock = IndexLock::acquire(&repo)?;
+    let mut index = Index::read(repo.index_file(), repo.algorithm())?;
+
+    let mut needs_update = 0;
+    if options.refresh {
+        needs_update = refresh(&repo, &mut index)?;
+    }
+
+    for cacheinfo in &options.cacheinfo {
+        let mut parts = cacheinfo.splitn(3, ',');
+        let (Some(mode), Some(oid), Some(path)) = (parts.next(), parts.next(), parts.next()) else {
+            anyhow::bail!("option 'cacheinfo' expects <mode>,<sha1>,<path>");
+        };
+        let mode = u32::from_str_radix(mode, 8)
+            .ok()
+            .filter(|mode| {
+                [
+                    tree::MODE_FILE,
+                    tree::MODE_EXECUTABLE,
+                    tree::MODE_SYMLINK,
+                    tree::MODE_GITLINK,
+                ]
+                .contains(mode)
+            })
+            .with_context(|| format!("invalid mode {mode} for '{path}'"))?;
+        let oid: ObjectId = oid.parse()?;
+        anyhow::ensure!(
+            oid.algorithm() == repo.algorithm(),
+            "{oid} is not a {} object name",
+            repo.algorithm()
+        );
+        add(
+            &mut index,
+            Entry::new(path.as_bytes().to_vec(), mode, oid),
+            options.add,
+        )
+        .with_context(|| format!("git update-index: --cacheinfo cannot add {path}"))?;
+    }
+
+    for file in &options.files {
+        update_file(&repo, &mut index, file, &options, executable)
+            .with_context(|| format!("Unable to process path {}", file.display()))?;
+    }
+
+    lock.commit(&index)?;
+
+    anyhow::ensure!(needs_update == 0, "{needs_update} files need update");
+
+    Ok(())
+}
+
+/// Records current content of the work tree file in the index, or removes the file
+/// from the index if it was deleted
+fn update_file(
+    repo: &Repository,
+    index: &mut Index,
+    file: &Path,
+    options: &Options,
+    executable: Option<bool>,
+) -> anyhow::Result<()> {
+    let path = repo.work_tree_path(file)?;
+    let name = String::from_utf8_lossy(&path).into_owned();
+    let full_path = repo
+        .work_tree()
+        .context("this operation must be run in a work tree")?
+        .join(OsStr::from_bytes(&path));
+
+    let metadata = match fs::symlink_metadata(&full_path) {
+        Ok(metadata) => metadata,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            anyhow::ensure!(
+                options.remove,
+                "{name}: does not exist and --remove not passed"
+            );
+            index.remove(&path);
+            return Ok(());
+        }
+        Err(err) => return Err(err).with_context(|| format!("stat {}", full_path.display())),
+    };
+    anyhow::ensure!(
+        !metadata.is_dir(),
+        "{name}: is a directory - add files inside instead"
+    );
+
+    let mut mode = index::mode_of(&metadata);
+    if let Some(executable) = executable {
+        anyhow::ensure!(mode != tree::MODE_SYMLINK, "cannot chmod on '{name}'");
+        mode = match executable {
+            true => tree::MODE_EXECUTABLE,
+            false => tree::MODE_FILE,
+        };
+    }
+
+    let oid = repo.hash_file(&full_path, true)?;
+    let mut entry = Entry::new(path, mode, oid);
+    entry.stat = Stat::from_metadata(&metadata);
+    add(index, entry, options.add)
+}
+
+/// Adds or replaces entry, new paths are only allowed with `allow_add`
+fn add(index: &mut Index, entry: Entry, allow_add: bool) -> anyhow::Result<()> {
+    let name = String::from_utf8_lossy(&entry.path).into_owned();
+    anyhow::ensure!(index::is_valid_path(&entry.path), "invalid path '{name}'");
+
+    if !index.contains(&entry.path) {
+        anyhow::ensure!(
+            allow_add,
+            "{name}: cannot add to the index - missing --add option?"
+        );
+        if let Some(conflict) = index.conflicting(&entry.path) {
+            anyhow::bail!(
+                "'{name}' conflicts with '{}', which appears as both a file and as a directory",
+                String::from_utf8_lossy(&conflict.path)
+            );
+        }
+    }
+
+    index.add(entry);
+    Ok(())
+}
+
+/// Updates stat data of entries whose files are unchanged. Returns number of entries
+/// that need to be updated or merged.
+fn refresh(repo: &Repository, index: &mut Index) -> anyhow::Result<usize> {
+    let mut stats = Vec::new();
+    let mut needs_update = 0;
+    for (i, entry) in index.entries.iter().enumerate() {
+        let path = String::from_utf8_lossy(&entry.path);
+        if entry.stage != 0 {
+            // stages of a path are adjacent, every conflicting path is reported once
+            if i == 0 || index.entries[i - 1].path != entry.path {
+                println!("{path}: needs merge");
+                needs_update += 1;
+            }
+            continue;
+        }
+
+        match index.file_state(repo, entry)? {
+            FileState::Unchanged(stat) => stats.push((i, stat)),
+            FileState::Modified | FileState::Deleted => {
+                println!("{path}: needs update");
+                needs_update += 1;
+            }
+        }
+    }
+
+    for (i, stat) in stats {
+        index.entries[i].stat = stat;
+    }
+
+    Ok(needs_update)
+}
@@ -0,0 +1,338 @@
+use std::io::Read;
+
+use anyhow::Context;
+use git_starter_rust::{
+    refs::{self, Expected, RefUpdate, Transaction},
+    repository::Repository,
+    ObjectId,
+};
+
+// https://git-scm.com/docs/git-update-ref
+
+pub struct Options {
+    /// Reflog message of the updates
+    pub message: Option<String>,
+    pub delete: bool,
+    /// Update symbolic references themselves instead of the references they point to
+    pub no_deref: bool,
+    /// Read transaction commands from standard input
+    pub stdin: bool,
+    /// Commands and their arguments are NUL-terminated
+    pub null_terminated: bool,
+    pub name: Option<String>,
+    pub new: Option<String>,
+    pub old: Option<String>,
+}
+
+/// Change of a reference requested by a command, `new` is `None` for deletion
+struct Command {
+    name: String,
+    new: Option<ObjectId>,
+    expected: Expected,
+    verify_only: bool,
+    no_deref: bool,
+}
+
+impl Command {
+    fn new(name: String, no_deref: bool) -> Self {
+        Self {
+            name,
+            new: None,
+            expected: Expected::Any,
+            verify_only: false,
+            no_deref,
+        }
+    }
+}
+
+/// git update-ref command
+pub fn invoke(options: Options) -> anyhow::Result<()> {
+    let repo = super::open_repository()?;
+    let message = options.message.as_deref().unwrap_or_default();
+
+    if options.stdin {
+        let mut input = Vec::new();
+        std::io::stdin()
+            .read_to_end(&mut input)
+            .context("reading standard input")?;
+        let input = String::from_utf8(input).context("input is not valid UTF-8")?;
+        return run_stdin(&repo, &input, &options, message);
+    }
+
+    let name = options
+        .name
+        .context("usage: update-ref [<options>] <refname> <new-val> [<old-val>]")?;
+    let null = repo.algorithm().null();
+    let expected = match &options.old {
+        None => Expected::Any,
+        Some(old) if old.is_empty() => Expected::Missing,
+        Some(old) => match super::resolve_revision(&repo, old) {
+            Ok(old) if old == null => Expected::Missing,
+            Ok(old) => Expected::Value(old),
+            Err(_) => anyhow::bail!("{old}: not a valid old SHA1"),
+        },
+    };
+    let new = match options.delete {
+        true => None,
+        false => {
+            let new = options.new.context("update-ref: missing <new-val>")?;
+            let new = super::resolve_revision(&repo, &new)
+                .map_err(|_| anyhow::anyhow!("{new}: not a valid SHA1"))?;
+            (new != null).then_some(new)
+        }
+    };
+    let command = Command {
+        name: name.clone(),
+        new,
+        expected,
+        verify_only: false,
+        no_deref: options.no_deref,
+    };
+    apply(&repo, vec![command], message).map_err(|err| match options.delete {
+        true => err,
+        false => anyhow::anyhow!("update_ref failed for ref '{name}': {err}"),
+    })
+}
+
+/// Runs `update`, `create`, `delete`, `verify`, `option`, `start`, `prepare`, `commit`
+/// and `abort` commands, updates before `commit` or the end of input are applied together
+fn run_stdin(
+    repo: &Repository,
+    input: &str,
+    options: &Options,
+    message: &str,
+) -> anyhow::Result<()> {
+    let mut parser = Parser {
+        input,
+        null_terminated: options.null_terminated,
+    };
+    let mut commands = Vec::new();
+    // `option no-deref` applies to the next command only
+    let mut no_deref = false;
+
+    while let Some(line) = parser.command()? {
+        let (verb, mut args) = Args::split(&line);
+        let mut ref_name = || -> anyhow::Result<String> {
+            args.next_arg()?
+                .filter(|name| !name.is_empty())
+                .with_context(|| format!("{verb}: missing <ref>"))
+        };
+        let (name, count) = match verb.as_str() {
+            "update" => (ref_name()?, 2),
+            "create" | "delete" | "verify" => (ref_name()?, 1),
+            "option" => {
+                match args.rest() {
+                    "no-deref" => no_deref = true,
+                    option => anyhow::bail!("option unknown: {option}"),
+                }
+                continue;
+            }
+            "start" | "prepare" | "commit" | "abort" => {
+                if verb == "commit" {
+                    apply(repo, std::mem::take(&mut commands), message)?;
+                } else if verb == "abort" {
+                    commands.clear();
+                }
+                println!("{verb}: ok");
+                continue;
+            }
+            _ => anyhow::bail!("unknown command: {line}"),
+        };
+
+        let mut values = Vec::new();
+        for _ in 0..count {
+            values.push(parser.value(&mut args)?);
+        }
+        let rest = args.rest();
+        anyhow::ensure!(rest.is_empty(), "{verb} {name}: extra input: {rest}");
+
+        let no_deref = std::mem::take(&mut no_deref) || options.no_deref;
+        let null = repo.algorithm().null();
+        let value = |value: Option<&String>, kind: &str| -> anyhow::Result<Option<ObjectId>> {
+            match value.filter(|value| !value.is_empty()) {
+                Some(value) => super::resolve_revision(repo, value)
+                    .map(Some)
+                    .map_err(|_| anyhow::anyhow!("{verb} {name}: invalid <{kind}>: {value}")),
+                None => Ok(None),
+            }
+        };
+        let expected = |old: Option<ObjectId>| match old {
+            None => Expected::Any,
+            Some(old) if old == null => Expected::Missing,
+            Some(old) => Expected::Value(old),
+        };
+
+        let command = match verb.as_str() {
+            "update" => {
+                let new = value(values[0].as_ref(), "newvalue")?
+                    .with_context(|| format!("update {name}: missing <newvalue>"))?;
+                Command {
+                    new: (new != null).then_some(new),
+                    expected: expected(value(values[1].as_ref(), "oldvalue")?),
+                    ..Command::new(name, no_deref)
+                }
+            }
+            "create" => {
+                let new = value(values[0].as_ref(), "newvalue")?
+                    .with_context(|| format!("create {name}: missing <newvalue>"))?;
+                anyhow::ensure!(new != null, "create {name}: zero <newvalue>");
+                Command {
+                    new: Some(new),
+                    expected: Expected::Missing,
+                    ..Command::new(name, no_deref)
+                }
+            }
+            "delete" => {
+                let old = value(values[0].as_ref(), "oldvalue")?;
+                anyhow::ensure!(old != Some(null), "delete {name}: zero <oldvalue>");
+                Command {
+                    expected: expected(old),
+                    ..Command::new(name, no_deref)
+                }
+            }
+            _ => Command {
+                // missing value means the reference must not exist
+                expected: expected(Some(value(values[0].as_ref(), "oldvalue")?.unwrap_or(null))),
+                verify_only: true,
+                ..Command::new(name, no_deref)
+            },
+        };
+        commands.push(command);
+    }
+
+    apply(repo, commands, message)
+}
+
+/// Applies the commands in one transaction and records the updates in reflogs
+fn apply(repo: &Repository, commands: Vec<Command>, message: &str) -> anyhow::Result<()> {
+    let git_dir = repo.git_dir();
+    let head = refs::head_target(git_dir)?;
+    let mut transaction = Transaction::new(git_dir, repo.shared());
+    let mut logged = Vec::new();
+
+    for command in commands {
+        let name = match command.no_deref {
+            true => command.name,
+            false => refs::resolve_name(git_dir, &command.name)?,
+        };
+        if command.verify_only {
+            transaction.verify(&name, command.expected)?;
+            continue;
+        }
+        let old = repo.resolve_ref(&name)?;
+        transaction.add(RefUpdate {
+            name: name.clone(),
+            expected: command.expected,
+            new: command.new,
+        })?;
+        if let Some(new) = command.new {
+            logged.push((name, old, new));
+        }
+    }
+    transaction.commit()?;
+
+    if logged.is_empty() {
+        return Ok(());
+    }
+    let committer = repo.committer()?;
+    for (name, old, new) in logged {
+        repo.log_ref_update(&name, old.as_ref(), &new, &committer, message)?;
+        // the branch checked out is updated through HEAD
+        if head.as_deref() == Some(name.as_str()) {
+            repo.log_ref_update("HEAD", old.as_ref(), &new, &committer, message)?;
+        }
+    }
+    Ok(())
+}
+
+/// Reads commands as lines of space-separated, possibly quoted arguments, or with `-z`
+/// as `<command> SP <ref> NUL` followed by NUL-terminated values
+struct Parser<'a> {
+    input: &'a str,
+    null_terminated: bool,
+}
+
+/// Arguments of a command following the command name
+struct Args<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Parser<'a> {
+    fn terminator(&self) -> char {
+        match self.null_terminated {
+            true => '\0',
+            false => '\n',
+        }
+    }
+
+    /// Next command with its arguments in the same line or field
+    fn command(&mut self) -> anyhow::Result<Option<String>> {
+        if self.input.is_empty() {
+            return Ok(None);
+        }
+        let (command, rest) = self
+            .input
+            .split_once(self.terminator())
+            .unwrap_or((self.input, ""));
+        self.input = rest;
+        anyhow::ensure!(!command.is_empty(), "empty command in input");
+        Ok(Some(command.to_string()))
+    }
+
+    /// Next value, from the command line or with `-z` the next field
+    fn value(&mut self, args: &mut Args) -> anyhow::Result<Option<String>> {
+        if !self.null_terminated {
+            return args.next_arg();
+        }
+        let (value, rest) = self
+            .input
+            .split_once('\0')
+            .context("unexpected end of input")?;
+        self.input = rest;
+        Ok(Some(value.to_string()))
+    }
+}
+
+impl<'a> Args<'a> {
+    /// Splits the command name from its arguments
+    fn split(line: &'a str) -> (String, Self) {
+        let (verb, rest) = line.split_once(' ').unwrap_or((line, ""));
+        (verb.to_string(), Self { rest })
+    }
+
+    /// Next space-separated argument, quoted arguments are unquoted
+    fn next_arg(&mut self) -> anyhow::Result<Option<String>> {
+        if self.rest.is_empty() {
+            return Ok(None);
+        }
+        if let Some(quoted) = self.rest.strip_prefix('"') {
+            let mut value = String::new();
+            let mut chars = quoted.char_indices();
+            while let Some((i, c)) = chars.next() {
+                match c {
+                    '"' => {
+                        self.rest = quoted[i + 1..]
+                            .strip_prefix(' ')
+                            .unwrap_or(&quoted[i + 1..]);
+                        return Ok(Some(value));
+                    }
+                    '\\' => match chars.next().map(|(_, c)| c) {
+                        Some('n') => value.push('\n'),
+                        Some('t') => value.push('\t'),
+                        Some(c @ ('"' | '\\')) => value.push(c),
+                        _ => anyhow::bail!("badly quoted argument: {}", self.rest),
+                    },
+                    c => value.push(c),
+                }
+            }
+            anyhow::bail!("badly quoted argument: {}", self.rest);
+        }
+        let (value, rest) = self.rest.split_once(' ').unwrap_or((self.rest, ""));
+        self.rest = rest;
+        Ok(Some(value.to_string()))
+    }
+
+    fn rest(&self) -> &str {
+        self.rest
+    }
+}
@@ -0,0 +1,633 @@
+use std::{
+    cell::{Cell, OnceCell},
+    collections::HashSet,
+    io::{prelude::*, BufReader},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+
+use git_starter_rust::{
+    config,
+    object::{Commit, ObjectId, ObjectType, Tag},
+    pack::{self, writer::PackOptions},
+    pkt_line::{self, Band, Packet, SidebandWriter},
+    refs,
+    repository::Repository,
+    rev_list::{self, ObjectEntry},
+};
+
+// https://github.com/git/git/blob/795ea8776befc95ea2becd8020c7a284677b4161/Documentation/gitprotocol-pack.txt
+// https://github.com/git/git/blob/795ea8776befc95ea2becd8020c7a284677b4161/Documentation/gitprotocol-v2.txt
+
+pub const AGENT: &str = concat!("git-starter-rust/", env!("CARGO_PKG_VERSION"));
+
+/// Part of the conversation with client carried by one connection
+#[derive(Clone, Copy, PartialEq)]
+pub enum Mode {
+    /// Whole conversation over one bidirectional connection (git://, ssh)
+    Stateful,
+    /// Only the reference (or capability) advertisement
+    AdvertiseRefs,
+    /// One request and response without the advertisement (smart HTTP)
+    StatelessRpc,
+}
+
+/// git upload-pack command, serves pack over stdin/stdout (used by ssh and file:// transports)
+pub fn invoke(dir: &Path, mode: Mode) -> anyhow::Result<()> {
+    let git_dir = enter_repo(dir)
+        .with_context(|| format!("'{}' does not appear to be a git repository", dir.display()))?;
+
+    // ssh and local transports pass extra parameters in GIT_PROTOCOL environment variable
+    let git_protocol = std::env::var("GIT_PROTOCOL").unwrap_or_default();
+    let version = protocol_version(git_protocol.split(':'));
+
+    let mut stdin = BufReader::new(std::io::stdin().lock());
+    let mut stdout = std::io::stdout().lock();
+
+    serve(&git_dir, version, mode, &mut stdin, &mut stdout)
+}
+
+/// Finds git directory for a repository path the same way as `git daemon` does,
+/// trying `<path>/.git`, `<path>`, `<path>.git/.git` and `<path>.git`
+pub fn enter_repo(path: &Path) -> Option<PathBuf> {
+    let path = path.as_os_str();
+
+    for suffix in ["/.git", "", ".git/.git", ".git"] {
+        let mut candidate = path.to_os_string();
+        candidate.push(suffix);
+        let candidate = PathBuf::from(candidate);
+
+        if candidate.join("objects").is_dir()
+            && candidate.join("refs").is_dir()
+            && candidate.join("HEAD").is_file()
+        {
+            return Some(candidate);
+        }
+    }
+
+    None
+}
+
+/// Returns protocol version requested in extra parameters (ie. `version=2`)
+pub fn protocol_version<'a>(params: impl Iterator<Item = &'a str>) -> u8 {
+    params
+        .filter_map(|param| param.strip_prefix("version="))
+        .filter_map(|version| version.parse().ok())
+        .max()
+        .unwrap_or(0)
+}
+
+/// Runs upload-pack service for repository in `git_dir` over the connection
+pub fn serve(
+    git_dir: &Path,
+    version: u8,
+    mode: Mode,
+    reader: &mut impl Read,
+    writer: &mut impl Write,
+) -> anyhow::Result<()> {
+    let upload_pack = UploadPack {
+        repo: Repository::open(git_dir)?,
+        mode,
+        allowed_wants: OnceCell::new(),
+        sending_pack: Cell::new(false),
+    };
+    super::report_problems(&upload_pack.repo);
+
+    let result = if version == 2 {
+        upload_pack.serve_v2(reader, writer)
+    } else {
+        upload_pack.serve_v0(reader, writer)
+    };
+
+    // errors in the request are reported to client, errors while sending the pack
+    // are reported on the sideband
+    if let Err(err) = &result {
+        if !upload_pack.sending_pack.get() {
+            let _ = pkt_line::write_str(writer, &format!("ERR {err}\n"))
+                .and_then(|_| writer.flush().context("flushing error"));
+        }
+    }
+    result
+}
+
+struct UploadPack {
+    repo: Repository,
+    mode: Mode,
+    /// Objects clients may ask for, `None` if any object is allowed
+    allowed_wants: OnceCell<Option<HashSet<ObjectId>>>,
+    /// Set once the pack is being sent
+    sending_pack: Cell<bool>,
+}
+
+/// Options negotiated with client that affect how the pack is sent
+struct SendOptions {
+    sideband: Option<usize>,
+    no_progress: bool,
+    include_tag: bool,
+    ofs_delta: bool,
+}
+
+impl UploadPack {
+    fn serve_v0(&self, reader: &mut impl Read, writer: &mut impl Write) -> anyhow::Result<()> {
+        if self.mode != Mode::StatelessRpc {
+            self.advertise_refs(writer)?;
+            if self.mode == Mode::AdvertiseRefs {
+                return Ok(());
+            }
+        }
+
+        // read wants
+        let mut wants = Vec::new();
+        let mut capabilities = Vec::new();
+        loop {
+            match pkt_line::read(reader)? {
+                // client just wanted the list of refs (ls-remote) and closed the connection
+                None => return Ok(()),
+                Some(Packet::Flush) => break,
+                Some(packet) => {
+                    let line = packet.as_text().context("want line is not valid UTF-8")?;
+                    let mut words = line.split(' ');
+                    anyhow::ensure!(
+                        words.next() == Some("want"),
+                        "protocol error: expected want, got '{line}'"
+                    );
+                    let hash = words.next().context("protocol error: missing want hash")?;
+                    wants.push(self.check_want(hash)?);
+                    if capabilities.is_empty() {
+                        capabilities.extend(words.map(|capability| capability.to_string()));
+                    }
+                }
+            }
+        }
+
+        if wants.is_empty() {
+            return Ok(());
+        }
+
+        if let Some(format) = capabilities
+            .iter()
+            .find_map(|c| c.strip_prefix("object-format="))
+        {
+            self.check_object_format(format)?;
+        }
+
+        let has_capability = |name: &str| capabilities.iter().any(|c| c == name);
+        let multi_ack = has_capability("multi_ack_detailed");
+
+        // negotiate common commits; without multi_ack only the first common object is acknowledged,
+        // with multi_ack_detailed every common object is acknowledged but we never signal "ready"
+        let mut common = Vec::new();
+        loop {
+            match pkt_line::read(reader)? {
+                None => return Ok(()),
+                Some(Packet::Flush) => {
+                    if common.is_empty() || multi_ack {
+                        pkt_line::write_str(writer, "NAK\n")?;
+                    }
+                    writer.flush()?;
+                    if self.mode == Mode::StatelessRpc {
+                        // client sends next round of haves in a new request
+                        return Ok(());
+                    }
+                }
+                Some(packet) => {
+                    let line = packet.as_text().context("have line is not valid UTF-8")?;
+                    if line == "done" {
+                        match common.last() {
+                            Some(last) if multi_ack => {
+                                pkt_line::write_str(writer, &format!("ACK {last}\n"))?
+                            }
+                            Some(_) => {}
+                            None => pkt_line::write_str(writer, "NAK\n")?,
+                        }
+                        break;
+                    }
+                    let hash = line
+                        .strip_prefix("have ")
+                        .with_context(|| format!("protocol error: expected have, got '{line}'"))?;
+                    let hash = parse_have(hash)?;
+                    if self.has_object(&hash) {
+                        if multi_ack {
+                            pkt_line::write_str(writer, &format!("ACK {hash} common\n"))?;
+                        } else if common.is_empty() {
+                            pkt_line::write_str(writer, &format!("ACK {hash}\n"))?;
+                        }
+                        common.push(hash);
+                    }
+                }
+            }
+        }
+
+        let options = SendOptions {
+            sideband: if has_capability("side-band-64k") {
+                Some(pkt_line::MAX_SIDEBAND_DATA)
+            } else if has_capability("side-band") {
+                Some(pkt_line::MAX_SIDEBAND_SMALL_DATA)
+            } else {
+                None
+            },
+            no_progress: has_capability("no-progress"),
+            include_tag: has_capability("include-tag"),
+            ofs_delta: has_capability("ofs-delta"),
+        };
+
+        self.send_pack(writer, &wants, &common, &options)
+    }
+
+    /// Sends reference advertisement with capabilities behind the first ref
+    fn advertise_refs(&self, writer: &mut impl Write) -> anyhow::Result<()> {
+        let mut capabilities = String::from(
+            "multi_ack_detailed include-tag side-band side-band-64k ofs-delta no-progress",
+        );
+        if let Some(target) = refs::head_target(self.repo.git_dir())? {
+            capabilities.push_str(&format!(" symref=HEAD:{target}"));
+        }
+        let git_dir = self.repo.git_dir();
+        let allows = |key: &str| config::get_bool(git_dir, key).map(|value| value == Some(true));
+        let any = allows("uploadpack.allowAnySHA1InWant")?;
+        if any || allows("uploadpack.allowTipSHA1InWant")? {
+            capabilities.push_str(" allow-tip-sha1-in-want");
+        }
+        if any || allows("uploadpack.allowReachableSHA1InWant")? {
+            capabilities.push_str(" allow-reachable-sha1-in-want");
+        }
+        capabilities.push_str(&format!(
+            " object-format={} agent={AGENT}",
+            self.repo.algorithm()
+        ));
+
+        let mut lines = Vec::new();
+        if let Some(head) = refs::resolve(self.repo.git_dir(), "HEAD")? {
+            lines.push((head, "HEAD".to_string()));
+        }
+        for r in refs::list(self.repo.git_dir())? {
+            let peeled = self.peel(&r.hash)?;
+            let name = r.name;
+            lines.push((r.hash, name.clone()));
+            if let Some(peeled) = peeled {
+                lines.push((peeled, format!("{name}^{{}}")));
+            }
+        }
+
+        if lines.is_empty() {
+            // empty repository still needs to send capabilities
+            pkt_line::write_str(
+                writer,
+                &format!(
+                    "{} capabilities^{{}}\0{capabilities}\n",
+                    self.repo.algorithm().null()
+                ),
+            )?;
+        }
+
+        for (i, (hash, name)) in lines.iter().enumerate() {
+            if i == 0 {
+                pkt_line::write_str(writer, &format!("{hash} {name}\0{capabilities}\n"))?;
+            } else {
+                pkt_line::write_str(writer, &format!("{hash} {name}\n"))?;
+            }
+        }
+
+        pkt_line::flush(writer)
+    }
+
+    fn serve_v2(&self, reader: &mut impl Read, writer: &mut impl Write) -> anyhow::Result<()> {
+        if self.mode != Mode::StatelessRpc {
+            pkt_line::write_str(writer, "version 2\n")?;
+            pkt_line::write_str(writer, &format!("agent={AGENT}\n"))?;
+            pkt_line::write_str(writer, "ls-refs\n")?;
+            pkt_line::write_str(writer, "fetch\n")?;
+            pkt_line::write_str(writer, "server-option\n")?;
+            pkt_line::write_str(
+                writer,
+                &format!("object-format={}\n", self.repo.algorithm()),
+            )?;
+            pkt_line::flush(writer)?;
+            if self.mode == Mode::AdvertiseRefs {
+                return Ok(());
+            }
+        }
+
+        loop {
+            // command=<name>, capability list, delimiter, arguments, flush
+            let Some(packet) = pkt_line::read(reader)? else {
+                return Ok(());
+            };
+            if packet == Packet::Flush {
+                // empty request
+                continue;
+            }
+            let line = packet
+                .as_text()
+                .context("command line is not valid UTF-8")?;
+            let command = line
+                .strip_prefix("command=")
+                .with_context(|| format!("protocol error: expected command, got '{line}'"))?
+                .to_string();
+
+            let mut args = Vec::new();
+            let mut in_args = false;
+            loop {
+                match pkt_line::read(reader)?.context("unexpected end of request")? {
+                    Packet::Flush => break,
+                    Packet::Delim => in_args = true,
+                    Packet::ResponseEnd => anyhow::bail!("protocol error: unexpected response end"),
+                    packet => {
+                        let line = packet
+                            .as_text()
+                            .context("request line is not valid UTF-8")?
+                            .to_string();
+                        if in_args {
+                            args.push(line);
+                        } else if let Some(format) = line.strip_prefix("object-format=") {
+                            self.check_object_format(format)?;
+                        }
+                    }
+                }
+            }
+
+            match command.as_str() {
+                "ls-refs" => self.ls_refs(writer, &args)?,
+                "fetch" => self.fetch(writer, &args)?,
+                _ => anyhow::bail!("unknown command '{command}'"),
+            }
+        }
+    }
+
+    fn ls_refs(&self, writer: &mut impl Write, args: &[String]) -> anyhow::Result<()> {
+        let symrefs = args.iter().any(|arg| arg == "symrefs");
+        let peel = args.iter().any(|arg| arg == "peel");
+        let unborn = args.iter().any(|arg| arg == "unborn");
+        let prefixes: Vec<_> = args
+            .iter()
+            .filter_map(|arg| arg.strip_prefix("ref-prefix "))
+            .collect();
+        let wanted =
+            |name: &str| prefixes.is_empty() || prefixes.iter().any(|p| name.starts_with(p));
+
+        if wanted("HEAD") {
+            let target = refs::head_target(self.repo.git_dir())?;
+            let mut line = match refs::resolve(self.repo.git_dir(), "HEAD")? {
+                Some(hash) => format!("{hash} HEAD"),
+                None if unborn => "unborn HEAD".to_string(),
+                None => String::new(),
+            };
+            if !line.is_empty() {
+                if let (true, Some(target)) = (symrefs, target) {
+                    line.push_str(&format!(" symref-target:{target}"));
+                }
+                line.push('\n');
+                pkt_line::write_str(writer, &line)?;
+            }
+        }
+
+        for r in refs::list(self.repo.git_dir())? {
+            if !wanted(&r.name) {
+                continue;
+            }
+            let mut line = format!("{} {}", r.hash, r.name);
+            if peel {
+                if let Some(peeled) = self.peel(&r.hash)? {
+                    line.push_str(&format!(" peeled:{peeled}"));
+                }
+            }
+            line.push('\n');
+            pkt_line::write_str(writer, &line)?;
+        }
+
+        pkt_line::flush(writer)
+    }
+
+    fn fetch(&self, writer: &mut impl Write, args: &[String]) -> anyhow::Result<()> {
+        let mut wants = Vec::new();
+        let mut haves = Vec::new();
+        let mut done = false;
+        let mut options = SendOptions {
+            // packfile section is always multiplexed in protocol v2
+            sideband: Some(pkt_line::MAX_SIDEBAND_DATA),
+            no_progress: false,
+            include_tag: false,
+            ofs_delta: false,
+        };
+
+        for arg in args {
+            if let Some(hash) = arg.strip_prefix("want ") {
+                wants.push(self.check_want(hash)?);
+            } else if let Some(hash) = arg.strip_prefix("have ") {
+                haves.push(parse_have(hash)?);
+            } else {
+                match arg.as_str() {
+                    "done" => done = true,
+                    "no-progress" => options.no_progress = true,
+                    "include-tag" => options.include_tag = true,
+                    "ofs-delta" => options.ofs_delta = true,
+                    // we never send thin packs, so this does not change anything
+                    "thin-pack" => {}
+                    _ => anyhow::bail!("unexpected fetch argument '{arg}'"),
+                }
+            }
+        }
+
+        let common: Vec<_> = haves
+            .into_iter()
+            .filter(|hash| self.has_object(hash))
+            .collect();
+
+        if !done {
+            pkt_line::write_str(writer, "acknowledgments\n")?;
+            if common.is_empty() {
+                pkt_line::write_str(writer, "NAK\n")?;
+            }
+            for hash in &common {
+                pkt_line::write_str(writer, &format!("ACK {hash}\n"))?;
+            }
+            if common.is_empty() || !self.all_have_common(&wants, &common)? {
+                // let client send more haves or done
+                return pkt_line::flush(writer);
+            }
+            pkt_line::write_str(writer, "ready\n")?;
+            pkt_line::delim(writer)?;
+        }
+
+        pkt_line::write_str(writer, "packfile\n")?;
+        self.send_pack(writer, &wants, &common, &options)
+    }
+
+    /// Sends packfile with all objects reachable from `wants` that are not reachable from `common`
+    fn send_pack(
+        &self,
+        writer: &mut impl Write,
+        wants: &[ObjectId],
+        common: &[ObjectId],
+        options: &SendOptions,
+    ) -> anyhow::Result<()> {
+        self.sending_pack.set(true);
+        let mut objects = rev_list::objects(self.repo.odb(), wants, common)?;
+
+        if options.include_tag {
+            // add annotated tags pointing to objects being sent
+            let sent: HashSet<_> = objects.iter().map(|entry| entry.hash).collect();
+            for r in refs::list(self.repo.git_dir())? {
+                if !r.name.starts_with("refs/tags/") || sent.contains(&r.hash) {
+                    continue;
+                }
+                if let Some(peeled) = self.peel(&r.hash)? {
+                    if sent.contains(&peeled) {
+                        objects.extend(self.tag_chain(&r.hash)?);
+                    }
+                }
+            }
+        }
+
+        let pack_options = PackOptions {
+            ofs_delta: options.ofs_delta,
+            ..Default::default()
+        };
+
+        match options.sideband {
+            Some(max_data) => {
+                if !options.no_progress {
+                    let mut progress = SidebandWriter::new(&mut *writer, Band::Progress, max_data);
+                    writeln!(progress, "Enumerating objects: {}, done.", objects.len())?;
+                }
+                let mut data = SidebandWriter::new(&mut *writer, Band::Data, max_data);
+                if let Err(err) =
+                    pack::writer::write(self.repo.odb(), &objects, &pack_options, &mut data)
+                {
+                    // report the failure to client before closing the connection
+                    let mut error = SidebandWriter::new(&mut *writer, Band::Error, max_data);
+                    writeln!(error, "error: {err:#}")?;
+                    pkt_line::flush(writer)?;
+                    return Err(err);
+                }
+                pkt_line::flush(writer)
+            }
+            None => {
+                pack::writer::write(self.repo.odb(), &objects, &pack_options, writer)?;
+                writer.flush().context("flushing pack")
+            }
+        }
+    }
+
+    /// Returns whether each wanted commit has an ancestor in `common`, so that more haves
+    /// would not make the pack smaller. Wanted tags are peeled, other objects are not checked.
+    fn all_have_common(&self, wants: &[ObjectId], common: &[ObjectId]) -> anyhow::Result<bool> {
+        let common: HashSet<_> = common.iter().collect();
+        let odb = self.repo.odb();
+
+        for want in wants {
+            let want = self.peel(want)?.unwrap_or(*want);
+            let header = odb
+                .read_header(&want)?
+                .with_context(|| format!("object {want} not found"))?;
+            if header.typ != ObjectType::Commit {
+                continue;
+            }
+
+            let mut seen = HashSet::new();
+            let mut stack = vec![want];
+            let mut found = false;
+            while let Some(id) = stack.pop() {
+                if common.contains(&id) {
+                    found = true;
+                    break;
+                }
+                if seen.insert(id) {
+                    stack.extend(Commit::read(&id, odb)?.parents);
+                }
+            }
+            if !found {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Ensures that client uses the same object format as the repository
+    fn check_object_format(&self, format: &str) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            format == self.repo.algorithm().name(),
+            "client requested object format {format}, repository uses {}",
+            self.repo.algorithm()
+        );
+        Ok(())
+    }
+
+    /// Returns the tag and all tags it points to
+    fn tag_chain(&self, hash: &ObjectId) -> anyhow::Result<Vec<ObjectEntry>> {
+        let mut tags = Vec::new();
+        let mut hash = *hash;
+
+        loop {
+            let tag = Tag::read(&hash, self.repo.odb())?;
+            tags.push(ObjectEntry {
+                hash,
+                path: String::new(),
+            });
+            if tag.typ != ObjectType::Tag {
+                return Ok(tags);
+            }
+            hash = tag.object;
+        }
+    }
+
+    /// Returns target of an annotated tag, following chains of tags
+    fn peel(&self, hash: &ObjectId) -> anyhow::Result<Option<ObjectId>> {
+        self.repo.peel_tag(hash)
+    }
+
+    /// Accepts advertised ref tips, or objects reachable from them or any object
+    /// if allowed by `uploadpack.allowReachableSHA1InWant` or `uploadpack.allowAnySHA1InWant`
+    fn check_want(&self, hash: &str) -> anyhow::Result<ObjectId> {
+        let id = ObjectId::from_hex(hash)
+            .with_context(|| format!("protocol error: malformed want '{hash}'"))?;
+        let allowed = match self.allowed_wants.get() {
+            Some(allowed) => allowed,
+            None => {
+                let allowed = self.allowed_wants()?;
+                self.allowed_wants.get_or_init(|| allowed)
+            }
+        };
+        let ours = match allowed {
+            Some(allowed) => allowed.contains(&id),
+            None => self.has_object(&id),
+        };
+        anyhow::ensure!(ours, "git upload-pack: not our ref {id}");
+        Ok(id)
+    }
+
+    // https://git-scm.com/docs/git-config#Documentation/git-config.txt-uploadpackallowTipSHA1InWant
+    fn allowed_wants(&self) -> anyhow::Result<Option<HashSet<ObjectId>>> {
+        let git_dir = self.repo.git_dir();
+        let allows = |key: &str| -> anyhow::Result<bool> {
+            Ok(config::get_bool(git_dir, key)?.unwrap_or(false))
+        };
+        if allows("uploadpack.allowAnySHA1InWant")? {
+            return Ok(None);
+        }
+
+        // tips of hidden refs would be allowed by allowTipSHA1InWant, but no refs are hidden
+        let mut tips: Vec<ObjectId> = refs::resolve(git_dir, "HEAD")?.into_iter().collect();
+        for r in refs::list(git_dir)? {
+            tips.extend(self.peel(&r.hash)?);
+            tips.push(r.hash);
+        }
+        if allows("uploadpack.allowReachableSHA1InWant")? {
+            let reachable = rev_list::objects(self.repo.odb(), &tips, &[])?;
+            return Ok(Some(
+                reachable.into_iter().map(|entry| entry.hash).collect(),
+            ));
+        }
+        Ok(Some(tips.into_iter().collect()))
+    }
+
+    fn has_object(&self, id: &ObjectId) -> bool {
+        self.repo.odb().contains(id).unwrap_or(false)
+    }
+}
+
+fn parse_have(hash: &str) -> anyhow::Result<ObjectId> {
+    ObjectId::from_hex(hash).with_context(|| format!("protocol error: malformed have '{hash}'"))
+}
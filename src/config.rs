@@ -0,0 +1,556 @@
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+
+use crate::{refs, wildmatch::wildmatch};
+
+mod file;
+
+pub use file::{ConfigFile, Variable};
+
+// https://git-scm.com/docs/git-config#_configuration_file
+
+/// Where the configuration comes from, in increasing precedence
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Scope {
+    System,
+    Global,
+    Local,
+    Command,
+}
+
+/// Source of a config value as shown by `git config --show-origin`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Origin {
+    File(PathBuf),
+    CommandLine,
+}
+
+impl Display for Origin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Origin::File(path) => write!(f, "file:{}", path.display()),
+            Origin::CommandLine => write!(f, "command line:"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Entry {
+    /// Canonical key with lowercase section and name, ie. `remote.origin.url`
+    pub key: String,
+    /// `None` for variables without `=`, which mean `true`
+    pub value: Option<String>,
+    pub scope: Scope,
+    pub origin: Origin,
+}
+
+/// Merged configuration of all scopes, later entries take precedence
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    entries: Vec<Entry>,
+    /// Repository the conditional includes are evaluated against
+    git_dir: Option<PathBuf>,
+}
+
+const MAX_INCLUDE_DEPTH: usize = 10;
+
+impl Config {
+    /// Reads the system, global and repository config files (when in a repository)
+    /// followed by values given by `GIT_CONFIG_COUNT`, `GIT_CONFIG_KEY_<n>`,
+    /// `GIT_CONFIG_VALUE_<n>` and `-c` (passed in `GIT_CONFIG_PARAMETERS`)
+    pub fn load(git_dir: Option<&Path>) -> anyhow::Result<Self> {
+        let mut config = Self {
+            entries: Vec::new(),
+            git_dir: git_dir.map(Path::to_path_buf),
+        };
+        if let Some(path) = system_path() {
+            config.add_file(&path, Scope::System, 0)?;
+        }
+        for path in global_paths() {
+            config.add_file(&path, Scope::Global, 0)?;
+        }
+        if let Some(git_dir) = git_dir {
+            config.add_file(&git_dir.join("config"), Scope::Local, 0)?;
+        }
+
+        for (key, value) in command_line_values()? {
+            config.entries.push(Entry {
+                key,
+                value,
+                scope: Scope::Command,
+                origin: Origin::CommandLine,
+            });
+        }
+        Ok(config)
+    }
+
+    /// Reads only the file, without following includes
+    pub fn from_file(path: &Path, scope: Scope) -> anyhow::Result<Self> {
+        let file = ConfigFile::read(path)?;
+        let entries = file
+            .variables()
+            .map(|variable| Entry {
+                key: variable.key.clone(),
+                value: variable.value.clone(),
+                scope,
+                origin: Origin::File(path.to_path_buf()),
+            })
+            .collect();
+        Ok(Self {
+            entries,
+            git_dir: None,
+        })
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// Returns all entries of the key (ie. `Remote.origin.URL` or `remote.origin.url`)
+    /// in increasing precedence
+    pub fn entries_of(&self, key: &str) -> anyhow::Result<Vec<&Entry>> {
+        let key = Key::parse(key)?.to_string();
+        Ok(self
+            .entries
+            .iter()
+            .filter(|entry| entry.key == key)
+            .collect())
+    }
+
+    /// Returns the value with the highest precedence
+    pub fn get(&self, key: &str) -> anyhow::Result<Option<&str>> {
+        self.entries_of(key)?
+            .last()
+            .map(|entry| string(entry))
+            .transpose()
+    }
+
+    /// Returns all values of a multivalued key (ie. `remote.origin.fetch`)
+    pub fn get_all(&self, key: &str) -> anyhow::Result<Vec<&str>> {
+        self.entries_of(key)?.into_iter().map(string).collect()
+    }
+
+    pub fn get_bool(&self, key: &str) -> anyhow::Result<Option<bool>> {
+        self.entries_of(key)?
+            .last()
+            .map(|entry| {
+                parse_bool(entry.value.as_deref())
+                    .with_context(|| format!("bad boolean config value for '{}'", entry.key))
+            })
+            .transpose()
+    }
+
+    pub fn get_int(&self, key: &str) -> anyhow::Result<Option<i64>> {
+        self.entries_of(key)?
+            .last()
+            .map(|entry| {
+                parse_int(string(entry)?)
+                    .with_context(|| format!("bad numeric config value for '{}'", entry.key))
+            })
+            .transpose()
+    }
+
+    /// Returns the path with `~/` expanded to the home directory
+    pub fn get_path(&self, key: &str) -> anyhow::Result<Option<PathBuf>> {
+        self.entries_of(key)?
+            .last()
+            .map(|entry| expand_path(string(entry)?))
+            .transpose()
+    }
+
+    fn add_file(&mut self, path: &Path, scope: Scope, depth: usize) -> anyhow::Result<()> {
+        let file = ConfigFile::read(path)?;
+        for variable in file.variables() {
+            self.entries.push(Entry {
+                key: variable.key.clone(),
+                value: variable.value.clone(),
+                scope,
+                origin: Origin::File(path.to_path_buf()),
+            });
+
+            // https://git-scm.com/docs/git-config#_includes
+            let included = match variable.key.strip_suffix(".path") {
+                Some("include") => true,
+                Some(condition) => match condition.strip_prefix("includeif.") {
+                    Some(condition) => self.condition_holds(condition, path)?,
+                    None => false,
+                },
+                None => false,
+            };
+            let Some(include) = variable.value.as_deref().filter(|_| included) else {
+                continue;
+            };
+            anyhow::ensure!(
+                depth < MAX_INCLUDE_DEPTH,
+                "exceeded maximum include depth ({MAX_INCLUDE_DEPTH}) while including\n\t\
+                 {include}\nfrom\n\t{}",
+                path.display()
+            );
+            let mut include = expand_path(include)?;
+            if include.is_relative() {
+                include = path.parent().unwrap_or(Path::new("")).join(include);
+            }
+            self.add_file(&include, scope, depth + 1)?;
+        }
+        Ok(())
+    }
+
+    /// Evaluates `gitdir:`, `gitdir/i:` and `onbranch:` condition of `includeIf` section
+    /// found in the file
+    fn condition_holds(&self, condition: &str, file: &Path) -> anyhow::Result<bool> {
+        let Some(git_dir) = &self.git_dir else {
+            return Ok(false);
+        };
+
+        if let Some(pattern) = condition.strip_prefix("onbranch:") {
+            let Some(branch) = refs::head_target(git_dir)? else {
+                return Ok(false);
+            };
+            let Some(branch) = branch.strip_prefix("refs/heads/") else {
+                return Ok(false);
+            };
+            let mut pattern = pattern.to_string();
+            if pattern.ends_with('/') {
+                pattern.push_str("**");
+            }
+            return Ok(wildmatch(pattern.as_bytes(), branch.as_bytes(), true));
+        }
+
+        let (pattern, icase) = match condition.strip_prefix("gitdir:") {
+            Some(pattern) => (pattern, false),
+            None => match condition.strip_prefix("gitdir/i:") {
+                Some(pattern) => (pattern, true),
+                None => return Ok(false),
+            },
+        };
+        let mut pattern = match pattern.strip_prefix("./") {
+            Some(rest) => file
+                .parent()
+                .unwrap_or(Path::new(""))
+                .join(rest)
+                .to_string_lossy()
+                .into_owned(),
+            None => expand_path(pattern)?.to_string_lossy().into_owned(),
+        };
+        if !Path::new(&pattern).is_absolute() {
+            pattern.insert_str(0, "**/");
+        }
+        if pattern.ends_with('/') {
+            pattern.push_str("**");
+        }
+
+        let mut candidates = vec![git_dir.clone()];
+        if let Ok(real) = git_dir.canonicalize() {
+            candidates.push(real);
+        }
+        Ok(candidates.iter().any(|git_dir| {
+            let git_dir = git_dir.to_string_lossy();
+            match icase {
+                true => wildmatch(
+                    pattern.to_lowercase().as_bytes(),
+                    git_dir.to_lowercase().as_bytes(),
+                    true,
+                ),
+                false => wildmatch(pattern.as_bytes(), git_dir.as_bytes(), true),
+            }
+        }))
+    }
+}
+
+/// Value of the entry for string getters, where variables without `=` are invalid
+fn string(entry: &Entry) -> anyhow::Result<&str> {
+    entry
+        .value
+        .as_deref()
+        .with_context(|| format!("missing value for '{}'", entry.key))
+}
+
+/// Returns the value of the key with the highest precedence in the configuration
+/// of the repository
+pub fn get(git_dir: &Path, key: &str) -> anyhow::Result<Option<String>> {
+    Ok(Config::load(Some(git_dir))?.get(key)?.map(str::to_string))
+}
+
+/// Returns all values of the key in the configuration of the repository,
+/// in increasing precedence
+pub fn get_all(git_dir: &Path, key: &str) -> anyhow::Result<Vec<String>> {
+    Ok(Config::load(Some(git_dir))?
+        .get_all(key)?
+        .into_iter()
+        .map(str::to_string)
+        .collect())
+}
+
+/// Returns the boolean value of the key in the configuration of the repository
+pub fn get_bool(git_dir: &Path, key: &str) -> anyhow::Result<Option<bool>> {
+    Config::load(Some(git_dir))?.get_bool(key)
+}
+
+/// `/etc/gitconfig` unless overridden by `GIT_CONFIG_SYSTEM` or disabled
+/// by `GIT_CONFIG_NOSYSTEM`
+pub fn system_path() -> Option<PathBuf> {
+    let disabled = std::env::var("GIT_CONFIG_NOSYSTEM")
+        .is_ok_and(|value| parse_bool(Some(&value)).unwrap_or(false));
+    if disabled {
+        return None;
+    }
+    Some(
+        std::env::var_os("GIT_CONFIG_SYSTEM")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from("/etc/gitconfig")),
+    )
+}
+
+/// `$XDG_CONFIG_HOME/git/config` and `~/.gitconfig` unless overridden by `GIT_CONFIG_GLOBAL`
+pub fn global_paths() -> Vec<PathBuf> {
+    if let Some(path) = std::env::var_os("GIT_CONFIG_GLOBAL") {
+        return vec![PathBuf::from(path)];
+    }
+    let xdg = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::home_dir().map(|home| home.join(".config")))
+        .map(|dir| dir.join("git/config"));
+    let home = std::env::home_dir().map(|home| home.join(".gitconfig"));
+    xdg.into_iter().chain(home).collect()
+}
+
+/// Path `git config --global` writes to: `~/.gitconfig` unless only the XDG file exists
+pub fn global_write_path() -> anyhow::Result<PathBuf> {
+    let paths = global_paths();
+    let path = match paths.as_slice() {
+        [xdg, home] if xdg.exists() && !home.exists() => xdg,
+        [.., path] => path,
+        [] => anyhow::bail!("$HOME not set"),
+    };
+    Ok(path.clone())
+}
+
+/// Reads `GIT_CONFIG_COUNT` pairs and `GIT_CONFIG_PARAMETERS` set from `-c` options
+fn command_line_values() -> anyhow::Result<Vec<(String, Option<String>)>> {
+    let mut values = Vec::new();
+
+    if let Ok(count) = std::env::var("GIT_CONFIG_COUNT") {
+        let count: usize = count.parse().context("bogus count in GIT_CONFIG_COUNT")?;
+        for i in 0..count {
+            let key = std::env::var(format!("GIT_CONFIG_KEY_{i}"))
+                .with_context(|| format!("missing config key GIT_CONFIG_KEY_{i}"))?;
+            let value = std::env::var(format!("GIT_CONFIG_VALUE_{i}"))
+                .with_context(|| format!("missing config value GIT_CONFIG_VALUE_{i}"))?;
+            values.push((Key::parse(&key)?.to_string(), Some(value)));
+        }
+    }
+
+    if let Ok(parameters) = std::env::var("GIT_CONFIG_PARAMETERS") {
+        for (key, value) in
+            parse_parameters(&parameters).context("bogus format in GIT_CONFIG_PARAMETERS")?
+        {
+            values.push((Key::parse(&key)?.to_string(), value));
+        }
+    }
+
+    Ok(values)
+}
+
+/// Adds `key=value` given by `-c` to `GIT_CONFIG_PARAMETERS`, so that it applies to this
+/// process and the git processes it runs. Without `=` the key is set to `true`.
+pub fn push_parameter(parameter: &str) -> anyhow::Result<()> {
+    let (key, value) = match parameter.split_once('=') {
+        Some((key, value)) => (key, Some(value)),
+        None => (parameter, None),
+    };
+    Key::parse(key)?;
+
+    let quote = |value: &str| format!("'{}'", value.replace('\'', r"'\''"));
+    let mut parameters = std::env::var("GIT_CONFIG_PARAMETERS").unwrap_or_default();
+    if !parameters.is_empty() {
+        parameters.push(' ');
+    }
+    parameters.push_str(&quote(key));
+    if let Some(value) = value {
+        parameters.push('=');
+        parameters.push_str(&quote(value));
+    }
+    std::env::set_var("GIT_CONFIG_PARAMETERS", parameters);
+    Ok(())
+}
+
+/// Parses space separated single-quoted `'key'='value'` and `'key=value'` items
+fn parse_parameters(parameters: &str) -> anyhow::Result<Vec<(String, Option<String>)>> {
+    let mut values = Vec::new();
+    let mut rest = parameters.trim_start();
+    while !rest.is_empty() {
+        let mut chars = rest.chars().peekable();
+        let (item, len) = parse_quoted(&mut chars)?;
+        rest = &rest[len..];
+        let (key, value) = match rest.strip_prefix('=') {
+            Some(after) => {
+                let mut chars = after.chars().peekable();
+                let (value, len) = parse_quoted(&mut chars)?;
+                rest = &after[len..];
+                (item, Some(value))
+            }
+            None => match item.split_once('=') {
+                Some((key, value)) => (key.to_string(), Some(value.to_string())),
+                None => (item, None),
+            },
+        };
+        values.push((key, value));
+        rest = rest.trim_start();
+    }
+    Ok(values)
+}
+
+/// Parses single-quoted string as written by the shell quoting, returns it with the number
+/// of bytes consumed
+fn parse_quoted(
+    chars: &mut std::iter::Peekable<std::str::Chars>,
+) -> anyhow::Result<(String, usize)> {
+    let mut len = 0;
+    let mut next = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+        let c = chars.next();
+        len += c.map_or(0, char::len_utf8);
+        c
+    };
+    anyhow::ensure!(next(chars) == Some('\''), "missing quote");
+    let mut value = String::new();
+    loop {
+        match next(chars).context("unterminated quote")? {
+            // quote inside is written as '\''
+            '\'' if chars.peek() == Some(&'\\') => {
+                next(chars);
+                anyhow::ensure!(next(chars) == Some('\''), "bad quote escape");
+                anyhow::ensure!(next(chars) == Some('\''), "bad quote escape");
+                value.push('\'');
+            }
+            '\'' => return Ok((value, len)),
+            c => value.push(c),
+        }
+    }
+}
+
+/// Interprets boolean value, variables without `=` are `true`
+pub fn parse_bool(value: Option<&str>) -> anyhow::Result<bool> {
+    let Some(value) = value else {
+        return Ok(true);
+    };
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "yes" | "on" => Ok(true),
+        "false" | "no" | "off" | "" => Ok(false),
+        _ => Ok(parse_int(value).with_context(|| format!("invalid boolean '{value}'"))? != 0),
+    }
+}
+
+/// Interprets integer with optional `k`, `m` or `g` suffix
+pub fn parse_int(value: &str) -> anyhow::Result<i64> {
+    let value = value.trim();
+    let (number, unit) = match value.char_indices().last() {
+        Some((i, c)) if c.is_ascii_alphabetic() => (&value[..i], c.to_ascii_lowercase()),
+        _ => (value, ' '),
+    };
+    let factor = match unit {
+        ' ' => 1,
+        'k' => 1 << 10,
+        'm' => 1 << 20,
+        'g' => 1 << 30,
+        _ => anyhow::bail!("invalid unit"),
+    };
+    let number: i64 = number.parse().context("invalid unit")?;
+    number.checked_mul(factor).context("out of range")
+}
+
+/// Expands `~/` at the start of the path to the home directory
+pub fn expand_path(value: &str) -> anyhow::Result<PathBuf> {
+    match value.strip_prefix("~/") {
+        Some(rest) => Ok(std::env::home_dir()
+            .context("failed to expand user dir: $HOME not set")?
+            .join(rest)),
+        None if value == "~" => std::env::home_dir().context("failed to expand user dir"),
+        None if value.starts_with('~') => {
+            anyhow::bail!("failed to expand user dir in: '{value}'")
+        }
+        None => Ok(PathBuf::from(value)),
+    }
+}
+
+/// Config key split into its parts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Key {
+    /// Section as written, it is compared case-insensitively
+    pub section: String,
+    /// Subsection, which is case-sensitive
+    pub subsection: Option<String>,
+    /// Variable name as written, it is compared case-insensitively
+    pub written_name: String,
+}
+
+impl Key {
+    /// Splits `section.name` or `section.subsection.name`, checking that section and
+    /// variable names contain only alphanumeric characters and `-`
+    pub fn parse(key: &str) -> anyhow::Result<Self> {
+        let (section, name) = key
+            .rsplit_once('.')
+            .with_context(|| format!("key does not contain a section: {key}"))?;
+        anyhow::ensure!(
+            !name.is_empty(),
+            "key does not contain variable name: {key}"
+        );
+        let (section, subsection) = match section.split_once('.') {
+            Some((section, subsection)) => (section, Some(subsection)),
+            None => (section, None),
+        };
+
+        let valid_section = !section.is_empty()
+            && section
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'-');
+        let valid_name = name.starts_with(|c: char| c.is_ascii_alphabetic())
+            && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-');
+        anyhow::ensure!(
+            valid_section && valid_name && !subsection.is_some_and(|sub| sub.contains('\n')),
+            "invalid key: {key}"
+        );
+
+        Ok(Self {
+            section: section.to_string(),
+            subsection: subsection.map(str::to_string),
+            written_name: name.to_string(),
+        })
+    }
+
+    /// Canonical `section` or `section.subsection` prefix of the key
+    pub fn section_prefix(&self) -> String {
+        let section = self.section.to_ascii_lowercase();
+        match &self.subsection {
+            Some(subsection) => format!("{section}.{subsection}"),
+            None => section,
+        }
+    }
+
+    /// Line starting section of the key in a config file
+    pub fn header(&self) -> String {
+        match &self.subsection {
+            Some(subsection) => {
+                let subsection = subsection.replace('\\', r"\\").replace('"', "\\\"");
+                format!("[{} \"{subsection}\"]\n", self.section)
+            }
+            None => format!("[{}]\n", self.section),
+        }
+    }
+}
+
+/// Canonical form with lowercase section and name
+impl Display for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}.{}",
+            self.section_prefix(),
+            self.written_name.to_ascii_lowercase()
+        )
+    }
+}
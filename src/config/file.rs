@@ -0,0 +1,404 @@
+use std::{fs, io::Write, ops::Range, path::Path};
+
+use anyhow::Context;
+
+use super::Key;
+
+/// Config file kept as written, so that editing it preserves comments and formatting
+#[derive(Debug, Clone, Default)]
+pub struct ConfigFile {
+    text: String,
+    items: Vec<Item>,
+}
+
+/// Variable as found in the file
+#[derive(Debug, Clone)]
+pub struct Variable {
+    /// Canonical key with lowercase section and name, ie. `remote.origin.url`
+    pub key: String,
+    /// `None` for variables without `=`, which mean `true`
+    pub value: Option<String>,
+    /// Line the variable starts at, counted from 1
+    pub line: usize,
+    /// Whole lines of the variable including the newline
+    span: Range<usize>,
+}
+
+#[derive(Debug, Clone)]
+enum Item {
+    Section {
+        /// Canonical section and subsection, ie. `remote.origin`
+        prefix: String,
+        span: Range<usize>,
+    },
+    Variable(Variable),
+}
+
+impl ConfigFile {
+    /// Reads the config file, missing file is empty
+    pub fn read(path: &Path) -> anyhow::Result<Self> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err).with_context(|| format!("reading {}", path.display())),
+        };
+        Self::parse(text).with_context(|| format!("bad config file {}", path.display()))
+    }
+
+    pub fn parse(text: String) -> anyhow::Result<Self> {
+        let items = Parser::new(&text).items()?;
+        Ok(Self { text, items })
+    }
+
+    /// Variables in the order they appear
+    pub fn variables(&self) -> impl Iterator<Item = &Variable> {
+        self.items.iter().filter_map(|item| match item {
+            Item::Variable(variable) => Some(variable),
+            Item::Section { .. } => None,
+        })
+    }
+
+    /// Sets the variable, replacing its value if it exists. With `add` another value is
+    /// added at the end of the section instead, with `replace_all` all the values are
+    /// replaced by this one.
+    pub fn set(
+        &mut self,
+        key: &str,
+        value: &str,
+        add: bool,
+        replace_all: bool,
+    ) -> anyhow::Result<()> {
+        let key = Key::parse(key)?;
+        let canonical = key.to_string();
+        let existing: Vec<Range<usize>> = self
+            .variables()
+            .filter(|variable| variable.key == canonical)
+            .map(|variable| variable.span.clone())
+            .collect();
+        let line = format!("\t{} = {}\n", key.written_name, quote(value));
+
+        if !add && !existing.is_empty() {
+            anyhow::ensure!(
+                existing.len() == 1 || replace_all,
+                "{key} has multiple values"
+            );
+            // the last value is replaced, others removed
+            let mut text = self.text.clone();
+            for (i, span) in existing.iter().enumerate().rev() {
+                let replacement = if i == existing.len() - 1 { &line } else { "" };
+                Self::replace_lines(&mut text, span.clone(), replacement);
+            }
+            return self.update(text);
+        }
+
+        // new value goes to the end of the last section of the key
+        let section = key.section_prefix();
+        let position = self
+            .items
+            .iter()
+            .fold((None, false), |(position, in_section), item| match item {
+                Item::Section { prefix, span } if *prefix == section => (Some(span.end), true),
+                Item::Section { .. } => (position, false),
+                Item::Variable(variable) if in_section => (Some(variable.span.end), true),
+                Item::Variable(_) => (position, in_section),
+            })
+            .0;
+
+        let mut text = self.text.clone();
+        let (position, mut insert) = match position {
+            Some(position) => (position, line),
+            None => (text.len(), format!("{}{line}", key.header())),
+        };
+        if position > 0 && !text[..position].ends_with('\n') {
+            insert.insert(0, '\n');
+        }
+        text.insert_str(position, &insert);
+        self.update(text)
+    }
+
+    /// Removes the variable, or all its values with `all`. Returns number of removed values.
+    pub fn unset(&mut self, key: &str, all: bool) -> anyhow::Result<usize> {
+        let canonical = Key::parse(key)?.to_string();
+        let existing: Vec<Range<usize>> = self
+            .variables()
+            .filter(|variable| variable.key == canonical)
+            .map(|variable| variable.span.clone())
+            .collect();
+        if existing.len() > 1 && !all {
+            anyhow::bail!("{canonical} has multiple values");
+        }
+
+        let mut text = self.text.clone();
+        for span in existing.iter().rev() {
+            Self::replace_lines(&mut text, span.clone(), "");
+        }
+        self.update(text)?;
+        Ok(existing.len())
+    }
+
+    /// Writes the file through a lock file
+    pub fn write(&self, path: &Path) -> anyhow::Result<()> {
+        let mut lock_path = path.as_os_str().to_owned();
+        lock_path.push(".lock");
+        let mut lock = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&lock_path)
+            .with_context(|| format!("could not lock config file {}", path.display()))?;
+        let written = lock
+            .write_all(self.text.as_bytes())
+            .and_then(|_| fs::rename(&lock_path, path));
+        if written.is_err() {
+            let _ = fs::remove_file(&lock_path);
+        }
+        written.with_context(|| format!("writing {}", path.display()))
+    }
+
+    /// Replaces lines of a variable, which may follow a section header on its line
+    fn replace_lines(text: &mut String, span: Range<usize>, replacement: &str) {
+        let newline = match span.start > 0 && !text[..span.start].ends_with('\n') {
+            true => "\n",
+            false => "",
+        };
+        text.replace_range(span, &format!("{newline}{replacement}"));
+    }
+
+    fn update(&mut self, text: String) -> anyhow::Result<()> {
+        *self = Self::parse(text)?;
+        Ok(())
+    }
+}
+
+/// Quotes the value if it would not be read back the same otherwise
+fn quote(value: &str) -> String {
+    let quoted = value.starts_with([' ', '\t'])
+        || value.ends_with([' ', '\t'])
+        || value.contains([';', '#']);
+    let mut result = String::new();
+    if quoted {
+        result.push('"');
+    }
+    for c in value.chars() {
+        match c {
+            '\n' => result.push_str("\\n"),
+            '\t' => result.push_str("\\t"),
+            '\u{8}' => result.push_str("\\b"),
+            '"' | '\\' => {
+                result.push('\\');
+                result.push(c);
+            }
+            c => result.push(c),
+        }
+    }
+    if quoted {
+        result.push('"');
+    }
+    result
+}
+
+// https://git-scm.com/docs/git-config#_syntax
+
+struct Parser<'t> {
+    text: &'t [u8],
+    position: usize,
+    line: usize,
+}
+
+impl<'t> Parser<'t> {
+    fn new(text: &'t str) -> Self {
+        let position = match text.starts_with('\u{feff}') {
+            true => '\u{feff}'.len_utf8(),
+            false => 0,
+        };
+        Self {
+            text: text.as_bytes(),
+            position,
+            line: 1,
+        }
+    }
+
+    fn items(mut self) -> anyhow::Result<Vec<Item>> {
+        let mut items = Vec::new();
+        let mut section = None;
+        loop {
+            let start = self.position;
+            self.skip_blanks();
+            let Some(c) = self.next() else {
+                return Ok(items);
+            };
+            match c {
+                b'\n' => {}
+                b'#' | b';' => self.skip_line(),
+                b'[' => {
+                    let prefix = self.section_header().with_context(|| self.error())?;
+                    // comment after the header belongs to it, a variable may follow it
+                    let end = self.position;
+                    self.skip_blanks();
+                    match self.peek() {
+                        Some(b'#' | b';' | b'\n') | None => self.skip_line(),
+                        Some(_) => self.position = end,
+                    }
+                    items.push(Item::Section {
+                        prefix: prefix.clone(),
+                        span: start..self.position,
+                    });
+                    section = Some(prefix);
+                }
+                c if c.is_ascii_alphabetic() => {
+                    let line = self.line;
+                    let prefix = section.as_ref().with_context(|| self.error())?;
+                    let mut name = vec![c.to_ascii_lowercase()];
+                    while let Some(c) = self
+                        .peek()
+                        .filter(|&c| c.is_ascii_alphanumeric() || c == b'-')
+                    {
+                        name.push(c.to_ascii_lowercase());
+                        self.position += 1;
+                    }
+                    self.skip_blanks();
+                    let value = match self.peek() {
+                        Some(b'=') => {
+                            self.position += 1;
+                            Some(self.value().with_context(|| self.error())?)
+                        }
+                        Some(b'\n' | b'#' | b';') | None => {
+                            self.skip_line();
+                            None
+                        }
+                        Some(_) => anyhow::bail!(self.error()),
+                    };
+                    items.push(Item::Variable(Variable {
+                        key: format!("{prefix}.{}", String::from_utf8_lossy(&name)),
+                        value,
+                        line,
+                        span: start..self.position,
+                    }));
+                }
+                _ => anyhow::bail!(self.error()),
+            }
+        }
+    }
+
+    fn error(&self) -> String {
+        format!("bad config line {}", self.line)
+    }
+
+    fn peek(&self) -> Option<u8> {
+        match self.text.get(self.position..) {
+            Some([b'\r', b'\n', ..]) => Some(b'\n'),
+            Some(rest) => rest.first().copied(),
+            None => None,
+        }
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let c = self.peek()?;
+        self.position += if self.text[self.position] == b'\r' && c == b'\n' {
+            2
+        } else {
+            1
+        };
+        if c == b'\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn skip_blanks(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t')) {
+            self.position += 1;
+        }
+    }
+
+    /// Skips to the start of the next line
+    fn skip_line(&mut self) {
+        while let Some(c) = self.next() {
+            if c == b'\n' {
+                break;
+            }
+        }
+    }
+
+    /// Parses `[section]`, `[section "subsection"]` or deprecated `[section.subsection]`
+    /// following the `[`, returns the canonical prefix of keys in it
+    fn section_header(&mut self) -> anyhow::Result<String> {
+        let mut name = String::new();
+        while let Some(c) = self
+            .peek()
+            .filter(|&c| c.is_ascii_alphanumeric() || c == b'-' || c == b'.')
+        {
+            name.push(c.to_ascii_lowercase() as char);
+            self.position += 1;
+        }
+        anyhow::ensure!(!name.is_empty(), "missing section name");
+
+        match self.next() {
+            Some(b']') => Ok(name),
+            Some(b' ' | b'\t') if !name.contains('.') => {
+                self.skip_blanks();
+                anyhow::ensure!(self.next() == Some(b'"'), "missing subsection quote");
+                let mut subsection = Vec::new();
+                loop {
+                    match self.next() {
+                        Some(b'"') => break,
+                        Some(b'\\') => {
+                            subsection.push(self.next().context("unterminated subsection")?)
+                        }
+                        Some(b'\n') | None => anyhow::bail!("unterminated subsection"),
+                        Some(c) => subsection.push(c),
+                    }
+                }
+                anyhow::ensure!(self.next() == Some(b']'), "missing ']'");
+                let subsection =
+                    String::from_utf8(subsection).context("subsection is not valid UTF-8")?;
+                Ok(format!("{name}.{subsection}"))
+            }
+            _ => anyhow::bail!("invalid section header"),
+        }
+    }
+
+    /// Parses value following the `=` up to the end of line. Whitespace around it and
+    /// comments are dropped, quotes and escapes interpreted and lines ending with
+    /// a backslash continued.
+    fn value(&mut self) -> anyhow::Result<String> {
+        let mut value = Vec::new();
+        let mut quoted = false;
+        let mut spaces = 0;
+        loop {
+            let c = match self.next() {
+                Some(b'\n') | None if quoted => anyhow::bail!("unterminated quote"),
+                Some(b'\n') | None => break,
+                Some(c) => c,
+            };
+            if !quoted {
+                if c == b' ' || c == b'\t' {
+                    // whitespace inside the value is kept as spaces, not at its ends
+                    if !value.is_empty() {
+                        spaces += 1;
+                    }
+                    continue;
+                }
+                if c == b'#' || c == b';' {
+                    self.skip_line();
+                    break;
+                }
+            }
+            value.extend(std::iter::repeat_n(b' ', spaces));
+            spaces = 0;
+
+            match c {
+                b'\\' => match self.next() {
+                    Some(b'\n') => {}
+                    Some(b'n') => value.push(b'\n'),
+                    Some(b't') => value.push(b'\t'),
+                    Some(b'b') => value.push(b'\x08'),
+                    Some(c @ (b'"' | b'\\')) => value.push(c),
+                    _ => anyhow::bail!("invalid escape sequence"),
+                },
+                b'"' => quoted = !quoted,
+                c => value.push(c),
+            }
+        }
+        String::from_utf8(value).context("value is not valid UTF-8")
+    }
+}
@@ -0,0 +1,239 @@
+use anyhow::Context;
+
+// https://git-scm.com/docs/git-commit#_date_formats
+
+/// Current time with the offset of the local timezone
+pub fn now() -> anyhow::Result<(i64, String)> {
+    let time = std::time::SystemTime::now()
+        .duration_since(std::time::SystemTime::UNIX_EPOCH)
+        .context("current system time is before UNIX epoch")?
+        .as_secs() as i64;
+    Ok((time, format_offset(local_offset(time))))
+}
+
+/// Offset of the local timezone from UTC in minutes at the given time
+pub fn local_offset(time: i64) -> i64 {
+    let time = time as libc::time_t;
+    // SAFETY: tm is plain data filled in by localtime_r, which does not keep the pointers
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&time, &mut tm) }.is_null() {
+        return 0;
+    }
+    tm.tm_gmtoff / 60
+}
+
+/// Formats offset in minutes as `+HHMM` or `-HHMM`
+pub fn format_offset(offset: i64) -> String {
+    let sign = if offset < 0 { '-' } else { '+' };
+    let offset = offset.abs();
+    format!("{sign}{:02}{:02}", offset / 60, offset % 60)
+}
+
+/// Parses date given to git (ie. in `GIT_AUTHOR_DATE`) as seconds since UNIX epoch and
+/// timezone. Understands git's internal `<unix timestamp> <timezone>` (optionally with `@`),
+/// RFC 2822 (`Thu, 07 Apr 2005 22:13:13 +0200`) and ISO 8601 (`2005-04-07T22:13:13+02:00`).
+/// Dates without a timezone are in the local one.
+pub fn parse(value: &str) -> anyhow::Result<(i64, String)> {
+    parse_fields(value).with_context(|| format!("invalid date format: {value}"))
+}
+
+#[derive(Default)]
+struct Fields {
+    timestamp: Option<i64>,
+    year: Option<i64>,
+    month: Option<i64>,
+    day: Option<i64>,
+    time: Option<(i64, i64, i64)>,
+    /// Minutes east of UTC
+    offset: Option<i64>,
+}
+
+fn parse_fields(value: &str) -> Option<(i64, String)> {
+    let bytes = value.as_bytes();
+    let mut fields = Fields::default();
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i];
+        if c.is_ascii_alphabetic() {
+            let len = bytes[i..]
+                .iter()
+                .take_while(|c| c.is_ascii_alphabetic())
+                .count();
+            match_word(&value[i..i + len], &mut fields)?;
+            i += len;
+        } else if c.is_ascii_digit() {
+            i += match_number(&bytes[i..], &mut fields)?;
+        } else if (c == b'+' || c == b'-') && bytes.get(i + 1).is_some_and(u8::is_ascii_digit) {
+            i += match_offset(&bytes[i..], &mut fields)?;
+        } else if c == b'@' && i == 0 {
+            let len = bytes[1..].iter().take_while(|c| c.is_ascii_digit()).count();
+            fields.timestamp = Some(value.get(1..1 + len)?.parse().ok()?);
+            i += 1 + len;
+        } else if c.is_ascii_whitespace() || c == b',' {
+            i += 1;
+        } else {
+            return None;
+        }
+    }
+
+    let time = match fields.timestamp {
+        Some(timestamp) => {
+            if fields.year.is_some() || fields.time.is_some() {
+                return None;
+            }
+            timestamp
+        }
+        None => {
+            let (year, month, day) = (fields.year?, fields.month?, fields.day?);
+            let (hour, minute, second) = fields.time.unwrap_or_default();
+            if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+                return None;
+            }
+            let local =
+                days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second;
+            match fields.offset {
+                Some(offset) => local - offset * 60,
+                // the offset depends on the time itself because of daylight saving time
+                None => {
+                    let guess = local - local_offset(local) * 60;
+                    local - local_offset(guess) * 60
+                }
+            }
+        }
+    };
+    let offset = fields.offset.unwrap_or_else(|| local_offset(time));
+    Some((time, format_offset(offset)))
+}
+
+/// Handles month and weekday names and named timezones
+fn match_word(word: &str, fields: &mut Fields) -> Option<()> {
+    const MONTHS: [&str; 12] = [
+        "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+    ];
+    const WEEKDAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+    let lower = word.to_ascii_lowercase();
+    if let Some(month) = MONTHS.iter().position(|month| lower.starts_with(month)) {
+        fields.month = Some(month as i64 + 1);
+    } else if WEEKDAYS.iter().any(|weekday| lower.starts_with(weekday)) {
+        // the weekday follows from the date
+    } else if matches!(lower.as_str(), "utc" | "gmt" | "z") {
+        fields.offset = Some(0);
+    } else if lower != "t" {
+        return None;
+    }
+    Some(())
+}
+
+/// Handles the number and what follows it: time, date, timestamp, day or year.
+/// Returns number of bytes consumed.
+fn match_number(bytes: &[u8], fields: &mut Fields) -> Option<usize> {
+    let len = bytes.iter().take_while(|c| c.is_ascii_digit()).count();
+    match bytes.get(len) {
+        Some(b':') => {
+            let (parts, mut consumed) = numbers(bytes, b':')?;
+            let (hour, minute, second) = match parts[..] {
+                [(hour, _), (minute, _)] => (hour, minute, 0),
+                [(hour, _), (minute, _), (second, _)] => (hour, minute, second),
+                _ => return None,
+            };
+            if hour > 23 || minute > 59 || second > 60 || fields.time.is_some() {
+                return None;
+            }
+            fields.time = Some((hour, minute, second));
+            // fractions of seconds are dropped
+            if bytes.get(consumed) == Some(&b'.') {
+                consumed += 1;
+                consumed += bytes[consumed..]
+                    .iter()
+                    .take_while(|c| c.is_ascii_digit())
+                    .count();
+            }
+            Some(consumed)
+        }
+        Some(&separator @ (b'-' | b'/' | b'.')) => {
+            let (parts, consumed) = numbers(bytes, separator)?;
+            let [(a, a_len), (b, _), (c, _)] = parts[..] else {
+                return None;
+            };
+            let (year, month, day) = match (a_len, separator) {
+                (4, _) => (a, b, c),
+                // US order with slashes, European with dots
+                (_, b'/') => (c, a, b),
+                _ => (c, b, a),
+            };
+            if fields.year.is_some() {
+                return None;
+            }
+            fields.year = Some(year);
+            fields.month = Some(month);
+            fields.day = Some(day);
+            Some(consumed)
+        }
+        _ => {
+            let number: i64 = std::str::from_utf8(&bytes[..len]).ok()?.parse().ok()?;
+            if len >= 9 && fields.timestamp.is_none() && fields.year.is_none() {
+                fields.timestamp = Some(number);
+            } else if len <= 2 && (1..=31).contains(&number) && fields.day.is_none() {
+                fields.day = Some(number);
+            } else if len == 4 && number >= 1970 && fields.year.is_none() {
+                fields.year = Some(number);
+            } else {
+                return None;
+            }
+            Some(len)
+        }
+    }
+}
+
+/// Splits the numbers separated by the separator, returns them with their number of
+/// digits and the number of bytes consumed
+fn numbers(bytes: &[u8], separator: u8) -> Option<(Vec<(i64, usize)>, usize)> {
+    let mut numbers = Vec::new();
+    let mut i = 0;
+    loop {
+        let len = bytes[i..].iter().take_while(|c| c.is_ascii_digit()).count();
+        numbers.push((
+            std::str::from_utf8(&bytes[i..i + len]).ok()?.parse().ok()?,
+            len,
+        ));
+        i += len;
+        if bytes.get(i) != Some(&separator) || !bytes.get(i + 1).is_some_and(u8::is_ascii_digit) {
+            return Some((numbers, i));
+        }
+        i += 1;
+    }
+}
+
+/// Handles `+HHMM`, `+HH:MM` and `+HH` timezones, returns number of bytes consumed
+fn match_offset(bytes: &[u8], fields: &mut Fields) -> Option<usize> {
+    let digits = |bytes: &[u8]| bytes.iter().take_while(|c| c.is_ascii_digit()).count();
+    let number = |bytes: &[u8]| std::str::from_utf8(bytes).ok()?.parse::<i64>().ok();
+
+    let len = digits(&bytes[1..]);
+    let (hours, minutes, consumed) = match len {
+        4 => (number(&bytes[1..3])?, number(&bytes[3..5])?, 5),
+        2 if bytes.get(3) == Some(&b':') && digits(&bytes[4..]) == 2 => {
+            (number(&bytes[1..3])?, number(&bytes[4..6])?, 6)
+        }
+        1 | 2 => (number(&bytes[1..1 + len])?, 0, 1 + len),
+        _ => return None,
+    };
+    if minutes > 59 || fields.offset.is_some() {
+        return None;
+    }
+    let offset = hours * 60 + minutes;
+    fields.offset = Some(if bytes[0] == b'-' { -offset } else { offset });
+    Some(consumed)
+}
+
+/// Days since UNIX epoch of the date in the proleptic Gregorian calendar
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    // http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
@@ -0,0 +1,47 @@
+use std::{
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use anyhow::Context;
+
+use crate::config;
+
+// https://git-scm.com/docs/git-config#Documentation/git-config.txt-gpgprogram
+
+/// Creates detached ASCII-armored signature of the payload with the key (`user.signingKey`
+/// or the signer identity), using `gpg.program`
+pub fn sign(git_dir: &Path, payload: &[u8], key: &str) -> anyhow::Result<String> {
+    let program = match config::get(git_dir, "gpg.openpgp.program")? {
+        Some(program) => program,
+        None => config::get(git_dir, "gpg.program")?.unwrap_or_else(|| "gpg".to_string()),
+    };
+
+    let mut child = Command::new(&program)
+        .args(["--status-fd=2", "-bsau", key])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("cannot run {program}"))?;
+    child
+        .stdin
+        .take()
+        .context("gpg stdin is not piped")?
+        .write_all(payload)
+        .context("writing data to gpg")?;
+    let output = child.wait_with_output().context("waiting for gpg")?;
+
+    // gpg may exit successfully without signing, the status tells whether it did
+    let status = String::from_utf8_lossy(&output.stderr);
+    let signed = status
+        .lines()
+        .any(|line| line.starts_with("[GNUPG:] SIG_CREATED "));
+    if !output.status.success() || !signed || output.stdout.is_empty() {
+        eprint!("{status}");
+        anyhow::bail!("gpg failed to sign the data");
+    }
+
+    String::from_utf8(output.stdout).context("gpg signature is not valid UTF-8")
+}
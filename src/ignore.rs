@@ -0,0 +1,178 @@
+use std::{collections::HashMap, ffi::OsStr, fs, os::unix::ffi::OsStrExt, path::Path};
+
+use anyhow::Context;
+
+use crate::{config::Config, repository::Repository, wildmatch::wildmatch};
+
+// https://git-scm.com/docs/gitignore
+
+/// Pattern of an ignore file
+#[derive(Debug)]
+struct Pattern {
+    pattern: Vec<u8>,
+    /// Re-includes matching paths (`!` prefix)
+    negated: bool,
+    /// Matches only directories (`/` suffix)
+    dir_only: bool,
+    /// Matches path relative to `base` instead of just the file name (contains `/`)
+    anchored: bool,
+    /// Directory of the `.gitignore` file, empty or ending with '/'
+    base: Vec<u8>,
+}
+
+impl Pattern {
+    /// Parses line of an ignore file, `None` for blank lines and comments
+    fn parse(line: &[u8], base: &[u8]) -> Option<Self> {
+        // trailing spaces are ignored unless escaped
+        let mut line = line.strip_suffix(b"\r").unwrap_or(line);
+        while let Some(rest) = line.strip_suffix(b" ") {
+            if rest.ends_with(b"\\") {
+                break;
+            }
+            line = rest;
+        }
+        if line.is_empty() || line.starts_with(b"#") {
+            return None;
+        }
+
+        let (negated, line) = match line.strip_prefix(b"!") {
+            Some(line) => (true, line),
+            None => (false, line),
+        };
+        let (dir_only, line) = match line.strip_suffix(b"/") {
+            Some(line) => (true, line),
+            None => (false, line),
+        };
+        let anchored = line.contains(&b'/');
+        let line = line.strip_prefix(b"/").unwrap_or(line);
+        if line.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            pattern: line.to_vec(),
+            negated,
+            dir_only,
+            anchored,
+            base: base.to_vec(),
+        })
+    }
+
+    fn matches(&self, path: &[u8], is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        let Some(relative) = path.strip_prefix(self.base.as_slice()) else {
+            return false;
+        };
+        if self.anchored {
+            wildmatch(&self.pattern, relative, true)
+        } else {
+            let name = relative.rsplit(|&b| b == b'/').next().unwrap_or(relative);
+            wildmatch(&self.pattern, name, true)
+        }
+    }
+}
+
+/// Ignore rules of a work tree: `.gitignore` files, `info/exclude` and the file
+/// configured by `core.excludesFile`
+pub struct Ignore<'r> {
+    repo: &'r Repository,
+    /// Patterns that apply to the whole work tree, in increasing precedence
+    global: Vec<Pattern>,
+    /// Patterns of `.gitignore` files read so far, by directory
+    dirs: HashMap<Vec<u8>, Vec<Pattern>>,
+}
+
+impl<'r> Ignore<'r> {
+    pub fn new(repo: &'r Repository) -> anyhow::Result<Self> {
+        let config = Config::load(Some(repo.git_dir()))?;
+        let excludes_file = match config.get_path("core.excludesFile")? {
+            Some(file) => Some(file),
+            None => std::env::var_os("XDG_CONFIG_HOME")
+                .filter(|dir| !dir.is_empty())
+                .map(|dir| Path::new(&dir).join("git"))
+                .or_else(|| std::env::home_dir().map(|home| home.join(".config/git")))
+                .map(|dir| dir.join("ignore")),
+        };
+
+        let mut global = Vec::new();
+        for file in excludes_file
+            .into_iter()
+            .chain([repo.git_dir().join("info/exclude")])
+        {
+            global.extend(read_patterns(&file, b"")?);
+        }
+
+        Ok(Self {
+            repo,
+            global,
+            dirs: HashMap::new(),
+        })
+    }
+
+    /// Checks whether the path relative to the top of the work tree is ignored, either
+    /// itself or because one of its parent directories is
+    pub fn is_ignored(&mut self, path: &[u8], is_dir: bool) -> anyhow::Result<bool> {
+        for (i, _) in path.iter().enumerate().filter(|(_, &b)| b == b'/') {
+            if self.is_excluded(&path[..i], true)? {
+                return Ok(true);
+            }
+        }
+        self.is_excluded(path, is_dir)
+    }
+
+    /// Checks the path against the patterns only, assuming its parent directories are
+    /// not ignored. The last matching pattern decides, patterns of `.gitignore` files in
+    /// deeper directories take precedence.
+    pub fn is_excluded(&mut self, path: &[u8], is_dir: bool) -> anyhow::Result<bool> {
+        let mut dirs = vec![Vec::new()];
+        for (i, _) in path.iter().enumerate().filter(|(_, &b)| b == b'/') {
+            dirs.push(path[..=i].to_vec());
+        }
+        for dir in &dirs {
+            if !self.dirs.contains_key(dir) {
+                let file = self
+                    .repo
+                    .work_tree()
+                    .context("this operation must be run in a work tree")?
+                    .join(OsStr::from_bytes(dir))
+                    .join(".gitignore");
+                let patterns = read_patterns(&file, dir)?;
+                self.dirs.insert(dir.clone(), patterns);
+            }
+        }
+
+        let patterns = self
+            .global
+            .iter()
+            .chain(dirs.iter().flat_map(|dir| &self.dirs[dir]));
+        let mut ignored = false;
+        for pattern in patterns {
+            if pattern.matches(path, is_dir) {
+                ignored = !pattern.negated;
+            }
+        }
+        Ok(ignored)
+    }
+}
+
+/// Reads patterns of the ignore file, which may not exist
+fn read_patterns(file: &Path, base: &[u8]) -> anyhow::Result<Vec<Pattern>> {
+    let data = match fs::read(file) {
+        Ok(data) => data,
+        Err(err)
+            if matches!(
+                err.kind(),
+                std::io::ErrorKind::NotFound | std::io::ErrorKind::NotADirectory
+            ) =>
+        {
+            return Ok(Vec::new())
+        }
+        Err(err) => return Err(err).with_context(|| format!("reading {}", file.display())),
+    };
+    Ok(data
+        .split(|&b| b == b'\n')
+        .filter_map(|line| Pattern::parse(line, base))
+        .collect())
+}
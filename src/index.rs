@@ -0,0 +1,637 @@
+use std::{
+    ffi::OsStr,
+    fs::{self, Metadata},
+    io::Write,
+    os::unix::{ffi::OsStrExt, fs::MetadataExt},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use anyhow::Context;
+
+use crate::{
+    object::{tree, HashAlgorithm, ObjectId},
+    odb::ObjectDatabase,
+    repository::Repository,
+};
+
+mod cache_tree;
+
+pub use cache_tree::CacheTree;
+
+// https://git-scm.com/docs/index-format
+
+/// Version written for new indexes
+const DEFAULT_VERSION: u32 = 2;
+
+const FLAG_ASSUME_VALID: u16 = 0x8000;
+const FLAG_EXTENDED: u16 = 0x4000;
+const FLAG_STAGE_MASK: u16 = 0x3000;
+/// Path lengths at or above this are stored as this value
+const NAME_MASK: u16 = 0x0fff;
+
+const EXTENDED_SKIP_WORKTREE: u16 = 0x4000;
+const EXTENDED_INTENT_TO_ADD: u16 = 0x2000;
+
+/// File attributes used to detect changes in the work tree without reading file content.
+/// Values are truncated to 32 bits like in git.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stat {
+    pub ctime: (u32, u32),
+    pub mtime: (u32, u32),
+    pub dev: u32,
+    pub ino: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub size: u32,
+}
+
+impl Stat {
+    pub fn from_metadata(metadata: &Metadata) -> Self {
+        Self {
+            ctime: (metadata.ctime() as u32, metadata.ctime_nsec() as u32),
+            mtime: (metadata.mtime() as u32, metadata.mtime_nsec() as u32),
+            dev: metadata.dev() as u32,
+            ino: metadata.ino() as u32,
+            uid: metadata.uid(),
+            gid: metadata.gid(),
+            size: metadata.size() as u32,
+        }
+    }
+}
+
+/// Mode recorded for a work tree file: symbolic link, executable or regular file
+pub fn mode_of(metadata: &Metadata) -> u32 {
+    if metadata.is_symlink() {
+        tree::MODE_SYMLINK
+    } else if metadata.mode() & 0o100 != 0 {
+        tree::MODE_EXECUTABLE
+    } else {
+        tree::MODE_FILE
+    }
+}
+
+/// Checks that the path can be recorded in the index: relative, without empty, `.`, `..`
+/// and `.git` components
+pub fn is_valid_path(path: &[u8]) -> bool {
+    !path.is_empty()
+        && path.split(|&b| b == b'/').all(|name| {
+            !name.is_empty() && name != b"." && name != b".." && !name.eq_ignore_ascii_case(b".git")
+        })
+}
+
+/// State of the work tree file of an index entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileState {
+    /// Content and mode match the entry, the file has the stat data
+    Unchanged(Stat),
+    Modified,
+    Deleted,
+}
+
+/// Entry of the index file (staging area)
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub stat: Stat,
+    pub mode: u32,
+    pub oid: ObjectId,
+    /// Merge stage, 0 for entries without conflicts
+    pub stage: u8,
+    /// The file is assumed unchanged, the work tree is not checked
+    pub assume_valid: bool,
+    /// The file is not checked out in sparse checkouts
+    pub skip_worktree: bool,
+    /// The path is going to be added (`git add -N`), the object is the empty blob
+    pub intent_to_add: bool,
+    /// Path relative to the work tree, not necessarily valid UTF-8
+    pub path: Vec<u8>,
+}
+
+impl Entry {
+    /// Creates entry of the object at the path with given mode and no stat data
+    pub fn new(path: Vec<u8>, mode: u32, oid: ObjectId) -> Self {
+        Self {
+            stat: Stat::default(),
+            mode,
+            oid,
+            stage: 0,
+            assume_valid: false,
+            skip_worktree: false,
+            intent_to_add: false,
+            path,
+        }
+    }
+
+    /// Checks that the file looks unchanged since the entry was recorded: stat data
+    /// and file type match. File content is not compared.
+    pub fn matches(&self, metadata: &Metadata) -> bool {
+        // submodules are checked out as directories, their commits are not compared here
+        if self.mode == tree::MODE_GITLINK {
+            return metadata.is_dir();
+        }
+        self.mode == mode_of(metadata) && self.stat == Stat::from_metadata(metadata)
+    }
+
+    fn has_extended_flags(&self) -> bool {
+        self.skip_worktree || self.intent_to_add
+    }
+}
+
+/// Content of the index file: entries sorted by path and stage
+#[derive(Debug, Clone)]
+pub struct Index {
+    pub version: u32,
+    /// Entries should be changed through [`Index::add`] and [`Index::remove`], which keep
+    /// the cache tree up to date
+    pub entries: Vec<Entry>,
+    /// Trees of directories recorded in the TREE extension
+    pub cache_tree: Option<CacheTree>,
+    algorithm: HashAlgorithm,
+    /// Modification time of the file the index was read from
+    timestamp: Option<SystemTime>,
+}
+
+impl Index {
+    /// Creates empty index
+    pub fn new(algorithm: HashAlgorithm) -> Self {
+        Self {
+            version: DEFAULT_VERSION,
+            entries: Vec::new(),
+            cache_tree: None,
+            algorithm,
+            timestamp: None,
+        }
+    }
+
+    /// Reads the index file (versions 2 to 4) of repository using the hash algorithm.
+    /// Missing index file is treated as empty index.
+    pub fn read(path: &Path, algorithm: HashAlgorithm) -> anyhow::Result<Self> {
+        let data = match fs::read(path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Self::new(algorithm))
+            }
+            Err(err) => return Err(err).with_context(|| format!("reading {}", path.display())),
+        };
+
+        let mut index =
+            Self::parse(&data, algorithm).with_context(|| format!("{}", path.display()))?;
+        index.timestamp = fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        Ok(index)
+    }
+
+    /// Parses content of the index file, verifying its checksum
+    pub fn parse(data: &[u8], algorithm: HashAlgorithm) -> anyhow::Result<Self> {
+        let hash_size = algorithm.size();
+        anyhow::ensure!(
+            data.len() >= 12 + hash_size && data.starts_with(b"DIRC"),
+            "bad index file signature"
+        );
+        let version = u32::from_be_bytes(data[4..8].try_into().expect("4 bytes"));
+        anyhow::ensure!(
+            (2..=4).contains(&version),
+            "unsupported index version {version}"
+        );
+        let count = u32::from_be_bytes(data[8..12].try_into().expect("4 bytes"));
+
+        // the checksum may be all zeros when index.skipHash is set
+        let (data, checksum) = data.split_at(data.len() - hash_size);
+        anyhow::ensure!(
+            checksum.iter().all(|&b| b == 0) || algorithm.digest(data).as_bytes() == checksum,
+            "index file corrupt: bad checksum"
+        );
+
+        let u32_at = |pos: usize| -> anyhow::Result<u32> {
+            Ok(u32::from_be_bytes(
+                data.get(pos..pos + 4)
+                    .context("index entry truncated")?
+                    .try_into()
+                    .expect("4 bytes"),
+            ))
+        };
+        let u16_at = |pos: usize| -> anyhow::Result<u16> {
+            Ok(u16::from_be_bytes(
+                data.get(pos..pos + 2)
+                    .context("index entry truncated")?
+                    .try_into()
+                    .expect("2 bytes"),
+            ))
+        };
+
+        let mut entries: Vec<Entry> = Vec::with_capacity(count as usize);
+        let mut pos = 12;
+        for _ in 0..count {
+            let start = pos;
+            let stat = Stat {
+                ctime: (u32_at(pos)?, u32_at(pos + 4)?),
+                mtime: (u32_at(pos + 8)?, u32_at(pos + 12)?),
+                dev: u32_at(pos + 16)?,
+                ino: u32_at(pos + 20)?,
+                uid: u32_at(pos + 28)?,
+                gid: u32_at(pos + 32)?,
+                size: u32_at(pos + 36)?,
+            };
+            let mode = u32_at(pos + 24)?;
+            let oid = data
+                .get(pos + 40..pos + 40 + hash_size)
+                .context("index entry truncated")?;
+            let oid = ObjectId::from_bytes(algorithm, oid)?;
+            pos += 40 + hash_size;
+            let flags = u16_at(pos)?;
+            pos += 2;
+            let mut extended = 0;
+            if flags & FLAG_EXTENDED != 0 {
+                anyhow::ensure!(version >= 3, "extended flags in index version {version}");
+                extended = u16_at(pos)?;
+                pos += 2;
+            }
+
+            let mut path = Vec::new();
+            if version == 4 {
+                // path is prefix-compressed: number of bytes to strip from the previous path
+                // is encoded as variable width integer
+                let mut byte = *data.get(pos).context("index entry truncated")?;
+                let mut strip = (byte & 0x7f) as usize;
+                while byte & 0x80 != 0 {
+                    pos += 1;
+                    byte = *data.get(pos).context("index entry truncated")?;
+                    strip = ((strip + 1) << 7) + (byte & 0x7f) as usize;
+                }
+                pos += 1;
+
+                let previous = entries.last().map(|e| e.path.as_slice()).unwrap_or(&[]);
+                anyhow::ensure!(
+                    strip <= previous.len(),
+                    "invalid path prefix in index entry"
+                );
+                path.extend_from_slice(&previous[..previous.len() - strip]);
+            }
+            let name_len = data
+                .get(pos..)
+                .and_then(|rest| rest.iter().position(|&b| b == 0))
+                .context("index entry path not terminated")?;
+            path.extend_from_slice(&data[pos..pos + name_len]);
+            pos += name_len + 1;
+            if version < 4 {
+                // entries are padded with NULs to a multiple of 8 bytes
+                pos = start + (pos - start).div_ceil(8) * 8;
+            }
+
+            entries.push(Entry {
+                stat,
+                mode,
+                oid,
+                stage: ((flags & FLAG_STAGE_MASK) >> 12) as u8,
+                assume_valid: flags & FLAG_ASSUME_VALID != 0,
+                skip_worktree: extended & EXTENDED_SKIP_WORKTREE != 0,
+                intent_to_add: extended & EXTENDED_INTENT_TO_ADD != 0,
+                path,
+            });
+        }
+
+        // extensions: 4 byte signature and 4 byte size followed by the data;
+        // those with a lowercase signature are required to understand the index
+        let mut cache_tree = None;
+        while pos + 8 <= data.len() {
+            let signature = &data[pos..pos + 4];
+            let size = u32_at(pos + 4)? as usize;
+            let extension = data
+                .get(pos + 8..pos + 8 + size)
+                .context("index extension truncated")?;
+            match signature {
+                b"TREE" => cache_tree = Some(CacheTree::parse(extension, algorithm)?),
+                _ => anyhow::ensure!(
+                    !signature[0].is_ascii_lowercase(),
+                    "index uses {} extension, which we do not understand",
+                    String::from_utf8_lossy(signature)
+                ),
+            }
+            pos += 8 + size;
+        }
+        anyhow::ensure!(pos == data.len(), "index extension truncated");
+
+        Ok(Self {
+            version,
+            entries,
+            cache_tree,
+            algorithm,
+            timestamp: None,
+        })
+    }
+
+    /// Serializes the index in its version, version 2 is upgraded to 3 when entries
+    /// need extended flags
+    pub fn serialize(&self) -> Vec<u8> {
+        let version = match self.version {
+            2 if self.entries.iter().any(Entry::has_extended_flags) => 3,
+            version => version,
+        };
+
+        let mut data = Vec::new();
+        data.extend_from_slice(b"DIRC");
+        data.extend_from_slice(&version.to_be_bytes());
+        data.extend_from_slice(&(self.entries.len() as u32).to_be_bytes());
+
+        // files modified in the second the index is written may change again unnoticed
+        // by their stat data, their size is cleared to force comparing their content
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|now| now.as_secs() as u32)
+            .unwrap_or_default();
+
+        let mut previous: &[u8] = &[];
+        for entry in &self.entries {
+            let start = data.len();
+            let mut stat = entry.stat;
+            if stat.mtime.0 >= now {
+                stat.size = 0;
+            }
+            for value in [
+                stat.ctime.0,
+                stat.ctime.1,
+                stat.mtime.0,
+                stat.mtime.1,
+                stat.dev,
+                stat.ino,
+                entry.mode,
+                stat.uid,
+                stat.gid,
+                stat.size,
+            ] {
+                data.extend_from_slice(&value.to_be_bytes());
+            }
+            data.extend_from_slice(entry.oid.as_bytes());
+
+            let mut flags = (entry.path.len() as u16).min(NAME_MASK);
+            flags |= (entry.stage as u16) << 12;
+            if entry.assume_valid {
+                flags |= FLAG_ASSUME_VALID;
+            }
+            if entry.has_extended_flags() {
+                flags |= FLAG_EXTENDED;
+            }
+            data.extend_from_slice(&flags.to_be_bytes());
+            if entry.has_extended_flags() {
+                let mut extended = 0;
+                if entry.skip_worktree {
+                    extended |= EXTENDED_SKIP_WORKTREE;
+                }
+                if entry.intent_to_add {
+                    extended |= EXTENDED_INTENT_TO_ADD;
+                }
+                data.extend_from_slice(&extended.to_be_bytes());
+            }
+
+            if version == 4 {
+                let common = previous
+                    .iter()
+                    .zip(&entry.path)
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                encode_varint(previous.len() - common, &mut data);
+                data.extend_from_slice(&entry.path[common..]);
+                data.push(0);
+                previous = &entry.path;
+            } else {
+                data.extend_from_slice(&entry.path);
+                // at least one NUL terminating the path, up to a multiple of 8 bytes
+                let len = (data.len() - start + 1).div_ceil(8) * 8;
+                data.resize(start + len, 0);
+            }
+        }
+
+        if let Some(cache_tree) = &self.cache_tree {
+            let extension = cache_tree.serialize();
+            data.extend_from_slice(b"TREE");
+            data.extend_from_slice(&(extension.len() as u32).to_be_bytes());
+            data.extend_from_slice(&extension);
+        }
+
+        let checksum = self.algorithm.digest(&data);
+        data.extend_from_slice(checksum.as_bytes());
+        data
+    }
+
+    /// Position of the entry with the path and stage, or where it would be inserted
+    fn position(&self, path: &[u8], stage: u8) -> Result<usize, usize> {
+        self.entries
+            .binary_search_by(|entry| (entry.path.as_slice(), entry.stage).cmp(&(path, stage)))
+    }
+
+    pub fn get(&self, path: &[u8], stage: u8) -> Option<&Entry> {
+        self.position(path, stage).ok().map(|i| &self.entries[i])
+    }
+
+    /// Gives access to the entry to change it, trees of its directories are invalidated
+    pub fn get_mut(&mut self, path: &[u8], stage: u8) -> Option<&mut Entry> {
+        if let Some(cache_tree) = &mut self.cache_tree {
+            cache_tree.invalidate(path);
+        }
+        self.position(path, stage)
+            .ok()
+            .map(|i| &mut self.entries[i])
+    }
+
+    /// Checks that the path is in the index in any stage
+    pub fn contains(&self, path: &[u8]) -> bool {
+        !self.entries_of(path).is_empty()
+    }
+
+    /// Entries of the path in all stages
+    pub fn entries_of(&self, path: &[u8]) -> &[Entry] {
+        let start = self.position(path, 0).unwrap_or_else(|i| i);
+        let len = self.entries[start..]
+            .iter()
+            .take_while(|entry| entry.path == path)
+            .count();
+        &self.entries[start..start + len]
+    }
+
+    /// Adds the entry, replacing entry of the same path and stage. Adding a stage 0
+    /// entry resolves conflicts of the path.
+    pub fn add(&mut self, entry: Entry) {
+        if let Some(cache_tree) = &mut self.cache_tree {
+            cache_tree.invalidate(&entry.path);
+        }
+        if entry.stage == 0 {
+            self.entries
+                .retain(|e| e.path != entry.path || e.stage == 0);
+        }
+        match self.position(&entry.path, entry.stage) {
+            Ok(i) => self.entries[i] = entry,
+            Err(i) => self.entries.insert(i, entry),
+        }
+    }
+
+    /// Removes all stages of the path, returns whether there were any
+    pub fn remove(&mut self, path: &[u8]) -> bool {
+        if let Some(cache_tree) = &mut self.cache_tree {
+            cache_tree.invalidate(path);
+        }
+        let len = self.entries.len();
+        self.entries.retain(|entry| entry.path != path);
+        self.entries.len() != len
+    }
+
+    /// Checks whether the file of the entry may have been modified in the same second
+    /// the index was written, so that its stat data cannot prove it is unchanged
+    pub fn is_racy(&self, entry: &Entry) -> bool {
+        let Some(timestamp) = self.timestamp else {
+            return false;
+        };
+        let Ok(timestamp) = timestamp.duration_since(SystemTime::UNIX_EPOCH) else {
+            return false;
+        };
+        timestamp.as_secs() as u32 <= entry.stat.mtime.0
+    }
+
+    /// Checks that the file of the entry is unchanged according to its stat data
+    pub fn is_up_to_date(&self, entry: &Entry, metadata: &Metadata) -> bool {
+        entry.matches(metadata) && !self.is_racy(entry)
+    }
+
+    /// Compares the entry with its file in the work tree of the repository. File content
+    /// is hashed only when stat data cannot tell whether the file has changed.
+    pub fn file_state(&self, repo: &Repository, entry: &Entry) -> anyhow::Result<FileState> {
+        if entry.assume_valid || entry.skip_worktree {
+            return Ok(FileState::Unchanged(entry.stat));
+        }
+
+        let work_tree = repo
+            .work_tree()
+            .context("this operation must be run in a work tree")?;
+        let path = work_tree.join(OsStr::from_bytes(&entry.path));
+        let metadata = match fs::symlink_metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(err)
+                if matches!(
+                    err.kind(),
+                    std::io::ErrorKind::NotFound | std::io::ErrorKind::NotADirectory
+                ) =>
+            {
+                return Ok(FileState::Deleted)
+            }
+            Err(err) => return Err(err).with_context(|| format!("stat {}", path.display())),
+        };
+
+        let stat = Stat::from_metadata(&metadata);
+        if self.is_up_to_date(entry, &metadata) {
+            return Ok(FileState::Unchanged(stat));
+        }
+        if entry.mode == tree::MODE_GITLINK || metadata.is_dir() || mode_of(&metadata) != entry.mode
+        {
+            return Ok(FileState::Modified);
+        }
+
+        match repo.hash_file(&path, false)? == entry.oid {
+            true => Ok(FileState::Unchanged(stat)),
+            false => Ok(FileState::Modified),
+        }
+    }
+
+    /// Writes tree objects of the staged content and returns the root tree. Trees of
+    /// directories that have not changed since they were last written are reused.
+    /// Objects of entries do not have to exist with `missing_ok`.
+    pub fn write_tree(
+        &mut self,
+        odb: &dyn ObjectDatabase,
+        missing_ok: bool,
+    ) -> anyhow::Result<ObjectId> {
+        let cache_tree = self.cache_tree.get_or_insert_with(CacheTree::default);
+        let (_, oid) = cache_tree.update(&self.entries, b"", odb, missing_ok)?;
+        Ok(oid.expect("root tree is always written"))
+    }
+
+    /// Finds entry that cannot coexist with a file at the path: a file in place of one of
+    /// its parent directories, or a file inside a directory at the path
+    pub fn conflicting(&self, path: &[u8]) -> Option<&Entry> {
+        let parent = path
+            .iter()
+            .enumerate()
+            .filter(|(_, &b)| b == b'/')
+            .find_map(|(i, _)| self.entries_of(&path[..i]).first());
+        parent.or_else(|| {
+            let mut dir = path.to_vec();
+            dir.push(b'/');
+            let start = self.position(&dir, 0).unwrap_or_else(|i| i);
+            self.entries
+                .get(start)
+                .filter(|entry| entry.path.starts_with(&dir))
+        })
+    }
+}
+
+/// Variable width integer of index version 4, each continuation adds one to the value
+/// shifted so far
+fn encode_varint(mut value: usize, data: &mut Vec<u8>) {
+    let mut buf = vec![(value & 0x7f) as u8];
+    value >>= 7;
+    while value != 0 {
+        value -= 1;
+        buf.push(0x80 | (value & 0x7f) as u8);
+        value >>= 7;
+    }
+    data.extend(buf.iter().rev());
+}
+
+/// Exclusive lock of the index file held by `index.lock` file. New content is written
+/// into the lock file, which then replaces the index. The lock is released when dropped.
+pub struct IndexLock {
+    path: PathBuf,
+    lock_path: PathBuf,
+    committed: bool,
+}
+
+impl IndexLock {
+    /// Locks the index file of the repository, the lock gets permissions given
+    /// by `core.sharedRepository`
+    pub fn acquire(repo: &Repository) -> anyhow::Result<Self> {
+        let path = repo.index_file();
+        let mut lock_path = path.as_os_str().to_os_string();
+        lock_path.push(".lock");
+        let lock_path = PathBuf::from(lock_path);
+
+        fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&lock_path)
+            .with_context(|| {
+                format!(
+                    "Unable to create '{}': another git process seems to be running \
+                     in this repository",
+                    lock_path.display()
+                )
+            })?;
+
+        let lock = Self {
+            path: path.to_path_buf(),
+            lock_path,
+            committed: false,
+        };
+        repo.shared().adjust(&lock.lock_path)?;
+        Ok(lock)
+    }
+
+    /// Writes the index and atomically replaces the index file with it
+    pub fn commit(mut self, index: &Index) -> anyhow::Result<()> {
+        let mut f = fs::File::create(&self.lock_path)
+            .with_context(|| format!("opening {}", self.lock_path.display()))?;
+        f.write_all(&index.serialize())
+            .and_then(|()| f.sync_all())
+            .with_context(|| format!("writing {}", self.lock_path.display()))?;
+        fs::rename(&self.lock_path, &self.path)
+            .with_context(|| format!("moving lock file to {}", self.path.display()))?;
+        self.committed = true;
+        Ok(())
+    }
+}
+
+impl Drop for IndexLock {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = fs::remove_file(&self.lock_path);
+        }
+    }
+}
@@ -0,0 +1,236 @@
+use anyhow::Context;
+
+use crate::{
+    object::{tree, HashAlgorithm, Header, ObjectId, ObjectType, Tree, TreeEntry},
+    odb::ObjectDatabase,
+};
+
+use super::Entry;
+
+// https://git-scm.com/docs/index-format#_cache_tree
+
+/// Tree objects of directories in the index (the TREE extension), so that trees
+/// of unchanged directories do not need to be written again
+#[derive(Debug, Clone, Default)]
+pub struct CacheTree {
+    /// Tree of the directory, `None` when an entry in it has changed
+    pub oid: Option<ObjectId>,
+    /// Number of index entries in the directory and its subdirectories
+    pub entry_count: usize,
+    /// Subdirectories by name
+    pub children: Vec<(Vec<u8>, CacheTree)>,
+}
+
+impl CacheTree {
+    /// Parses content of the TREE extension
+    pub fn parse(data: &[u8], algorithm: HashAlgorithm) -> anyhow::Result<Self> {
+        let mut data = data;
+        let (_, tree) = Self::parse_one(&mut data, algorithm)?;
+        anyhow::ensure!(data.is_empty(), "trailing data in cache tree");
+        Ok(tree)
+    }
+
+    /// Parses directory and its subdirectories:
+    /// `<name> NUL <entry count> SP <subtree count> LF [<object name>]`,
+    /// the entry count is -1 for invalidated directories that have no object name
+    fn parse_one(data: &mut &[u8], algorithm: HashAlgorithm) -> anyhow::Result<(Vec<u8>, Self)> {
+        let nul = data
+            .iter()
+            .position(|&b| b == 0)
+            .context("cache tree entry truncated")?;
+        let name = data[..nul].to_vec();
+        let newline = data[nul..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .context("cache tree entry truncated")?
+            + nul;
+        let counts = std::str::from_utf8(&data[nul + 1..newline])
+            .ok()
+            .and_then(|counts| counts.split_once(' '))
+            .context("invalid counts in cache tree entry")?;
+        let entry_count: i64 = counts.0.parse().context("invalid cache tree entry count")?;
+        let subtree_count: usize = counts
+            .1
+            .parse()
+            .context("invalid cache tree subtree count")?;
+        *data = &data[newline + 1..];
+
+        let mut tree = Self::default();
+        if entry_count >= 0 {
+            let oid = data
+                .get(..algorithm.size())
+                .context("cache tree entry truncated")?;
+            tree.oid = Some(ObjectId::from_bytes(algorithm, oid)?);
+            tree.entry_count = entry_count as usize;
+            *data = &data[algorithm.size()..];
+        }
+        for _ in 0..subtree_count {
+            tree.children.push(Self::parse_one(data, algorithm)?);
+        }
+
+        Ok((name, tree))
+    }
+
+    /// Serializes the tree as content of the TREE extension
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        self.serialize_one(b"", &mut data);
+        data
+    }
+
+    fn serialize_one(&self, name: &[u8], data: &mut Vec<u8>) {
+        data.extend_from_slice(name);
+        data.push(0);
+        let entry_count = match self.oid {
+            Some(_) => self.entry_count as i64,
+            None => -1,
+        };
+        data.extend_from_slice(format!("{entry_count} {}\n", self.children.len()).as_bytes());
+        if let Some(oid) = &self.oid {
+            data.extend_from_slice(oid.as_bytes());
+        }
+        for (name, child) in &self.children {
+            child.serialize_one(name, data);
+        }
+    }
+
+    /// Marks directories containing the path as changed
+    pub fn invalidate(&mut self, path: &[u8]) {
+        self.oid = None;
+        if let Some(slash) = path.iter().position(|&b| b == b'/') {
+            if let Some(child) = self.child_mut(&path[..slash]) {
+                child.invalidate(&path[slash + 1..]);
+            }
+        }
+    }
+
+    /// Finds the tree of the directory given as path relative to this one
+    pub fn find(&self, path: &[u8]) -> Option<&Self> {
+        path.split(|&b| b == b'/')
+            .filter(|name| !name.is_empty())
+            .try_fold(self, |tree, name| {
+                tree.children
+                    .iter()
+                    .find(|(child, _)| child == name)
+                    .map(|(_, child)| child)
+            })
+    }
+
+    fn child_mut(&mut self, name: &[u8]) -> Option<&mut Self> {
+        self.children
+            .iter_mut()
+            .find(|(child, _)| child == name)
+            .map(|(_, child)| child)
+    }
+
+    /// Writes trees of the directory `base` (empty or ending with '/') whose entries are
+    /// at the start of `entries`, reusing trees that are still valid. Returns number of
+    /// the entries in the directory and its tree, `None` if it has no entries to write.
+    pub(super) fn update(
+        &mut self,
+        entries: &[Entry],
+        base: &[u8],
+        odb: &dyn ObjectDatabase,
+        missing_ok: bool,
+    ) -> anyhow::Result<(usize, Option<ObjectId>)> {
+        if let Some(oid) = self.oid {
+            // the recorded number of entries must still cover exactly the directory
+            let count = self.entry_count;
+            let covered = count > 0
+                && entries
+                    .get(count - 1)
+                    .is_some_and(|entry| entry.path.starts_with(base))
+                && entries
+                    .get(count)
+                    .is_none_or(|entry| !entry.path.starts_with(base));
+            if covered && odb.contains(&oid)? {
+                return Ok((count, Some(oid)));
+            }
+        }
+
+        let mut tree = Tree::default();
+        let mut children = Vec::new();
+        // directories with intent-to-add entries are written without them, but their trees
+        // are not cached, so that they are written again once the entries are added
+        let mut valid = true;
+        let mut i = 0;
+        while let Some(entry) = entries.get(i) {
+            let Some(name) = entry.path.strip_prefix(base) else {
+                break;
+            };
+
+            if let Some(slash) = name.iter().position(|&b| b == b'/') {
+                let dir = &name[..slash];
+                let mut child = self
+                    .children
+                    .iter()
+                    .position(|(child, _)| child == dir)
+                    .map(|pos| self.children.swap_remove(pos).1)
+                    .unwrap_or_default();
+                let mut child_base = base.to_vec();
+                child_base.extend_from_slice(&name[..=slash]);
+                let (count, oid) = child.update(&entries[i..], &child_base, odb, missing_ok)?;
+                i += count;
+
+                valid &= child.oid.is_some();
+                if let Some(oid) = oid {
+                    tree.entries.push(TreeEntry {
+                        mode: tree::MODE_TREE,
+                        name: dir.to_vec(),
+                        oid,
+                    });
+                }
+                children.push((dir.to_vec(), child));
+                continue;
+            }
+
+            i += 1;
+            let path = String::from_utf8_lossy(&entry.path);
+            anyhow::ensure!(
+                entry.stage == 0,
+                "{path}: unmerged ({}), cannot write tree",
+                entry.oid
+            );
+            if entry.intent_to_add {
+                valid = false;
+                continue;
+            }
+            if entry.mode != tree::MODE_GITLINK && !missing_ok {
+                anyhow::ensure!(
+                    odb.contains(&entry.oid)?,
+                    "invalid object {:06o} {} for '{path}'",
+                    entry.mode,
+                    entry.oid
+                );
+            }
+            tree.entries.push(TreeEntry {
+                mode: entry.mode,
+                name: name.to_vec(),
+                oid: entry.oid,
+            });
+        }
+
+        // directories that are gone from the index are dropped
+        self.children = children;
+        self.entry_count = i;
+        self.oid = None;
+
+        // only the root tree is written when empty
+        if tree.entries.is_empty() && !base.is_empty() {
+            return Ok((i, None));
+        }
+
+        tree.sort();
+        let data = tree.serialize();
+        let header = Header {
+            typ: ObjectType::Tree,
+            size: data.len(),
+        };
+        let oid = odb.write(&header, &mut data.as_slice())?;
+        if valid {
+            self.oid = Some(oid);
+        }
+
+        Ok((i, Some(oid)))
+    }
+}
@@ -0,0 +1,57 @@
+//! Git implementation usable as a library.
+//!
+//! The entry point is [`Repository`], which opens, creates or discovers repositories and
+//! gives access to their objects and references:
+//!
+//! ```no_run
+//! use git_starter_rust::Repository;
+//!
+//! # fn main() -> anyhow::Result<()> {
+//! let repo = Repository::discover(".")?;
+//! let head = repo.rev_parse("HEAD")?;
+//! let commit = repo.read_commit(&head)?;
+//! for (path, entry) in repo.walk_tree(&commit.tree)? {
+//!     println!("{} {}", entry.oid, String::from_utf8_lossy(&path));
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! # Stability
+//!
+//! [`Repository`] and the [`object`], [`odb`] and [`refs`] modules form the public API and
+//! follow semantic versioning. The remaining modules are building blocks of the command line
+//! tool, they are public only so that the tool can use them and may change in any release.
+
+pub mod object;
+pub mod odb;
+pub mod refs;
+pub mod repository;
+
+#[doc(hidden)]
+pub mod config;
+#[doc(hidden)]
+pub mod date;
+#[doc(hidden)]
+pub mod gpg;
+#[doc(hidden)]
+pub mod ignore;
+#[doc(hidden)]
+pub mod index;
+#[doc(hidden)]
+pub mod pack;
+#[doc(hidden)]
+pub mod pathspec;
+#[doc(hidden)]
+pub mod pkt_line;
+#[doc(hidden)]
+pub mod rev_list;
+#[doc(hidden)]
+pub mod rev_parse;
+#[doc(hidden)]
+pub mod status;
+#[doc(hidden)]
+pub mod wildmatch;
+
+pub use object::{HashAlgorithm, ObjectId, ObjectType};
+pub use repository::Repository;
@@ -1,13 +1,24 @@
 mod commands;
-mod object;
 
 use std::path::PathBuf;
 
+use anyhow::Context;
 use clap::{Parser, Subcommand};
+use git_starter_rust::{
+    config, refs, repository::SharedRepository, status::UntrackedFiles, HashAlgorithm,
+};
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
 struct Cli {
+    /// Run as if git was started in <path> instead of the current working directory
+    #[arg(short = 'C', value_name = "path")]
+    directories: Vec<PathBuf>,
+
+    /// Pass a configuration parameter to the command, overriding config files
+    #[arg(short = 'c', value_name = "name>=<value")]
+    config: Vec<String>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -15,7 +26,44 @@ struct Cli {
 #[derive(Subcommand)]
 enum Commands {
     /// Create an empty Git repository
-    Init,
+    Init {
+        /// Directory to create the repository in, the current directory by default
+        dir: Option<PathBuf>,
+
+        /// Create a bare repository
+        #[arg(long)]
+        bare: bool,
+
+        /// Specify the hash algorithm to use (sha1 or sha256)
+        #[arg(long)]
+        object_format: Option<HashAlgorithm>,
+
+        /// Use the specified name for the initial branch in the newly created repository
+        #[arg(short = 'b', long)]
+        initial_branch: Option<String>,
+
+        /// Specify the directory from which templates will be used
+        #[arg(long, value_name = "template-directory")]
+        template: Option<String>,
+
+        /// Create the repository in <git-dir> with a .git file pointing to it
+        #[arg(long, value_name = "git-dir")]
+        separate_git_dir: Option<PathBuf>,
+
+        /// Make the repository shared among several users (group, all, umask or octal mode)
+        #[arg(
+            long,
+            value_name = "permissions",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "group"
+        )]
+        shared: Option<SharedRepository>,
+
+        /// Only print error and warning messages
+        #[arg(short, long)]
+        quiet: bool,
+    },
 
     /// Provide content or type and size information for repository objects
     #[command(allow_missing_positional = true)]
@@ -40,7 +88,7 @@ enum Commands {
         )]
         object_type: Option<String>,
 
-        /// Object hash
+        /// The name of the object to show
         #[arg(id = "object", required = true)]
         hash: String,
     },
@@ -74,24 +122,305 @@ enum Commands {
         hash: String,
     },
 
-    /// Create a tree object
-    WriteTree {},
+    /// Show information about files in the index and the working tree
+    LsFiles {
+        /// Show cached files in the output (the default)
+        #[arg(short, long)]
+        cached: bool,
+
+        /// Show staged contents' mode bits, object name and stage number in the output
+        #[arg(short, long)]
+        stage: bool,
+
+        /// Show modified files in the output
+        #[arg(short, long)]
+        modified: bool,
+
+        /// Show deleted files in the output
+        #[arg(short, long)]
+        deleted: bool,
+
+        /// Show other (i.e. untracked) files in the output
+        #[arg(short, long)]
+        others: bool,
+    },
+
+    /// Register file contents in the working tree to the index
+    UpdateIndex {
+        /// If a specified file isn't in the index already then it's added
+        #[arg(long)]
+        add: bool,
+
+        /// If a specified file is in the index but is missing then it's removed
+        #[arg(long)]
+        remove: bool,
+
+        /// Looks at the current index and checks to see if merges or updates are needed
+        #[arg(long)]
+        refresh: bool,
+
+        /// Set the execute permissions on the updated files
+        #[arg(long, value_name = "(+|-)x", allow_hyphen_values = true)]
+        chmod: Option<String>,
+
+        /// Directly insert the specified info into the index
+        #[arg(long, value_name = "mode>,<object>,<path")]
+        cacheinfo: Vec<String>,
+
+        /// Files to act on
+        #[arg(id = "file")]
+        files: Vec<PathBuf>,
+    },
+
+    /// Add file contents to the index
+    Add {
+        /// Add, modify, and remove index entries to match the working tree
+        #[arg(short = 'A', long, conflicts_with = "update")]
+        all: bool,
+
+        /// Update the index just where it already has an entry matching <pathspec>
+        #[arg(short, long)]
+        update: bool,
+
+        /// Record only the fact that the path will be added later
+        #[arg(short = 'N', long)]
+        intent_to_add: bool,
+
+        /// Allow adding otherwise ignored files
+        #[arg(short, long)]
+        force: bool,
+
+        /// Files to add content from
+        #[arg(id = "pathspec")]
+        pathspec: Vec<PathBuf>,
+    },
+
+    /// Remove files from the working tree and from the index
+    Rm {
+        /// Only remove from the index, keeping the working tree files
+        #[arg(long)]
+        cached: bool,
+
+        /// Override the up-to-date check
+        #[arg(short, long)]
+        force: bool,
+
+        /// Allow recursive removal when a leading directory name is given
+        #[arg(short = 'r')]
+        recursive: bool,
+
+        /// Suppress the output of removed files
+        #[arg(short, long)]
+        quiet: bool,
+
+        /// Exit with a zero status even if no files matched
+        #[arg(long)]
+        ignore_unmatch: bool,
+
+        /// Files to remove
+        #[arg(id = "pathspec")]
+        pathspec: Vec<PathBuf>,
+    },
+
+    /// Move or rename a file, a directory, or a symlink
+    Mv {
+        /// Force renaming or moving of a file even if the target exists
+        #[arg(short, long)]
+        force: bool,
+
+        /// Skip move or rename actions which would lead to an error condition
+        #[arg(short = 'k')]
+        skip_errors: bool,
+
+        /// Do nothing; only show what would happen
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+
+        /// Report the names of files as they are moved
+        #[arg(short, long)]
+        verbose: bool,
+
+        /// Sources followed by the destination
+        #[arg(id = "source>... <destination", required = true, num_args = 2..)]
+        paths: Vec<PathBuf>,
+    },
+
+    /// Show the working tree status
+    Status {
+        /// Give the output in the short-format
+        #[arg(short, long)]
+        short: bool,
+
+        /// Show the branch and tracking info even in short-format
+        #[arg(short, long)]
+        branch: bool,
+
+        /// Give the output in an easy-to-parse format for scripts
+        #[arg(
+            long,
+            value_name = "version",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "v1"
+        )]
+        porcelain: Option<String>,
+
+        /// Show untracked files (no, normal or all)
+        #[arg(
+            short = 'u',
+            long,
+            value_name = "mode",
+            num_args = 0..=1,
+            default_value = "normal",
+            default_missing_value = "all"
+        )]
+        untracked_files: UntrackedFiles,
+    },
+
+    /// Record changes to the repository
+    Commit {
+        /// Use the given <msg> as the commit message
+        #[arg(short, long, value_name = "msg", conflicts_with = "file")]
+        message: Vec<String>,
+
+        /// Take the commit message from the given file, - reads the standard input
+        #[arg(short = 'F', long, value_name = "file")]
+        file: Option<PathBuf>,
+
+        /// Automatically stage files that have been modified and deleted
+        #[arg(short, long)]
+        all: bool,
+
+        /// Replace the tip of the current branch by creating a new commit
+        #[arg(long)]
+        amend: bool,
+
+        /// Allow recording a commit that has the exact same tree as its sole parent commit
+        #[arg(long)]
+        allow_empty: bool,
+
+        /// Allow recording a commit with an empty message
+        #[arg(long)]
+        allow_empty_message: bool,
+    },
+
+    /// Get and set repository or global options
+    #[command(group(clap::ArgGroup::new("action").args([
+        "get", "get_all", "set", "add", "replace_all", "unset", "unset_all", "list"
+    ])))]
+    #[command(group(clap::ArgGroup::new("location").args(["global", "system", "local", "file"])))]
+    Config {
+        /// Use the per-user config file
+        #[arg(long)]
+        global: bool,
+
+        /// Use the system-wide config file
+        #[arg(long)]
+        system: bool,
+
+        /// Use the repository config file
+        #[arg(long)]
+        local: bool,
+
+        /// Use the given config file
+        #[arg(short, long, value_name = "file")]
+        file: Option<PathBuf>,
+
+        /// Get the value of the key (the last one of multivalued keys)
+        #[arg(long)]
+        get: bool,
+
+        /// Get all values of a multivalued key
+        #[arg(long)]
+        get_all: bool,
+
+        /// Set the value of the key (the default when a value is given)
+        #[arg(long)]
+        set: bool,
+
+        /// Add a new line to the option without altering any existing values
+        #[arg(long)]
+        add: bool,
+
+        /// Replace all values of a multivalued key with the value
+        #[arg(long)]
+        replace_all: bool,
+
+        /// Remove the key
+        #[arg(long)]
+        unset: bool,
+
+        /// Remove all values of a multivalued key
+        #[arg(long)]
+        unset_all: bool,
+
+        /// List all variables set in config files along with their values
+        #[arg(short, long)]
+        list: bool,
+
+        /// Show the origin of each value (file or command line)
+        #[arg(long)]
+        show_origin: bool,
+
+        /// Check values against the type (bool, int or path) and show them in canonical form
+        #[arg(short = 't', long = "type", value_name = "type")]
+        typ: Option<commands::config::Type>,
+
+        /// Key as section.name or section.subsection.name
+        name: Option<String>,
+
+        /// Value to set
+        value: Option<String>,
+    },
+
+    /// Create a tree object from the current index
+    WriteTree {
+        /// Allow objects of the index to be missing in the object database
+        #[arg(long)]
+        missing_ok: bool,
+
+        /// Write a tree object that represents a subdirectory <prefix>
+        #[arg(long, value_name = "prefix>/")]
+        prefix: Option<String>,
+    },
 
     /// Create a new commit object
     CommitTree {
-        /// Parent commit object
-        #[arg(short, id = "parent")]
-        parent_hash: Option<String>,
+        /// Each -p indicates the id of a parent commit object
+        #[arg(short, value_name = "parent")]
+        parent: Vec<String>,
+
+        /// A paragraph in the commit log message
+        #[arg(short, value_name = "message")]
+        message: Vec<String>,
+
+        /// Read the commit log message from the given file, - reads the standard input
+        #[arg(short = 'F', value_name = "file")]
+        file: Vec<PathBuf>,
+
+        /// GPG-sign the commit, with the default key unless <keyid> is given
+        #[arg(
+            short = 'S',
+            long,
+            value_name = "keyid",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = ""
+        )]
+        gpg_sign: Option<String>,
 
-        /// Commit log message
-        #[arg(short, id = "message")]
-        message: String,
+        /// Do not GPG-sign the commit, countermanding an earlier --gpg-sign
+        #[arg(long, overrides_with = "gpg_sign")]
+        no_gpg_sign: bool,
 
         /// An existing tree object
         #[arg(id = "tree")]
         tree_hash: String,
     },
 
+    /// Creates a tag object with extra validation
+    Mktag,
+
     /// Clone a repository into a new directory
     Clone {
         /// The repository URL to clone from
@@ -101,13 +430,402 @@ enum Commands {
         /// The name of a new directory to clone into
         #[arg(id = "directory")]
         dir: Option<PathBuf>,
+
+        /// Borrow objects from the reference repository, only missing objects are fetched
+        #[arg(long, value_name = "repository")]
+        reference: Vec<PathBuf>,
+
+        /// Borrow all objects from the local source repository instead of copying them
+        #[arg(short, long)]
+        shared: bool,
+
+        /// Copy borrowed objects into the clone and stop borrowing them
+        #[arg(long)]
+        dissociate: bool,
+    },
+
+    /// A really simple server for Git repositories
+    Daemon {
+        /// Remap all the path requests as relative to the given path
+        #[arg(long)]
+        base_path: Option<PathBuf>,
+
+        /// Allow pulling from all directories that look like Git repositories
+        #[arg(long)]
+        export_all: bool,
+
+        /// Listen on a specific IP address or hostname
+        #[arg(long, default_value = "0.0.0.0")]
+        listen: String,
+
+        /// Listen on an alternative port
+        #[arg(long, default_value_t = 9418)]
+        port: u16,
+
+        /// Log details about the incoming connections and requested files
+        #[arg(long)]
+        verbose: bool,
+    },
+
+    /// Send objects packed back to git-fetch-pack
+    UploadPack {
+        /// Perform only a single read-write cycle with stdin and stdout
+        #[arg(long)]
+        stateless_rpc: bool,
+
+        /// Only the initial ref advertisement is output, and the program exits immediately
+        #[arg(long)]
+        advertise_refs: bool,
+
+        /// The repository to sync from
+        #[arg(id = "directory")]
+        dir: PathBuf,
+    },
+
+    /// Pick out and massage parameters
+    RevParse {
+        /// Verify that exactly one parameter is provided and it names an object
+        #[arg(long)]
+        verify: bool,
+
+        /// Revisions to resolve to object names
+        #[arg(id = "rev")]
+        revs: Vec<String>,
+    },
+
+    /// Update the object name stored in a ref safely
+    UpdateRef {
+        /// Use <reason> as the reflog message
+        #[arg(short, value_name = "reason")]
+        message: Option<String>,
+
+        /// Delete the reference
+        #[arg(short)]
+        delete: bool,
+
+        /// Update the reference itself instead of the reference it points to
+        #[arg(long)]
+        no_deref: bool,
+
+        /// Read update commands from standard input
+        #[arg(long)]
+        stdin: bool,
+
+        /// With --stdin, commands and their arguments are NUL-terminated
+        #[arg(short = 'z', requires = "stdin")]
+        null_terminated: bool,
+
+        /// Reference to update
+        #[arg(id = "ref", required_unless_present = "stdin")]
+        name: Option<String>,
+
+        /// New value of the reference, or its old value with -d
+        #[arg(id = "new-val")]
+        new: Option<String>,
+
+        /// Value the reference must have, empty if it must not exist
+        #[arg(id = "old-val")]
+        old: Option<String>,
+    },
+
+    /// Read, modify and delete symbolic refs
+    SymbolicRef {
+        /// Delete the symbolic ref
+        #[arg(short, long)]
+        delete: bool,
+
+        /// Do not issue an error message if <name> is not a symbolic ref
+        #[arg(short, long)]
+        quiet: bool,
+
+        /// Shorten the ref output, ie. refs/heads/master as master
+        #[arg(long)]
+        short: bool,
+
+        /// Update the reflog for <name> with <reason>
+        #[arg(short, value_name = "reason")]
+        message: Option<String>,
+
+        /// Symbolic ref to read or modify
+        name: String,
+
+        /// Reference the symbolic ref should point to
+        #[arg(id = "ref")]
+        target: Option<String>,
+    },
+
+    /// List references in a local repository
+    ShowRef {
+        /// Show the HEAD reference, even if it would be filtered out
+        #[arg(long)]
+        head: bool,
+
+        /// Dereference tags into object IDs as well
+        #[arg(short, long)]
+        dereference: bool,
+
+        /// Only show the object ID, abbreviated to <n> characters if given
+        #[arg(
+            short = 's',
+            long,
+            value_name = "n",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "0"
+        )]
+        hash: Option<usize>,
+
+        /// Abbreviate object IDs to <n> characters
+        #[arg(
+            long,
+            value_name = "n",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "7"
+        )]
+        abbrev: Option<usize>,
+
+        /// Only show references exactly matching the full names given
+        #[arg(long)]
+        verify: bool,
+
+        /// Limit to branches
+        #[arg(long)]
+        heads: bool,
+
+        /// Limit to tags
+        #[arg(long)]
+        tags: bool,
+
+        /// Do not print any results, only set the exit status
+        #[arg(short, long)]
+        quiet: bool,
+
+        /// Show references matching any of the patterns
+        #[arg(id = "pattern")]
+        patterns: Vec<String>,
+    },
+
+    /// Ensure that a reference name is well formed
+    CheckRefFormat {
+        /// Collapse repeated and remove leading slashes, print the result
+        #[arg(long)]
+        normalize: bool,
+
+        /// Allow names with a single component
+        #[arg(long, overrides_with = "no_allow_onelevel")]
+        allow_onelevel: bool,
+
+        /// Require names with more than one component, the last of the two options wins
+        #[arg(long, overrides_with = "allow_onelevel")]
+        no_allow_onelevel: bool,
+
+        /// Allow a single '*' in the name
+        #[arg(long)]
+        refspec_pattern: bool,
+
+        /// Check that the name is a valid branch name and print it
+        #[arg(long)]
+        branch: bool,
+
+        /// Reference name to check
+        refname: String,
     },
+
+    /// Pack heads and tags for efficient repository access
+    PackRefs {
+        /// Pack all refs, not only tags and already packed refs
+        #[arg(long)]
+        all: bool,
+
+        /// Keep the loose files of packed refs
+        #[arg(long)]
+        no_prune: bool,
+    },
+
+    /// Create a packed archive of objects
+    PackObjects {
+        /// Write the pack contents to the standard output
+        #[arg(long)]
+        stdout: bool,
+
+        /// Read revision arguments instead of object names from the standard input
+        #[arg(long)]
+        revs: bool,
+
+        /// Pack everything reachable from refs (implies --revs)
+        #[arg(long)]
+        all: bool,
+
+        /// Number of objects considered as delta base for each object
+        #[arg(long, default_value_t = 10)]
+        window: usize,
+
+        /// Maximum delta depth
+        #[arg(long, default_value_t = 50)]
+        depth: usize,
+
+        /// Store delta bases as offsets in the pack (OFS_DELTA) instead of object names
+        #[arg(long)]
+        delta_base_offset: bool,
+
+        /// Write into pairs of files (.pack and .idx) named <base-name>-<SHA-1>
+        #[arg(id = "base-name", required_unless_present = "stdout")]
+        base_name: Option<String>,
+    },
+
+    /// Verifies the connectivity and validity of the objects in the database
+    Fsck {
+        /// Print out objects that exist but that aren't reachable from any of the reference nodes
+        #[arg(long)]
+        unreachable: bool,
+
+        /// Do not print dangling objects
+        #[arg(long)]
+        no_dangling: bool,
+    },
+
+    /// Pack unpacked objects in a repository
+    Repack {
+        /// Pack everything referenced into a single pack
+        #[arg(short = 'a')]
+        all: bool,
+
+        /// Same as -a, unreachable objects of a previous pack become loose objects
+        #[arg(short = 'A')]
+        loosen_unreachable: bool,
+
+        /// Remove redundant packs and loose objects after packing
+        #[arg(short = 'd')]
+        delete: bool,
+
+        /// Number of objects considered as delta base for each object
+        #[arg(long, default_value_t = 10)]
+        window: usize,
+
+        /// Maximum delta depth
+        #[arg(long, default_value_t = 50)]
+        depth: usize,
+    },
+
+    /// Remove extra objects that are already in pack files
+    PrunePacked {
+        /// Don't actually remove any objects, only show those that would have been removed
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+    },
+
+    /// Prune all unreachable objects from the object database
+    Prune {
+        /// Do not remove anything; just report what it would remove
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+
+        /// Report all removed objects
+        #[arg(short, long)]
+        verbose: bool,
+
+        /// Only expire loose objects older than <time>
+        #[arg(long, id = "time")]
+        expire: Option<String>,
+    },
+
+    /// Cleanup unnecessary files and optimize the local repository
+    Gc {
+        /// Prune loose objects older than date
+        #[arg(long, id = "date", default_value = "2.weeks.ago")]
+        prune: String,
+
+        /// More aggressively optimize the repository at the expense of taking much more time
+        #[arg(long)]
+        aggressive: bool,
+    },
+
+    /// Receive what is pushed into the repository
+    ReceivePack {
+        /// Perform only a single read-write cycle with stdin and stdout
+        #[arg(long)]
+        stateless_rpc: bool,
+
+        /// Only the initial ref advertisement is output, and the program exits immediately
+        #[arg(long)]
+        advertise_refs: bool,
+
+        /// The repository to sync into
+        #[arg(id = "directory")]
+        dir: PathBuf,
+    },
+
+    /// Server side implementation of Git over HTTP
+    HttpBackend {
+        /// Directory containing the repositories
+        #[arg(long, default_value = ".")]
+        base_path: PathBuf,
+
+        /// Serve all directories that look like Git repositories
+        #[arg(long)]
+        export_all: bool,
+
+        /// Listen on a specific IP address or hostname
+        #[arg(long, default_value = "0.0.0.0")]
+        listen: String,
+
+        /// Listen on an alternative port
+        #[arg(long, default_value_t = 8080)]
+        port: u16,
+
+        /// Enable git-receive-pack service for pushing into repositories
+        #[arg(long)]
+        receive_pack: bool,
+
+        /// Log incoming requests
+        #[arg(long)]
+        verbose: bool,
+    },
+}
+
+fn rpc_mode(stateless_rpc: bool, advertise_refs: bool) -> commands::upload_pack::Mode {
+    if advertise_refs {
+        commands::upload_pack::Mode::AdvertiseRefs
+    } else if stateless_rpc {
+        commands::upload_pack::Mode::StatelessRpc
+    } else {
+        commands::upload_pack::Mode::Stateful
+    }
 }
 
 fn main() -> anyhow::Result<()> {
     let args = Cli::parse();
+
+    // each -C is interpreted relative to the preceding one
+    for dir in &args.directories {
+        std::env::set_current_dir(dir)
+            .with_context(|| format!("cannot change to '{}'", dir.display()))?;
+    }
+    for parameter in &args.config {
+        config::push_parameter(parameter).context("unable to parse command-line config")?;
+    }
+
     match args.command {
-        Commands::Init => commands::init::invoke(),
+        Commands::Init {
+            dir,
+            bare,
+            object_format,
+            initial_branch,
+            template,
+            separate_git_dir,
+            shared,
+            quiet,
+        } => commands::init::invoke(commands::init::Options {
+            dir,
+            bare,
+            object_format,
+            initial_branch,
+            template,
+            separate_git_dir,
+            shared,
+            quiet,
+        }),
         Commands::CatFile {
             pretty_print,
             object_type,
@@ -121,7 +839,7 @@ fn main() -> anyhow::Result<()> {
             typ: _,
         } => {
             let hash = commands::hash_object::invoke(file, write)?;
-            println!("{}", hex::encode(hash));
+            println!("{hash}");
             Ok(())
         }
         Commands::LsTree {
@@ -129,16 +847,360 @@ fn main() -> anyhow::Result<()> {
             name_only,
             hash,
         } => commands::ls_tree::invoke(&hash, recurse, name_only),
-        Commands::WriteTree {} => commands::write_tree::invoke(),
+        Commands::LsFiles {
+            cached,
+            stage,
+            modified,
+            deleted,
+            others,
+        } => commands::ls_files::invoke(commands::ls_files::Options {
+            cached,
+            stage,
+            modified,
+            deleted,
+            others,
+        }),
+        Commands::UpdateIndex {
+            add,
+            remove,
+            refresh,
+            chmod,
+            cacheinfo,
+            files,
+        } => commands::update_index::invoke(commands::update_index::Options {
+            add,
+            remove,
+            refresh,
+            chmod,
+            cacheinfo,
+            files,
+        }),
+        Commands::Add {
+            all,
+            update,
+            intent_to_add,
+            force,
+            pathspec,
+        } => commands::add::invoke(commands::add::Options {
+            all,
+            update,
+            intent_to_add,
+            force,
+            pathspec,
+        }),
+        Commands::Rm {
+            cached,
+            force,
+            recursive,
+            quiet,
+            ignore_unmatch,
+            pathspec,
+        } => commands::rm::invoke(commands::rm::Options {
+            cached,
+            force,
+            recursive,
+            quiet,
+            ignore_unmatch,
+            pathspec,
+        }),
+        Commands::Mv {
+            force,
+            skip_errors,
+            dry_run,
+            verbose,
+            paths,
+        } => commands::mv::invoke(commands::mv::Options {
+            force,
+            skip_errors,
+            dry_run,
+            verbose,
+            paths,
+        }),
+        Commands::Status {
+            short,
+            branch,
+            porcelain,
+            untracked_files,
+        } => commands::status::invoke(commands::status::Options {
+            short,
+            branch,
+            porcelain,
+            untracked_files,
+        }),
+        Commands::Commit {
+            message,
+            file,
+            all,
+            amend,
+            allow_empty,
+            allow_empty_message,
+        } => commands::commit::invoke(commands::commit::Options {
+            message,
+            file,
+            all,
+            amend,
+            allow_empty,
+            allow_empty_message,
+        }),
+        Commands::Config {
+            global,
+            system,
+            local,
+            file,
+            get,
+            get_all,
+            set,
+            add,
+            replace_all,
+            unset,
+            unset_all,
+            list,
+            show_origin,
+            typ,
+            name,
+            value,
+        } => {
+            use commands::config::{Action, Location};
+
+            let action = if get {
+                Action::Get
+            } else if get_all {
+                Action::GetAll
+            } else if add {
+                Action::Add
+            } else if replace_all {
+                Action::ReplaceAll
+            } else if unset {
+                Action::Unset
+            } else if unset_all {
+                Action::UnsetAll
+            } else if list {
+                Action::List
+            } else if set || value.is_some() {
+                Action::Set
+            } else {
+                Action::Get
+            };
+            let location = match file {
+                Some(file) => Some(Location::File(file)),
+                None if global => Some(Location::Global),
+                None if system => Some(Location::System),
+                None if local => Some(Location::Local),
+                None => None,
+            };
+            commands::config::invoke(commands::config::Options {
+                action,
+                location,
+                typ,
+                show_origin,
+                name,
+                value,
+            })
+        }
+        Commands::WriteTree { missing_ok, prefix } => {
+            commands::write_tree::invoke(missing_ok, prefix.as_deref())
+        }
         Commands::CommitTree {
-            parent_hash,
+            parent,
             message,
+            file,
+            gpg_sign,
+            no_gpg_sign,
             tree_hash,
         } => {
-            let hash = commands::commit_tree::invoke(&tree_hash, &message, parent_hash)?;
-            println!("{}", hex::encode(hash));
+            let hash = commands::commit_tree::invoke(commands::commit_tree::Options {
+                tree: tree_hash,
+                parents: parent,
+                message,
+                file,
+                gpg_sign: gpg_sign.filter(|_| !no_gpg_sign),
+            })?;
+            println!("{hash}");
+            Ok(())
+        }
+        Commands::Mktag => {
+            let hash = commands::mktag::invoke()?;
+            println!("{hash}");
             Ok(())
         }
-        Commands::Clone { repository, dir } => commands::clone::invoke(&repository, dir),
+        Commands::Clone {
+            repository,
+            dir,
+            reference,
+            shared,
+            dissociate,
+        } => commands::clone::invoke(
+            &repository,
+            dir,
+            commands::clone::Options {
+                reference,
+                shared,
+                dissociate,
+            },
+        ),
+        Commands::Daemon {
+            base_path,
+            export_all,
+            listen,
+            port,
+            verbose,
+        } => commands::daemon::invoke(commands::daemon::Options {
+            base_path,
+            export_all,
+            listen,
+            port,
+            verbose,
+        }),
+        Commands::UploadPack {
+            stateless_rpc,
+            advertise_refs,
+            dir,
+        } => commands::upload_pack::invoke(&dir, rpc_mode(stateless_rpc, advertise_refs)),
+        Commands::RevParse { verify, revs } => commands::rev_parse::invoke(&revs, verify),
+        Commands::UpdateRef {
+            message,
+            delete,
+            no_deref,
+            stdin,
+            null_terminated,
+            name,
+            new,
+            old,
+        } => {
+            // with -d the second argument is the old value
+            let (new, old) = match delete {
+                true => (None, new),
+                false => (new, old),
+            };
+            commands::update_ref::invoke(commands::update_ref::Options {
+                message,
+                delete,
+                no_deref,
+                stdin,
+                null_terminated,
+                name,
+                new,
+                old,
+            })
+        }
+        Commands::SymbolicRef {
+            delete,
+            quiet,
+            short,
+            message,
+            name,
+            target,
+        } => commands::symbolic_ref::invoke(commands::symbolic_ref::Options {
+            name,
+            target,
+            delete,
+            quiet,
+            short,
+            message,
+        }),
+        Commands::ShowRef {
+            head,
+            dereference,
+            hash,
+            abbrev,
+            verify,
+            heads,
+            tags,
+            quiet,
+            patterns,
+        } => commands::show_ref::invoke(commands::show_ref::Options {
+            head,
+            dereference,
+            hash,
+            abbrev,
+            verify,
+            heads,
+            tags,
+            quiet,
+            patterns,
+        }),
+        Commands::CheckRefFormat {
+            normalize,
+            allow_onelevel,
+            no_allow_onelevel,
+            refspec_pattern,
+            branch,
+            refname,
+        } => commands::check_ref_format::invoke(
+            &refname,
+            normalize,
+            refs::NameOptions {
+                allow_onelevel: allow_onelevel && !no_allow_onelevel,
+                refspec_pattern,
+            },
+            branch,
+        ),
+        Commands::PackRefs { all, no_prune } => commands::pack_refs::invoke(all, no_prune),
+        Commands::PackObjects {
+            stdout,
+            revs,
+            all,
+            window,
+            depth,
+            delta_base_offset,
+            base_name,
+        } => commands::pack_objects::invoke(
+            base_name.as_deref(),
+            commands::pack_objects::Options {
+                stdout,
+                revs,
+                all,
+                window,
+                depth,
+                delta_base_offset,
+            },
+        ),
+        Commands::Fsck {
+            unreachable,
+            no_dangling,
+        } => commands::fsck::invoke(commands::fsck::Options {
+            unreachable,
+            no_dangling,
+        }),
+        Commands::Repack {
+            all,
+            loosen_unreachable,
+            delete,
+            window,
+            depth,
+        } => commands::repack::invoke(commands::repack::Options {
+            all,
+            loosen_unreachable,
+            delete,
+            window,
+            depth,
+        }),
+        Commands::PrunePacked { dry_run } => commands::prune_packed::invoke(dry_run),
+        Commands::Prune {
+            dry_run,
+            verbose,
+            expire,
+        } => commands::prune::invoke(expire.as_deref(), dry_run, verbose),
+        Commands::Gc { prune, aggressive } => commands::gc::invoke(&prune, aggressive),
+        Commands::ReceivePack {
+            stateless_rpc,
+            advertise_refs,
+            dir,
+        } => commands::receive_pack::invoke(&dir, rpc_mode(stateless_rpc, advertise_refs)),
+        Commands::HttpBackend {
+            base_path,
+            export_all,
+            listen,
+            port,
+            receive_pack,
+            verbose,
+        } => commands::http_backend::invoke(commands::http_backend::Options {
+            base_path,
+            export_all,
+            listen,
+            port,
+            receive_pack,
+            verbose,
+        }),
     }
 }
@@ -1,17 +1,21 @@
-use std::{
-    fmt::Display,
-    fs,
-    io::{prelude::*, BufReader},
-    os::unix::fs::MetadataExt,
-    path::{Path, PathBuf},
-};
+use std::{fmt::Display, fs, io::prelude::*, os::unix::fs::MetadataExt, path::Path, str::FromStr};
 
 use anyhow::Context;
-use flate2::read::ZlibDecoder;
-use flate2::{write::ZlibEncoder, Compression};
-use sha1::{Digest, Sha1};
 
-const OBJECTS_PATH: &str = ".git/objects";
+use crate::odb::{Object, ObjectDatabase};
+
+pub mod commit;
+mod headers;
+pub mod id;
+pub mod signature;
+pub mod tag;
+pub mod tree;
+
+pub use commit::Commit;
+pub use id::{CollisionError, HashAlgorithm, Hasher, ObjectId};
+pub use signature::Signature;
+pub use tag::Tag;
+pub use tree::{Tree, TreeEntry};
 
 #[derive(PartialEq, Clone, Debug)]
 pub enum ObjectType {
@@ -36,6 +40,20 @@ impl Display for ObjectType {
     }
 }
 
+impl FromStr for ObjectType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "blob" => Ok(ObjectType::Blob),
+            "tree" => Ok(ObjectType::Tree),
+            "commit" => Ok(ObjectType::Commit),
+            "tag" => Ok(ObjectType::Tag),
+            _ => anyhow::bail!("unknown object type {}", s),
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Header {
     pub typ: ObjectType,
@@ -48,54 +66,10 @@ pub struct ObjectFile<R> {
 }
 
 impl ObjectFile<()> {
-    pub fn read(hash: &str, custom_dir: Option<&Path>) -> anyhow::Result<ObjectFile<impl BufRead>> {
-        let mut path = Self::hash_to_path(hash);
-        if let Some(custom_dir) = custom_dir {
-            let mut custom_path = PathBuf::from(custom_dir);
-            custom_path.push(path);
-            path = custom_path;
-        }
-
-        let f =
-            fs::File::open(&path).with_context(|| format!("opening file {}", path.display()))?;
-
-        let mut decoder = BufReader::new(ZlibDecoder::new(f));
-
-        let mut buf = Vec::new();
-
-        decoder
-            .read_until(0, &mut buf)
-            .context("reading object header")?;
-
-        let header = std::ffi::CStr::from_bytes_with_nul(&buf)
-            .expect("should be null terminated string")
-            .to_str()
-            .context("file header is not valid UTF-8")?;
-
-        let Some((typ, size)) = header.split_once(' ') else {
-            anyhow::bail!("incorrect object header: {}", header)
-        };
-
-        let size = size
-            .parse::<usize>()
-            .context("parsing object size in header")?;
-
-        let object_type = match typ {
-            "blob" => ObjectType::Blob,
-            "tree" => ObjectType::Tree,
-            "commit" => ObjectType::Commit,
-            _ => anyhow::bail!("unknown object type {}", typ),
-        };
-
-        let header = Header {
-            typ: object_type,
-            size,
-        };
-
-        Ok(ObjectFile {
-            header,
-            reader: decoder,
-        })
+    /// Reads object from the object database, fails if it does not exist
+    pub fn read<'a>(id: &ObjectId, odb: &'a dyn ObjectDatabase) -> anyhow::Result<Object<'a>> {
+        odb.read(id)?
+            .with_context(|| format!("object {id} not found"))
     }
 
     pub fn from_file(path: impl AsRef<Path>) -> anyhow::Result<ObjectFile<impl Read>> {
@@ -115,23 +89,43 @@ impl ObjectFile<()> {
 
         Ok(ObjectFile { header, reader: r })
     }
+}
+
+impl<R: BufRead> ObjectFile<R> {
+    /// Returns names of objects this object points to: tree and parents of a commit,
+    /// target of a tag or entries of a tree (except submodule commits)
+    pub fn links(&mut self, algorithm: HashAlgorithm) -> anyhow::Result<Vec<ObjectId>> {
+        let mut data = Vec::with_capacity(self.header.size);
+        self.reader
+            .read_to_end(&mut data)
+            .context("reading object content")?;
+
+        let links = match self.header.typ {
+            ObjectType::Commit => {
+                let commit = Commit::parse(&data)?;
+                std::iter::once(commit.tree).chain(commit.parents).collect()
+            }
+            ObjectType::Tag => vec![Tag::parse(&data)?.object],
+            // submodules (gitlinks) point to commits in other repositories
+            ObjectType::Tree => Tree::parse(&data, algorithm)?
+                .entries
+                .iter()
+                .filter(|entry| entry.mode != tree::MODE_GITLINK)
+                .map(|entry| entry.oid)
+                .collect(),
+            _ => Vec::new(),
+        };
 
-    pub fn hash_to_path(hash: &str) -> PathBuf {
-        let dir = &hash[..2];
-        let file = &hash[2..];
-        let mut path = PathBuf::from(OBJECTS_PATH);
-        path.push(dir);
-        path.push(file);
-        path
+        Ok(links)
     }
 }
 
 impl<R: Read> ObjectFile<R> {
     /// Computes and returns object hash ID
-    pub fn hash(mut self) -> anyhow::Result<[u8; 20]> {
+    pub fn hash(mut self, algorithm: HashAlgorithm) -> anyhow::Result<ObjectId> {
         let mut hasher = HashWriter {
             writer: std::io::sink(), // just consume all data
-            hasher: Sha1::new(),
+            hasher: algorithm.hasher(),
         };
 
         let header = self.header;
@@ -140,63 +134,19 @@ impl<R: Read> ObjectFile<R> {
         std::io::copy(&mut self.reader, &mut hasher)
             .context("streaming object's data to hasher")?;
 
-        let digest = hasher.hasher.finalize();
-
-        Ok(digest.into())
+        Ok(hasher.hasher.try_finalize()?)
     }
 
-    /// Compresses and write object's data to disk. Returns object hash ID.
-    pub fn write(&mut self, custom_dir: Option<&Path>) -> anyhow::Result<[u8; 20]> {
-        let dir = tempfile::tempdir().context("creating temp dir")?;
-
-        let tmp_file_path = dir.path().join("tmpfile");
-        let tmp_file = fs::File::create(&tmp_file_path).context("creating temp file")?;
-
-        let encoder = ZlibEncoder::new(tmp_file, Compression::fast());
-
-        let mut compressor = HashWriter {
-            writer: encoder,
-            hasher: Sha1::new(),
-        };
-
-        let header = &self.header;
-        write!(compressor, "{} {}\0", header.typ, header.size)?;
-
-        std::io::copy(&mut self.reader, &mut compressor)
-            .context("streaming object's content to file on disk")?;
-
-        let _ = compressor.writer.finish()?;
-
-        let digest = compressor.hasher.finalize();
-
-        let hash = hex::encode(digest);
-        let dir = &hash[..2]; // first 2 chars of the digest
-        let filename = &hash[2..]; // rest of the digest
-
-        let mut path = PathBuf::from(OBJECTS_PATH);
-        path.push(dir);
-
-        if let Some(custom_dir) = custom_dir {
-            let mut custom_path = PathBuf::from(custom_dir);
-            custom_path.push(path);
-            path = custom_path;
-        }
-
-        fs::create_dir_all(&path)
-            .with_context(|| format!("creating directory {}", path.display()))?;
-
-        path.push(filename);
-
-        fs::rename(tmp_file_path, &path)
-            .with_context(|| format!("moving temp file to {}", path.display()))?;
-
-        Ok(digest.into())
+    /// Stores object into the object database. Returns object hash ID.
+    pub fn write(&mut self, odb: &dyn ObjectDatabase) -> anyhow::Result<ObjectId> {
+        odb.write(&self.header, &mut self.reader)
     }
 }
 
-struct HashWriter<W> {
-    writer: W,
-    hasher: Sha1,
+/// Writer that computes hash of all data passing through it
+pub struct HashWriter<W> {
+    pub writer: W,
+    pub hasher: Hasher,
 }
 
 impl<W> Write for HashWriter<W>
@@ -213,3 +163,29 @@ where
         self.writer.flush()
     }
 }
+
+/// Reads whole content of an object that has to be of the expected type
+pub fn read_data(
+    id: &ObjectId,
+    odb: &dyn ObjectDatabase,
+    expected: ObjectType,
+) -> anyhow::Result<Vec<u8>> {
+    let mut object = ObjectFile::read(id, odb).with_context(|| format!("reading object {id}"))?;
+    anyhow::ensure!(
+        object.header.typ == expected,
+        "object {id} is a {}, not a {expected}",
+        object.header.typ
+    );
+
+    let mut data = Vec::with_capacity(object.header.size);
+    object
+        .reader
+        .read_to_end(&mut data)
+        .with_context(|| format!("reading object {id}"))?;
+    anyhow::ensure!(
+        data.len() == object.header.size,
+        "object {id} size differs from size stated in object header"
+    );
+
+    Ok(data)
+}
@@ -0,0 +1,81 @@
+use anyhow::Context;
+
+use crate::odb::ObjectDatabase;
+
+use super::{headers, read_data, ObjectId, ObjectType, Signature};
+
+/// Commit object
+#[derive(Debug, Clone, PartialEq)]
+pub struct Commit {
+    pub tree: ObjectId,
+    pub parents: Vec<ObjectId>,
+    pub author: Signature,
+    pub committer: Signature,
+    /// Other headers (ie. `encoding`, `gpgsig` or `mergetag`) in their original order.
+    /// Values spanning multiple lines are joined with `\n`.
+    pub extra_headers: Vec<(String, String)>,
+    /// Message as stored, its encoding is given by the `encoding` header (UTF-8 by default)
+    pub message: Vec<u8>,
+}
+
+impl Commit {
+    /// Parses content of a commit object.
+    /// Only canonical encoding is accepted, so serializing the commit gives back the same data.
+    pub fn parse(data: &[u8]) -> anyhow::Result<Self> {
+        let (headers, message) = headers::split(data)?;
+        let mut headers = headers.into_iter().peekable();
+
+        let tree = match headers.next() {
+            Some((key, value)) if key == "tree" => headers::parse_hash(&value)?,
+            _ => anyhow::bail!("missingTree: invalid format - expected 'tree' line"),
+        };
+        let mut parents = Vec::new();
+        while let Some((_, value)) = headers.next_if(|(key, _)| key == "parent") {
+            parents.push(headers::parse_hash(&value)?);
+        }
+        let author = match headers.next() {
+            Some((key, value)) if key == "author" => {
+                Signature::parse(&value).context("invalid author line")?
+            }
+            _ => anyhow::bail!("missingAuthor: invalid format - expected 'author' line"),
+        };
+        let committer = match headers.next() {
+            Some((key, value)) if key == "committer" => {
+                Signature::parse(&value).context("invalid committer line")?
+            }
+            _ => anyhow::bail!("missingCommitter: invalid format - expected 'committer' line"),
+        };
+
+        Ok(Self {
+            tree,
+            parents,
+            author,
+            committer,
+            extra_headers: headers.collect(),
+            message: message.to_vec(),
+        })
+    }
+
+    /// Reads commit object from the object database
+    pub fn read(id: &ObjectId, odb: &dyn ObjectDatabase) -> anyhow::Result<Self> {
+        let data = read_data(id, odb, ObjectType::Commit)?;
+        Self::parse(&data).with_context(|| format!("parsing commit {id}"))
+    }
+
+    /// Returns content of the commit object
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut data = format!("tree {}\n", self.tree);
+        for parent in &self.parents {
+            data.push_str(&format!("parent {parent}\n"));
+        }
+        data.push_str(&format!("author {}\n", self.author));
+        data.push_str(&format!("committer {}\n", self.committer));
+
+        let mut data = data.into_bytes();
+        headers::write(&mut data, &self.extra_headers);
+        data.push(b'\n');
+        data.extend_from_slice(&self.message);
+
+        data
+    }
+}
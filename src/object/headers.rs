@@ -0,0 +1,56 @@
+//! Header lines shared by commit and tag objects
+
+use anyhow::Context;
+
+use super::{HashAlgorithm, ObjectId};
+
+/// Header keys with values in their original order
+pub type Headers = Vec<(String, String)>;
+
+/// Splits object content into headers and message separated by an empty line.
+/// Continuation lines of multi-line headers (starting with a space) are joined with `\n`.
+pub fn split(data: &[u8]) -> anyhow::Result<(Headers, &[u8])> {
+    let end = data
+        .windows(2)
+        .position(|w| w == b"\n\n")
+        .context("missing empty line between headers and message")?;
+    let text = std::str::from_utf8(&data[..end]).context("headers are not valid UTF-8")?;
+
+    let mut headers = Headers::new();
+    for line in text.split('\n') {
+        if let Some(continuation) = line.strip_prefix(' ') {
+            let (_, value) = headers
+                .last_mut()
+                .context("continuation line without a header")?;
+            value.push('\n');
+            value.push_str(continuation);
+            continue;
+        }
+
+        let (key, value) = line
+            .split_once(' ')
+            .with_context(|| format!("malformed header line '{line}'"))?;
+        headers.push((key.to_string(), value.to_string()));
+    }
+
+    Ok((headers, &data[end + 2..]))
+}
+
+/// Writes headers, continuing multi-line values on lines starting with a space
+pub fn write(data: &mut Vec<u8>, headers: &[(String, String)]) {
+    for (key, value) in headers {
+        data.extend_from_slice(key.as_bytes());
+        data.push(b' ');
+        data.extend_from_slice(value.replace('\n', "\n ").as_bytes());
+        data.push(b'\n');
+    }
+}
+
+/// Checks that header value is a full lowercase object name of any supported algorithm
+pub fn parse_hash(value: &str) -> anyhow::Result<ObjectId> {
+    anyhow::ensure!(
+        HashAlgorithm::from_hex_size(value.len()).is_some_and(|algorithm| algorithm.is_hex(value)),
+        "invalid object name '{value}'"
+    );
+    ObjectId::from_hex(value)
+}
@@ -0,0 +1,235 @@
+use std::{fmt::Display, io::Write, path::Path, str::FromStr};
+
+use anyhow::Context;
+use sha1_checked::Sha1;
+use sha2::{Digest, Sha256};
+
+use crate::config;
+
+// https://git-scm.com/docs/hash-function-transition
+
+/// Hash function used to name objects of a repository
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum HashAlgorithm {
+    #[default]
+    Sha1,
+    Sha256,
+}
+
+impl HashAlgorithm {
+    /// Length of binary object names
+    pub const fn size(self) -> usize {
+        match self {
+            HashAlgorithm::Sha1 => 20,
+            HashAlgorithm::Sha256 => 32,
+        }
+    }
+
+    /// Length of hex object names
+    pub const fn hex_size(self) -> usize {
+        self.size() * 2
+    }
+
+    /// Name used in `extensions.objectFormat` and the `object-format` capability
+    pub const fn name(self) -> &'static str {
+        match self {
+            HashAlgorithm::Sha1 => "sha1",
+            HashAlgorithm::Sha256 => "sha256",
+        }
+    }
+
+    /// Algorithm producing hex object names of the length
+    pub fn from_hex_size(len: usize) -> Option<Self> {
+        [HashAlgorithm::Sha1, HashAlgorithm::Sha256]
+            .into_iter()
+            .find(|algorithm| algorithm.hex_size() == len)
+    }
+
+    /// Reads `extensions.objectFormat` from the repository config, SHA-1 is the default
+    pub fn of_repository(git_dir: &Path) -> anyhow::Result<Self> {
+        match config::get(git_dir, "extensions.objectformat")? {
+            Some(name) => name.parse(),
+            None => Ok(HashAlgorithm::Sha1),
+        }
+    }
+
+    pub fn hasher(self) -> Hasher {
+        match self {
+            // collisions are reported instead of being mitigated by a different hash
+            HashAlgorithm::Sha1 => Hasher::Sha1(Box::new(Sha1::builder().safe_hash(false).build())),
+            HashAlgorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+        }
+    }
+
+    /// Hashes data in one go, collision attacks are not reported
+    pub fn digest(self, data: &[u8]) -> ObjectId {
+        let mut hasher = self.hasher();
+        hasher.update(data);
+        hasher.finalize()
+    }
+
+    /// Object name consisting of zeros used in place of missing objects (ie. nonexistent refs)
+    pub fn null(self) -> ObjectId {
+        ObjectId {
+            algorithm: self,
+            bytes: [0; 32],
+        }
+    }
+
+    /// Checks that the string is a full lowercase hex object name
+    pub fn is_hex(self, hash: &str) -> bool {
+        hash.len() == self.hex_size()
+            && hash.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+    }
+}
+
+impl Display for HashAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "sha1" => Ok(HashAlgorithm::Sha1),
+            "sha256" => Ok(HashAlgorithm::Sha256),
+            _ => anyhow::bail!("unknown hash algorithm '{s}'"),
+        }
+    }
+}
+
+/// Incremental hasher of any supported algorithm. SHA-1 detects data crafted to collide
+/// with other data (ie. SHAttered) like git's sha1dc does.
+#[derive(Clone)]
+pub enum Hasher {
+    // collision detection keeps a large state
+    Sha1(Box<Sha1>),
+    Sha256(Sha256),
+}
+
+impl Hasher {
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Sha1(hasher) => hasher.update(data),
+            Hasher::Sha256(hasher) => hasher.update(data),
+        }
+    }
+
+    /// Returns the hash even if the data is part of a collision attack,
+    /// suitable for checksums but not for naming objects
+    pub fn finalize(self) -> ObjectId {
+        match self.try_finalize() {
+            Ok(id) => id,
+            Err(CollisionError(id)) => id,
+        }
+    }
+
+    /// Returns the hash naming the hashed object, fails if the data is part
+    /// of a SHA-1 collision attack
+    pub fn try_finalize(self) -> Result<ObjectId, CollisionError> {
+        let (algorithm, digest, collision) = match self {
+            Hasher::Sha1(hasher) => {
+                let result = hasher.try_finalize();
+                let collision = result.has_collision();
+                (HashAlgorithm::Sha1, result.hash().to_vec(), collision)
+            }
+            Hasher::Sha256(hasher) => (HashAlgorithm::Sha256, hasher.finalize().to_vec(), false),
+        };
+        let id = ObjectId::from_bytes(algorithm, &digest).expect("digest has the algorithm's size");
+        match collision {
+            true => Err(CollisionError(id)),
+            false => Ok(id),
+        }
+    }
+}
+
+/// Hashed data contains a SHA-1 collision attack pattern
+#[derive(Debug, Clone, Copy)]
+pub struct CollisionError(pub ObjectId);
+
+impl Display for CollisionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "SHA-1 appears to be part of a collision attack: {}",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for CollisionError {}
+
+impl Write for Hasher {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Binary object name of any supported hash algorithm
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ObjectId {
+    algorithm: HashAlgorithm,
+    /// Name padded with zeros to the longest supported size
+    bytes: [u8; 32],
+}
+
+impl ObjectId {
+    pub fn from_bytes(algorithm: HashAlgorithm, bytes: &[u8]) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            bytes.len() == algorithm.size(),
+            "{algorithm} object name must have {} bytes, got {}",
+            algorithm.size(),
+            bytes.len()
+        );
+        let mut id = algorithm.null();
+        id.bytes[..bytes.len()].copy_from_slice(bytes);
+        Ok(id)
+    }
+
+    /// Parses full lowercase hex object name, the algorithm is given by its length
+    pub fn from_hex(hash: &str) -> anyhow::Result<Self> {
+        let algorithm = HashAlgorithm::from_hex_size(hash.len())
+            .filter(|algorithm| algorithm.is_hex(hash))
+            .with_context(|| format!("invalid object name {hash}"))?;
+        let mut id = algorithm.null();
+        hex::decode_to_slice(hash, &mut id.bytes[..algorithm.size()])
+            .with_context(|| format!("invalid object name {hash}"))?;
+        Ok(id)
+    }
+
+    pub fn algorithm(&self) -> HashAlgorithm {
+        self.algorithm
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.algorithm.size()]
+    }
+}
+
+impl Display for ObjectId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", hex::encode(self.as_bytes()))
+    }
+}
+
+impl std::fmt::Debug for ObjectId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ObjectId({self})")
+    }
+}
+
+impl FromStr for ObjectId {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_hex(s)
+    }
+}
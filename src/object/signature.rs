@@ -0,0 +1,66 @@
+use std::fmt::Display;
+
+use anyhow::Context;
+
+/// Identity with a timestamp as recorded in author, committer and tagger headers:
+/// `Name <email> timestamp timezone`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Signature {
+    pub name: String,
+    pub email: String,
+    /// Seconds since UNIX epoch
+    pub time: i64,
+    /// Offset from UTC as `+HHMM` or `-HHMM`, kept verbatim as `-0000` differs from `+0000`
+    pub timezone: String,
+}
+
+impl Signature {
+    pub fn parse(value: &str) -> anyhow::Result<Self> {
+        let (name, rest) = value
+            .split_once('<')
+            .context("missingEmail: missing email")?;
+        let name = name
+            .strip_suffix(' ')
+            .context("missingSpaceBeforeEmail: missing space before email")?;
+        let (email, rest) = rest
+            .split_once('>')
+            .context("badEmail: missing '>' after email")?;
+        anyhow::ensure!(!email.contains('<'), "badEmail: bad email");
+        let (time, timezone) = rest
+            .strip_prefix(' ')
+            .and_then(|rest| rest.split_once(' '))
+            .context("missingSpaceBeforeDate: missing space before date")?;
+
+        // leading zeros or signs would not survive serialization
+        anyhow::ensure!(
+            !time.is_empty()
+                && time.bytes().all(|b| b.is_ascii_digit())
+                && (time == "0" || !time.starts_with('0')),
+            "badDate: invalid date format"
+        );
+        let time = time.parse().context("badDateOverflow: date out of range")?;
+        anyhow::ensure!(
+            timezone.len() == 5
+                && timezone.starts_with(['+', '-'])
+                && timezone[1..].bytes().all(|b| b.is_ascii_digit()),
+            "badTimezone: invalid time zone"
+        );
+
+        Ok(Self {
+            name: name.to_string(),
+            email: email.to_string(),
+            time,
+            timezone: timezone.to_string(),
+        })
+    }
+}
+
+impl Display for Signature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} <{}> {} {}",
+            self.name, self.email, self.time, self.timezone
+        )
+    }
+}
@@ -0,0 +1,132 @@
+use anyhow::Context;
+
+use crate::odb::ObjectDatabase;
+
+use super::{headers, read_data, ObjectId, ObjectType, Signature};
+
+// https://git-scm.com/docs/signature-format
+
+/// Lines starting signatures appended to the tag message
+const SIGNATURE_STARTS: [&str; 4] = [
+    "-----BEGIN PGP SIGNATURE-----",
+    "-----BEGIN PGP MESSAGE-----",
+    "-----BEGIN SSH SIGNATURE-----",
+    "-----BEGIN SIGNED MESSAGE-----",
+];
+
+/// Annotated tag object
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tag {
+    /// Name of the tagged object
+    pub object: ObjectId,
+    /// Type of the tagged object
+    pub typ: ObjectType,
+    /// Name of the tag
+    pub name: String,
+    /// Missing in some very old tags
+    pub tagger: Option<Signature>,
+    /// Other headers in their original order, multi-line values are joined with `\n`
+    pub extra_headers: Vec<(String, String)>,
+    pub message: Vec<u8>,
+    /// Signature following the message, including its BEGIN and END lines
+    pub signature: Option<Vec<u8>>,
+}
+
+impl Tag {
+    /// Parses content of a tag object.
+    /// Only canonical encoding is accepted, so serializing the tag gives back the same data.
+    pub fn parse(data: &[u8]) -> anyhow::Result<Self> {
+        let (headers, body) = headers::split(data)?;
+        let mut headers = headers.into_iter().peekable();
+
+        let object = match headers.next() {
+            Some((key, value)) if key == "object" => headers::parse_hash(&value)?,
+            _ => anyhow::bail!("missingObject: invalid format - expected 'object' line"),
+        };
+        let typ = match headers.next() {
+            Some((key, value)) if key == "type" => value
+                .parse()
+                .with_context(|| format!("badType: invalid 'type' value '{value}'"))?,
+            _ => anyhow::bail!("missingTypeEntry: invalid format - expected 'type' line"),
+        };
+        let name = match headers.next() {
+            Some((key, value)) if key == "tag" && !value.is_empty() => value,
+            _ => anyhow::bail!("missingTagEntry: invalid format - expected 'tag' line"),
+        };
+        let tagger = match headers.next_if(|(key, _)| key == "tagger") {
+            Some((_, value)) => Some(Signature::parse(&value).context("invalid tagger line")?),
+            None => None,
+        };
+
+        // signature starts on its own line after the message
+        let signature_start = (0..body.len())
+            .filter(|&pos| pos == 0 || body[pos - 1] == b'\n')
+            .find(|&pos| {
+                SIGNATURE_STARTS
+                    .iter()
+                    .any(|start| body[pos..].starts_with(start.as_bytes()))
+            });
+        let (message, signature) = match signature_start {
+            Some(pos) => (&body[..pos], Some(body[pos..].to_vec())),
+            None => (body, None),
+        };
+
+        Ok(Self {
+            object,
+            typ,
+            name,
+            tagger,
+            extra_headers: headers.collect(),
+            message: message.to_vec(),
+            signature,
+        })
+    }
+
+    /// Reads tag object from the object database
+    pub fn read(id: &ObjectId, odb: &dyn ObjectDatabase) -> anyhow::Result<Self> {
+        let data = read_data(id, odb, ObjectType::Tag)?;
+        Self::parse(&data).with_context(|| format!("parsing tag {id}"))
+    }
+
+    /// Returns content of the tag object
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut data = format!(
+            "object {}\ntype {}\ntag {}\n",
+            self.object, self.typ, self.name
+        );
+        if let Some(tagger) = &self.tagger {
+            data.push_str(&format!("tagger {tagger}\n"));
+        }
+
+        let mut data = data.into_bytes();
+        headers::write(&mut data, &self.extra_headers);
+        data.push(b'\n');
+        data.extend_from_slice(&self.message);
+        if let Some(signature) = &self.signature {
+            data.extend_from_slice(signature);
+        }
+
+        data
+    }
+}
+
+/// Follows chain of tags starting at `start` to the first object that is not a tag.
+/// Returns name and type of that object (`start` itself if it is not a tag).
+pub fn peel(start: &ObjectId, odb: &dyn ObjectDatabase) -> anyhow::Result<(ObjectId, ObjectType)> {
+    let mut id = *start;
+
+    // tags may point to each other, limit the length of the chain to detect cycles
+    for _ in 0..64 {
+        let header = odb
+            .read_header(&id)
+            .with_context(|| format!("reading object {id}"))?
+            .with_context(|| format!("object {id} not found"))?;
+        if header.typ != ObjectType::Tag {
+            return Ok((id, header.typ));
+        }
+
+        id = Tag::read(&id, odb)?.object;
+    }
+
+    anyhow::bail!("tag chain starting at {start} is too long")
+}
@@ -0,0 +1,124 @@
+use std::cmp::Ordering;
+
+use anyhow::Context;
+
+use crate::odb::ObjectDatabase;
+
+use super::{read_data, HashAlgorithm, ObjectId, ObjectType};
+
+/// Mode of a subtree entry
+pub const MODE_TREE: u32 = 0o40000;
+/// Mode of a regular file
+pub const MODE_FILE: u32 = 0o100644;
+/// Mode of an executable file
+pub const MODE_EXECUTABLE: u32 = 0o100755;
+/// Mode of a symbolic link
+pub const MODE_SYMLINK: u32 = 0o120000;
+/// Mode of a submodule commit (gitlink)
+pub const MODE_GITLINK: u32 = 0o160000;
+
+/// Entry of a tree object
+#[derive(Debug, Clone, PartialEq)]
+pub struct TreeEntry {
+    pub mode: u32,
+    /// File name, not necessarily valid UTF-8
+    pub name: Vec<u8>,
+    pub oid: ObjectId,
+}
+
+impl TreeEntry {
+    /// Type of the object the entry points to
+    pub fn kind(&self) -> ObjectType {
+        match self.mode {
+            MODE_TREE => ObjectType::Tree,
+            MODE_GITLINK => ObjectType::Commit,
+            _ => ObjectType::Blob,
+        }
+    }
+
+    pub fn is_tree(&self) -> bool {
+        self.mode == MODE_TREE
+    }
+
+    /// Compares entries in the order required in trees: by name,
+    /// with names of subtrees compared as if they ended with '/'
+    pub fn git_cmp(&self, other: &Self) -> Ordering {
+        let a = self.name.iter().chain(self.is_tree().then_some(&b'/'));
+        let b = other.name.iter().chain(other.is_tree().then_some(&b'/'));
+        a.cmp(b)
+    }
+}
+
+/// Tree object
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Tree {
+    pub entries: Vec<TreeEntry>,
+}
+
+impl Tree {
+    /// Parses content of a tree object.
+    /// Modes must be canonical (without leading zeros), so serializing gives back the same data.
+    pub fn parse(mut data: &[u8], algorithm: HashAlgorithm) -> anyhow::Result<Self> {
+        let hash_size = algorithm.size();
+        let mut entries = Vec::new();
+
+        while !data.is_empty() {
+            let end = data
+                .iter()
+                .position(|&b| b == 0)
+                .context("truncated tree entry")?;
+            let (mode, name) = data[..end]
+                .iter()
+                .position(|&b| b == b' ')
+                .map(|space| (&data[..space], &data[space + 1..end]))
+                .context("missing mode of tree entry")?;
+            let oid = data
+                .get(end + 1..end + 1 + hash_size)
+                .context("truncated tree entry")?;
+            let oid = ObjectId::from_bytes(algorithm, oid)?;
+            data = &data[end + 1 + hash_size..];
+
+            anyhow::ensure!(
+                !mode.is_empty()
+                    && !mode.starts_with(b"0")
+                    && mode.iter().all(|b| (b'0'..=b'7').contains(b)),
+                "badFilemode: contains bad file mode {}",
+                String::from_utf8_lossy(mode)
+            );
+            let mode = mode.iter().fold(0, |mode, b| mode * 8 + (b - b'0') as u32);
+
+            entries.push(TreeEntry {
+                mode,
+                name: name.to_vec(),
+                oid,
+            });
+        }
+
+        Ok(Self { entries })
+    }
+
+    /// Reads tree object from the object database
+    pub fn read(id: &ObjectId, odb: &dyn ObjectDatabase) -> anyhow::Result<Self> {
+        let data = read_data(id, odb, ObjectType::Tree)?;
+        // entries are named by the same algorithm as the tree itself
+        Self::parse(&data, id.algorithm()).with_context(|| format!("parsing tree {id}"))
+    }
+
+    /// Sorts entries in the order required in trees
+    pub fn sort(&mut self) {
+        self.entries.sort_by(|a, b| a.git_cmp(b));
+    }
+
+    /// Returns content of the tree object
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        for entry in &self.entries {
+            // <mode> <name>\0<20_byte_sha>
+            data.extend_from_slice(format!("{:o} ", entry.mode).as_bytes());
+            data.extend_from_slice(&entry.name);
+            data.push(0);
+            data.extend_from_slice(entry.oid.as_bytes());
+        }
+        data
+    }
+}
@@ -0,0 +1,221 @@
+use std::io::{BufRead, Read};
+
+use crate::object::{HashAlgorithm, Header, ObjectFile, ObjectId};
+
+pub mod alternates;
+pub mod combined;
+pub mod loose;
+pub mod memory;
+pub mod packed;
+
+pub use combined::Combined;
+pub use loose::Loose;
+pub use memory::Memory;
+pub use packed::Packed;
+
+/// Object stream returned by object databases
+pub type Object<'a> = ObjectFile<Box<dyn BufRead + 'a>>;
+
+/// Storage of objects addressed by their names
+pub trait ObjectDatabase: Send + Sync {
+    /// Hash algorithm naming objects of the store
+    fn algorithm(&self) -> HashAlgorithm;
+
+    /// Reads type and size of the object, `None` if the store does not contain it
+    fn read_header(&self, id: &ObjectId) -> anyhow::Result<Option<Header>>;
+
+    /// Opens stream with content of the object, `None` if the store does not contain it
+    fn read(&self, id: &ObjectId) -> anyhow::Result<Option<Object<'_>>>;
+
+    fn contains(&self, id: &ObjectId) -> anyhow::Result<bool> {
+        Ok(self.read_header(id)?.is_some())
+    }
+
+    /// Stores object of the given type and size with content read from `data`.
+    /// Returns name of the object.
+    fn write(&self, header: &Header, data: &mut dyn Read) -> anyhow::Result<ObjectId>;
+
+    /// Iterates over names of all objects in the store in sorted order
+    fn iter(&self) -> anyhow::Result<Box<dyn Iterator<Item = ObjectId> + '_>>;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        object::ObjectType,
+        pack::{self, writer},
+        rev_list::ObjectEntry,
+    };
+
+    use super::*;
+
+    /// Blobs similar enough to be stored as deltas of each other, a tree and a commit
+    fn objects() -> Vec<(ObjectType, Vec<u8>)> {
+        let text: String = (0..40).map(|i| format!("line {i}\n")).collect();
+        vec![
+            (ObjectType::Blob, text.clone().into_bytes()),
+            (
+                ObjectType::Blob,
+                format!("{text}one more line\n").into_bytes(),
+            ),
+            (ObjectType::Blob, format!("first line\n{text}").into_bytes()),
+            (ObjectType::Blob, Vec::new()),
+            (ObjectType::Tree, Vec::new()),
+            (
+                ObjectType::Commit,
+                b"tree 4b825dc642cb6eb9a060e54bf8d69288fbee4904\n\
+                  author A <a@b> 0 +0000\ncommitter A <a@b> 0 +0000\n\nempty\n"
+                    .to_vec(),
+            ),
+        ]
+    }
+
+    fn write_all(
+        odb: &dyn ObjectDatabase,
+        objects: &[(ObjectType, Vec<u8>)],
+    ) -> anyhow::Result<Vec<ObjectId>> {
+        objects
+            .iter()
+            .map(|(typ, data)| {
+                let header = Header {
+                    typ: typ.clone(),
+                    size: data.len(),
+                };
+                odb.write(&header, &mut data.as_slice())
+            })
+            .collect()
+    }
+
+    /// Writes a pack with the objects into the objects directory
+    fn write_pack(
+        objects_dir: &std::path::Path,
+        source: &dyn ObjectDatabase,
+        ids: &[ObjectId],
+    ) -> anyhow::Result<()> {
+        let entries: Vec<_> = ids
+            .iter()
+            .map(|&hash| ObjectEntry {
+                hash,
+                path: String::new(),
+            })
+            .collect();
+        let mut data = Vec::new();
+        let (checksum, mut index) =
+            writer::write(source, &entries, &Default::default(), &mut data)?;
+        pack::install(objects_dir, &data, &mut index, &checksum)?;
+        Ok(())
+    }
+
+    /// Checks that the store returns exactly the given objects
+    fn check_store(
+        odb: &dyn ObjectDatabase,
+        ids: &[ObjectId],
+        objects: &[(ObjectType, Vec<u8>)],
+    ) -> anyhow::Result<()> {
+        for (id, (typ, data)) in ids.iter().zip(objects) {
+            assert!(odb.contains(id)?, "{id} is in the store");
+
+            let header = odb.read_header(id)?.expect("object has a header");
+            assert_eq!(header.typ, *typ, "type of {id}");
+            assert_eq!(header.size, data.len(), "size of {id}");
+
+            let mut object = odb.read(id)?.expect("object is readable");
+            assert_eq!(object.header.typ, *typ, "type of {id}");
+            assert_eq!(object.header.size, data.len(), "size of {id}");
+            let mut content = Vec::new();
+            object.reader.read_to_end(&mut content)?;
+            assert_eq!(content, *data, "content of {id}");
+        }
+
+        let mut expected = ids.to_vec();
+        expected.sort();
+        expected.dedup();
+        assert_eq!(odb.iter()?.collect::<Vec<_>>(), expected);
+
+        let missing = odb.algorithm().null();
+        assert!(!odb.contains(&missing)?);
+        assert!(odb.read_header(&missing)?.is_none());
+        assert!(odb.read(&missing)?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn memory() -> anyhow::Result<()> {
+        let objects = objects();
+        let memory = Memory::new(HashAlgorithm::Sha1);
+        let ids = write_all(&memory, &objects)?;
+        assert_eq!(
+            ids[4].to_string(),
+            "4b825dc642cb6eb9a060e54bf8d69288fbee4904",
+            "empty tree"
+        );
+        check_store(&memory, &ids, &objects)
+    }
+
+    #[test]
+    fn loose() -> anyhow::Result<()> {
+        let objects = objects();
+        let dir = tempfile::tempdir()?;
+        let loose = Loose::new(dir.path(), HashAlgorithm::Sha1);
+        let ids = write_all(&loose, &objects)?;
+        assert_eq!(ids, write_all(&Memory::new(HashAlgorithm::Sha1), &objects)?);
+
+        // writing an existing object again is fine
+        assert_eq!(write_all(&loose, &objects[..1])?, ids[..1]);
+
+        check_store(&loose, &ids, &objects)
+    }
+
+    #[test]
+    fn packed() -> anyhow::Result<()> {
+        let objects = objects();
+        let memory = Memory::new(HashAlgorithm::Sha1);
+        let ids = write_all(&memory, &objects)?;
+
+        let dir = tempfile::tempdir()?;
+        let packed = Packed::new(dir.path(), HashAlgorithm::Sha1);
+        assert!(!packed.contains(&ids[0])?);
+
+        // the pack written after the first lookup is found
+        write_pack(dir.path(), &memory, &ids[..3])?;
+        check_store(&packed, &ids[..3], &objects[..3])?;
+
+        write_pack(dir.path(), &memory, &ids[3..])?;
+        check_store(&packed, &ids, &objects)?;
+
+        let header = Header {
+            typ: ObjectType::Blob,
+            size: 0,
+        };
+        assert!(packed.write(&header, &mut [].as_slice()).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn combined() -> anyhow::Result<()> {
+        let objects = objects();
+        let memory = Memory::new(HashAlgorithm::Sha1);
+        let ids = write_all(&memory, &objects)?;
+
+        let dir = tempfile::tempdir()?;
+        write_pack(dir.path(), &memory, &ids[..4])?;
+        let combined = Combined::new(vec![
+            Box::new(Loose::new(dir.path(), HashAlgorithm::Sha1)),
+            Box::new(Packed::new(dir.path(), HashAlgorithm::Sha1)),
+        ]);
+
+        // new objects go to the first store, objects in both stores are listed once
+        assert_eq!(write_all(&combined, &objects[2..])?, ids[2..]);
+        assert_eq!(
+            Loose::new(dir.path(), HashAlgorithm::Sha1)
+                .iter()?
+                .collect::<Vec<_>>()
+                .len(),
+            objects.len() - 2
+        );
+
+        check_store(&combined, &ids, &objects)
+    }
+}
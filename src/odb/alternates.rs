@@ -0,0 +1,122 @@
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+
+// https://git-scm.com/docs/gitrepository-layout#Documentation/gitrepository-layout.txt-objectsinfoalternates
+
+/// Maximum nesting of alternates of alternates, deeper ones are ignored like in git
+const MAX_DEPTH: usize = 5;
+
+/// Path of the file listing object directories the repository borrows objects from
+pub fn file(objects_dir: &Path) -> PathBuf {
+    objects_dir.join("info").join("alternates")
+}
+
+/// Reads object directories listed in `info/alternates` of the objects directory.
+/// Relative paths are relative to the objects directory.
+pub fn read(objects_dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let path = file(objects_dir);
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err).with_context(|| format!("reading {}", path.display())),
+    };
+
+    Ok(content
+        .lines()
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| objects_dir.join(line))
+        .collect())
+}
+
+/// Replaces the list of object directories the repository borrows objects from,
+/// the file is removed if the list is empty
+pub fn write(objects_dir: &Path, dirs: &[PathBuf]) -> anyhow::Result<()> {
+    let path = file(objects_dir);
+    if dirs.is_empty() {
+        return match fs::remove_file(&path) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                Err(err).with_context(|| format!("removing {}", path.display()))
+            }
+            _ => Ok(()),
+        };
+    }
+
+    let mut content = String::new();
+    for dir in dirs {
+        let dir = dir
+            .to_str()
+            .with_context(|| format!("alternate path {} is not valid UTF-8", dir.display()))?;
+        content.push_str(dir);
+        content.push('\n');
+    }
+
+    let info = path.parent().expect("alternates file has a directory");
+    fs::create_dir_all(info).with_context(|| format!("creating {}", info.display()))?;
+    fs::write(&path, content).with_context(|| format!("writing {}", path.display()))
+}
+
+/// Lists all object directories the repository borrows objects from: `extra` ones
+/// (ie. from `GIT_ALTERNATE_OBJECT_DIRECTORIES`) first, then those in `info/alternates`,
+/// each followed by its own alternates. Every directory is listed once.
+/// Also returns problems with directories that were skipped.
+pub fn resolve(
+    objects_dir: &Path,
+    extra: &[PathBuf],
+) -> anyhow::Result<(Vec<PathBuf>, Vec<String>)> {
+    let mut seen = HashSet::new();
+    seen.insert(canonical(objects_dir));
+
+    let mut dirs = Vec::new();
+    let mut problems = Vec::new();
+    for dir in extra {
+        link(dir, 0, &mut seen, &mut dirs, &mut problems)?;
+    }
+    for dir in read(objects_dir)? {
+        link(&dir, 0, &mut seen, &mut dirs, &mut problems)?;
+    }
+
+    Ok((dirs, problems))
+}
+
+fn link(
+    dir: &Path,
+    depth: usize,
+    seen: &mut HashSet<PathBuf>,
+    dirs: &mut Vec<PathBuf>,
+    problems: &mut Vec<String>,
+) -> anyhow::Result<()> {
+    if depth > MAX_DEPTH {
+        problems.push(format!(
+            "{}: ignoring alternate object stores, nesting too deep",
+            dir.display()
+        ));
+        return Ok(());
+    }
+    if !dir.is_dir() {
+        problems.push(format!(
+            "object directory {} does not exist; check .git/objects/info/alternates",
+            dir.display()
+        ));
+        return Ok(());
+    }
+    // cycles end at the directory seen first
+    if !seen.insert(canonical(dir)) {
+        return Ok(());
+    }
+
+    dirs.push(dir.to_path_buf());
+    for nested in read(dir)? {
+        link(&nested, depth + 1, seen, dirs, problems)?;
+    }
+
+    Ok(())
+}
+
+fn canonical(dir: &Path) -> PathBuf {
+    dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf())
+}
@@ -0,0 +1,73 @@
+use std::{collections::BTreeSet, io::Read};
+
+use anyhow::Context;
+
+use crate::object::{HashAlgorithm, Header, ObjectId};
+
+use super::{Object, ObjectDatabase};
+
+/// Stores searched in order, new objects are written into the first one
+pub struct Combined {
+    stores: Vec<Box<dyn ObjectDatabase>>,
+}
+
+impl Combined {
+    pub fn new(stores: Vec<Box<dyn ObjectDatabase>>) -> Self {
+        Self { stores }
+    }
+
+    fn first(&self) -> anyhow::Result<&dyn ObjectDatabase> {
+        self.stores
+            .first()
+            .map(|store| store.as_ref())
+            .context("no object store configured")
+    }
+}
+
+impl ObjectDatabase for Combined {
+    fn algorithm(&self) -> HashAlgorithm {
+        self.stores
+            .first()
+            .map(|store| store.algorithm())
+            .unwrap_or_default()
+    }
+
+    fn read_header(&self, id: &ObjectId) -> anyhow::Result<Option<Header>> {
+        for store in &self.stores {
+            if let Some(header) = store.read_header(id)? {
+                return Ok(Some(header));
+            }
+        }
+        Ok(None)
+    }
+
+    fn read(&self, id: &ObjectId) -> anyhow::Result<Option<Object<'_>>> {
+        for store in &self.stores {
+            if let Some(object) = store.read(id)? {
+                return Ok(Some(object));
+            }
+        }
+        Ok(None)
+    }
+
+    fn contains(&self, id: &ObjectId) -> anyhow::Result<bool> {
+        for store in &self.stores {
+            if store.contains(id)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn write(&self, header: &Header, data: &mut dyn Read) -> anyhow::Result<ObjectId> {
+        self.first()?.write(header, data)
+    }
+
+    fn iter(&self) -> anyhow::Result<Box<dyn Iterator<Item = ObjectId> + '_>> {
+        let mut ids = BTreeSet::new();
+        for store in &self.stores {
+            ids.extend(store.iter()?);
+        }
+        Ok(Box::new(ids.into_iter()))
+    }
+}
@@ -0,0 +1,269 @@
+use std::{
+    fs,
+    io::{prelude::*, BufReader},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use anyhow::Context;
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+
+use crate::{
+    config::Config,
+    object::{HashAlgorithm, HashWriter, Header, ObjectId},
+    repository::SharedRepository,
+};
+
+use super::{Object, ObjectDatabase};
+
+/// Objects stored zlib compressed one per file in `objects/xx/yyyy...`
+pub struct Loose {
+    objects_dir: PathBuf,
+    algorithm: HashAlgorithm,
+    compression: Compression,
+    fsync: bool,
+    shared: SharedRepository,
+}
+
+impl Loose {
+    pub fn new(objects_dir: impl Into<PathBuf>, algorithm: HashAlgorithm) -> Self {
+        Self {
+            objects_dir: objects_dir.into(),
+            algorithm,
+            compression: Compression::fast(),
+            fsync: false,
+            shared: SharedRepository::Umask,
+        }
+    }
+
+    /// Creates store writing objects as configured in the repository
+    /// (`core.looseCompression`, `core.compression`, `core.fsync` and
+    /// `core.sharedRepository`)
+    pub fn configured(
+        git_dir: &Path,
+        objects_dir: impl Into<PathBuf>,
+        algorithm: HashAlgorithm,
+    ) -> anyhow::Result<Self> {
+        let mut loose = Self::new(objects_dir, algorithm);
+
+        // https://git-scm.com/docs/git-config#Documentation/git-config.txt-corelooseCompression
+        let config = Config::load(Some(git_dir))?;
+        let level = match config.get_int("core.loosecompression")? {
+            Some(level) => Some(level),
+            None => config.get_int("core.compression")?,
+        };
+        if let Some(level) = level {
+            loose.compression = match level {
+                -1 => Compression::default(),
+                0..=9 => Compression::new(level as u32),
+                _ => anyhow::bail!("bad zlib compression level {level}"),
+            };
+        }
+
+        if let Some(shared) = config.get("core.sharedrepository")? {
+            loose.shared = shared.parse()?;
+        }
+
+        // https://git-scm.com/docs/git-config#Documentation/git-config.txt-corefsync
+        loose.fsync = config.get_bool("core.fsyncobjectfiles")?.unwrap_or(false);
+        for value in config.get_all("core.fsync")? {
+            for component in value.split(',').map(str::trim) {
+                let (enable, component) = match component.strip_prefix('-') {
+                    Some(component) => (false, component),
+                    None => (true, component),
+                };
+                match component {
+                    "loose-object" | "objects" | "added" | "committed" | "all" => {
+                        loose.fsync = enable
+                    }
+                    // loose objects are not part of the default set
+                    "none" | "default" => loose.fsync = false,
+                    _ => {}
+                }
+            }
+        }
+
+        Ok(loose)
+    }
+
+    /// Path of the file holding the object
+    pub fn path(&self, id: &ObjectId) -> PathBuf {
+        let hash = id.to_string();
+        self.objects_dir.join(&hash[..2]).join(&hash[2..])
+    }
+}
+
+impl ObjectDatabase for Loose {
+    fn algorithm(&self) -> HashAlgorithm {
+        self.algorithm
+    }
+
+    fn read_header(&self, id: &ObjectId) -> anyhow::Result<Option<Header>> {
+        Ok(self.read(id)?.map(|object| object.header))
+    }
+
+    fn contains(&self, id: &ObjectId) -> anyhow::Result<bool> {
+        Ok(self.path(id).exists())
+    }
+
+    fn read(&self, id: &ObjectId) -> anyhow::Result<Option<Object<'_>>> {
+        let path = self.path(id);
+        let f = match fs::File::open(&path) {
+            Ok(f) => f,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => {
+                return Err(err).with_context(|| format!("opening file {}", path.display()))
+            }
+        };
+
+        let mut decoder = BufReader::new(ZlibDecoder::new(f));
+
+        let mut buf = Vec::new();
+
+        decoder
+            .read_until(0, &mut buf)
+            .context("reading object header")?;
+
+        let header = std::ffi::CStr::from_bytes_with_nul(&buf)
+            .ok()
+            .context("object header is not terminated")?
+            .to_str()
+            .context("file header is not valid UTF-8")?;
+
+        let Some((typ, size)) = header.split_once(' ') else {
+            anyhow::bail!("incorrect object header: {}", header)
+        };
+
+        let size = size
+            .parse::<usize>()
+            .context("parsing object size in header")?;
+
+        let header = Header {
+            typ: typ.parse()?,
+            size,
+        };
+
+        Ok(Some(Object {
+            header,
+            reader: Box::new(decoder),
+        }))
+    }
+
+    fn write(&self, header: &Header, data: &mut dyn Read) -> anyhow::Result<ObjectId> {
+        // the temp file is created next to its destination, so that it can be renamed into
+        // place atomically and a crash never leaves a truncated object behind
+        let tmp_file = tempfile::Builder::new()
+            .prefix("tmp_obj_")
+            .tempfile_in(&self.objects_dir)
+            .with_context(|| format!("creating temp file in {}", self.objects_dir.display()))?;
+
+        let encoder = ZlibEncoder::new(tmp_file, self.compression);
+
+        let mut compressor = HashWriter {
+            writer: encoder,
+            hasher: self.algorithm.hasher(),
+        };
+
+        write!(compressor, "{} {}\0", header.typ, header.size)?;
+
+        std::io::copy(data, &mut compressor)
+            .context("streaming object's content to file on disk")?;
+
+        let tmp_file = compressor.writer.finish()?;
+
+        let id = compressor.hasher.try_finalize()?;
+
+        let path = self.path(&id);
+
+        // objects are immutable, an existing copy only gets its modification time refreshed
+        // so that it is not pruned as unreachable garbage
+        if path.exists() {
+            fs::File::open(&path)
+                .and_then(|f| f.set_modified(SystemTime::now()))
+                .with_context(|| format!("freshening {}", path.display()))?;
+            return Ok(id);
+        }
+
+        if self.fsync {
+            tmp_file
+                .as_file()
+                .sync_all()
+                .context("flushing temp file to disk")?;
+        }
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(tmp_file.path(), fs::Permissions::from_mode(0o444))
+                .context("making temp file read-only")?;
+        }
+
+        let dir = path.parent().expect("object path has a directory");
+
+        self.shared.create_dir_all(dir)?;
+
+        match tmp_file.persist_noclobber(&path) {
+            Ok(_) => {}
+            // another process has just written the same object
+            Err(err) if err.error.kind() == std::io::ErrorKind::AlreadyExists => {}
+            Err(err) => {
+                return Err(err.error)
+                    .with_context(|| format!("moving temp file to {}", path.display()))
+            }
+        }
+
+        if self.fsync {
+            fs::File::open(dir)
+                .and_then(|dir| dir.sync_all())
+                .with_context(|| format!("flushing directory {} to disk", dir.display()))?;
+        }
+
+        Ok(id)
+    }
+
+    fn iter(&self) -> anyhow::Result<Box<dyn Iterator<Item = ObjectId> + '_>> {
+        let ids = list(&self.objects_dir)?
+            .into_iter()
+            .map(|(id, _)| id)
+            .filter(|id| id.algorithm() == self.algorithm);
+        Ok(Box::new(ids))
+    }
+}
+
+/// Lists names and paths of all loose objects in the objects directory
+pub fn list(objects_dir: &Path) -> anyhow::Result<Vec<(ObjectId, PathBuf)>> {
+    let entries = match fs::read_dir(objects_dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err).with_context(|| format!("reading {}", objects_dir.display())),
+    };
+
+    let mut objects = Vec::new();
+    for entry in entries {
+        let entry =
+            entry.with_context(|| format!("bad directory entry in {}", objects_dir.display()))?;
+        let dir_name = entry.file_name();
+        let Some(prefix) = dir_name.to_str() else {
+            continue;
+        };
+        // objects are stored in directories named by the first two hex digits of their name
+        if prefix.len() != 2 || !prefix.bytes().all(|b| b.is_ascii_hexdigit()) {
+            continue;
+        }
+
+        let dir = entry.path();
+        for file in fs::read_dir(&dir).with_context(|| format!("reading {}", dir.display()))? {
+            let file = file.with_context(|| format!("bad directory entry in {}", dir.display()))?;
+            let file_name = file.file_name();
+            let Some(rest) = file_name.to_str() else {
+                continue;
+            };
+            if let Ok(id) = ObjectId::from_hex(&format!("{prefix}{rest}")) {
+                objects.push((id, file.path()));
+            }
+        }
+    }
+    objects.sort();
+
+    Ok(objects)
+}
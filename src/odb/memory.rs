@@ -0,0 +1,77 @@
+use std::{
+    collections::BTreeMap,
+    io::{Cursor, Read},
+    sync::RwLock,
+};
+
+use anyhow::Context;
+
+use crate::object::{HashAlgorithm, Header, ObjectId, ObjectType};
+
+use super::{Object, ObjectDatabase};
+
+/// Objects kept in memory only, for embedding and tests
+#[derive(Default)]
+pub struct Memory {
+    algorithm: HashAlgorithm,
+    objects: RwLock<BTreeMap<ObjectId, (ObjectType, Vec<u8>)>>,
+}
+
+impl Memory {
+    pub fn new(algorithm: HashAlgorithm) -> Self {
+        Self {
+            algorithm,
+            objects: RwLock::default(),
+        }
+    }
+}
+
+impl ObjectDatabase for Memory {
+    fn algorithm(&self) -> HashAlgorithm {
+        self.algorithm
+    }
+
+    fn read_header(&self, id: &ObjectId) -> anyhow::Result<Option<Header>> {
+        let objects = self.objects.read().expect("lock is not poisoned");
+        Ok(objects.get(id).map(|(typ, data)| Header {
+            typ: typ.clone(),
+            size: data.len(),
+        }))
+    }
+
+    fn read(&self, id: &ObjectId) -> anyhow::Result<Option<Object<'_>>> {
+        let objects = self.objects.read().expect("lock is not poisoned");
+        Ok(objects.get(id).map(|(typ, data)| Object {
+            header: Header {
+                typ: typ.clone(),
+                size: data.len(),
+            },
+            reader: Box::new(Cursor::new(data.clone())),
+        }))
+    }
+
+    fn write(&self, header: &Header, data: &mut dyn Read) -> anyhow::Result<ObjectId> {
+        let mut content = Vec::with_capacity(header.size);
+        data.read_to_end(&mut content)
+            .context("reading object content")?;
+        anyhow::ensure!(
+            content.len() == header.size,
+            "object size differs from size stated in object header"
+        );
+
+        let mut hasher = self.algorithm.hasher();
+        hasher.update(format!("{} {}\0", header.typ, header.size).as_bytes());
+        hasher.update(&content);
+        let id = hasher.try_finalize()?;
+
+        let mut objects = self.objects.write().expect("lock is not poisoned");
+        objects.insert(id, (header.typ.clone(), content));
+        Ok(id)
+    }
+
+    fn iter(&self) -> anyhow::Result<Box<dyn Iterator<Item = ObjectId> + '_>> {
+        let objects = self.objects.read().expect("lock is not poisoned");
+        let ids: Vec<_> = objects.keys().copied().collect();
+        Ok(Box::new(ids.into_iter()))
+    }
+}
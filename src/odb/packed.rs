@@ -0,0 +1,56 @@
+use std::{collections::BTreeSet, io::Read, path::PathBuf};
+
+use crate::{
+    object::{HashAlgorithm, Header, ObjectId},
+    pack::PackSet,
+};
+
+use super::{Object, ObjectDatabase};
+
+/// Objects stored in packs of `objects/pack`, read only as packs are created as a whole
+pub struct Packed {
+    packs: PackSet,
+    algorithm: HashAlgorithm,
+}
+
+impl Packed {
+    pub fn new(objects_dir: impl Into<PathBuf>, algorithm: HashAlgorithm) -> Self {
+        Self {
+            packs: PackSet::new(objects_dir, algorithm),
+            algorithm,
+        }
+    }
+}
+
+impl ObjectDatabase for Packed {
+    fn algorithm(&self) -> HashAlgorithm {
+        self.algorithm
+    }
+
+    fn read_header(&self, id: &ObjectId) -> anyhow::Result<Option<Header>> {
+        self.packs.read_header(id)
+    }
+
+    fn read(&self, id: &ObjectId) -> anyhow::Result<Option<Object<'_>>> {
+        Ok(self.packs.read_object(id)?.map(|object| Object {
+            header: object.header,
+            reader: Box::new(object.reader),
+        }))
+    }
+
+    fn contains(&self, id: &ObjectId) -> anyhow::Result<bool> {
+        Ok(self.packs.find(id)?.is_some())
+    }
+
+    fn write(&self, _header: &Header, _data: &mut dyn Read) -> anyhow::Result<ObjectId> {
+        anyhow::bail!("objects cannot be added to existing packs")
+    }
+
+    fn iter(&self) -> anyhow::Result<Box<dyn Iterator<Item = ObjectId> + '_>> {
+        let mut ids = BTreeSet::new();
+        for (_, index) in self.packs.indexes()? {
+            ids.extend(index.names());
+        }
+        Ok(Box::new(ids.into_iter()))
+    }
+}
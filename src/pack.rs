@@ -0,0 +1,962 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fs,
+    io::{prelude::*, BufReader, SeekFrom},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    object::{CollisionError, HashAlgorithm, HashWriter, Header, ObjectFile, ObjectId, ObjectType},
+    odb::ObjectDatabase,
+    repository::Repository,
+};
+use anyhow::Context;
+use flate2::{bufread::ZlibDecoder, write::ZlibEncoder, Compression, Crc};
+
+// https://github.com/git/git/blob/795ea8776befc95ea2becd8020c7a284677b4161/Documentation/gitformat-pack.txt
+
+pub mod delta;
+pub mod writer;
+
+const IDX_SIGNATURE: &[u8] = b"\xfftOc";
+
+/// Encodes type and size of an object entry.
+/// The first byte holds the type in bits 4-6 and the lowest 4 bits of size,
+/// following bytes hold 7 more bits of size each while MSB is set.
+fn entry_header(typ: &ObjectType, size: usize) -> Vec<u8> {
+    let type_bits = match typ {
+        ObjectType::Commit => 1,
+        ObjectType::Tree => 2,
+        ObjectType::Blob => 3,
+        ObjectType::Tag => 4,
+        ObjectType::OfsDelta => 6,
+        ObjectType::RefDelta => 7,
+    };
+
+    let mut header = Vec::new();
+    let mut b = (type_bits << 4) | (size & 0b0000_1111) as u8;
+    let mut size = size >> 4;
+    while size > 0 {
+        header.push(b | 0b1000_0000);
+        b = (size & 0b0111_1111) as u8;
+        size >>= 7;
+    }
+    header.push(b);
+
+    header
+}
+
+/// Kind of a packfile entry with the reference to the base object for deltas
+enum EntryKind {
+    Object(ObjectType),
+    /// Delta against object at given (absolute) offset in the same pack
+    OfsDelta(u64),
+    /// Delta against object with given hash
+    RefDelta(ObjectId),
+}
+
+/// Parses type and size of an object entry starting at `offset` and the base of a delta object
+fn read_entry_header(
+    r: &mut impl Read,
+    offset: u64,
+    algorithm: HashAlgorithm,
+) -> anyhow::Result<(EntryKind, usize)> {
+    let mut b = [0];
+    r.read_exact(&mut b).context("reading entry type")?;
+    let b = b[0];
+
+    let typ = match (b & 0b0111_0000) >> 4 {
+        1 => ObjectType::Commit,
+        2 => ObjectType::Tree,
+        3 => ObjectType::Blob,
+        4 => ObjectType::Tag,
+        6 => ObjectType::OfsDelta,
+        7 => ObjectType::RefDelta,
+        other => anyhow::bail!("Unknown or unsupported object: {other}"),
+    };
+
+    let mut size = (b & 0b0000_1111) as usize;
+    let mut msb = b & 0b1000_0000 > 0;
+    let mut shift = 4;
+    while msb {
+        anyhow::ensure!(shift + 7 <= usize::BITS, "entry size too large");
+        let mut b = [0];
+        r.read_exact(&mut b).context("reading entry size")?;
+        msb = b[0] & 0b1000_0000 > 0;
+        size |= ((b[0] & 0b0111_1111) as usize) << shift;
+        shift += 7;
+    }
+
+    let kind = match typ {
+        ObjectType::OfsDelta => {
+            // offset is encoded in big-endian order and 1 is added to each 7 bits
+            // in all bytes but the last one to make the encoding unique
+            let mut b = [0];
+            r.read_exact(&mut b).context("reading base offset")?;
+            let mut distance = (b[0] & 0b0111_1111) as u64;
+            while b[0] & 0b1000_0000 > 0 {
+                anyhow::ensure!(distance < 1 << 56, "delta base offset out of bounds");
+                r.read_exact(&mut b).context("reading base offset")?;
+                distance = ((distance + 1) << 7) | (b[0] & 0b0111_1111) as u64;
+            }
+            // the base must precede the delta
+            let base = offset
+                .checked_sub(distance)
+                .filter(|_| distance > 0)
+                .context("delta base offset out of bounds")?;
+            EntryKind::OfsDelta(base)
+        }
+        ObjectType::RefDelta => {
+            let mut base = vec![0; algorithm.size()];
+            r.read_exact(&mut base)
+                .context("reading OBJ_REF_DELTA base object name")?;
+            EntryKind::RefDelta(ObjectId::from_bytes(algorithm, &base)?)
+        }
+        typ => EntryKind::Object(typ),
+    };
+
+    Ok((kind, size))
+}
+
+/// Object stored in the pack
+pub struct IndexEntry {
+    pub hash: ObjectId,
+    pub offset: u64,
+    pub crc32: u32,
+}
+
+/// Reads packfile from the stream, consuming exactly the bytes belonging to the pack,
+/// and verifies its trailing checksum. Returns the complete pack data.
+pub fn receive(reader: &mut impl BufRead, algorithm: HashAlgorithm) -> anyhow::Result<Vec<u8>> {
+    let mut r = RecordingReader {
+        inner: reader,
+        data: Vec::new(),
+    };
+
+    let mut header = [0; 12];
+    r.read_exact(&mut header).context("reading pack header")?;
+    anyhow::ensure!(
+        &header[..4] == b"PACK",
+        "malformed pack header: missing PACK"
+    );
+    let version = u32::from_be_bytes(header[4..8].try_into().expect("4 bytes"));
+    anyhow::ensure!(
+        version == 2 || version == 3,
+        "unsupported pack version {version}"
+    );
+    let num_obj = u32::from_be_bytes(header[8..12].try_into().expect("4 bytes"));
+
+    for _ in 0..num_obj {
+        let offset = r.data.len() as u64;
+        read_entry_header(&mut r, offset, algorithm)?;
+        let mut decoder = ZlibDecoder::new(&mut r);
+        std::io::copy(&mut decoder, &mut std::io::sink()).context("decompressing pack entry")?;
+    }
+
+    let checksum = algorithm.digest(&r.data);
+    let mut trailer = vec![0; algorithm.size()];
+    r.inner
+        .read_exact(&mut trailer)
+        .context("reading pack checksum")?;
+    anyhow::ensure!(checksum.as_bytes() == trailer, "pack checksum mismatch");
+    r.data.extend_from_slice(&trailer);
+
+    Ok(r.data)
+}
+
+/// BufRead wrapper keeping a copy of all consumed data
+struct RecordingReader<'a, R> {
+    inner: &'a mut R,
+    data: Vec<u8>,
+}
+
+impl<R: BufRead> Read for RecordingReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let available = self.fill_buf()?;
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for RecordingReader<'_, R> {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        if let Ok(buf) = self.inner.fill_buf() {
+            self.data.extend_from_slice(&buf[..amt]);
+        }
+        self.inner.consume(amt)
+    }
+}
+
+/// Pack data held in memory with all its objects resolved
+struct PackContent<'a> {
+    pack: &'a [u8],
+    algorithm: HashAlgorithm,
+    entries: Vec<RawEntry>,
+    /// resolved objects by entry offset
+    resolved: HashMap<u64, (ObjectType, Vec<u8>)>,
+}
+
+struct RawEntry {
+    offset: u64,
+    kind: EntryKind,
+    /// compressed data position and length
+    data: std::ops::Range<usize>,
+}
+
+impl<'a> PackContent<'a> {
+    fn parse(pack: &'a [u8], algorithm: HashAlgorithm) -> anyhow::Result<Self> {
+        let trailer = algorithm.size();
+        anyhow::ensure!(
+            pack.len() >= 12 + trailer && pack.starts_with(b"PACK"),
+            "malformed pack header: missing PACK"
+        );
+        let num_obj = u32::from_be_bytes(pack[8..12].try_into().expect("4 bytes"));
+
+        // every entry takes at least a few bytes, a larger count is caught below
+        let mut entries = Vec::with_capacity((num_obj as usize).min(pack.len() / 4));
+        let mut pos = 12;
+        for _ in 0..num_obj {
+            let offset = pos as u64;
+            let mut r = pack
+                .get(pos..pack.len() - trailer)
+                .context("pack truncated")?;
+            let (kind, _size) = read_entry_header(&mut r, offset, algorithm)?;
+            let data_start = pack.len() - trailer - r.len();
+
+            let mut decoder = ZlibDecoder::new(r);
+            std::io::copy(&mut decoder, &mut std::io::sink())
+                .context("decompressing pack entry")?;
+            pos = data_start + decoder.total_in() as usize;
+
+            entries.push(RawEntry {
+                offset,
+                kind,
+                data: data_start..pos,
+            });
+        }
+
+        anyhow::ensure!(
+            pos == pack.len() - trailer,
+            "pack has {} bytes of garbage after the last object",
+            pack.len() - trailer - pos
+        );
+
+        Ok(Self {
+            pack,
+            algorithm,
+            entries,
+            resolved: HashMap::new(),
+        })
+    }
+
+    /// Resolves object of entry with given index, returns `None` if the base of a REF_DELTA
+    /// is not part of the pack. The delta chain is followed down to a resolved or whole
+    /// object first, then the deltas are applied back up.
+    fn resolve(
+        &mut self,
+        index: usize,
+        by_hash: &HashMap<ObjectId, u64>,
+        by_offset: &HashMap<u64, usize>,
+    ) -> anyhow::Result<Option<(ObjectType, Vec<u8>)>> {
+        let mut chain = Vec::new();
+        let mut resolving = HashSet::new();
+        let mut current = index;
+
+        let (typ, mut data) = loop {
+            let entry = &self.entries[current];
+            let offset = entry.offset;
+            if let Some(resolved) = self.resolved.get(&offset) {
+                break resolved.clone();
+            }
+            anyhow::ensure!(
+                resolving.insert(current),
+                "delta chain of object at offset {offset} forms a cycle"
+            );
+
+            let base_offset = match entry.kind {
+                EntryKind::Object(ref typ) => {
+                    let resolved = (typ.clone(), self.inflate(current)?);
+                    self.resolved.insert(offset, resolved.clone());
+                    break resolved;
+                }
+                EntryKind::OfsDelta(base_offset) => base_offset,
+                EntryKind::RefDelta(base) => match by_hash.get(&base) {
+                    Some(&base_offset) => base_offset,
+                    None => return Ok(None),
+                },
+            };
+            chain.push(current);
+            current = *by_offset
+                .get(&base_offset)
+                .context("delta base is not an object in the pack")?;
+        };
+
+        while let Some(index) = chain.pop() {
+            let delta = self.inflate(index)?;
+            data = delta::apply(&data, &delta).context("applying delta")?;
+            self.resolved
+                .insert(self.entries[index].offset, (typ.clone(), data.clone()));
+        }
+        Ok(Some((typ, data)))
+    }
+
+    fn inflate(&self, index: usize) -> anyhow::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        ZlibDecoder::new(&self.pack[self.entries[index].data.clone()])
+            .read_to_end(&mut data)
+            .context("decompressing pack entry")?;
+        Ok(data)
+    }
+
+    /// Resolves all objects and computes their hashes.
+    /// Returns index entries and names of REF_DELTA bases missing in the pack (thin pack).
+    fn index(&mut self) -> anyhow::Result<(Vec<IndexEntry>, Vec<ObjectId>)> {
+        let by_offset: HashMap<_, _> = self
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| (entry.offset, i))
+            .collect();
+        let mut by_hash = HashMap::new();
+        let mut hashes: Vec<Option<ObjectId>> = vec![None; self.entries.len()];
+
+        // resolve repeatedly as REF_DELTA bases may appear after objects referencing them
+        loop {
+            let mut progress = false;
+            for (i, hash) in hashes.iter_mut().enumerate() {
+                if hash.is_some() {
+                    continue;
+                }
+                if let Some((typ, data)) = self.resolve(i, &by_hash, &by_offset)? {
+                    let resolved_hash = hash_object(&typ, &data, self.algorithm)?;
+                    by_hash.insert(resolved_hash, self.entries[i].offset);
+                    *hash = Some(resolved_hash);
+                    progress = true;
+                }
+            }
+            if !progress {
+                break;
+            }
+        }
+
+        let mut missing = Vec::new();
+        let mut index = Vec::with_capacity(self.entries.len());
+        for (entry, hash) in self.entries.iter().zip(hashes) {
+            match hash {
+                Some(hash) => {
+                    let start = entry.offset as usize;
+                    let mut crc = Crc::new();
+                    crc.update(&self.pack[start..entry.data.end]);
+                    index.push(IndexEntry {
+                        hash,
+                        offset: entry.offset,
+                        crc32: crc.sum(),
+                    });
+                }
+                None => {
+                    if let EntryKind::RefDelta(base) = entry.kind {
+                        if !by_hash.contains_key(&base) && !missing.contains(&base) {
+                            missing.push(base);
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok((index, missing))
+    }
+}
+
+fn hash_object(
+    typ: &ObjectType,
+    data: &[u8],
+    algorithm: HashAlgorithm,
+) -> Result<ObjectId, CollisionError> {
+    let mut hasher = algorithm.hasher();
+    hasher.update(format!("{typ} {}\0", data.len()).as_bytes());
+    hasher.update(data);
+    hasher.try_finalize()
+}
+
+/// Stores received pack in the objects directory together with its index.
+/// Thin packs are completed with missing base objects from the repository.
+/// Returns hashes of all objects in the pack.
+pub fn store(repo: &Repository, mut pack: Vec<u8>) -> anyhow::Result<Vec<ObjectId>> {
+    let algorithm = repo.algorithm();
+    let mut content = PackContent::parse(&pack, algorithm)?;
+    let (mut index, missing) = content.index()?;
+
+    if !missing.is_empty() {
+        pack = complete_thin_pack(repo.odb(), pack, &missing)?;
+        let mut content = PackContent::parse(&pack, algorithm)?;
+        let (completed_index, missing) = content.index()?;
+        anyhow::ensure!(
+            missing.is_empty(),
+            "pack has {} unresolved deltas",
+            missing.len()
+        );
+        index = completed_index;
+    }
+
+    let checksum = ObjectId::from_bytes(algorithm, &pack[pack.len() - algorithm.size()..])?;
+    install(&repo.objects_dir(), &pack, &mut index, &checksum)?;
+
+    Ok(index.into_iter().map(|entry| entry.hash).collect())
+}
+
+/// Writes pack and its index into the `pack` subdirectory of the objects directory.
+/// Returns path of the pack (without extension).
+pub fn install(
+    objects_dir: &Path,
+    pack: &[u8],
+    index: &mut [IndexEntry],
+    checksum: &ObjectId,
+) -> anyhow::Result<PathBuf> {
+    index.sort_unstable_by_key(|entry| entry.hash);
+
+    let pack_dir = objects_dir.join("pack");
+    fs::create_dir_all(&pack_dir)
+        .with_context(|| format!("creating directory {}", pack_dir.display()))?;
+    let path = pack_dir.join(format!("pack-{checksum}"));
+
+    // index is written last, as the pack is not visible to readers without it
+    write_file(&path.with_extension("pack"), pack)?;
+    let mut idx = Vec::new();
+    write_index(index, checksum, &mut idx)?;
+    write_file(&path.with_extension("idx"), &idx)?;
+
+    Ok(path)
+}
+
+/// Name, type and data of an object read from a pack
+pub type PackedObject = (ObjectId, ObjectType, Vec<u8>);
+
+/// Verifies pack checksum, that all deltas resolve and that the pack index matches pack content.
+/// Returns all objects in the pack (given without extension).
+pub fn verify(path: &Path, algorithm: HashAlgorithm) -> anyhow::Result<Vec<PackedObject>> {
+    let pack_path = path.with_extension("pack");
+    let pack = fs::read(&pack_path).with_context(|| format!("reading {}", pack_path.display()))?;
+    anyhow::ensure!(pack.len() >= 12 + algorithm.size(), "pack truncated");
+    let (data, trailer) = pack.split_at(pack.len() - algorithm.size());
+    anyhow::ensure!(
+        algorithm.digest(data).as_bytes() == trailer,
+        "pack checksum mismatch"
+    );
+
+    let mut content = PackContent::parse(&pack, algorithm)?;
+    let (mut entries, missing) = content.index()?;
+    anyhow::ensure!(
+        missing.is_empty(),
+        "pack has {} unresolved deltas",
+        missing.len()
+    );
+    entries.sort_unstable_by_key(|entry| entry.hash);
+
+    let index = PackIndex::read(&path.with_extension("idx"), algorithm)?;
+    anyhow::ensure!(
+        index.names.len() == entries.len()
+            && entries
+                .iter()
+                .zip(index.names.iter().zip(&index.offsets))
+                .all(|(entry, (name, &offset))| entry.hash == *name && entry.offset == offset),
+        "pack index does not match pack content"
+    );
+
+    let mut resolved = content.resolved;
+    Ok(entries
+        .into_iter()
+        .map(|entry| {
+            let (typ, data) = resolved.remove(&entry.offset).expect("entry is resolved");
+            (entry.hash, typ, data)
+        })
+        .collect())
+}
+
+fn write_file(path: &Path, data: &[u8]) -> anyhow::Result<()> {
+    let mut tmp_path = path.to_path_buf();
+    tmp_path.set_extension("tmp");
+    fs::write(&tmp_path, data).with_context(|| format!("writing {}", tmp_path.display()))?;
+    fs::rename(&tmp_path, path).with_context(|| format!("moving temp file to {}", path.display()))
+}
+
+/// Appends missing delta base objects from the repository to the pack
+fn complete_thin_pack(
+    odb: &dyn ObjectDatabase,
+    mut pack: Vec<u8>,
+    missing: &[ObjectId],
+) -> anyhow::Result<Vec<u8>> {
+    let algorithm = odb.algorithm();
+    pack.truncate(pack.len() - algorithm.size());
+
+    for base in missing {
+        let mut object = ObjectFile::read(base, odb)
+            .with_context(|| format!("delta base object {base} is missing"))?;
+
+        pack.extend(entry_header(&object.header.typ, object.header.size));
+        let mut encoder = ZlibEncoder::new(&mut pack, Compression::default());
+        std::io::copy(&mut object.reader, &mut encoder)
+            .with_context(|| format!("compressing object {base}"))?;
+        encoder.finish()?;
+    }
+
+    let num_obj = u32::from_be_bytes(pack[8..12].try_into().expect("4 bytes"));
+    let num_obj = num_obj + missing.len() as u32;
+    pack[8..12].copy_from_slice(&num_obj.to_be_bytes());
+
+    let checksum = algorithm.digest(&pack);
+    pack.extend_from_slice(checksum.as_bytes());
+
+    Ok(pack)
+}
+
+/// Writes version 2 pack index for entries sorted by hash
+pub fn write_index(
+    entries: &[IndexEntry],
+    pack_checksum: &ObjectId,
+    writer: &mut impl Write,
+) -> anyhow::Result<()> {
+    let mut w = HashWriter {
+        writer,
+        hasher: pack_checksum.algorithm().hasher(),
+    };
+
+    w.write_all(IDX_SIGNATURE)?;
+    w.write_all(&2u32.to_be_bytes())?;
+
+    // fan-out table: number of objects whose first byte of name is less than or equal to N
+    let mut fanout = [0u32; 256];
+    for entry in entries {
+        fanout[entry.hash.as_bytes()[0] as usize] += 1;
+    }
+    let mut count = 0;
+    for n in fanout {
+        count += n;
+        w.write_all(&count.to_be_bytes())?;
+    }
+
+    for entry in entries {
+        w.write_all(entry.hash.as_bytes())?;
+    }
+    for entry in entries {
+        w.write_all(&entry.crc32.to_be_bytes())?;
+    }
+
+    // offsets that do not fit in 31 bits are stored in the table of 8-byte offsets
+    let mut large_offsets = Vec::new();
+    for entry in entries {
+        if entry.offset < 0x8000_0000 {
+            w.write_all(&(entry.offset as u32).to_be_bytes())?;
+        } else {
+            let index = 0x8000_0000 | large_offsets.len() as u32;
+            w.write_all(&index.to_be_bytes())?;
+            large_offsets.push(entry.offset);
+        }
+    }
+    for offset in large_offsets {
+        w.write_all(&offset.to_be_bytes())?;
+    }
+
+    w.write_all(pack_checksum.as_bytes())?;
+    let checksum = w.hasher.finalize();
+    w.writer.write_all(checksum.as_bytes())?;
+
+    Ok(())
+}
+
+/// Version 2 pack index (`.idx` file)
+pub struct PackIndex {
+    names: Vec<ObjectId>,
+    offsets: Vec<u64>,
+}
+
+impl PackIndex {
+    pub fn read(path: &Path, algorithm: HashAlgorithm) -> anyhow::Result<Self> {
+        let data = fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+        let hash_size = algorithm.size();
+
+        anyhow::ensure!(
+            data.len() >= 8 + 256 * 4 + 2 * hash_size
+                && data.starts_with(IDX_SIGNATURE)
+                && data[4..8] == 2u32.to_be_bytes(),
+            "unsupported pack index {}",
+            path.display()
+        );
+
+        let u32_at = |pos: usize| -> anyhow::Result<u32> {
+            let bytes = data.get(pos..pos + 4).context("pack index truncated")?;
+            Ok(u32::from_be_bytes(bytes.try_into().expect("4 bytes")))
+        };
+
+        let num_obj = u32_at(8 + 255 * 4)? as usize;
+        let names_start = 8 + 256 * 4;
+        let offsets_start = names_start + num_obj * (hash_size + 4);
+        let large_offsets_start = offsets_start + num_obj * 4;
+        // names, CRCs, offsets and the two checksums must fit in the file
+        anyhow::ensure!(
+            large_offsets_start + 2 * hash_size <= data.len(),
+            "pack index {} is truncated",
+            path.display()
+        );
+
+        let mut names = Vec::with_capacity(num_obj);
+        let mut offsets = Vec::with_capacity(num_obj);
+        for i in 0..num_obj {
+            let name = data
+                .get(names_start + i * hash_size..names_start + (i + 1) * hash_size)
+                .context("pack index truncated")?;
+            names.push(ObjectId::from_bytes(algorithm, name)?);
+
+            let offset = u32_at(offsets_start + i * 4)?;
+            let offset = if offset & 0x8000_0000 == 0 {
+                offset as u64
+            } else {
+                let pos = large_offsets_start + (offset & 0x7fff_ffff) as usize * 8;
+                let bytes = data.get(pos..pos + 8).context("pack index truncated")?;
+                u64::from_be_bytes(bytes.try_into().expect("8 bytes"))
+            };
+            offsets.push(offset);
+        }
+
+        Ok(Self { names, offsets })
+    }
+
+    /// Returns sorted names of all objects in the pack
+    pub fn names(&self) -> &[ObjectId] {
+        &self.names
+    }
+
+    /// Returns offset of the object in the pack
+    pub fn find(&self, hash: &ObjectId) -> Option<u64> {
+        self.names
+            .binary_search(hash)
+            .ok()
+            .map(|index| self.offsets[index])
+    }
+}
+
+/// Returns paths of all packs (without extension) in the objects directory that have an index
+pub fn packs(objects_dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let pack_dir = objects_dir.join("pack");
+    let entries = match fs::read_dir(&pack_dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err).with_context(|| format!("reading {}", pack_dir.display())),
+    };
+
+    let mut packs = Vec::new();
+    for entry in entries {
+        let path = entry
+            .with_context(|| format!("bad directory entry in {}", pack_dir.display()))?
+            .path();
+        if path.extension().is_some_and(|ext| ext == "idx") {
+            packs.push(path.with_extension(""));
+        }
+    }
+    packs.sort();
+
+    Ok(packs)
+}
+
+/// Packs of an objects directory. Packs are listed and their indexes read once and recently
+/// used delta bases are kept, so that reading many objects does not parse indexes and resolve
+/// deltas again.
+pub struct PackSet {
+    objects_dir: PathBuf,
+    algorithm: HashAlgorithm,
+    /// `None` until the pack directory is listed
+    indexes: Mutex<Option<Vec<PackEntry>>>,
+    bases: Mutex<DeltaBaseCache>,
+}
+
+impl PackSet {
+    pub fn new(objects_dir: impl Into<PathBuf>, algorithm: HashAlgorithm) -> Self {
+        Self {
+            objects_dir: objects_dir.into(),
+            algorithm,
+            indexes: Mutex::new(None),
+            bases: Mutex::new(DeltaBaseCache::default()),
+        }
+    }
+
+    /// Returns packs (without extension) with their indexes, the pack directory is listed
+    /// on the first call only
+    pub fn indexes(&self) -> anyhow::Result<Vec<PackEntry>> {
+        let mut indexes = self.indexes.lock().expect("pack index lock poisoned");
+        if let Some(indexes) = &*indexes {
+            return Ok(indexes.clone());
+        }
+        let current = self.list(&[])?;
+        *indexes = Some(current.clone());
+        Ok(current)
+    }
+
+    /// Lists the pack directory again, picking up packs added and dropping packs removed since
+    /// it was last listed. Indexes of packs that are still present are not read again.
+    fn refresh(&self) -> anyhow::Result<Vec<PackEntry>> {
+        let mut indexes = self.indexes.lock().expect("pack index lock poisoned");
+        let current = self.list(indexes.as_deref().unwrap_or_default())?;
+        *indexes = Some(current.clone());
+        Ok(current)
+    }
+
+    fn list(&self, known: &[PackEntry]) -> anyhow::Result<Vec<PackEntry>> {
+        let mut current = Vec::new();
+        for pack in packs(&self.objects_dir)? {
+            let index = match known.iter().find(|(known, _)| *known == pack) {
+                Some((_, index)) => index.clone(),
+                None => Arc::new(PackIndex::read(
+                    &pack.with_extension("idx"),
+                    self.algorithm,
+                )?),
+            };
+            current.push((pack, index));
+        }
+        Ok(current)
+    }
+
+    /// Returns pack (without extension) containing the object and its offset there.
+    /// Packs are listed again when the object is not found, it may have been packed since.
+    pub fn find(&self, hash: &ObjectId) -> anyhow::Result<Option<(PathBuf, u64)>> {
+        let lookup = |indexes: Vec<PackEntry>| {
+            indexes
+                .into_iter()
+                .find_map(|(pack, index)| index.find(hash).map(|offset| (pack, offset)))
+        };
+        match lookup(self.indexes()?) {
+            Some(found) => Ok(Some(found)),
+            None => Ok(lookup(self.refresh()?)),
+        }
+    }
+
+    /// Looks up type and size of an object in the packs. Only entry headers are read: the
+    /// size of a delta comes from the delta data and its type from the end of its delta chain.
+    pub fn read_header(&self, hash: &ObjectId) -> anyhow::Result<Option<Header>> {
+        let Some((pack, offset)) = self.find(hash)? else {
+            return Ok(None);
+        };
+        self.read_entry_header(&pack, offset)
+            .with_context(|| {
+                format!(
+                    "reading object header at offset {offset} in {}",
+                    pack.with_extension("pack").display()
+                )
+            })
+            .map(Some)
+    }
+
+    fn read_entry_header(&self, pack: &Path, offset: u64) -> anyhow::Result<Header> {
+        let mut files = HashMap::new();
+        let mut visited = HashSet::new();
+        let mut key = (pack.to_path_buf(), offset);
+        let mut size = None;
+
+        loop {
+            if let Some(size) = size {
+                if let Some((typ, _)) = self.cached_base(&key) {
+                    return Ok(Header { typ, size });
+                }
+            }
+            anyhow::ensure!(
+                visited.insert(key.clone()),
+                "delta chain of object at offset {offset} forms a cycle"
+            );
+
+            let f = open_pack(&mut files, &key.0)?;
+            f.seek(SeekFrom::Start(key.1))?;
+            let (kind, entry_size) = read_entry_header(f, key.1, self.algorithm)?;
+            let base_key = match kind {
+                EntryKind::Object(typ) => {
+                    return Ok(Header {
+                        typ,
+                        size: size.unwrap_or(entry_size),
+                    })
+                }
+                EntryKind::OfsDelta(base_offset) => (key.0.clone(), base_offset),
+                EntryKind::RefDelta(base) => self
+                    .find(&base)?
+                    .with_context(|| format!("reading delta base {base}"))?,
+            };
+            if size.is_none() {
+                // the delta begins with the sizes of its base and of the object it produces
+                let mut start = Vec::new();
+                ZlibDecoder::new(&mut *f)
+                    .take(2 * 10)
+                    .read_to_end(&mut start)
+                    .context("decompressing pack entry")?;
+                size = Some(delta::target_size(&start)?);
+            }
+            key = base_key;
+        }
+    }
+
+    /// Looks up object in the packs
+    pub fn read_object(
+        &self,
+        hash: &ObjectId,
+    ) -> anyhow::Result<Option<ObjectFile<std::io::Cursor<Vec<u8>>>>> {
+        let Some((pack, offset)) = self.find(hash)? else {
+            return Ok(None);
+        };
+        let (typ, data) = self.read_at(&pack, offset)?;
+        Ok(Some(ObjectFile {
+            header: Header {
+                typ,
+                size: data.len(),
+            },
+            reader: std::io::Cursor::new(data),
+        }))
+    }
+
+    fn read_at(&self, pack: &Path, offset: u64) -> anyhow::Result<(ObjectType, Vec<u8>)> {
+        self.read_entry(pack, offset).with_context(|| {
+            format!(
+                "reading object at offset {offset} in {}",
+                pack.with_extension("pack").display()
+            )
+        })
+    }
+
+    /// Follows the delta chain of the entry down to a cached base or a whole object,
+    /// then applies the deltas back up. Intermediate results are kept as delta bases.
+    fn read_entry(&self, pack: &Path, offset: u64) -> anyhow::Result<(ObjectType, Vec<u8>)> {
+        let mut files = HashMap::new();
+        let mut deltas: Vec<(BaseKey, Vec<u8>)> = Vec::new();
+        let mut visited = HashSet::new();
+        let mut key = (pack.to_path_buf(), offset);
+
+        let (typ, mut base) = loop {
+            if !deltas.is_empty() {
+                if let Some(base) = self.cached_base(&key) {
+                    break base;
+                }
+            }
+            anyhow::ensure!(
+                visited.insert(key.clone()),
+                "delta chain of object at offset {offset} forms a cycle"
+            );
+
+            let (kind, data) = self.read_raw(&mut files, &key)?;
+            let base_key = match kind {
+                EntryKind::Object(typ) if deltas.is_empty() => return Ok((typ, data)),
+                EntryKind::Object(typ) => {
+                    let data: Arc<[u8]> = data.into();
+                    self.cache_base(key, typ.clone(), data.clone());
+                    break (typ, data);
+                }
+                EntryKind::OfsDelta(base_offset) => (key.0.clone(), base_offset),
+                // stored packs are never thin, the base is found in one of the packs
+                EntryKind::RefDelta(base) => self
+                    .find(&base)?
+                    .with_context(|| format!("reading delta base {base}"))?,
+            };
+            deltas.push((key, data));
+            key = base_key;
+        };
+
+        while let Some((key, delta)) = deltas.pop() {
+            let data = delta::apply(&base, &delta)?;
+            if deltas.is_empty() {
+                return Ok((typ, data));
+            }
+            base = data.into();
+            self.cache_base(key, typ.clone(), base.clone());
+        }
+        unreachable!("the chain ends with the requested delta")
+    }
+
+    /// Reads kind and inflated data of the entry, pack files stay open for the whole chain
+    fn read_raw(
+        &self,
+        files: &mut HashMap<PathBuf, BufReader<fs::File>>,
+        (pack, offset): &BaseKey,
+    ) -> anyhow::Result<(EntryKind, Vec<u8>)> {
+        let f = open_pack(files, pack)?;
+        f.seek(SeekFrom::Start(*offset))?;
+        let (kind, size) = read_entry_header(f, *offset, self.algorithm)?;
+
+        // the size is only trusted once the data has been inflated
+        let mut data = Vec::with_capacity(size.min(1 << 20));
+        ZlibDecoder::new(&mut *f)
+            .read_to_end(&mut data)
+            .context("decompressing pack entry")?;
+        anyhow::ensure!(data.len() == size, "pack entry size mismatch");
+        Ok((kind, data))
+    }
+
+    fn cached_base(&self, key: &BaseKey) -> Option<Base> {
+        self.bases
+            .lock()
+            .expect("delta base lock poisoned")
+            .get(key)
+    }
+
+    fn cache_base(&self, key: BaseKey, typ: ObjectType, data: Arc<[u8]>) {
+        self.bases
+            .lock()
+            .expect("delta base lock poisoned")
+            .insert(key, typ, data);
+    }
+}
+
+/// Returns reader of the pack file (given without extension), opening it on first use
+fn open_pack<'a>(
+    files: &'a mut HashMap<PathBuf, BufReader<fs::File>>,
+    pack: &Path,
+) -> anyhow::Result<&'a mut BufReader<fs::File>> {
+    match files.entry(pack.to_path_buf()) {
+        std::collections::hash_map::Entry::Occupied(entry) => Ok(entry.into_mut()),
+        std::collections::hash_map::Entry::Vacant(entry) => {
+            let path = pack.with_extension("pack");
+            let f = fs::File::open(&path).with_context(|| format!("opening {}", path.display()))?;
+            Ok(entry.insert(BufReader::new(f)))
+        }
+    }
+}
+
+/// Pack (without extension) with its index
+pub type PackEntry = (PathBuf, Arc<PackIndex>);
+
+/// Pack (without extension) and offset of a delta base
+type BaseKey = (PathBuf, u64);
+
+/// Type and content of a resolved delta base
+type Base = (ObjectType, Arc<[u8]>);
+
+/// Resolved delta bases by pack and offset, the oldest are dropped above the size limit
+#[derive(Default)]
+struct DeltaBaseCache {
+    entries: HashMap<BaseKey, Base>,
+    order: VecDeque<BaseKey>,
+    size: usize,
+}
+
+impl DeltaBaseCache {
+    // https://git-scm.com/docs/git-config#Documentation/git-config.txt-coredeltaBaseCacheLimit
+    const LIMIT: usize = 96 << 20;
+
+    fn get(&self, key: &BaseKey) -> Option<Base> {
+        self.entries.get(key).cloned()
+    }
+
+    fn insert(&mut self, key: BaseKey, typ: ObjectType, data: Arc<[u8]>) {
+        if data.len() > Self::LIMIT || self.entries.contains_key(&key) {
+            return;
+        }
+        self.size += data.len();
+        self.entries.insert(key.clone(), (typ, data));
+        self.order.push_back(key);
+        while self.size > Self::LIMIT {
+            let oldest = self.order.pop_front().expect("cache is not empty");
+            if let Some((_, data)) = self.entries.remove(&oldest) {
+                self.size -= data.len();
+            }
+        }
+    }
+}
@@ -0,0 +1,251 @@
+use std::collections::HashMap;
+
+use anyhow::Context;
+
+// https://github.com/git/git/blob/795ea8776befc95ea2becd8020c7a284677b4161/Documentation/gitformat-pack.txt#L128
+// https://github.com/git/git/blob/795ea8776befc95ea2becd8020c7a284677b4161/diff-delta.c
+
+/// Size of blocks of the source that are looked up in the target
+const BLOCK_SIZE: usize = 16;
+
+/// Maximum number of bytes copied by one copy instruction
+const MAX_COPY: usize = 0x10000;
+
+/// Maximum number of bytes inserted by one insert instruction
+const MAX_INSERT: usize = 0x7f;
+
+/// Reads variable-length size from the beginning of delta data
+fn read_delta_size(delta: &mut &[u8]) -> anyhow::Result<usize> {
+    let mut size = 0;
+    let mut shift = 0;
+    loop {
+        anyhow::ensure!(shift + 7 <= usize::BITS, "delta size too large");
+        let (&b, rest) = delta.split_first().context("delta data truncated")?;
+        *delta = rest;
+        size |= ((b & 0b0111_1111) as usize) << shift;
+        shift += 7;
+        if b & 0b1000_0000 == 0 {
+            return Ok(size);
+        }
+    }
+}
+
+/// Reads size of the object the delta produces, `delta` may be just the beginning of the data
+pub fn target_size(mut delta: &[u8]) -> anyhow::Result<usize> {
+    read_delta_size(&mut delta).context("reading source length")?;
+    read_delta_size(&mut delta).context("reading target length")
+}
+
+/// Reconstructs object by applying delta instructions to the base object data
+pub fn apply(base: &[u8], mut delta: &[u8]) -> anyhow::Result<Vec<u8>> {
+    /* The delta begins with the source and target lengths, both encoded as variable-length integers, which is useful for error checking,
+    but is not essential.
+    After this, there are a series of instructions, which may be either “copy” (MSB = 1) or “insert” (MSB = 0). */
+
+    let source_length = read_delta_size(&mut delta).context("reading source length")?;
+    let target_length = read_delta_size(&mut delta).context("reading target length")?;
+
+    anyhow::ensure!(
+        base.len() == source_length,
+        "incorrect base object length, expected {}, got {}",
+        source_length,
+        base.len()
+    );
+
+    // new_data contains data from base object with applied delta chunks
+    // the length is only checked at the end, copies of the base bound a sane result
+    let mut new_data = Vec::with_capacity(target_length.min(base.len() + delta.len()));
+
+    while let Some((&instruction, rest)) = delta.split_first() {
+        delta = rest;
+
+        if instruction & 0b1000_0000 == 0 {
+            // INSERT
+            // The insert instruction itself is the number of bytes to copy from the delta object to the output.
+            // Since insert instructions all have their MSB set to 0, the maximum number of bytes to insert is 127.
+            // So, if the instruction is 01001011, that means that we should read the next 75 bytes of the delta object and copy them to the output.
+            anyhow::ensure!(
+                instruction != 0,
+                "incorrect delta instruction {instruction}"
+            );
+
+            let length = instruction as usize;
+            let data = delta.get(..length).ok_or(anyhow::anyhow!(
+                "could not read delta object data to insert them"
+            ))?;
+
+            new_data.extend_from_slice(data);
+            delta = &delta[length..];
+        } else {
+            // COPY
+            // Copy instructions signal that we should copy a consecutive chunk of bytes from the base object to the output.
+            // There are two numbers that are necessary to perform this operation: the location (offset) of the first byte to copy, and the number of bytes to copy.
+            // These are stored as little-endian variable-length integers after each copy instruction; however, their contents are compressed.
+            //
+            // Even though the byte offset is a 32-bit integer, Git only includes the non-zero bytes to save space,
+            // and the last four bits of the copy instruction signal how many bytes to read.
+            //
+            // For example, let’s say that the last four bits of the copy instruction are 1010 and the next two bytes are 11010111 01001011.
+            // This means that the byte offset is 01001011 00000000 11010111 00000000, which is 1,258,346,240.
+            //
+            // The copy length is interpreted the same way, with the middle three bits of the instruction signifying whether to advance the cursor or not,
+            // just as the last four bits signify whether to advance the cursor when constructing the byte offset.
+
+            let mut offset = 0;
+            let mut length = 0;
+
+            for i in 0..4 {
+                if instruction & (1u8 << i) > 0 {
+                    let (&b, rest) = delta.split_first().context("delta data truncated")?;
+                    delta = rest;
+                    offset += (b as usize) << (i * 8);
+                }
+            }
+
+            for i in 0..3 {
+                if instruction & (1u8 << (i + 4)) > 0 {
+                    let (&b, rest) = delta.split_first().context("delta data truncated")?;
+                    delta = rest;
+                    length += (b as usize) << (i * 8);
+                }
+            }
+
+            // length of zero means 0x10000
+            if length == 0 {
+                length = 0x10000;
+            }
+
+            let data = base.get(offset..offset + length).ok_or(anyhow::anyhow!(
+                "could not read base object data to copy them"
+            ))?;
+            new_data.extend_from_slice(data);
+        }
+    }
+
+    anyhow::ensure!(
+        new_data.len() == target_length,
+        "incorrect new base object length, expected {}, got {}",
+        target_length,
+        new_data.len()
+    );
+
+    Ok(new_data)
+}
+
+/// Index of source (base) object blocks used to create deltas of similar objects against it
+pub struct DeltaIndex<'a> {
+    source: &'a [u8],
+    /// offset of the first occurrence of each aligned block in the source
+    blocks: HashMap<u128, usize>,
+}
+
+impl<'a> DeltaIndex<'a> {
+    pub fn new(source: &'a [u8]) -> Self {
+        let mut blocks = HashMap::with_capacity(source.len() / BLOCK_SIZE);
+        for offset in (0..source.len().saturating_sub(BLOCK_SIZE - 1)).step_by(BLOCK_SIZE) {
+            blocks.entry(block(&source[offset..])).or_insert(offset);
+        }
+
+        Self { source, blocks }
+    }
+
+    /// Creates delta that reconstructs `target` from the source.
+    /// Returns `None` if the delta would be larger than `max_size`.
+    pub fn create(&self, target: &[u8], max_size: usize) -> Option<Vec<u8>> {
+        let source = self.source;
+
+        let mut delta = Vec::new();
+        write_delta_size(&mut delta, source.len());
+        write_delta_size(&mut delta, target.len());
+
+        // start of target data not covered by copy instructions yet
+        let mut insert_start = 0;
+        let mut i = 0;
+        while i + BLOCK_SIZE <= target.len() {
+            let Some(&found) = self.blocks.get(&block(&target[i..])) else {
+                i += 1;
+                continue;
+            };
+
+            // extend the match backwards over pending insert data and then forwards
+            let (mut src, mut trg) = (found, i);
+            while trg > insert_start && src > 0 && source[src - 1] == target[trg - 1] {
+                src -= 1;
+                trg -= 1;
+            }
+            let mut length = i - trg + BLOCK_SIZE;
+            while src + length < source.len()
+                && trg + length < target.len()
+                && source[src + length] == target[trg + length]
+            {
+                length += 1;
+            }
+
+            write_insert(&mut delta, &target[insert_start..trg]);
+            write_copy(&mut delta, src, length);
+
+            i = trg + length;
+            insert_start = i;
+
+            if delta.len() > max_size {
+                return None;
+            }
+        }
+        write_insert(&mut delta, &target[insert_start..]);
+
+        (delta.len() <= max_size).then_some(delta)
+    }
+}
+
+fn block(data: &[u8]) -> u128 {
+    u128::from_le_bytes(data[..BLOCK_SIZE].try_into().expect("16 bytes"))
+}
+
+fn write_delta_size(delta: &mut Vec<u8>, mut size: usize) {
+    while size >= 0b1000_0000 {
+        delta.push((size & 0b0111_1111) as u8 | 0b1000_0000);
+        size >>= 7;
+    }
+    delta.push(size as u8);
+}
+
+/// Encodes insert instructions for data, each instruction can insert at most 127 bytes
+fn write_insert(delta: &mut Vec<u8>, data: &[u8]) {
+    for chunk in data.chunks(MAX_INSERT) {
+        delta.push(chunk.len() as u8);
+        delta.extend_from_slice(chunk);
+    }
+}
+
+/// Encodes copy instructions; only non-zero bytes of offset and length are stored
+/// and the instruction bits mark which of them are present
+fn write_copy(delta: &mut Vec<u8>, mut offset: usize, mut length: usize) {
+    while length > 0 {
+        let chunk = length.min(MAX_COPY);
+
+        let mut instruction = 0b1000_0000;
+        let mut args = Vec::with_capacity(7);
+        for i in 0..4 {
+            let b = (offset >> (i * 8)) as u8;
+            if b != 0 {
+                instruction |= 1 << i;
+                args.push(b);
+            }
+        }
+        // length of 0x10000 is encoded as zero
+        let encoded_length = if chunk == MAX_COPY { 0 } else { chunk };
+        for i in 0..3 {
+            let b = (encoded_length >> (i * 8)) as u8;
+            if b != 0 {
+                instruction |= 1 << (i + 4);
+                args.push(b);
+            }
+        }
+
+        delta.push(instruction);
+        delta.extend(args);
+
+        offset += chunk;
+        length -= chunk;
+    }
+}
@@ -0,0 +1,269 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io::prelude::*,
+};
+
+use anyhow::Context;
+use flate2::{write::ZlibEncoder, Compression, Crc};
+
+use crate::{
+    object::{HashWriter, ObjectFile, ObjectId, ObjectType},
+    odb::ObjectDatabase,
+    rev_list::ObjectEntry,
+};
+
+use super::{delta::DeltaIndex, entry_header, IndexEntry};
+
+// https://github.com/git/git/blob/795ea8776befc95ea2becd8020c7a284677b4161/Documentation/technical/pack-heuristics.txt
+
+/// Objects smaller than this are not worth deltifying
+const MIN_DELTA_SIZE: usize = 50;
+
+pub struct PackOptions {
+    /// Number of objects considered as delta base for each object
+    pub window: usize,
+    /// Maximum length of delta chains
+    pub depth: usize,
+    /// Reference delta bases by offset in the pack (OFS_DELTA) instead of by name (REF_DELTA)
+    pub ofs_delta: bool,
+}
+
+impl Default for PackOptions {
+    fn default() -> Self {
+        Self {
+            window: 10,
+            depth: 50,
+            ofs_delta: true,
+        }
+    }
+}
+
+/// Object loaded into memory for packing
+struct PackObject {
+    hash: ObjectId,
+    typ: ObjectType,
+    data: Vec<u8>,
+    name_hash: u32,
+    /// index of the delta base object with the delta itself
+    delta: Option<(usize, Vec<u8>)>,
+    depth: usize,
+}
+
+/// Writes version 2 packfile with given objects, storing similar objects as deltas.
+/// Objects are written in the given order, except that delta bases always precede their deltas.
+/// Returns the pack checksum (also written as the pack trailer) and index entries of written objects.
+pub fn write(
+    odb: &dyn ObjectDatabase,
+    entries: &[ObjectEntry],
+    options: &PackOptions,
+    writer: &mut impl Write,
+) -> anyhow::Result<(ObjectId, Vec<IndexEntry>)> {
+    let algorithm = odb.algorithm();
+    let mut objects = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let mut object = ObjectFile::read(&entry.hash, odb)
+            .with_context(|| format!("reading object {}", entry.hash))?;
+        let mut data = Vec::with_capacity(object.header.size);
+        object
+            .reader
+            .read_to_end(&mut data)
+            .with_context(|| format!("reading object {}", entry.hash))?;
+        anyhow::ensure!(
+            data.len() == object.header.size,
+            "object {} size differs from size stated in object header",
+            entry.hash
+        );
+
+        let hash = entry.hash;
+        anyhow::ensure!(
+            hash.algorithm() == algorithm,
+            "object {hash} is not a {algorithm} object"
+        );
+
+        objects.push(PackObject {
+            hash,
+            typ: object.header.typ,
+            data,
+            name_hash: name_hash(&entry.path),
+            delta: None,
+            depth: 0,
+        });
+    }
+
+    find_deltas(&mut objects, options);
+
+    let mut w = HashWriter {
+        writer,
+        hasher: algorithm.hasher(),
+    };
+
+    w.write_all(b"PACK")?;
+    w.write_all(&2u32.to_be_bytes())?;
+    let num_obj = u32::try_from(objects.len()).context("too many objects for one pack")?;
+    w.write_all(&num_obj.to_be_bytes())?;
+
+    let mut pack_writer = PackWriter {
+        objects: &objects,
+        options,
+        offset: 12,
+        offsets: HashMap::new(),
+        index: Vec::with_capacity(objects.len()),
+    };
+    for i in 0..objects.len() {
+        pack_writer.write_entry(&mut w, i)?;
+    }
+    let index = pack_writer.index;
+
+    let checksum = w.hasher.finalize();
+    w.writer.write_all(checksum.as_bytes())?;
+
+    Ok((checksum, index))
+}
+
+/// Hash of the path used to sort objects so that files with the same name are next to each other.
+/// The last characters affect the hash the most, so files with the same suffix group together.
+fn name_hash(path: &str) -> u32 {
+    let mut hash = 0u32;
+    for c in path.bytes().filter(|c| !c.is_ascii_whitespace()) {
+        hash = (hash >> 2).wrapping_add((c as u32) << 24);
+    }
+    hash
+}
+
+/// Finds delta bases using a sliding window over objects sorted by type, name hash and size
+fn find_deltas(objects: &mut [PackObject], options: &PackOptions) {
+    if options.window == 0 || options.depth == 0 {
+        return;
+    }
+
+    let mut order: Vec<usize> = (0..objects.len()).collect();
+    order.sort_by(|&a, &b| {
+        let (a, b) = (&objects[a], &objects[b]);
+        type_order(&a.typ)
+            .cmp(&type_order(&b.typ))
+            .then(a.name_hash.cmp(&b.name_hash))
+            .then(b.data.len().cmp(&a.data.len()))
+    });
+
+    // deltas are computed first and assigned afterwards, as delta indexes borrow object data
+    let mut deltas: Vec<Option<(usize, Vec<u8>)>> = vec![None; objects.len()];
+    let mut depths = vec![0; objects.len()];
+    {
+        let mut window: VecDeque<(usize, DeltaIndex)> = VecDeque::with_capacity(options.window);
+
+        for &target in &order {
+            let object = &objects[target];
+            // the delta has to be smaller than half of the object to be worth it
+            let mut max_size =
+                (object.data.len() / 2).saturating_sub(object.hash.algorithm().size());
+            if object.data.len() >= MIN_DELTA_SIZE && max_size > 0 {
+                for (base, index) in window.iter().rev() {
+                    let base_object = &objects[*base];
+                    if base_object.typ != object.typ || depths[*base] >= options.depth {
+                        continue;
+                    }
+                    // objects of very different sizes are unlikely to produce small deltas
+                    if base_object.data.len() / 32 > object.data.len() {
+                        continue;
+                    }
+                    if let Some(delta) = index.create(&object.data, max_size) {
+                        max_size = delta.len().saturating_sub(1);
+                        depths[target] = depths[*base] + 1;
+                        deltas[target] = Some((*base, delta));
+                    }
+                }
+            }
+
+            if window.len() == options.window {
+                window.pop_front();
+            }
+            window.push_back((target, DeltaIndex::new(&objects[target].data)));
+        }
+    }
+
+    for (object, (delta, depth)) in objects.iter_mut().zip(deltas.into_iter().zip(depths)) {
+        object.delta = delta;
+        object.depth = depth;
+    }
+}
+
+fn type_order(typ: &ObjectType) -> u8 {
+    match typ {
+        ObjectType::Commit => 1,
+        ObjectType::Tree => 2,
+        ObjectType::Blob => 3,
+        ObjectType::Tag => 4,
+        ObjectType::OfsDelta => 6,
+        ObjectType::RefDelta => 7,
+    }
+}
+
+struct PackWriter<'a> {
+    objects: &'a [PackObject],
+    options: &'a PackOptions,
+    /// offset of the next written entry
+    offset: u64,
+    /// offsets of already written objects
+    offsets: HashMap<usize, u64>,
+    index: Vec<IndexEntry>,
+}
+
+impl PackWriter<'_> {
+    /// Writes object entry, writing its delta base first if it was not written yet
+    fn write_entry(&mut self, w: &mut impl Write, i: usize) -> anyhow::Result<()> {
+        if self.offsets.contains_key(&i) {
+            return Ok(());
+        }
+
+        let object = &self.objects[i];
+        let mut entry = Vec::new();
+        let data = match &object.delta {
+            Some((base, delta)) => {
+                self.write_entry(w, *base)?;
+                if self.options.ofs_delta {
+                    entry.extend(entry_header(&ObjectType::OfsDelta, delta.len()));
+                    entry.extend(encode_offset(self.offset - self.offsets[base]));
+                } else {
+                    entry.extend(entry_header(&ObjectType::RefDelta, delta.len()));
+                    entry.extend(self.objects[*base].hash.as_bytes());
+                }
+                delta
+            }
+            None => {
+                entry.extend(entry_header(&object.typ, object.data.len()));
+                &object.data
+            }
+        };
+
+        let mut encoder = ZlibEncoder::new(entry, Compression::default());
+        encoder.write_all(data)?;
+        let entry = encoder.finish()?;
+
+        w.write_all(&entry)?;
+
+        let mut crc = Crc::new();
+        crc.update(&entry);
+        self.index.push(IndexEntry {
+            hash: object.hash,
+            offset: self.offset,
+            crc32: crc.sum(),
+        });
+        self.offsets.insert(i, self.offset);
+        self.offset += entry.len() as u64;
+
+        Ok(())
+    }
+}
+
+/// Encodes relative offset of OFS_DELTA base, the inverse of decoding in `read_entry_header`
+fn encode_offset(mut distance: u64) -> Vec<u8> {
+    let mut bytes = vec![(distance & 0b0111_1111) as u8];
+    distance >>= 7;
+    while distance > 0 {
+        distance -= 1;
+        bytes.push(0b1000_0000 | (distance & 0b0111_1111) as u8);
+        distance >>= 7;
+    }
+    bytes.reverse();
+    bytes
+}
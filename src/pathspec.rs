@@ -0,0 +1,73 @@
+use std::path::Path;
+
+use crate::{repository::Repository, wildmatch::wildmatch};
+
+// https://git-scm.com/docs/gitglossary#Documentation/gitglossary.txt-aiddefpathspecapathspec
+
+/// Pattern selecting paths of the work tree
+#[derive(Debug)]
+pub struct Item {
+    /// Argument the item was given as
+    pub original: String,
+    /// Pattern relative to the top of the work tree, empty for the whole tree
+    pub path: Vec<u8>,
+    glob: bool,
+}
+
+impl Item {
+    /// Checks whether the path is the item itself or inside the directory it names,
+    /// or matches it as a glob (where `*` matches `/` too)
+    pub fn matches(&self, path: &[u8]) -> bool {
+        self.matches_exactly(path)
+            || self.path.is_empty()
+            || path
+                .strip_prefix(self.path.as_slice())
+                .is_some_and(|rest| rest.starts_with(b"/"))
+            || (self.glob && wildmatch(&self.path, path, false))
+    }
+
+    /// Checks whether the item names the path itself rather than a directory above it
+    pub fn matches_exactly(&self, path: &[u8]) -> bool {
+        self.path == path || (self.glob && wildmatch(&self.path, path, false))
+    }
+}
+
+/// Patterns selecting paths of the work tree, given relative to the current directory
+#[derive(Debug)]
+pub struct Pathspec {
+    pub items: Vec<Item>,
+}
+
+impl Pathspec {
+    pub fn new(repo: &Repository, args: &[impl AsRef<Path>]) -> anyhow::Result<Self> {
+        let items = args
+            .iter()
+            .map(|arg| {
+                let arg = arg.as_ref();
+                let path = repo.work_tree_path(arg)?;
+                Ok(Item {
+                    original: arg.display().to_string(),
+                    glob: path.iter().any(|b| b"*?[".contains(b)),
+                    path,
+                })
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self { items })
+    }
+
+    /// Pathspec matching the whole work tree
+    pub fn all() -> Self {
+        Self {
+            items: vec![Item {
+                original: ".".to_string(),
+                path: Vec::new(),
+                glob: false,
+            }],
+        }
+    }
+
+    /// Checks whether any of the items matches the path
+    pub fn matches(&self, path: &[u8]) -> bool {
+        self.items.iter().any(|item| item.matches(path))
+    }
+}
@@ -0,0 +1,136 @@
+use std::io::{Read, Write};
+
+use anyhow::Context;
+
+// https://github.com/git/git/blob/795ea8776befc95ea2becd8020c7a284677b4161/Documentation/gitprotocol-common.txt
+
+/// Maximum length of a pkt-line including the 4 bytes of the length prefix
+pub const MAX_PKT_LEN: usize = 65520;
+
+/// Maximum length of data sent over a single side-band-64k packet (minus length prefix and band byte)
+pub const MAX_SIDEBAND_DATA: usize = MAX_PKT_LEN - 5;
+
+/// Maximum length of data sent over a single side-band packet (minus length prefix and band byte)
+pub const MAX_SIDEBAND_SMALL_DATA: usize = 1000 - 5;
+
+#[derive(Debug, PartialEq)]
+pub enum Packet {
+    /// Regular pkt-line with payload
+    Data(Vec<u8>),
+    /// 0000
+    Flush,
+    /// 0001 (protocol v2 only)
+    Delim,
+    /// 0002 (protocol v2 only)
+    ResponseEnd,
+}
+
+impl Packet {
+    /// Returns payload as string with trailing LF stripped
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            Packet::Data(data) => {
+                let data = data.strip_suffix(b"\n").unwrap_or(data);
+                std::str::from_utf8(data).ok()
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Reads one pkt-line. Returns `None` when the stream is closed before the length prefix.
+pub fn read(r: &mut impl Read) -> anyhow::Result<Option<Packet>> {
+    let mut len = [0; 4];
+    match r.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err).context("reading pkt-line length"),
+    }
+
+    let len = std::str::from_utf8(&len)
+        .ok()
+        .and_then(|len| usize::from_str_radix(len, 16).ok())
+        .with_context(|| format!("invalid pkt-line length {len:?}"))?;
+
+    let packet = match len {
+        0 => Packet::Flush,
+        1 => Packet::Delim,
+        2 => Packet::ResponseEnd,
+        3 => anyhow::bail!("invalid pkt-line length 3"),
+        len => {
+            anyhow::ensure!(len <= MAX_PKT_LEN, "pkt-line too long: {len} bytes");
+            let mut data = vec![0; len - 4];
+            r.read_exact(&mut data).context("reading pkt-line data")?;
+            Packet::Data(data)
+        }
+    };
+
+    Ok(Some(packet))
+}
+
+/// Writes data as one pkt-line
+pub fn write(w: &mut impl Write, data: &[u8]) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        data.len() + 4 <= MAX_PKT_LEN,
+        "pkt-line data too long: {} bytes",
+        data.len()
+    );
+    write!(w, "{:04x}", data.len() + 4)?;
+    w.write_all(data)?;
+    Ok(())
+}
+
+/// Writes text as one pkt-line
+pub fn write_str(w: &mut impl Write, line: &str) -> anyhow::Result<()> {
+    write(w, line.as_bytes())
+}
+
+pub fn flush(w: &mut impl Write) -> anyhow::Result<()> {
+    w.write_all(b"0000")?;
+    w.flush()?;
+    Ok(())
+}
+
+pub fn delim(w: &mut impl Write) -> anyhow::Result<()> {
+    w.write_all(b"0001")?;
+    Ok(())
+}
+
+/// Band numbers used by side-band and side-band-64k capabilities
+#[derive(Clone, Copy)]
+pub enum Band {
+    Data = 1,
+    Progress = 2,
+    Error = 3,
+}
+
+/// Writer that multiplexes everything written into it into pkt-lines of one side-band channel
+pub struct SidebandWriter<W> {
+    writer: W,
+    band: Band,
+    max_data: usize,
+}
+
+impl<W: Write> SidebandWriter<W> {
+    pub fn new(writer: W, band: Band, max_data: usize) -> Self {
+        Self {
+            writer,
+            band,
+            max_data,
+        }
+    }
+}
+
+impl<W: Write> Write for SidebandWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = buf.len().min(self.max_data);
+        write!(self.writer, "{:04x}", n + 5)?;
+        self.writer.write_all(&[self.band as u8])?;
+        self.writer.write_all(&buf[..n])?;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
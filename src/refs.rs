@@ -0,0 +1,658 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+
+use crate::{
+    object::{ObjectId, Signature},
+    repository::SharedRepository,
+};
+
+// https://git-scm.com/book/en/v2/Git-Internals-Git-References
+
+/// Reference name with the object it points to
+#[derive(Debug, Clone)]
+pub struct Ref {
+    pub name: String,
+    pub hash: ObjectId,
+}
+
+/// Lists all references below `refs/` (loose and packed), sorted by name
+pub fn list(git_dir: &Path) -> anyhow::Result<Vec<Ref>> {
+    let mut loose = BTreeMap::new();
+    collect_loose_refs(git_dir, &git_dir.join("refs"), &mut loose)?;
+    let mut names: BTreeSet<String> = loose.into_keys().collect();
+    names.extend(read_packed(git_dir)?.into_iter().map(|packed| packed.name));
+
+    let mut resolved = Vec::with_capacity(names.len());
+    for name in &names {
+        // loose refs may be symbolic, follow them
+        if let Some(hash) = resolve(git_dir, name)? {
+            resolved.push(Ref {
+                name: name.clone(),
+                hash,
+            });
+        }
+    }
+
+    Ok(resolved)
+}
+
+/// Resolves reference name (ie. `HEAD` or `refs/heads/master`) to the object,
+/// following symbolic references. Returns `None` if the reference does not exist.
+pub fn resolve(git_dir: &Path, name: &str) -> anyhow::Result<Option<ObjectId>> {
+    let name = resolve_name(git_dir, name)?;
+    // loose refs take precedence over packed ones
+    match read_loose_ref(git_dir, &name)? {
+        Some(hash) => ObjectId::from_hex(&hash).map(Some),
+        None => Ok(read_packed(git_dir)?
+            .into_iter()
+            .find(|packed| packed.name == name)
+            .map(|packed| packed.hash)),
+    }
+}
+
+/// Follows symbolic references starting at `name` to the reference holding
+/// the object hash, which need not exist
+pub fn resolve_name(git_dir: &Path, name: &str) -> anyhow::Result<String> {
+    let mut name = name.to_string();
+
+    // git limits the depth of symbolic references to 5
+    for _ in 0..5 {
+        match read_symbolic(git_dir, &name)? {
+            Some(target) => name = target,
+            None => return Ok(name),
+        }
+    }
+
+    anyhow::bail!("symbolic reference {name} nested too deeply")
+}
+
+/// Returns the target of a symbolic reference, `None` if the reference is not symbolic
+/// or does not exist
+pub fn read_symbolic(git_dir: &Path, name: &str) -> anyhow::Result<Option<String>> {
+    Ok(read_loose_ref(git_dir, name)?
+        .and_then(|content| content.strip_prefix("ref: ").map(str::to_string)))
+}
+
+/// Points symbolic reference to the target reference, which need not exist
+pub fn write_symbolic(
+    git_dir: &Path,
+    shared: SharedRepository,
+    name: &str,
+    target: &str,
+) -> anyhow::Result<()> {
+    let path = git_dir.join(name);
+    let lock_path = lock_path(&path);
+    let mut lock = create_lock(shared, &lock_path)?;
+    let written = writeln!(lock, "ref: {target}").and_then(|_| fs::rename(&lock_path, &path));
+    if written.is_err() {
+        let _ = fs::remove_file(&lock_path);
+    }
+    written.with_context(|| format!("writing {}", path.display()))
+}
+
+/// Returns the name of the reference `HEAD` points to, or `None` if `HEAD` is detached
+pub fn head_target(git_dir: &Path) -> anyhow::Result<Option<String>> {
+    anyhow::ensure!(git_dir.join("HEAD").is_file(), "HEAD does not exist");
+    read_symbolic(git_dir, "HEAD")
+}
+
+fn read_loose_ref(git_dir: &Path, name: &str) -> anyhow::Result<Option<String>> {
+    let path = git_dir.join(name);
+    if !path.is_file() {
+        return Ok(None);
+    }
+
+    read_loose_file(&path).map(Some)
+}
+
+/// Reads loose reference file holding `ref: <target>` or an object name, which may be
+/// followed by whitespace and other content as in `FETCH_HEAD`
+fn read_loose_file(path: &Path) -> anyhow::Result<String> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("reading reference file {}", path.display()))?;
+    let content = content.trim_end();
+    if content.starts_with("ref: ") {
+        return Ok(content.to_string());
+    }
+
+    let hash = content
+        .split(char::is_whitespace)
+        .next()
+        .unwrap_or_default();
+    ObjectId::from_hex(hash).with_context(|| format!("bad reference file {}", path.display()))?;
+    Ok(hash.to_string())
+}
+
+fn collect_loose_refs(
+    git_dir: &Path,
+    dir: &Path,
+    refs: &mut BTreeMap<String, String>,
+) -> anyhow::Result<()> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err).with_context(|| format!("reading {}", dir.display())),
+    };
+
+    for entry in entries {
+        let entry = entry.with_context(|| format!("bad directory entry in {}", dir.display()))?;
+        let path = entry.path();
+
+        if path.is_dir() {
+            collect_loose_refs(git_dir, &path, refs)?;
+            continue;
+        }
+
+        let name = ref_name(git_dir, &path)?;
+        // skip lock files of ongoing updates
+        if name.ends_with(".lock") {
+            continue;
+        }
+
+        refs.insert(name, read_loose_file(&path)?);
+    }
+
+    Ok(())
+}
+
+fn ref_name(git_dir: &Path, path: &Path) -> anyhow::Result<String> {
+    let name = path
+        .strip_prefix(git_dir)
+        .context("reference outside of git directory")?;
+
+    let name = name
+        .to_str()
+        .with_context(|| format!("reference name {} is not valid UTF-8", name.display()))?;
+
+    Ok(name.to_string())
+}
+
+/// Deletes symbolic reference together with its reflog
+pub fn delete_symbolic(git_dir: &Path, name: &str) -> anyhow::Result<()> {
+    remove_loose(git_dir, name)
+}
+
+/// Entry of the `packed-refs` file
+#[derive(Debug, Clone)]
+pub struct PackedRef {
+    pub name: String,
+    pub hash: ObjectId,
+    /// Object an annotated tag points to after following chains of tags
+    pub peeled: Option<ObjectId>,
+}
+
+// https://git-scm.com/docs/git-pack-refs
+const PACKED_REFS_HEADER: &str = "# pack-refs with: peeled fully-peeled sorted \n";
+
+/// Reads `packed-refs` file, peeled (`^`) lines belong to the preceding reference
+pub fn read_packed(git_dir: &Path) -> anyhow::Result<Vec<PackedRef>> {
+    let path = PathBuf::from(git_dir).join("packed-refs");
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err).with_context(|| format!("reading {}", path.display())),
+    };
+
+    let mut refs: Vec<PackedRef> = Vec::new();
+    for line in content.lines() {
+        if line.starts_with('#') {
+            continue;
+        }
+        if let Some(peeled) = line.strip_prefix('^') {
+            let packed = refs
+                .last_mut()
+                .with_context(|| format!("peeled line without reference in packed-refs: {line}"))?;
+            packed.peeled = Some(
+                ObjectId::from_hex(peeled)
+                    .with_context(|| format!("malformed line in packed-refs: {line}"))?,
+            );
+            continue;
+        }
+        let (hash, name) = line
+            .split_once(' ')
+            .with_context(|| format!("malformed line in packed-refs: {line}"))?;
+        let hash = ObjectId::from_hex(hash)
+            .with_context(|| format!("malformed line in packed-refs: {line}"))?;
+        refs.push(PackedRef {
+            name: name.to_string(),
+            hash,
+            peeled: None,
+        });
+    }
+
+    Ok(refs)
+}
+
+/// Replaces `packed-refs` file with the references, sorted by name
+pub fn write_packed(
+    git_dir: &Path,
+    shared: SharedRepository,
+    refs: &[PackedRef],
+) -> anyhow::Result<()> {
+    let mut refs: Vec<&PackedRef> = refs.iter().collect();
+    refs.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut content = PACKED_REFS_HEADER.to_string();
+    for packed in refs {
+        content.push_str(&format!("{} {}\n", packed.hash, packed.name));
+        if let Some(peeled) = &packed.peeled {
+            content.push_str(&format!("^{peeled}\n"));
+        }
+    }
+
+    let path = git_dir.join("packed-refs");
+    let lock_path = lock_path(&path);
+    create_lock(shared, &lock_path)?;
+    let written = fs::write(&lock_path, content).and_then(|_| fs::rename(&lock_path, &path));
+    if written.is_err() {
+        let _ = fs::remove_file(&lock_path);
+    }
+    written.with_context(|| format!("writing {}", path.display()))
+}
+
+/// Moves loose tags and references that are already packed to `packed-refs`,
+/// or all loose references if `all` is set.
+/// Symbolic references stay loose. Loose files of the moved references are removed
+/// unless `prune` is false. `peel` gives the object a tag points to.
+pub fn pack(
+    git_dir: &Path,
+    shared: SharedRepository,
+    all: bool,
+    prune: bool,
+    peel: impl Fn(&ObjectId) -> anyhow::Result<Option<ObjectId>>,
+) -> anyhow::Result<()> {
+    let mut packed: BTreeMap<String, PackedRef> = read_packed(git_dir)?
+        .into_iter()
+        .map(|packed| (packed.name.clone(), packed))
+        .collect();
+
+    let mut loose = BTreeMap::new();
+    collect_loose_refs(git_dir, &git_dir.join("refs"), &mut loose)?;
+    let mut pruned = Vec::new();
+    for (name, content) in loose {
+        let moved = all || name.starts_with("refs/tags/") || packed.contains_key(&name);
+        if content.starts_with("ref: ") || !moved {
+            continue;
+        }
+        let hash = ObjectId::from_hex(&content)?;
+        let peeled = peel(&hash)?;
+        packed.insert(
+            name.clone(),
+            PackedRef {
+                name: name.clone(),
+                hash,
+                peeled,
+            },
+        );
+        pruned.push(name);
+    }
+
+    // peeled values of references packed before are refreshed too
+    let mut refs = Vec::with_capacity(packed.len());
+    for (_, mut packed) in packed {
+        if packed.peeled.is_none() {
+            packed.peeled = peel(&packed.hash)?;
+        }
+        refs.push(packed);
+    }
+    write_packed(git_dir, shared, &refs)?;
+
+    if prune {
+        // reflogs stay, the references still exist
+        for name in pruned {
+            remove_with_parents(&git_dir.join(name), &git_dir.join("refs"))?;
+        }
+    }
+    Ok(())
+}
+
+/// Removes loose reference file with its reflog and parent directories left empty
+/// below `refs/<kind>/`
+fn remove_loose(git_dir: &Path, name: &str) -> anyhow::Result<()> {
+    remove_with_parents(&git_dir.join(name), &git_dir.join("refs"))?;
+    let logs = git_dir.join("logs");
+    remove_with_parents(&logs.join(name), &logs.join("refs"))
+}
+
+/// Removes the file and its parent directories left empty, except `top` and its children
+fn remove_with_parents(path: &Path, top: &Path) -> anyhow::Result<()> {
+    match fs::remove_file(path) {
+        Ok(()) => {}
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+        Err(err) => return Err(err).with_context(|| format!("deleting {}", path.display())),
+    }
+
+    let mut dir = path.parent();
+    while let Some(parent) =
+        dir.filter(|dir| dir.starts_with(top) && *dir != top && dir.parent() != Some(top))
+    {
+        if fs::remove_dir(parent).is_err() {
+            break;
+        }
+        dir = parent.parent();
+    }
+    Ok(())
+}
+
+/// Relaxations of the reference name rules
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NameOptions {
+    /// Allow names without `/`, ie. `HEAD`
+    pub allow_onelevel: bool,
+    /// Allow a single `*` as in refspecs, ie. `refs/heads/*`
+    pub refspec_pattern: bool,
+}
+
+/// Checks reference name according to `git check-ref-format` rules
+pub fn is_valid_name(name: &str) -> bool {
+    check_name(name, NameOptions::default())
+}
+
+// https://git-scm.com/docs/git-check-ref-format
+/// Checks reference name according to `git check-ref-format` rules relaxed by the options
+pub fn check_name(name: &str, options: NameOptions) -> bool {
+    if name.is_empty()
+        || name == "@"
+        || name.starts_with('/')
+        || name.ends_with('/')
+        || name.ends_with('.')
+        || name.contains("//")
+        || name.contains("..")
+        || name.contains("@{")
+    {
+        return false;
+    }
+
+    if name
+        .chars()
+        .any(|c| c.is_ascii_control() || " ~^:?[\\".contains(c))
+    {
+        return false;
+    }
+    let stars = name.matches('*').count();
+    if stars > usize::from(options.refspec_pattern) {
+        return false;
+    }
+
+    name.split('/')
+        .all(|component| !component.starts_with('.') && !component.ends_with(".lock"))
+        && (options.allow_onelevel || name.contains('/'))
+}
+
+/// Removes the leading slash and repeated slashes from the name
+pub fn normalize_name(name: &str) -> String {
+    let mut normalized = String::new();
+    for c in name.trim_start_matches('/').chars() {
+        if c == '/' && normalized.ends_with('/') {
+            continue;
+        }
+        normalized.push(c);
+    }
+    normalized
+}
+
+/// Names outside of `refs/` that may be updated, ie. `HEAD` or `ORIG_HEAD`
+pub fn is_pseudo_ref(name: &str) -> bool {
+    !name.is_empty() && name.bytes().all(|b| b.is_ascii_uppercase() || b == b'_')
+}
+
+/// Entry of a reflog recording one update of the reference
+pub struct ReflogEntry<'a> {
+    pub old: ObjectId,
+    pub new: ObjectId,
+    pub identity: &'a Signature,
+    pub message: &'a str,
+}
+
+/// Appends entry to the reflog of the reference, the log is created only with `create`:
+/// `<old> <new> <identity> <timestamp> <tz>\t<message>`
+// https://git-scm.com/docs/git-reflog
+pub fn append_reflog(
+    git_dir: &Path,
+    shared: SharedRepository,
+    name: &str,
+    entry: &ReflogEntry,
+    create: bool,
+) -> anyhow::Result<()> {
+    let path = git_dir.join("logs").join(name);
+    let exists = path.exists();
+    if !create && !exists {
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        shared.create_dir_all(parent)?;
+    }
+
+    let message = entry.message.trim_end().replace('\n', " ");
+    let mut log = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("opening {}", path.display()))?;
+    if !exists {
+        shared.adjust(&path)?;
+    }
+    // entries without message have no tab
+    let separator = if message.is_empty() { "" } else { "\t" };
+    let ReflogEntry {
+        old, new, identity, ..
+    } = entry;
+    writeln!(log, "{old} {new} {identity}{separator}{message}")
+        .with_context(|| format!("writing {}", path.display()))
+}
+
+/// Expected current value of a reference verified before it is updated
+#[derive(Debug, Clone)]
+pub enum Expected {
+    /// The current value is not checked
+    Any,
+    /// The reference must not exist
+    Missing,
+    /// The reference must point to the object
+    Value(ObjectId),
+}
+
+#[derive(Debug, Clone)]
+pub struct RefUpdate {
+    pub name: String,
+    pub expected: Expected,
+    /// New value, `None` deletes the reference
+    pub new: Option<ObjectId>,
+}
+
+/// Set of reference updates that are applied together. Every reference is locked
+/// by creating `<ref>.lock` file when it is added to the transaction.
+/// Locks that were not committed are removed when the transaction is dropped.
+pub struct Transaction<'a> {
+    git_dir: &'a Path,
+    shared: SharedRepository,
+    locked: Vec<Lock>,
+}
+
+struct Lock {
+    update: RefUpdate,
+    path: PathBuf,
+    /// The reference is only verified, not changed
+    verify_only: bool,
+}
+
+impl<'a> Transaction<'a> {
+    /// Starts transaction creating lock files with the permissions of `shared`
+    pub fn new(git_dir: &'a Path, shared: SharedRepository) -> Self {
+        Self {
+            git_dir,
+            shared,
+            locked: Vec::new(),
+        }
+    }
+
+    /// Locks the reference, verifies its current value and prepares the new value
+    pub fn add(&mut self, update: RefUpdate) -> anyhow::Result<()> {
+        self.lock(update, false)
+    }
+
+    /// Locks the reference so that it keeps the expected value until the transaction
+    /// is committed
+    pub fn verify(&mut self, name: &str, expected: Expected) -> anyhow::Result<()> {
+        let update = RefUpdate {
+            name: name.to_string(),
+            expected,
+            new: None,
+        };
+        self.lock(update, true)
+    }
+
+    fn lock(&mut self, update: RefUpdate, verify_only: bool) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            is_valid_name(&update.name) || is_pseudo_ref(&update.name),
+            "refusing to update ref with bad name '{}'",
+            update.name
+        );
+        anyhow::ensure!(
+            !self
+                .locked
+                .iter()
+                .any(|lock| lock.update.name == update.name),
+            "multiple updates for ref '{}' not allowed",
+            update.name
+        );
+
+        let path = self.git_dir.join(&update.name);
+        let lock_path = lock_path(&path);
+        let mut lock = create_lock(self.shared, &lock_path)?;
+        self.locked.push(Lock {
+            update: update.clone(),
+            path: lock_path,
+            verify_only,
+        });
+
+        let current = resolve(self.git_dir, &update.name)?;
+        let name = &update.name;
+        match (&update.expected, current) {
+            (Expected::Any, _) | (Expected::Missing, None) => {}
+            (Expected::Value(expected), Some(current)) if *expected == current => {}
+            (Expected::Missing, Some(_)) => {
+                anyhow::bail!("cannot lock ref '{name}': reference already exists")
+            }
+            (Expected::Value(_), None) => {
+                anyhow::bail!("cannot lock ref '{name}': unable to resolve reference '{name}'")
+            }
+            (Expected::Value(expected), Some(current)) => {
+                anyhow::bail!("cannot lock ref '{name}': is at {current} but expected {expected}")
+            }
+        }
+
+        if let (Some(new), false) = (&update.new, verify_only) {
+            writeln!(lock, "{new}").with_context(|| format!("writing {}", path.display()))?;
+        }
+
+        Ok(())
+    }
+
+    /// Applies all updates and releases the locks
+    pub fn commit(mut self) -> anyhow::Result<()> {
+        let deleted: Vec<_> = self
+            .locked
+            .iter()
+            .filter(|lock| lock.update.new.is_none() && !lock.verify_only)
+            .map(|lock| lock.update.name.clone())
+            .collect();
+        if !deleted.is_empty() {
+            remove_packed_refs(self.git_dir, self.shared, &deleted)?;
+        }
+
+        for lock in std::mem::take(&mut self.locked) {
+            let name = &lock.update.name;
+            if lock.update.new.is_some() && !lock.verify_only {
+                let path = self.git_dir.join(name);
+                fs::rename(&lock.path, &path)
+                    .with_context(|| format!("moving lock file to {}", path.display()))?;
+                continue;
+            }
+            fs::remove_file(&lock.path)
+                .with_context(|| format!("removing {}", lock.path.display()))?;
+            if !lock.verify_only {
+                remove_loose(self.git_dir, name)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for Transaction<'_> {
+    fn drop(&mut self) {
+        for lock in &self.locked {
+            let _ = fs::remove_file(&lock.path);
+        }
+    }
+}
+
+fn lock_path(path: &Path) -> PathBuf {
+    let mut lock_path = path.as_os_str().to_os_string();
+    lock_path.push(".lock");
+    PathBuf::from(lock_path)
+}
+
+/// Creates the lock file and its missing parent directories with permissions given
+/// by `core.sharedRepository`
+fn create_lock(shared: SharedRepository, lock_path: &Path) -> anyhow::Result<fs::File> {
+    if let Some(parent) = lock_path.parent() {
+        shared.create_dir_all(parent)?;
+    }
+    let lock = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(lock_path)
+        .with_context(|| format!("unable to create '{}'", lock_path.display()))?;
+    if let Err(err) = shared.adjust(lock_path) {
+        let _ = fs::remove_file(lock_path);
+        return Err(err);
+    }
+    Ok(lock)
+}
+
+/// Rewrites `packed-refs` file without given references
+fn remove_packed_refs(
+    git_dir: &Path,
+    shared: SharedRepository,
+    names: &[String],
+) -> anyhow::Result<()> {
+    let path = git_dir.join("packed-refs");
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err).with_context(|| format!("reading {}", path.display())),
+    };
+
+    let mut new_content = String::new();
+    let mut skip_peeled = false;
+    for line in content.lines() {
+        if line.starts_with('^') && skip_peeled {
+            continue;
+        }
+        skip_peeled = line
+            .split_once(' ')
+            .is_some_and(|(_, name)| !line.starts_with('#') && names.iter().any(|n| n == name));
+        if !skip_peeled {
+            new_content.push_str(line);
+            new_content.push('\n');
+        }
+    }
+
+    if new_content == content {
+        return Ok(());
+    }
+
+    let lock_path = lock_path(&path);
+    create_lock(shared, &lock_path)?;
+    fs::write(&lock_path, new_content)
+        .with_context(|| format!("writing {}", lock_path.display()))?;
+    fs::rename(&lock_path, &path).with_context(|| format!("moving lock file to {}", path.display()))
+}
@@ -0,0 +1,572 @@
+use std::{
+    fs,
+    io::Cursor,
+    os::unix::ffi::OsStrExt,
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::Context;
+
+use crate::{
+    config, date,
+    object::{
+        tag, Commit, HashAlgorithm, Header, ObjectFile, ObjectId, ObjectType, Signature, Tag, Tree,
+        TreeEntry,
+    },
+    odb::{alternates, Combined, Loose, Object, ObjectDatabase, Packed},
+    refs::{self, Expected, Ref, RefUpdate, ReflogEntry, Transaction},
+    rev_parse,
+};
+
+mod discover;
+mod init;
+
+pub use discover::DiscoverOptions;
+pub use init::{InitOptions, SharedRepository};
+
+/// Handle of a repository giving access to its objects and references
+pub struct Repository {
+    git_dir: PathBuf,
+    work_tree: Option<PathBuf>,
+    objects_dir: PathBuf,
+    alternates: Vec<PathBuf>,
+    index_file: PathBuf,
+    odb: Box<dyn ObjectDatabase>,
+    /// Permissions of files created in the git directory
+    shared: SharedRepository,
+    problems: Vec<String>,
+}
+
+impl Repository {
+    /// Opens repository at `path`, which is either a work tree containing `.git`
+    /// (directory or `gitdir:` file) or the git directory itself (ie. a bare repository)
+    pub fn open(path: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        let path = path.into();
+        let (git_dir, work_tree) = discover::find_in(&path, None)?
+            .with_context(|| format!("not a git repository: {}", path.display()))?;
+        Self::open_git_dir(git_dir, work_tree, &DiscoverOptions::default())
+    }
+
+    /// Finds repository containing `start` by looking into it and its parent directories
+    pub fn discover(start: impl AsRef<Path>) -> anyhow::Result<Self> {
+        Self::discover_with(start, &DiscoverOptions::default())
+    }
+
+    /// Finds repository containing `start` unless the git directory is given in options
+    pub fn discover_with(
+        start: impl AsRef<Path>,
+        options: &DiscoverOptions,
+    ) -> anyhow::Result<Self> {
+        let start = start.as_ref();
+        let (git_dir, work_tree) = match &options.git_dir {
+            Some(git_dir) => {
+                anyhow::ensure!(
+                    discover::is_git_dir(git_dir, options.object_dir.as_deref()),
+                    "not a git repository: '{}'",
+                    git_dir.display()
+                );
+                // the current directory is the top of the work tree unless told otherwise
+                let bare = config::get_bool(git_dir, "core.bare")?.unwrap_or(false);
+                let work_tree = match &options.work_tree {
+                    Some(work_tree) => Some(work_tree.clone()),
+                    None if bare => None,
+                    None => Some(start.to_path_buf()),
+                };
+                (git_dir.clone(), work_tree)
+            }
+            None => {
+                let (git_dir, work_tree) = discover::find(start, options)?;
+                (git_dir, options.work_tree.clone().or(work_tree))
+            }
+        };
+
+        Self::open_git_dir(git_dir, work_tree, options)
+    }
+
+    /// Opens repository the way git commands do: discovered from the current directory,
+    /// honoring `GIT_DIR` and related environment variables
+    pub fn from_env() -> anyhow::Result<Self> {
+        Self::discover_with(".", &DiscoverOptions::from_env())
+    }
+
+    /// Creates empty repository with work tree at `path`, the directory is created if needed
+    pub fn init(path: impl Into<PathBuf>, algorithm: HashAlgorithm) -> anyhow::Result<Self> {
+        let options = InitOptions {
+            algorithm: Some(algorithm),
+            ..Default::default()
+        };
+        Ok(Self::init_with(path, &options)?.0)
+    }
+
+    /// Creates repository at `path` (the git directory itself if bare), or reinitializes
+    /// the existing one by adding what is missing. Returns whether it existed.
+    pub fn init_with(
+        path: impl Into<PathBuf>,
+        options: &InitOptions,
+    ) -> anyhow::Result<(Self, bool)> {
+        let initialized = init::init(&path.into(), options)?;
+        let repo = Self::open_git_dir(
+            initialized.git_dir,
+            initialized.work_tree,
+            &DiscoverOptions::default(),
+        )?;
+        Ok((repo, initialized.reinitialized))
+    }
+
+    /// Opens repository in the git directory storing objects loose and in packs
+    /// of its objects directory, objects of alternate object directories are read too
+    fn open_git_dir(
+        git_dir: PathBuf,
+        work_tree: Option<PathBuf>,
+        options: &DiscoverOptions,
+    ) -> anyhow::Result<Self> {
+        let algorithm = HashAlgorithm::of_repository(&git_dir)
+            .with_context(|| format!("reading object format of {}", git_dir.display()))?;
+
+        let objects_dir = options
+            .object_dir
+            .clone()
+            .unwrap_or_else(|| git_dir.join("objects"));
+        let index_file = options
+            .index_file
+            .clone()
+            .unwrap_or_else(|| git_dir.join("index"));
+        let mut stores: Vec<Box<dyn ObjectDatabase>> = vec![
+            Box::new(Loose::configured(&git_dir, &objects_dir, algorithm)?),
+            Box::new(Packed::new(&objects_dir, algorithm)),
+        ];
+        let (alternates, problems) =
+            alternates::resolve(&objects_dir, &options.alternate_object_dirs)?;
+        for dir in &alternates {
+            stores.push(Box::new(Loose::new(dir, algorithm)));
+            stores.push(Box::new(Packed::new(dir, algorithm)));
+        }
+        let odb = Combined::new(stores);
+        let shared = SharedRepository::configured(&git_dir)?;
+
+        Ok(Self {
+            git_dir,
+            work_tree,
+            objects_dir,
+            alternates,
+            index_file,
+            odb: Box::new(odb),
+            shared,
+            problems,
+        })
+    }
+
+    /// Creates handle of repository without work tree with custom object store
+    pub fn with_odb(git_dir: impl Into<PathBuf>, odb: Box<dyn ObjectDatabase>) -> Self {
+        let git_dir = git_dir.into();
+        Self {
+            objects_dir: git_dir.join("objects"),
+            alternates: Vec::new(),
+            index_file: git_dir.join("index"),
+            git_dir,
+            work_tree: None,
+            odb,
+            shared: SharedRepository::Umask,
+            problems: Vec::new(),
+        }
+    }
+
+    /// Value of `core.sharedRepository`, applied to files created in the git directory
+    pub fn shared(&self) -> SharedRepository {
+        self.shared
+    }
+
+    pub fn git_dir(&self) -> &Path {
+        &self.git_dir
+    }
+
+    /// Directory with checked out files, `None` for bare repositories
+    pub fn work_tree(&self) -> Option<&Path> {
+        self.work_tree.as_deref()
+    }
+
+    /// Directory of loose objects and packs
+    pub fn objects_dir(&self) -> PathBuf {
+        self.objects_dir.clone()
+    }
+
+    /// Object directories of other repositories objects are borrowed from
+    pub fn alternates(&self) -> &[PathBuf] {
+        &self.alternates
+    }
+
+    /// Problems found while opening the repository that did not prevent it,
+    /// like alternate object directories that do not exist
+    pub fn problems(&self) -> &[String] {
+        &self.problems
+    }
+
+    /// Path of the index file (staging area)
+    pub fn index_file(&self) -> &Path {
+        &self.index_file
+    }
+
+    pub fn algorithm(&self) -> HashAlgorithm {
+        self.odb.algorithm()
+    }
+
+    pub fn odb(&self) -> &dyn ObjectDatabase {
+        self.odb.as_ref()
+    }
+
+    /// Opens stream with content of the object, fails if it does not exist
+    pub fn read_object(&self, id: &ObjectId) -> anyhow::Result<Object<'_>> {
+        self.odb
+            .read(id)?
+            .with_context(|| format!("object {id} not found"))
+    }
+
+    pub fn read_commit(&self, id: &ObjectId) -> anyhow::Result<Commit> {
+        Commit::read(id, self.odb())
+    }
+
+    pub fn read_tree(&self, id: &ObjectId) -> anyhow::Result<Tree> {
+        Tree::read(id, self.odb())
+    }
+
+    pub fn read_tag(&self, id: &ObjectId) -> anyhow::Result<Tag> {
+        Tag::read(id, self.odb())
+    }
+
+    /// Stores object with the content. Returns name of the object.
+    pub fn write_object(&self, typ: ObjectType, data: &[u8]) -> anyhow::Result<ObjectId> {
+        let header = Header {
+            typ,
+            size: data.len(),
+        };
+        self.odb.write(&header, &mut Cursor::new(data))
+    }
+
+    /// Computes name of a blob with content of the work tree file, or the target
+    /// of a symbolic link, and optionally stores it
+    pub fn hash_file(&self, path: &Path, write: bool) -> anyhow::Result<ObjectId> {
+        let metadata =
+            fs::symlink_metadata(path).with_context(|| format!("stat file {}", path.display()))?;
+        if metadata.is_symlink() {
+            let target =
+                fs::read_link(path).with_context(|| format!("reading link {}", path.display()))?;
+            let data = target.as_os_str().as_bytes();
+            if write {
+                return self.write_object(ObjectType::Blob, data);
+            }
+            let header = Header {
+                typ: ObjectType::Blob,
+                size: data.len(),
+            };
+            return ObjectFile {
+                header,
+                reader: data,
+            }
+            .hash(self.algorithm());
+        }
+
+        let mut object = ObjectFile::from_file(path)?;
+        if write {
+            object.write(self.odb())
+        } else {
+            object.hash(self.algorithm())
+        }
+    }
+
+    /// Converts path relative to the current directory into path relative to the top
+    /// of the work tree, as recorded in the index
+    pub fn work_tree_path(&self, path: impl AsRef<Path>) -> anyhow::Result<Vec<u8>> {
+        let path = path.as_ref();
+        let work_tree = self
+            .work_tree()
+            .context("this operation must be run in a work tree")?;
+        let work_tree = work_tree
+            .canonicalize()
+            .with_context(|| format!("resolving {}", work_tree.display()))?;
+
+        // .. is resolved lexically, so that paths of missing files can be converted too
+        let mut absolute = std::env::current_dir().context("getting current directory")?;
+        for component in path.components() {
+            match component {
+                Component::ParentDir => {
+                    absolute.pop();
+                }
+                Component::CurDir => {}
+                component => absolute.push(component),
+            }
+        }
+
+        let relative = absolute.strip_prefix(&work_tree).with_context(|| {
+            format!(
+                "'{}' is outside repository at '{}'",
+                path.display(),
+                work_tree.display()
+            )
+        })?;
+        Ok(relative.as_os_str().as_bytes().to_vec())
+    }
+
+    /// Lists all references below `refs/`, sorted by name
+    pub fn references(&self) -> anyhow::Result<Vec<Ref>> {
+        refs::list(&self.git_dir)
+    }
+
+    /// Resolves reference name (ie. `HEAD` or `refs/heads/master`) to the object it points to,
+    /// following symbolic references. Returns `None` if the reference does not exist.
+    pub fn resolve_ref(&self, name: &str) -> anyhow::Result<Option<ObjectId>> {
+        refs::resolve(&self.git_dir, name)
+    }
+
+    /// Returns commit checked out in `HEAD`, `None` on unborn branch
+    pub fn head(&self) -> anyhow::Result<Option<ObjectId>> {
+        self.resolve_ref("HEAD")
+    }
+
+    /// Points the reference, or the reference it refers to when symbolic, to the object
+    /// if its current value is as expected and records the update in reflogs
+    pub fn update_ref(
+        &self,
+        name: &str,
+        new: &ObjectId,
+        expected: Expected,
+        message: &str,
+    ) -> anyhow::Result<()> {
+        let name = refs::resolve_name(&self.git_dir, name)?;
+        // the branch checked out is updated through HEAD too
+        let mut logged = vec![name.clone()];
+        if name != "HEAD" && refs::head_target(&self.git_dir)?.as_deref() == Some(name.as_str()) {
+            logged.push("HEAD".to_string());
+        }
+        let mut logs_update = false;
+        for name in &logged {
+            logs_update |=
+                self.creates_reflog(name)? || self.git_dir.join("logs").join(name).exists();
+        }
+        // identity is needed only when there is a reflog to write
+        let committer = match logs_update {
+            true => Some(self.committer()?),
+            false => None,
+        };
+
+        let old = self.resolve_ref(&name)?;
+        let mut transaction = Transaction::new(&self.git_dir, self.shared);
+        transaction.add(RefUpdate {
+            name,
+            expected,
+            new: Some(*new),
+        })?;
+        transaction.commit()?;
+
+        if let Some(committer) = committer {
+            for name in logged {
+                self.log_ref_update(&name, old.as_ref(), new, &committer, message)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Moves tags and already packed references, or all references with `all`, to
+    /// the `packed-refs` file together with the objects annotated tags point to
+    pub fn pack_refs(&self, all: bool, prune: bool) -> anyhow::Result<()> {
+        refs::pack(&self.git_dir, self.shared, all, prune, |id| {
+            self.peel_tag(id)
+        })
+    }
+
+    /// Object the annotated tag points to after following chains of tags,
+    /// `None` for other objects
+    pub fn peel_tag(&self, id: &ObjectId) -> anyhow::Result<Option<ObjectId>> {
+        let (peeled, _) = tag::peel(id, self.odb())?;
+        Ok((peeled != *id).then_some(peeled))
+    }
+
+    /// Records update of the reference in its reflog. Logs are created for `HEAD`, branches
+    /// and remote-tracking references of repositories with a work tree, unless configured
+    /// otherwise by `core.logAllRefUpdates`.
+    pub fn log_ref_update(
+        &self,
+        name: &str,
+        old: Option<&ObjectId>,
+        new: &ObjectId,
+        identity: &Signature,
+        message: &str,
+    ) -> anyhow::Result<()> {
+        let create = self.creates_reflog(name)?;
+        let old = old.copied().unwrap_or_else(|| self.algorithm().null());
+        refs::append_reflog(
+            &self.git_dir,
+            self.shared,
+            name,
+            &ReflogEntry {
+                old,
+                new: *new,
+                identity,
+                message,
+            },
+            create,
+        )
+    }
+
+    /// Whether updates of the reference create its reflog when it does not exist
+    fn creates_reflog(&self, name: &str) -> anyhow::Result<bool> {
+        let logged = name == "HEAD"
+            || ["refs/heads/", "refs/remotes/", "refs/notes/"]
+                .iter()
+                .any(|prefix| name.starts_with(prefix));
+        Ok(
+            match config::get(&self.git_dir, "core.logAllRefUpdates")?.as_deref() {
+                Some("always") => true,
+                Some("true" | "yes" | "on" | "1") => logged,
+                Some(_) => false,
+                None => logged && self.work_tree.is_some(),
+            },
+        )
+    }
+
+    /// Identity recorded as author of new commits, from `GIT_AUTHOR_NAME`, `GIT_AUTHOR_EMAIL`
+    /// and `GIT_AUTHOR_DATE` or the `author.*` and `user.*` config
+    pub fn author(&self) -> anyhow::Result<Signature> {
+        self.identity("author")
+    }
+
+    /// Identity recorded as committer of new commits and in reflogs, from `GIT_COMMITTER_*`
+    /// variables or the `committer.*` and `user.*` config
+    pub fn committer(&self) -> anyhow::Result<Signature> {
+        self.identity("committer")
+    }
+
+    // https://git-scm.com/docs/git-commit#_commit_information
+    fn identity(&self, role: &str) -> anyhow::Result<Signature> {
+        let var = |field: &str| std::env::var(format!("GIT_{}_{field}", role.to_uppercase())).ok();
+        let config = |field: &str| -> anyhow::Result<Option<String>> {
+            Ok(
+                match config::get(&self.git_dir, &format!("{role}.{field}"))? {
+                    Some(value) => Some(value),
+                    None => config::get(&self.git_dir, &format!("user.{field}"))?,
+                },
+            )
+        };
+
+        let name = match var("NAME") {
+            Some(name) => Some(name),
+            None => config("name")?,
+        };
+        let email = match var("EMAIL") {
+            Some(email) => Some(email),
+            None => config("email")?.or_else(|| std::env::var("EMAIL").ok()),
+        };
+        let (Some(name), Some(email)) = (name, email) else {
+            let mut role = role.to_string();
+            role[..1].make_ascii_uppercase();
+            anyhow::bail!(
+                "{role} identity unknown\n\n\
+                 *** Please tell me who you are.\n\n\
+                 Run\n\n  \
+                 git config --global user.email \"you@example.com\"\n  \
+                 git config --global user.name \"Your Name\"\n\n\
+                 to set your account's default identity.\n\
+                 Omit --global to set the identity only in this repository.\n"
+            );
+        };
+        let (name, email) = (strip_crud(&name), strip_crud(&email));
+        anyhow::ensure!(
+            !name.is_empty(),
+            "empty ident name (for <{email}>) not allowed"
+        );
+
+        let (time, timezone) = match var("DATE") {
+            Some(value) => date::parse(&value)?,
+            None => date::now()?,
+        };
+        Ok(Signature {
+            name,
+            email,
+            time,
+            timezone,
+        })
+    }
+
+    /// Resolves revision expression (ie. `HEAD~2`, `v1.0^{tree}` or `main:src/main.rs`)
+    pub fn rev_parse(&self, rev: &str) -> anyhow::Result<ObjectId> {
+        rev_parse::resolve(self, rev)
+    }
+
+    /// Lists entries of the tree and all its subtrees (except the subtrees themselves)
+    /// with paths relative to the tree, in tree order
+    pub fn walk_tree(&self, id: &ObjectId) -> anyhow::Result<Vec<(Vec<u8>, TreeEntry)>> {
+        let mut entries = Vec::new();
+        self.walk_subtree(id, &[], &mut entries)?;
+        Ok(entries)
+    }
+
+    fn walk_subtree(
+        &self,
+        id: &ObjectId,
+        prefix: &[u8],
+        entries: &mut Vec<(Vec<u8>, TreeEntry)>,
+    ) -> anyhow::Result<()> {
+        for entry in self.read_tree(id)?.entries {
+            let mut path = prefix.to_vec();
+            if !path.is_empty() {
+                path.push(b'/');
+            }
+            path.extend_from_slice(&entry.name);
+
+            if entry.is_tree() {
+                self.walk_subtree(&entry.oid, &path, entries)?;
+            } else {
+                entries.push((path, entry));
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes commit of the tree with given parents. References are not updated.
+    pub fn create_commit(
+        &self,
+        tree: &ObjectId,
+        parents: &[ObjectId],
+        author: &Signature,
+        committer: &Signature,
+        message: &str,
+    ) -> anyhow::Result<ObjectId> {
+        self.write_commit(&Commit {
+            tree: *tree,
+            parents: parents.to_vec(),
+            author: author.clone(),
+            committer: committer.clone(),
+            extra_headers: Vec::new(),
+            message: message.as_bytes().to_vec(),
+        })
+    }
+
+    /// Writes the commit after checking that its tree and parents exist with the right types
+    pub fn write_commit(&self, commit: &Commit) -> anyhow::Result<ObjectId> {
+        let header = self.read_object(&commit.tree)?.header;
+        anyhow::ensure!(
+            header.typ == ObjectType::Tree,
+            "{} is not a valid 'tree' object",
+            commit.tree
+        );
+        for parent in &commit.parents {
+            let header = self.read_object(parent)?.header;
+            anyhow::ensure!(
+                header.typ == ObjectType::Commit,
+                "{parent} is not a valid 'commit' object"
+            );
+        }
+
+        self.write_object(ObjectType::Commit, &commit.serialize())
+    }
+}
+
+/// Removes characters that would break the signature and surrounding punctuation and
+/// whitespace from the name or email
+fn strip_crud(value: &str) -> String {
+    let crud = |c: char| c <= ' ' || ".,:;<>\"\\'".contains(c);
+    value
+        .trim_matches(crud)
+        .chars()
+        .filter(|&c| !matches!(c, '<' | '>' | '\n'))
+        .collect()
+}
@@ -0,0 +1,168 @@
+use std::{
+    ffi::OsStr,
+    fs,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+
+// https://git-scm.com/docs/git#_the_git_repository
+
+/// Settings of repository discovery and locations overriding the defaults derived
+/// from the git directory
+#[derive(Debug, Clone, Default)]
+pub struct DiscoverOptions {
+    /// Use this git directory instead of searching for one
+    pub git_dir: Option<PathBuf>,
+    pub work_tree: Option<PathBuf>,
+    pub object_dir: Option<PathBuf>,
+    pub index_file: Option<PathBuf>,
+    /// Object directories to borrow objects from in addition to `info/alternates`
+    pub alternate_object_dirs: Vec<PathBuf>,
+    /// Directories the search does not enter (but may start in)
+    pub ceiling_dirs: Vec<PathBuf>,
+    /// Continue the search past filesystem boundaries
+    pub across_filesystem: bool,
+}
+
+impl DiscoverOptions {
+    /// Reads options from `GIT_DIR`, `GIT_WORK_TREE`, `GIT_OBJECT_DIRECTORY`, `GIT_INDEX_FILE`,
+    /// `GIT_ALTERNATE_OBJECT_DIRECTORIES`, `GIT_CEILING_DIRECTORIES`
+    /// and `GIT_DISCOVERY_ACROSS_FILESYSTEM` environment variables
+    pub fn from_env() -> Self {
+        let path = |name: &str| {
+            std::env::var_os(name)
+                .filter(|value| !value.is_empty())
+                .map(PathBuf::from)
+        };
+
+        Self {
+            git_dir: path("GIT_DIR"),
+            work_tree: path("GIT_WORK_TREE"),
+            object_dir: path("GIT_OBJECT_DIRECTORY"),
+            index_file: path("GIT_INDEX_FILE"),
+            alternate_object_dirs: std::env::var_os("GIT_ALTERNATE_OBJECT_DIRECTORIES")
+                .map(|dirs| {
+                    std::env::split_paths(&dirs)
+                        .filter(|dir| !dir.as_os_str().is_empty())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            // relative paths are ignored
+            ceiling_dirs: std::env::var_os("GIT_CEILING_DIRECTORIES")
+                .map(|dirs| {
+                    std::env::split_paths(&dirs)
+                        .filter(|dir| dir.is_absolute())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            across_filesystem: std::env::var_os("GIT_DISCOVERY_ACROSS_FILESYSTEM")
+                .is_some_and(|value| is_true(&value)),
+        }
+    }
+}
+
+fn is_true(value: &OsStr) -> bool {
+    let value = value.to_string_lossy().to_ascii_lowercase();
+    matches!(value.as_str(), "1" | "true" | "yes" | "on")
+}
+
+/// Walks up from `start` looking for a repository. Returns git directory
+/// and work tree (`None` for bare repositories).
+pub(super) fn find(
+    start: &Path,
+    options: &DiscoverOptions,
+) -> anyhow::Result<(PathBuf, Option<PathBuf>)> {
+    let start = start
+        .canonicalize()
+        .with_context(|| format!("resolving path {}", start.display()))?;
+    let ceilings: Vec<PathBuf> = options
+        .ceiling_dirs
+        .iter()
+        .filter_map(|dir| dir.canonicalize().ok())
+        .collect();
+    let start_device = device(&start)?;
+
+    let mut dir = start.as_path();
+    loop {
+        if let Some(found) = find_in(dir, options.object_dir.as_deref())? {
+            return Ok(found);
+        }
+
+        let Some(parent) = dir.parent() else {
+            break;
+        };
+        if ceilings.iter().any(|ceiling| ceiling == parent) {
+            break;
+        }
+        if !options.across_filesystem && device(parent)? != start_device {
+            anyhow::bail!(
+                "not a git repository (or any parent up to mount point {})\n\
+                 Stopping at filesystem boundary (GIT_DISCOVERY_ACROSS_FILESYSTEM not set).",
+                dir.display()
+            );
+        }
+        dir = parent;
+    }
+
+    anyhow::bail!("not a git repository (or any of the parent directories): .git")
+}
+
+fn device(path: &Path) -> anyhow::Result<u64> {
+    Ok(fs::metadata(path)
+        .with_context(|| format!("stat {}", path.display()))?
+        .dev())
+}
+
+/// Checks `.git` directory or file in the directory and the directory itself
+/// being a bare repository
+pub(super) fn find_in(
+    dir: &Path,
+    object_dir: Option<&Path>,
+) -> anyhow::Result<Option<(PathBuf, Option<PathBuf>)>> {
+    let dot_git = dir.join(".git");
+    if dot_git.is_file() {
+        let git_dir = read_gitfile(&dot_git)?;
+        anyhow::ensure!(
+            is_git_dir(&git_dir, object_dir),
+            "not a git repository: {}",
+            git_dir.display()
+        );
+        return Ok(Some((git_dir, Some(dir.to_path_buf()))));
+    }
+    if is_git_dir(&dot_git, object_dir) {
+        return Ok(Some((dot_git, Some(dir.to_path_buf()))));
+    }
+    if is_git_dir(dir, object_dir) {
+        return Ok(Some((dir.to_path_buf(), None)));
+    }
+
+    Ok(None)
+}
+
+/// Reads `gitdir: <path>` file used in place of `.git` directory by worktrees and submodules,
+/// relative paths are relative to the directory of the file
+pub(super) fn read_gitfile(path: &Path) -> anyhow::Result<PathBuf> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    let git_dir = content
+        .strip_prefix("gitdir: ")
+        .map(str::trim_end)
+        .filter(|git_dir| !git_dir.is_empty())
+        .with_context(|| format!("invalid gitfile format: {}", path.display()))?;
+
+    Ok(path
+        .parent()
+        .expect("gitfile is in a directory")
+        .join(git_dir))
+}
+
+/// Checks whether the directory looks like a git directory
+pub(super) fn is_git_dir(path: &Path, object_dir: Option<&Path>) -> bool {
+    let objects = match object_dir {
+        Some(object_dir) => object_dir.to_path_buf(),
+        None => path.join("objects"),
+    };
+    objects.is_dir() && path.join("refs").is_dir() && path.join("HEAD").is_file()
+}
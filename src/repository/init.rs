@@ -0,0 +1,329 @@
+use std::{
+    fs,
+    os::unix::fs::{MetadataExt, PermissionsExt},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::Context;
+
+use crate::{
+    config::{self, Config, ConfigFile},
+    object::HashAlgorithm,
+    refs,
+};
+
+use super::discover;
+
+// https://git-scm.com/docs/git-init
+
+/// Settings of a new repository, existing repositories keep their object format and `HEAD`
+#[derive(Debug, Clone, Default)]
+pub struct InitOptions {
+    /// Create repository without work tree in the directory itself
+    pub bare: bool,
+    /// Object format, the default is SHA-1 for new and the current one for existing
+    /// repositories
+    pub algorithm: Option<HashAlgorithm>,
+    /// Branch `HEAD` points to, `init.defaultBranch` or `master` by default
+    pub initial_branch: Option<String>,
+    /// Directory with files copied to the new git directory, `GIT_TEMPLATE_DIR`,
+    /// `init.templateDir` or the system templates by default. Empty path means no templates.
+    pub template: Option<PathBuf>,
+    /// Place the git directory there, with a `.git` file pointing to it in the work tree
+    pub separate_git_dir: Option<PathBuf>,
+    /// Permissions to make the repository accessible to other users
+    pub shared: Option<SharedRepository>,
+}
+
+/// Value of `core.sharedRepository`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SharedRepository {
+    /// Permissions given by umask
+    Umask,
+    /// Writable by the group
+    Group,
+    /// Writable by the group and readable by everyone
+    All,
+    /// Exact permissions of files
+    Mode(u32),
+}
+
+impl SharedRepository {
+    /// Reads `core.sharedRepository` of the repository
+    pub fn configured(git_dir: &Path) -> anyhow::Result<Self> {
+        match config::get(git_dir, "core.sharedrepository")? {
+            Some(value) => value.parse(),
+            None => Ok(Self::Umask),
+        }
+    }
+
+    /// Value stored in the config, `None` for the default
+    fn config_value(self) -> Option<String> {
+        match self {
+            Self::Umask => None,
+            Self::Group => Some("1".to_string()),
+            Self::All => Some("2".to_string()),
+            Self::Mode(mode) => Some(format!("{mode:04o}")),
+        }
+    }
+
+    /// Adds permissions to the file or directory (with its set-group-ID bit set,
+    /// so that new files belong to the group of the repository)
+    pub fn adjust(self, path: &Path) -> anyhow::Result<()> {
+        let mut tweak = match self {
+            Self::Umask => return Ok(()),
+            Self::Group => 0o660,
+            Self::All => 0o664,
+            Self::Mode(mode) => mode,
+        };
+        let metadata = fs::symlink_metadata(path)
+            .with_context(|| format!("reading metadata of {}", path.display()))?;
+        if metadata.file_type().is_symlink() {
+            return Ok(());
+        }
+        let mode = metadata.mode() & 0o7777;
+        if mode & 0o200 == 0 {
+            tweak &= !0o222;
+        }
+        if mode & 0o100 != 0 {
+            tweak |= (tweak & 0o444) >> 2;
+        }
+        let mut new_mode = match self {
+            Self::Mode(_) => (mode & !0o777) | tweak,
+            _ => mode | tweak,
+        };
+        if metadata.is_dir() {
+            new_mode |= 0o2000;
+        }
+        if new_mode != mode {
+            fs::set_permissions(path, fs::Permissions::from_mode(new_mode))
+                .with_context(|| format!("could not set permissions on '{}'", path.display()))?;
+        }
+        Ok(())
+    }
+
+    /// Creates the directory and its missing parents, adjusting permissions of those created
+    pub fn create_dir_all(self, dir: &Path) -> anyhow::Result<()> {
+        let missing: Vec<&Path> = dir.ancestors().take_while(|dir| !dir.exists()).collect();
+        fs::create_dir_all(dir).with_context(|| format!("creating directory {}", dir.display()))?;
+        for dir in missing.into_iter().rev() {
+            self.adjust(dir)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for SharedRepository {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "umask" => return Ok(Self::Umask),
+            "group" => return Ok(Self::Group),
+            "all" | "world" | "everybody" => return Ok(Self::All),
+            _ => {}
+        }
+
+        if s.len() > 1 && s.starts_with('0') {
+            let mode = u32::from_str_radix(s, 8)
+                .ok()
+                .filter(|mode| *mode <= 0o777)
+                .with_context(|| format!("invalid core.sharedRepository value '{s}'"))?;
+            anyhow::ensure!(
+                mode & 0o600 == 0o600,
+                "problem with core.sharedRepository filemode value ({mode:04o}).\n\
+                 The owner of files must always have read and write permissions."
+            );
+            return Ok(Self::Mode(mode));
+        }
+        match config::parse_int(s) {
+            Ok(1) => Ok(Self::Group),
+            Ok(2) => Ok(Self::All),
+            _ => match config::parse_bool(Some(s)) {
+                Ok(true) => Ok(Self::Group),
+                Ok(false) => Ok(Self::Umask),
+                Err(_) => anyhow::bail!("bad boolean config value '{s}' for 'arg'"),
+            },
+        }
+    }
+}
+
+/// Result of creating or reinitializing repository
+pub(super) struct Initialized {
+    pub git_dir: PathBuf,
+    pub work_tree: Option<PathBuf>,
+    pub reinitialized: bool,
+}
+
+/// Creates git directory for work tree at `path` (the git directory itself if bare),
+/// or completes the existing one
+pub(super) fn init(path: &Path, options: &InitOptions) -> anyhow::Result<Initialized> {
+    fs::create_dir_all(path).with_context(|| format!("cannot mkdir {}", path.display()))?;
+    let work_tree = (!options.bare).then(|| path.to_path_buf());
+    let dot_git = path.join(".git");
+    let git_dir = match (&options.separate_git_dir, options.bare) {
+        (Some(separate), false) => {
+            let separate = std::path::absolute(separate)?;
+            // existing git directory moves to the new place
+            if dot_git.is_dir() && !separate.exists() {
+                fs::rename(&dot_git, &separate)
+                    .with_context(|| format!("unable to move {}", dot_git.display()))?;
+            }
+            separate
+        }
+        // work tree of a separate git directory
+        (None, false) if dot_git.is_file() => discover::read_gitfile(&dot_git)?,
+        (None, false) => dot_git.clone(),
+        (_, true) => path.to_path_buf(),
+    };
+
+    let reinitialized = discover::is_git_dir(&git_dir, None);
+    let existing = match reinitialized {
+        true => Some(HashAlgorithm::of_repository(&git_dir)?),
+        false => None,
+    };
+    if let (Some(existing), Some(algorithm)) = (existing, options.algorithm) {
+        anyhow::ensure!(
+            existing == algorithm,
+            "attempt to reinitialize repository with different hash"
+        );
+    }
+    let algorithm = existing.or(options.algorithm).unwrap_or_default();
+
+    fs::create_dir_all(&git_dir).with_context(|| format!("cannot mkdir {}", git_dir.display()))?;
+    let global = Config::load(None)?;
+    copy_templates(&git_dir, options.template.as_deref(), &global)?;
+    for dir in ["refs/heads", "refs/tags"] {
+        let dir = git_dir.join(dir);
+        fs::create_dir_all(&dir).with_context(|| format!("cannot mkdir {}", dir.display()))?;
+    }
+    if !reinitialized {
+        let branch = match &options.initial_branch {
+            Some(branch) => branch.clone(),
+            None => global
+                .get("init.defaultBranch")?
+                .unwrap_or("master")
+                .to_string(),
+        };
+        let head = format!("refs/heads/{branch}");
+        anyhow::ensure!(
+            refs::is_valid_name(&head),
+            "invalid initial branch name: '{branch}'"
+        );
+        fs::write(git_dir.join("HEAD"), format!("ref: {head}\n")).context("writing HEAD")?;
+    }
+
+    for dir in ["objects/info", "objects/pack"] {
+        let dir = git_dir.join(dir);
+        fs::create_dir_all(&dir).with_context(|| format!("cannot mkdir {}", dir.display()))?;
+    }
+
+    // https://git-scm.com/docs/hash-function-transition#_repository_format_extension
+    let config_path = git_dir.join("config");
+    let mut config = ConfigFile::read(&config_path)?;
+    let version = match algorithm {
+        HashAlgorithm::Sha1 => "0",
+        _ => "1",
+    };
+    config.set("core.repositoryformatversion", version, false, false)?;
+    config.set("core.filemode", "true", false, false)?;
+    config.set("core.bare", &options.bare.to_string(), false, false)?;
+    if !options.bare {
+        config.set("core.logallrefupdates", "true", false, false)?;
+    }
+    if algorithm != HashAlgorithm::Sha1 {
+        config.set("extensions.objectformat", algorithm.name(), false, false)?;
+    }
+    if let Some(value) = options.shared.and_then(SharedRepository::config_value) {
+        config.set("core.sharedrepository", &value, false, false)?;
+        config.set("receive.denyNonFastforwards", "true", false, false)?;
+    }
+    config.write(&config_path)?;
+
+    if let Some(separate) = options.separate_git_dir.as_ref().filter(|_| !options.bare) {
+        let separate = std::path::absolute(separate)?;
+        fs::write(&dot_git, format!("gitdir: {}\n", separate.display()))
+            .with_context(|| format!("writing {}", dot_git.display()))?;
+    }
+
+    // existing repositories keep permissions of their files
+    if let Some(shared) = options.shared {
+        match reinitialized {
+            true => {
+                shared.adjust(&git_dir)?;
+                shared.adjust(&git_dir.join("refs"))?;
+            }
+            false => adjust_shared(&git_dir, shared)?,
+        }
+    }
+
+    Ok(Initialized {
+        git_dir,
+        work_tree,
+        reinitialized,
+    })
+}
+
+/// Copies files of the template directory that are not in the git directory yet
+fn copy_templates(git_dir: &Path, template: Option<&Path>, global: &Config) -> anyhow::Result<()> {
+    let (template, explicit) = match template {
+        Some(template) => (template.to_path_buf(), true),
+        None => match std::env::var_os("GIT_TEMPLATE_DIR") {
+            Some(template) => (PathBuf::from(template), true),
+            None => match global.get_path("init.templateDir")? {
+                Some(template) => (template, true),
+                None => (PathBuf::from("/usr/share/git-core/templates"), false),
+            },
+        },
+    };
+    if template.as_os_str().is_empty() {
+        return Ok(());
+    }
+    if !template.is_dir() {
+        if explicit {
+            eprintln!("warning: templates not found in {}", template.display());
+        }
+        return Ok(());
+    }
+    copy_missing(&template, git_dir)
+}
+
+fn copy_missing(from: &Path, to: &Path) -> anyhow::Result<()> {
+    for entry in fs::read_dir(from).with_context(|| format!("reading {}", from.display()))? {
+        let entry = entry?;
+        let source = entry.path();
+        let target = to.join(entry.file_name());
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            fs::create_dir_all(&target)
+                .with_context(|| format!("cannot mkdir {}", target.display()))?;
+            copy_missing(&source, &target)?;
+        } else if fs::symlink_metadata(&target).is_err() {
+            if file_type.is_symlink() {
+                std::os::unix::fs::symlink(fs::read_link(&source)?, &target)
+            } else {
+                fs::copy(&source, &target).map(|_| ())
+            }
+            .with_context(|| {
+                format!(
+                    "cannot copy '{}' to '{}'",
+                    source.display(),
+                    target.display()
+                )
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// Applies shared permissions to the directory and everything in it
+fn adjust_shared(path: &Path, shared: SharedRepository) -> anyhow::Result<()> {
+    shared.adjust(path)?;
+    if fs::symlink_metadata(path)?.is_dir() {
+        for entry in fs::read_dir(path)? {
+            adjust_shared(&entry?.path(), shared)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,224 @@
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+
+use crate::{
+    index::Index,
+    object::{tree::MODE_GITLINK, Commit, ObjectId, ObjectType, Tag, Tree},
+    odb::ObjectDatabase,
+    refs,
+    repository::Repository,
+};
+
+/// Object found by the walk together with the path it was reached through
+#[derive(Debug, Clone)]
+pub struct ObjectEntry {
+    pub hash: ObjectId,
+    pub path: String,
+}
+
+/// Lists objects reachable from `include` that are not reachable from `exclude`
+/// (like `git rev-list --objects include ^exclude`). Commits and tags are listed first,
+/// followed by trees and blobs.
+pub fn objects(
+    odb: &dyn ObjectDatabase,
+    include: &[ObjectId],
+    exclude: &[ObjectId],
+) -> anyhow::Result<Vec<ObjectEntry>> {
+    let mut seen = HashSet::new();
+    walk(odb, exclude, &mut seen)?;
+    walk(odb, include, &mut seen)
+}
+
+/// Counts commits reachable from `ours` but not from `theirs` and the other way around
+/// (like `git rev-list --count --left-right ours...theirs`)
+pub fn ahead_behind(
+    odb: &dyn ObjectDatabase,
+    ours: &ObjectId,
+    theirs: &ObjectId,
+) -> anyhow::Result<(usize, usize)> {
+    let ours = ancestors(odb, ours)?;
+    let theirs = ancestors(odb, theirs)?;
+    Ok((
+        ours.difference(&theirs).count(),
+        theirs.difference(&ours).count(),
+    ))
+}
+
+/// Collects the commit and all commits reachable from it
+fn ancestors(odb: &dyn ObjectDatabase, tip: &ObjectId) -> anyhow::Result<HashSet<ObjectId>> {
+    let mut seen = HashSet::new();
+    let mut stack = vec![*tip];
+    while let Some(id) = stack.pop() {
+        if seen.insert(id) {
+            stack.extend(Commit::read(&id, odb)?.parents);
+        }
+    }
+    Ok(seen)
+}
+
+/// Returns objects that must be kept in the repository: targets of all references and `HEAD`,
+/// objects recorded in reflogs and blobs staged in the index.
+/// Reflog entries pointing to objects that no longer exist are ignored.
+pub fn roots(repo: &Repository) -> anyhow::Result<Vec<ObjectId>> {
+    let git_dir = repo.git_dir();
+    let algorithm = repo.algorithm();
+    let null = algorithm.null();
+
+    let mut roots: Vec<ObjectId> = refs::list(git_dir)?.into_iter().map(|r| r.hash).collect();
+    roots.extend(refs::resolve(git_dir, "HEAD")?);
+
+    let mut logs = Vec::new();
+    collect_files(&git_dir.join("logs"), &mut logs)?;
+    for path in logs {
+        let content =
+            fs::read_to_string(&path).with_context(|| format!("reading {}", path.display()))?;
+        // <old> <new> <identity> <timestamp> <tz>\t<message>
+        for line in content.lines() {
+            // entries of expired or broken reflogs may name objects that are gone
+            for hash in line.split(' ').take(2) {
+                let Ok(id) = ObjectId::from_hex(hash) else {
+                    continue;
+                };
+                if id != null && repo.odb().contains(&id)? {
+                    roots.push(id);
+                }
+            }
+        }
+    }
+
+    // gitlinks point to commits of submodules
+    roots.extend(
+        Index::read(repo.index_file(), algorithm)?
+            .entries
+            .into_iter()
+            .filter(|entry| entry.mode != MODE_GITLINK)
+            .map(|entry| entry.oid),
+    );
+
+    roots.sort();
+    roots.dedup();
+
+    Ok(roots)
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> anyhow::Result<()> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err).with_context(|| format!("reading {}", dir.display())),
+    };
+
+    for entry in entries {
+        let path = entry
+            .with_context(|| format!("bad directory entry in {}", dir.display()))?
+            .path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Walks objects reachable from `tips` skipping objects in `seen` and adding all visited ones to it
+fn walk(
+    odb: &dyn ObjectDatabase,
+    tips: &[ObjectId],
+    seen: &mut HashSet<ObjectId>,
+) -> anyhow::Result<Vec<ObjectEntry>> {
+    let mut objects = Vec::new();
+    let mut trees = Vec::new();
+
+    // commits and tags first, root trees are walked after all commits
+    let mut stack: Vec<_> = tips.iter().rev().copied().collect();
+    while let Some(hash) = stack.pop() {
+        if seen.contains(&hash) {
+            continue;
+        }
+
+        let header = odb
+            .read_header(&hash)?
+            .with_context(|| format!("object {hash} not found"))?;
+
+        match header.typ {
+            ObjectType::Commit | ObjectType::Tag => {
+                seen.insert(hash);
+                if header.typ == ObjectType::Commit {
+                    let commit = Commit::read(&hash, odb)?;
+                    trees.push(commit.tree);
+                    stack.extend(commit.parents.iter().rev());
+                } else {
+                    stack.push(Tag::read(&hash, odb)?.object);
+                }
+                objects.push(ObjectEntry {
+                    hash,
+                    path: String::new(),
+                });
+            }
+            ObjectType::Tree => trees.push(hash),
+            _ => {
+                seen.insert(hash);
+                objects.push(ObjectEntry {
+                    hash,
+                    path: String::new(),
+                });
+            }
+        }
+    }
+
+    for tree in trees {
+        walk_tree(odb, tree, String::new(), seen, &mut objects)?;
+    }
+
+    Ok(objects)
+}
+
+fn walk_tree(
+    odb: &dyn ObjectDatabase,
+    hash: ObjectId,
+    path: String,
+    seen: &mut HashSet<ObjectId>,
+    objects: &mut Vec<ObjectEntry>,
+) -> anyhow::Result<()> {
+    if !seen.insert(hash) {
+        return Ok(());
+    }
+
+    let tree = Tree::read(&hash, odb)?;
+    objects.push(ObjectEntry {
+        hash,
+        path: path.clone(),
+    });
+
+    for entry in tree.entries {
+        let name = String::from_utf8_lossy(&entry.name);
+        let entry_path = if path.is_empty() {
+            name.to_string()
+        } else {
+            format!("{path}/{name}")
+        };
+
+        match entry.kind() {
+            ObjectType::Tree => walk_tree(odb, entry.oid, entry_path, seen, objects)?,
+            // submodules (gitlinks) point to commits in other repositories
+            ObjectType::Commit => {}
+            _ => {
+                if seen.insert(entry.oid) {
+                    objects.push(ObjectEntry {
+                        hash: entry.oid,
+                        path: entry_path,
+                    });
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,380 @@
+use std::{fs, path::Path};
+
+use anyhow::Context;
+
+use crate::{
+    config,
+    index::Index,
+    object::{tag, Commit, ObjectFile, ObjectId, ObjectType, Tag, Tree},
+    odb::ObjectDatabase,
+    refs,
+    repository::Repository,
+};
+
+// https://git-scm.com/docs/gitrevisions
+
+/// Shortest accepted abbreviation of an object name
+const MIN_ABBREV: usize = 4;
+
+/// Rules used to expand short reference names, in order of precedence
+const REF_RULES: [&str; 6] = [
+    "{}",
+    "refs/{}",
+    "refs/tags/{}",
+    "refs/heads/{}",
+    "refs/remotes/{}",
+    "refs/remotes/{}/HEAD",
+];
+
+/// Resolves revision expression (ie. `HEAD~2`, `v1.0^{tree}`, `main:src/main.rs` or `:file`)
+/// to the object
+pub fn resolve(repo: &Repository, rev: &str) -> anyhow::Result<ObjectId> {
+    resolve_with_warnings(repo, rev, &mut Vec::new())
+}
+
+/// Resolves revision expression like [`resolve`], collecting warnings about the revision
+/// (ie. reference names that are ambiguous) in `warnings`
+pub fn resolve_with_warnings(
+    repo: &Repository,
+    rev: &str,
+    warnings: &mut Vec<String>,
+) -> anyhow::Result<ObjectId> {
+    // :path or :<stage>:path names a blob in the index
+    if let Some(path) = rev.strip_prefix(':') {
+        let (stage, path) = match path.as_bytes() {
+            [stage @ b'0'..=b'3', b':', ..] => (stage - b'0', &path[2..]),
+            _ => (0, path),
+        };
+        return index_path(repo, stage, path);
+    }
+
+    // <rev>:<path> names an object in the tree of <rev>
+    if let Some(colon) = find_path_separator(rev) {
+        let tree = resolve_rev(repo, &rev[..colon], warnings)?;
+        let tree = peel(repo.odb(), &tree, ObjectType::Tree)
+            .with_context(|| format!("invalid object name '{}'", &rev[..colon]))?;
+        return tree_path(repo.odb(), tree, &rev[colon + 1..]).with_context(|| {
+            format!(
+                "path '{}' does not exist in '{}'",
+                &rev[colon + 1..],
+                &rev[..colon]
+            )
+        });
+    }
+
+    resolve_rev(repo, rev, warnings)
+}
+
+/// Finds colon separating revision from path, ignoring colons inside `{}` (ie. `@{1:00}`)
+fn find_path_separator(rev: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in rev.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' if depth > 0 => depth -= 1,
+            ':' if depth == 0 => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Resolves revision without path, applying suffixes from the right
+fn resolve_rev(
+    repo: &Repository,
+    rev: &str,
+    warnings: &mut Vec<String>,
+) -> anyhow::Result<ObjectId> {
+    if rev.ends_with('}') {
+        // <rev>^{<type>}
+        if let Some(start) = rev.rfind("^{") {
+            let id = resolve_rev(repo, &rev[..start], warnings)?;
+            let typ = &rev[start + 2..rev.len() - 1];
+            return match typ {
+                "" => Ok(tag::peel(&id, repo.odb())?.0),
+                "object" => {
+                    ObjectFile::read(&id, repo.odb())?;
+                    Ok(id)
+                }
+                _ => peel(repo.odb(), &id, typ.parse()?)
+                    .with_context(|| format!("invalid object name '{rev}'")),
+            };
+        }
+
+        // <ref>@{<spec>}
+        if let Some(start) = rev.rfind("@{") {
+            return resolve_at(
+                repo.git_dir(),
+                &rev[..start],
+                &rev[start + 2..rev.len() - 1],
+                warnings,
+            );
+        }
+    }
+
+    // <rev>^<n> and <rev>~<n>, number defaults to 1
+    let digits = rev.len() - rev.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+    let operator = rev[..rev.len() - digits].chars().last();
+    if let Some(operator @ ('^' | '~')) = operator {
+        let base = &rev[..rev.len() - digits - 1];
+        let n: usize = match digits {
+            0 => 1,
+            _ => rev[rev.len() - digits..]
+                .parse()
+                .with_context(|| format!("invalid number in '{rev}'"))?,
+        };
+        let mut hash = peel(
+            repo.odb(),
+            &resolve_rev(repo, base, warnings)?,
+            ObjectType::Commit,
+        )?;
+
+        if operator == '^' {
+            if n == 0 {
+                return Ok(hash);
+            }
+            return Commit::read(&hash, repo.odb())?
+                .parents
+                .get(n - 1)
+                .copied()
+                .with_context(|| format!("commit {hash} does not have parent {n} ('{rev}')"));
+        }
+
+        for _ in 0..n {
+            hash = Commit::read(&hash, repo.odb())?
+                .parents
+                .first()
+                .copied()
+                .with_context(|| format!("commit {hash} has no parent ('{rev}')"))?;
+        }
+        return Ok(hash);
+    }
+
+    resolve_name(repo, rev, warnings)
+}
+
+/// Resolves full object name, reference name or unique object name prefix
+fn resolve_name(
+    repo: &Repository,
+    name: &str,
+    warnings: &mut Vec<String>,
+) -> anyhow::Result<ObjectId> {
+    let algorithm = repo.algorithm();
+    if name.len() == algorithm.hex_size() && name.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return ObjectId::from_hex(&name.to_ascii_lowercase());
+    }
+
+    if let Some((_, hash)) = dwim_ref(repo.git_dir(), name, warnings)? {
+        return Ok(hash);
+    }
+
+    if (MIN_ABBREV..algorithm.hex_size()).contains(&name.len())
+        && name.bytes().all(|b| b.is_ascii_hexdigit())
+    {
+        let prefix = name.to_ascii_lowercase();
+        let candidates = find_prefix(repo.odb(), &prefix)?;
+        match candidates.as_slice() {
+            [id] => return Ok(*id),
+            [] => {}
+            _ => {
+                let mut message =
+                    format!("short object ID {name} is ambiguous\nThe candidates are:");
+                for id in &candidates {
+                    let typ = ObjectFile::read(id, repo.odb())
+                        .map(|object| object.header.typ.to_string())
+                        .unwrap_or_else(|_| "unknown".to_string());
+                    message.push_str(&format!("\n  {id} {typ}"));
+                }
+                anyhow::bail!(message);
+            }
+        }
+    }
+
+    anyhow::bail!("ambiguous argument '{name}': unknown revision")
+}
+
+/// Expands short reference name (ie. `main`, `origin/main` or `v1.0`) to the full
+/// reference name and the object it points to. Names matching more than one reference
+/// resolve to the first one with a warning.
+fn dwim_ref(
+    git_dir: &Path,
+    name: &str,
+    warnings: &mut Vec<String>,
+) -> anyhow::Result<Option<(String, ObjectId)>> {
+    let name = if name == "@" { "HEAD" } else { name };
+    if name.is_empty() {
+        return Ok(None);
+    }
+
+    let mut found: Option<(String, ObjectId)> = None;
+    for rule in REF_RULES {
+        let full_name = rule.replace("{}", name);
+        // only references below refs/ and root references such as HEAD or FETCH_HEAD
+        // can be named as they are, anything else could be a file in the git directory
+        let allowed = if full_name.starts_with("refs/") {
+            refs::is_valid_name(&full_name)
+        } else {
+            full_name
+                .bytes()
+                .all(|b| b.is_ascii_uppercase() || b == b'_')
+        };
+        if !allowed {
+            continue;
+        }
+
+        if let Some(hash) = refs::resolve(git_dir, &full_name)? {
+            match &found {
+                None => found = Some((full_name, hash)),
+                Some(_) => {
+                    warnings.push(format!("refname '{name}' is ambiguous."));
+                    break;
+                }
+            }
+        }
+    }
+
+    Ok(found)
+}
+
+/// Finds all objects whose names start with the prefix
+fn find_prefix(odb: &dyn ObjectDatabase, prefix: &str) -> anyhow::Result<Vec<ObjectId>> {
+    // hex encoding keeps the order of sorted names
+    Ok(odb
+        .iter()?
+        .map(|id| (id.to_string(), id))
+        .skip_while(|(hash, _)| hash.as_str() < prefix)
+        .take_while(|(hash, _)| hash.starts_with(prefix))
+        .map(|(_, id)| id)
+        .collect())
+}
+
+/// Finds object of requested type that can be trivially dereferenced from the given object:
+/// tags are peeled and commits give their tree
+pub fn peel(odb: &dyn ObjectDatabase, id: &ObjectId, typ: ObjectType) -> anyhow::Result<ObjectId> {
+    let mut id = *id;
+
+    loop {
+        let header = odb
+            .read_header(&id)?
+            .with_context(|| format!("object {id} not found"))?;
+        if header.typ == typ {
+            return Ok(id);
+        }
+
+        id = match header.typ {
+            ObjectType::Tag => Tag::read(&id, odb)?.object,
+            ObjectType::Commit if typ == ObjectType::Tree => Commit::read(&id, odb)?.tree,
+            _ => anyhow::bail!("{id}: bad file, object is not a {typ}"),
+        };
+    }
+}
+
+/// Resolves `@{<spec>}` suffix of the reference (current branch if empty)
+fn resolve_at(
+    git_dir: &Path,
+    name: &str,
+    spec: &str,
+    warnings: &mut Vec<String>,
+) -> anyhow::Result<ObjectId> {
+    let full_name = match name {
+        "" => refs::head_target(git_dir)?.unwrap_or_else(|| "HEAD".to_string()),
+        _ => {
+            dwim_ref(git_dir, name, warnings)?
+                .with_context(|| format!("ambiguous argument '{name}': unknown revision"))?
+                .0
+        }
+    };
+
+    if spec.eq_ignore_ascii_case("u") || spec.eq_ignore_ascii_case("upstream") {
+        let upstream = upstream(git_dir, &full_name)?;
+        return refs::resolve(git_dir, &upstream)?
+            .with_context(|| format!("upstream branch {upstream} of {full_name} does not exist"));
+    }
+
+    // <ref>@{<n>} is the n-th prior value of the reference
+    let n: usize = spec
+        .parse()
+        .with_context(|| format!("unsupported revision suffix '@{{{spec}}}'"))?;
+    let path = git_dir.join("logs").join(&full_name);
+    let content =
+        fs::read_to_string(&path).with_context(|| format!("reading reflog of {full_name}"))?;
+    // <old> <new> <identity> <timestamp> <tz>\t<message>
+    let entries: Vec<_> = content.lines().collect();
+    let entry = entries
+        .len()
+        .checked_sub(n + 1)
+        .map(|i| entries[i])
+        .with_context(|| format!("log for '{full_name}' only has {} entries", entries.len()))?;
+    entry
+        .split(' ')
+        .nth(1)
+        .and_then(|hash| ObjectId::from_hex(hash).ok())
+        .with_context(|| format!("malformed reflog entry of {full_name}"))
+}
+
+/// Returns remote-tracking reference of the upstream branch configured for the branch
+fn upstream(git_dir: &Path, full_name: &str) -> anyhow::Result<String> {
+    let branch = full_name
+        .strip_prefix("refs/heads/")
+        .with_context(|| format!("{full_name} is not a branch"))?;
+
+    let (Some(remote), Some(merge)) = (
+        config::get(git_dir, &format!("branch.{branch}.remote"))?,
+        config::get(git_dir, &format!("branch.{branch}.merge"))?,
+    ) else {
+        anyhow::bail!("no upstream configured for branch '{branch}'");
+    };
+
+    // branches of the local repository
+    if remote == "." {
+        return Ok(merge);
+    }
+
+    for refspec in config::get_all(git_dir, &format!("remote.{remote}.fetch"))? {
+        let refspec = refspec.trim_start_matches('+');
+        let Some((src, dst)) = refspec.split_once(':') else {
+            continue;
+        };
+        let tracking = match (src.split_once('*'), dst.split_once('*')) {
+            (Some((src_prefix, src_suffix)), Some((dst_prefix, dst_suffix))) => merge
+                .strip_prefix(src_prefix)
+                .and_then(|rest| rest.strip_suffix(src_suffix))
+                .map(|matched| format!("{dst_prefix}{matched}{dst_suffix}")),
+            (None, None) if src == merge => Some(dst.to_string()),
+            _ => None,
+        };
+        if let Some(tracking) = tracking {
+            return Ok(tracking);
+        }
+    }
+
+    anyhow::bail!("upstream branch '{merge}' not stored as a remote-tracking branch")
+}
+
+/// Resolves path in the tree, empty path names the tree itself
+fn tree_path(odb: &dyn ObjectDatabase, mut id: ObjectId, path: &str) -> anyhow::Result<ObjectId> {
+    for name in path.split('/').filter(|name| !name.is_empty()) {
+        let tree = Tree::read(&id, odb)?;
+        id = tree
+            .entries
+            .into_iter()
+            .find(|entry| entry.name == name.as_bytes())
+            .map(|entry| entry.oid)
+            .with_context(|| format!("no entry {name}"))?;
+    }
+
+    Ok(id)
+}
+
+/// Resolves path of the index entry at the merge stage
+fn index_path(repo: &Repository, stage: u8, path: &str) -> anyhow::Result<ObjectId> {
+    let index = Index::read(repo.index_file(), repo.algorithm())?;
+
+    match index.get(path.as_bytes(), stage) {
+        Some(entry) => Ok(entry.oid),
+        None if index.contains(path.as_bytes()) => {
+            anyhow::bail!("path '{path}' is in the index, but not at stage {stage}")
+        }
+        None => anyhow::bail!("path '{path}' does not exist in the index"),
+    }
+}
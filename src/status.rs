@@ -0,0 +1,529 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    ffi::OsStr,
+    fs,
+    os::unix::ffi::OsStrExt,
+    str::FromStr,
+};
+
+use anyhow::Context;
+
+use crate::{
+    config,
+    ignore::Ignore,
+    index::{self, FileState, Index},
+    object::{tree, ObjectId},
+    refs,
+    repository::Repository,
+    rev_list,
+};
+
+// https://git-scm.com/docs/git-status
+
+/// Change of a path between two of `HEAD`, the index and the work tree
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    Unmodified,
+    Added,
+    Modified,
+    Deleted,
+    /// File became a symbolic link or a submodule, or the other way around
+    TypeChanged,
+    Renamed,
+}
+
+impl Change {
+    /// Letter of the change in short format
+    pub fn code(self) -> char {
+        match self {
+            Self::Unmodified => ' ',
+            Self::Added => 'A',
+            Self::Modified => 'M',
+            Self::Deleted => 'D',
+            Self::TypeChanged => 'T',
+            Self::Renamed => 'R',
+        }
+    }
+
+    /// Compares two versions of a path given by mode and object name
+    fn between(old: Option<(u32, ObjectId)>, new: Option<(u32, ObjectId)>) -> Self {
+        match (old, new) {
+            (None, None) => Self::Unmodified,
+            (None, Some(_)) => Self::Added,
+            (Some(_), None) => Self::Deleted,
+            (Some(old), Some(new)) if old == new => Self::Unmodified,
+            (Some((old, _)), Some((new, _))) if object_type(old) != object_type(new) => {
+                Self::TypeChanged
+            }
+            _ => Self::Modified,
+        }
+    }
+}
+
+/// Kind of object an entry of the given mode points to, executable and regular files
+/// are the same kind
+fn object_type(mode: u32) -> u32 {
+    mode & 0o170000
+}
+
+/// Tracked path whose staged or work tree content differs
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub path: Vec<u8>,
+    /// Path in `HEAD` the staged file was renamed from
+    pub orig_path: Option<Vec<u8>>,
+    /// Change from `HEAD` to the index
+    pub staged: Change,
+    /// Change from the index to the work tree
+    pub unstaged: Change,
+    /// Mode and object in `HEAD`
+    pub head: Option<(u32, ObjectId)>,
+    /// Mode and object in the index
+    pub index: Option<(u32, ObjectId)>,
+    /// Mode in the work tree, 0 if the file is missing
+    pub work_tree_mode: u32,
+}
+
+/// Path with unresolved merge conflict
+#[derive(Debug, Clone)]
+pub struct Unmerged {
+    pub path: Vec<u8>,
+    /// Mode and object of the common ancestor, ours and theirs (stages 1 to 3)
+    pub stages: [Option<(u32, ObjectId)>; 3],
+    /// Mode in the work tree, 0 if the file is missing
+    pub work_tree_mode: u32,
+}
+
+impl Unmerged {
+    /// Two letter code of the conflict in short format (ie. `UU` when both sides modified it)
+    pub fn code(&self) -> &'static str {
+        match self.stages.each_ref().map(Option::is_some) {
+            [true, false, false] => "DD",
+            [false, true, false] => "AU",
+            [true, true, false] => "UD",
+            [false, false, true] => "UA",
+            [true, false, true] => "DU",
+            [false, true, true] => "AA",
+            _ => "UU",
+        }
+    }
+}
+
+/// Which untracked files are reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UntrackedFiles {
+    /// None
+    No,
+    /// Files, directories without tracked files as a whole
+    #[default]
+    Normal,
+    /// All files in untracked directories too
+    All,
+}
+
+impl FromStr for UntrackedFiles {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "no" => Ok(Self::No),
+            "normal" => Ok(Self::Normal),
+            "all" => Ok(Self::All),
+            _ => anyhow::bail!("Invalid untracked files mode '{s}'"),
+        }
+    }
+}
+
+/// Differences between `HEAD`, the index and the work tree
+#[derive(Debug, Default)]
+pub struct Status {
+    /// Changed tracked paths, sorted by path
+    pub entries: Vec<Entry>,
+    /// Conflicting paths, sorted by path
+    pub unmerged: Vec<Unmerged>,
+    /// Untracked files and directories (ending with '/'), sorted by path
+    pub untracked: Vec<Vec<u8>>,
+}
+
+impl Status {
+    /// Compares `HEAD`, the index and the work tree. Stat data of files found unchanged
+    /// is refreshed in the index, so that they do not have to be hashed again.
+    pub fn collect(
+        repo: &Repository,
+        index: &mut Index,
+        untracked_files: UntrackedFiles,
+    ) -> anyhow::Result<Self> {
+        let mut head: BTreeMap<_, _> = match repo.head()? {
+            Some(head) => repo
+                .walk_tree(&repo.read_commit(&head)?.tree)?
+                .into_iter()
+                .map(|(path, entry)| (path, (entry.mode, entry.oid)))
+                .collect(),
+            None => BTreeMap::new(),
+        };
+
+        let mut status = Self::default();
+        let mut stats = Vec::new();
+        let mut i = 0;
+        while let Some(entry) = index.entries.get(i) {
+            let stages = index.entries_of(&entry.path);
+            i += stages.len();
+            let head = head.remove(&entry.path);
+
+            if entry.stage != 0 {
+                let mut unmerged = Unmerged {
+                    path: entry.path.clone(),
+                    stages: [None; 3],
+                    work_tree_mode: work_tree_mode(repo, &entry.path)?,
+                };
+                for stage in stages {
+                    unmerged.stages[stage.stage as usize - 1] = Some((stage.mode, stage.oid));
+                }
+                status.unmerged.push(unmerged);
+                continue;
+            }
+
+            // intents to add are not staged yet, they are new files in the work tree
+            let index_version = Some((entry.mode, entry.oid)).filter(|_| !entry.intent_to_add);
+            let (unstaged, work_tree_mode) = if entry.intent_to_add {
+                (Change::Added, work_tree_mode(repo, &entry.path)?)
+            } else {
+                match index.file_state(repo, entry)? {
+                    FileState::Unchanged(stat) => {
+                        stats.push((i - 1, stat));
+                        (Change::Unmodified, entry.mode)
+                    }
+                    FileState::Deleted => (Change::Deleted, 0),
+                    FileState::Modified => {
+                        let mode = work_tree_mode(repo, &entry.path)?;
+                        match object_type(mode) != object_type(entry.mode) {
+                            true => (Change::TypeChanged, mode),
+                            false => (Change::Modified, mode),
+                        }
+                    }
+                }
+            };
+
+            let staged = Change::between(head, index_version);
+            if staged != Change::Unmodified || unstaged != Change::Unmodified {
+                status.entries.push(Entry {
+                    path: entry.path.clone(),
+                    orig_path: None,
+                    staged,
+                    unstaged,
+                    head,
+                    index: index_version,
+                    work_tree_mode,
+                });
+            }
+        }
+        for (i, stat) in stats {
+            index.entries[i].stat = stat;
+        }
+
+        // what is left of HEAD was removed from the index
+        for (path, head) in head {
+            status.entries.push(Entry {
+                path,
+                orig_path: None,
+                staged: Change::Deleted,
+                unstaged: Change::Unmodified,
+                head: Some(head),
+                index: None,
+                work_tree_mode: 0,
+            });
+        }
+        status.detect_renames();
+        status.entries.sort_by(|a, b| a.path.cmp(&b.path));
+
+        if untracked_files != UntrackedFiles::No {
+            let mut ignore = Ignore::new(repo)?;
+            collect_untracked(
+                repo,
+                index,
+                &mut ignore,
+                untracked_files,
+                b"",
+                &mut status.untracked,
+            )?;
+            status.untracked.sort();
+        }
+
+        Ok(status)
+    }
+
+    /// Pairs staged deletions with staged additions of the same content as renames
+    fn detect_renames(&mut self) {
+        let mut deleted: HashMap<_, Vec<_>> = HashMap::new();
+        for (i, entry) in self.entries.iter().enumerate() {
+            if let (Change::Deleted, Some((mode, oid))) = (entry.staged, entry.head) {
+                if mode != tree::MODE_GITLINK {
+                    deleted.entry(oid).or_default().push(i);
+                }
+            }
+        }
+
+        let mut renames = Vec::new();
+        for (i, entry) in self.entries.iter().enumerate() {
+            let (Change::Added, Some((mode, oid))) = (entry.staged, entry.index) else {
+                continue;
+            };
+            if mode == tree::MODE_GITLINK {
+                continue;
+            }
+            if let Some(source) = deleted.get_mut(&oid).and_then(|sources| sources.pop()) {
+                renames.push((i, source));
+            }
+        }
+
+        for &(i, source) in &renames {
+            self.entries[i].staged = Change::Renamed;
+            self.entries[i].head = self.entries[source].head;
+            self.entries[i].orig_path = Some(self.entries[source].path.clone());
+        }
+        let mut i = 0;
+        self.entries.retain(|_| {
+            i += 1;
+            !renames.iter().any(|&(_, source)| source == i - 1)
+        });
+    }
+
+    /// Checks whether anything is staged to be committed
+    pub fn has_staged(&self) -> bool {
+        self.entries
+            .iter()
+            .any(|entry| entry.staged != Change::Unmodified)
+    }
+
+    /// Checks whether any tracked file differs from the index
+    pub fn has_unstaged(&self) -> bool {
+        !self.unmerged.is_empty()
+            || self
+                .entries
+                .iter()
+                .any(|entry| entry.unstaged != Change::Unmodified)
+    }
+}
+
+/// Mode of the work tree file, 0 if it is missing
+fn work_tree_mode(repo: &Repository, path: &[u8]) -> anyhow::Result<u32> {
+    let full_path = repo
+        .work_tree()
+        .context("this operation must be run in a work tree")?
+        .join(OsStr::from_bytes(path));
+    match fs::symlink_metadata(&full_path) {
+        Ok(metadata) if metadata.is_dir() => Ok(tree::MODE_GITLINK),
+        Ok(metadata) => Ok(index::mode_of(&metadata)),
+        Err(err)
+            if matches!(
+                err.kind(),
+                std::io::ErrorKind::NotFound | std::io::ErrorKind::NotADirectory
+            ) =>
+        {
+            Ok(0)
+        }
+        Err(err) => Err(err).with_context(|| format!("stat {}", full_path.display())),
+    }
+}
+
+/// Collects paths of files in the directory of the work tree and its subdirectories,
+/// skipping git directories, nested repositories and files excluded by `ignore` if given
+pub fn collect_files(
+    repo: &Repository,
+    mut ignore: Option<&mut Ignore>,
+    dir: &[u8],
+    files: &mut Vec<Vec<u8>>,
+) -> anyhow::Result<()> {
+    let path = repo
+        .work_tree()
+        .context("this operation must be run in a work tree")?
+        .join(OsStr::from_bytes(dir));
+    let entries = match fs::read_dir(&path) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err).with_context(|| format!("reading {}", path.display())),
+    };
+
+    for entry in entries {
+        let entry = entry.with_context(|| format!("bad directory entry in {}", path.display()))?;
+        let name = entry.file_name();
+        if name == ".git" {
+            continue;
+        }
+
+        let mut file = dir.to_vec();
+        file.extend_from_slice(name.as_bytes());
+        let is_dir = entry
+            .file_type()
+            .with_context(|| format!("stat {}", entry.path().display()))?
+            .is_dir();
+        if is_dir && entry.path().join(".git").exists() {
+            continue;
+        }
+        if let Some(ignore) = ignore.as_deref_mut() {
+            if ignore.is_excluded(&file, is_dir)? {
+                continue;
+            }
+        }
+
+        if is_dir {
+            file.push(b'/');
+            collect_files(repo, ignore.as_deref_mut(), &file, files)?;
+        } else {
+            files.push(file);
+        }
+    }
+
+    Ok(())
+}
+
+/// Collects files of the directory of the work tree that are neither tracked nor ignored.
+/// In normal mode directories without tracked files are collected as a whole.
+fn collect_untracked(
+    repo: &Repository,
+    index: &Index,
+    ignore: &mut Ignore,
+    mode: UntrackedFiles,
+    dir: &[u8],
+    untracked: &mut Vec<Vec<u8>>,
+) -> anyhow::Result<()> {
+    let path = repo
+        .work_tree()
+        .context("this operation must be run in a work tree")?
+        .join(OsStr::from_bytes(dir));
+    let entries = fs::read_dir(&path).with_context(|| format!("reading {}", path.display()))?;
+
+    for entry in entries {
+        let entry = entry.with_context(|| format!("bad directory entry in {}", path.display()))?;
+        let name = entry.file_name();
+        if name == ".git" {
+            continue;
+        }
+
+        let mut file = dir.to_vec();
+        file.extend_from_slice(name.as_bytes());
+        if index.contains(&file) {
+            continue;
+        }
+        let is_dir = entry
+            .file_type()
+            .with_context(|| format!("stat {}", entry.path().display()))?
+            .is_dir();
+        if ignore.is_excluded(&file, is_dir)? {
+            continue;
+        }
+        if !is_dir {
+            untracked.push(file);
+            continue;
+        }
+
+        file.push(b'/');
+        // nested repositories are reported without looking inside
+        if entry.path().join(".git").exists() {
+            untracked.push(file);
+            continue;
+        }
+
+        let tracked = index
+            .entries
+            .get(index.entries.partition_point(|entry| entry.path < file))
+            .is_some_and(|entry| entry.path.starts_with(&file));
+        if mode == UntrackedFiles::Normal && !tracked {
+            let mut files = Vec::new();
+            collect_untracked(repo, index, ignore, mode, &file, &mut files)?;
+            if !files.is_empty() {
+                untracked.push(file);
+            }
+        } else {
+            collect_untracked(repo, index, ignore, mode, &file, untracked)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Branch checked out in `HEAD` and how it relates to its upstream branch
+#[derive(Debug)]
+pub struct Branch {
+    /// Reference of the branch, `None` if `HEAD` is detached
+    pub name: Option<String>,
+    /// Commit of `HEAD`, `None` on unborn branch
+    pub oid: Option<ObjectId>,
+    /// Reference the branch is configured to track
+    pub upstream: Option<String>,
+    /// Number of commits only in the branch and only in the upstream, `None` if
+    /// the upstream reference does not exist
+    pub ahead_behind: Option<(usize, usize)>,
+}
+
+impl Branch {
+    pub fn read(repo: &Repository) -> anyhow::Result<Self> {
+        let name = refs::head_target(repo.git_dir())?;
+        let oid = repo.head()?;
+        let upstream = match name
+            .as_deref()
+            .and_then(|name| name.strip_prefix("refs/heads/"))
+        {
+            Some(branch) => upstream(repo, branch)?,
+            None => None,
+        };
+
+        let mut ahead_behind = None;
+        if let Some(upstream) = &upstream {
+            if let Some(theirs) = repo.resolve_ref(upstream)? {
+                ahead_behind = Some(match oid {
+                    Some(ours) => rev_list::ahead_behind(repo.odb(), &ours, &theirs)?,
+                    None => (0, 0),
+                });
+            }
+        }
+
+        Ok(Self {
+            name,
+            oid,
+            upstream,
+            ahead_behind,
+        })
+    }
+}
+
+/// Shortens reference name for display (ie. `refs/remotes/origin/main` to `origin/main`)
+pub fn short_ref_name(name: &str) -> &str {
+    ["refs/heads/", "refs/tags/", "refs/remotes/"]
+        .iter()
+        .find_map(|prefix| name.strip_prefix(prefix))
+        .unwrap_or(name)
+}
+
+/// Finds the remote-tracking reference of the upstream branch given by `branch.<name>.remote`
+/// and `branch.<name>.merge`, mapped through fetch refspecs of the remote
+fn upstream(repo: &Repository, branch: &str) -> anyhow::Result<Option<String>> {
+    let git_dir = repo.git_dir();
+    let (Some(remote), Some(merge)) = (
+        config::get(git_dir, &format!("branch.{branch}.remote"))?,
+        config::get(git_dir, &format!("branch.{branch}.merge"))?,
+    ) else {
+        return Ok(None);
+    };
+    if remote == "." {
+        return Ok(Some(merge));
+    }
+
+    for refspec in config::get_all(git_dir, &format!("remote.{remote}.fetch"))? {
+        let refspec = refspec.strip_prefix('+').unwrap_or(&refspec);
+        let Some((src, dst)) = refspec.split_once(':') else {
+            continue;
+        };
+        match (src.strip_suffix('*'), dst.strip_suffix('*')) {
+            (Some(src), Some(dst)) => {
+                if let Some(name) = merge.strip_prefix(src) {
+                    return Ok(Some(format!("{dst}{name}")));
+                }
+            }
+            _ if src == merge => return Ok(Some(dst.to_string())),
+            _ => {}
+        }
+    }
+
+    Ok(None)
+}
@@ -0,0 +1,148 @@
+// https://git-scm.com/docs/gitignore#_pattern_format
+
+/// Matches the text against a shell glob pattern as git does for ignore rules and
+/// pathspecs. `*`, `?` and brackets do not match `/` with `pathname`, where `**`
+/// between slashes matches any number of directories instead.
+pub fn wildmatch(pattern: &[u8], text: &[u8], pathname: bool) -> bool {
+    let mut p = 0;
+    let mut t = 0;
+    while p < pattern.len() {
+        match pattern[p] {
+            b'*' => {
+                let mut end = p + 1;
+                while pattern.get(end) == Some(&b'*') {
+                    end += 1;
+                }
+
+                // `**` as a whole path component
+                let component = (p == 0 || pattern[p - 1] == b'/')
+                    && pattern.get(end).is_none_or(|&c| c == b'/');
+                if pathname && end - p >= 2 && component {
+                    let Some(rest) = pattern.get(end + 1..) else {
+                        return true;
+                    };
+                    // `**/` matches zero or more leading directories
+                    return wildmatch(rest, &text[t..], pathname)
+                        || (t..text.len())
+                            .filter(|&i| text[i] == b'/')
+                            .any(|i| wildmatch(rest, &text[i + 1..], pathname));
+                }
+
+                let rest = &pattern[end..];
+                for i in t..=text.len() {
+                    if wildmatch(rest, &text[i..], pathname) {
+                        return true;
+                    }
+                    if pathname && text.get(i) == Some(&b'/') {
+                        return false;
+                    }
+                }
+                return false;
+            }
+            b'?' => {
+                if t == text.len() || (pathname && text[t] == b'/') {
+                    return false;
+                }
+                p += 1;
+                t += 1;
+            }
+            b'[' => {
+                let Some(&c) = text.get(t) else {
+                    return false;
+                };
+                if pathname && c == b'/' {
+                    return false;
+                }
+                match match_class(&pattern[p + 1..], c) {
+                    Some((true, len)) => {
+                        p += 1 + len;
+                        t += 1;
+                    }
+                    Some((false, _)) => return false,
+                    // unterminated brackets are matched literally
+                    None if c == b'[' => {
+                        p += 1;
+                        t += 1;
+                    }
+                    None => return false,
+                }
+            }
+            c => {
+                let c = match c {
+                    b'\\' if p + 1 < pattern.len() => {
+                        p += 1;
+                        pattern[p]
+                    }
+                    c => c,
+                };
+                if text.get(t) != Some(&c) {
+                    return false;
+                }
+                p += 1;
+                t += 1;
+            }
+        }
+    }
+    t == text.len()
+}
+
+/// Checks whether the character is in the bracket expression at the start of the
+/// pattern (following the `[`). Returns whether it matched and length of the expression
+/// including the closing `]`, `None` if it is not terminated.
+fn match_class(pattern: &[u8], c: u8) -> Option<(bool, usize)> {
+    let mut i = 0;
+    let negated = matches!(pattern.first(), Some(b'!' | b'^'));
+    if negated {
+        i += 1;
+    }
+
+    let mut matched = false;
+    let mut first = true;
+    loop {
+        let mut start = *pattern.get(i)?;
+        if start == b']' && !first {
+            break;
+        }
+        first = false;
+
+        if start == b'[' && pattern.get(i + 1) == Some(&b':') {
+            let len = pattern[i + 2..].windows(2).position(|w| w == b":]")?;
+            matched |= match &pattern[i + 2..i + 2 + len] {
+                b"alnum" => c.is_ascii_alphanumeric(),
+                b"alpha" => c.is_ascii_alphabetic(),
+                b"blank" => c == b' ' || c == b'\t',
+                b"cntrl" => c.is_ascii_control(),
+                b"digit" => c.is_ascii_digit(),
+                b"graph" => c.is_ascii_graphic(),
+                b"lower" => c.is_ascii_lowercase(),
+                b"print" => c.is_ascii_graphic() || c == b' ',
+                b"punct" => c.is_ascii_punctuation(),
+                b"space" => c.is_ascii_whitespace(),
+                b"upper" => c.is_ascii_uppercase(),
+                b"xdigit" => c.is_ascii_hexdigit(),
+                _ => return Some((false, 0)),
+            };
+            i += len + 4;
+            continue;
+        }
+
+        if start == b'\\' {
+            i += 1;
+            start = *pattern.get(i)?;
+        }
+        i += 1;
+
+        let mut end = start;
+        if pattern.get(i) == Some(&b'-') && pattern.get(i + 1).is_some_and(|&c| c != b']') {
+            end = pattern[i + 1];
+            if end == b'\\' {
+                i += 1;
+                end = *pattern.get(i + 1)?;
+            }
+            i += 2;
+        }
+        matched |= (start..=end).contains(&c);
+    }
+
+    Some((matched != negated, i + 1))
+}